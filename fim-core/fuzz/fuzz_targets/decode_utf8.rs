@@ -0,0 +1,14 @@
+#![no_main]
+
+use std::sync::LazyLock;
+
+use fim_core::reader::fixture;
+use libfuzzer_sys::fuzz_target;
+use tokio::runtime::{Builder, Runtime};
+
+static RUNTIME: LazyLock<Runtime> =
+    LazyLock::new(|| Builder::new_current_thread().enable_time().build().unwrap());
+
+fuzz_target!(|data: &[u8]| {
+    RUNTIME.block_on(fixture::fuzz_decode(data));
+});
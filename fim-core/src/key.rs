@@ -0,0 +1,321 @@
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// tab默认的显示宽度
+pub const TAB_STOP: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Key {
+    // 普通字符
+    Char(char),
+    // 方向键
+    ArrowKey(Direction),
+    // 功能键
+    FunctionKey(u8),
+    // 控制键
+    ControlKey(ControlKey),
+    /// 其他特殊键
+    SpecialKey(SpecialKey),
+    // 鼠标事件
+    MouseEvent(MouseEvent),
+    /// 带有修饰键的按键，比如Ctrl+方向键
+    Modified(Modifiers, Box<Key>),
+    /// 输入法一次提交的多个字符，作为一次插入处理
+    Text(String),
+    // 未知或无法解析的输入
+    // Unknown(Vec<u8>),
+}
+
+impl Key {
+    pub fn render(&self, tab_width: usize) -> String {
+        match self{
+            Key::Char(c) => format!("{c}"),
+            Key::Text(text) => text.clone(),
+            Key::ControlKey(ControlKey::Tab) => " ".repeat(tab_width),
+            _ => {
+                "".to_owned()
+            }
+        }
+    }
+
+    /// 终端报告的事件，不是用户按下的键
+    pub fn is_event(&self) -> bool {
+        matches!(
+            self,
+            Key::SpecialKey(SpecialKey::FocusGained | SpecialKey::FocusLost | SpecialKey::Resize)
+        )
+    }
+
+    /// 拆分出修饰键和按键本身，没有修饰键时返回空的修饰键
+    pub fn modified(&self) -> (Modifiers, &Key) {
+        match self {
+            Key::Modified(modifiers, key) => (*modifiers, key),
+            key => (Modifiers::default(), key),
+        }
+    }
+
+    /// 按键的文本表示，比如 a、<C-f>、<S-Up>，用于显示宏和寄存器的内容
+    pub fn notation(&self) -> String {
+        let name = match self {
+            Key::Char('<') => "lt".to_string(),
+            Key::Char(c) => return c.to_string(),
+            // 解析时得到逐个字符的按键，插入的结果相同
+            Key::Text(text) => return text.chars().map(|c| Key::Char(c).notation()).collect(),
+            Key::ArrowKey(direction) => format!("{:?}", direction),
+            Key::FunctionKey(n) => format!("F{}", n),
+            Key::ControlKey(ControlKey::Ctrl(c)) => format!("C-{}", c),
+            Key::ControlKey(ControlKey::Alt(c)) => format!("A-{}", c),
+            Key::ControlKey(ControlKey::LF) => "NL".to_string(),
+            Key::ControlKey(ControlKey::Escape) => "Esc".to_string(),
+            Key::ControlKey(ControlKey::Backspace) => "BS".to_string(),
+            Key::ControlKey(ControlKey::Delete) => "Del".to_string(),
+            Key::ControlKey(key) => format!("{:?}", key),
+            Key::SpecialKey(key) => format!("{:?}", key),
+            Key::MouseEvent(_) => "Mouse".to_string(),
+            Key::Modified(modifiers, key) => {
+                let key = key.notation();
+                let key = key.strip_prefix('<').and_then(|k| k.strip_suffix('>')).unwrap_or(&key);
+                format!("{}{}", modifiers.prefix(), key)
+            }
+        };
+        format!("<{}>", name)
+    }
+
+    /// 解析notation生成的按键序列，比如 gg、<C-s>、<S-F3>
+    pub fn parse_notation(text: &str) -> Result<Vec<Key>, String> {
+        let mut keys = Vec::new();
+        let mut rest = text;
+        while let Some(c) = rest.chars().next() {
+            let name = match c {
+                '<' => rest[1..].split_once('>').map(|(name, _)| name),
+                _ => None,
+            };
+            match name {
+                // <后面没有>时当作普通字符
+                Some(name) if !name.is_empty() => {
+                    let key = Self::parse_name(name).ok_or_else(|| format!("unknown key <{}>", name))?;
+                    keys.push(key);
+                    rest = &rest[name.len() + 2..];
+                }
+                _ => {
+                    keys.push(Key::Char(c));
+                    rest = &rest[c.len_utf8()..];
+                }
+            }
+        }
+        if keys.is_empty() {
+            return Err("empty key sequence".to_string());
+        }
+        Ok(keys)
+    }
+
+    /// <>中的按键名称，可以带有S-、A-、C-前缀
+    fn parse_name(name: &str) -> Option<Key> {
+        let (modifiers, base) = Modifiers::parse_prefix(name);
+        let mut chars = base.chars();
+        let key = match (chars.next()?, chars.next()) {
+            (c, None) => Key::Char(c),
+            _ => match base {
+                "lt" => Key::Char('<'),
+                "Up" => Key::ArrowKey(Direction::Up),
+                "Down" => Key::ArrowKey(Direction::Down),
+                "Left" => Key::ArrowKey(Direction::Left),
+                "Right" => Key::ArrowKey(Direction::Right),
+                "NL" => Key::ControlKey(ControlKey::LF),
+                "CR" => Key::ControlKey(ControlKey::CR),
+                "Tab" => Key::ControlKey(ControlKey::Tab),
+                "Esc" => Key::ControlKey(ControlKey::Escape),
+                "BS" => Key::ControlKey(ControlKey::Backspace),
+                "Del" => Key::ControlKey(ControlKey::Delete),
+                "Home" => Key::ControlKey(ControlKey::Home),
+                "End" => Key::ControlKey(ControlKey::End),
+                "PageUp" => Key::ControlKey(ControlKey::PageUp),
+                "PageDown" => Key::ControlKey(ControlKey::PageDown),
+                "Insert" => Key::ControlKey(ControlKey::Insert),
+                "CapsLock" => Key::SpecialKey(SpecialKey::CapsLock),
+                "NumLock" => Key::SpecialKey(SpecialKey::NumLock),
+                "ScrollLock" => Key::SpecialKey(SpecialKey::ScrollLock),
+                "PrintScreen" => Key::SpecialKey(SpecialKey::PrintScreen),
+                "PauseBreak" => Key::SpecialKey(SpecialKey::PauseBreak),
+                "Menu" => Key::SpecialKey(SpecialKey::Menu),
+                "PasteStart" => Key::SpecialKey(SpecialKey::PasteStart),
+                "PasteEnd" => Key::SpecialKey(SpecialKey::PasteEnd),
+                "FocusGained" => Key::SpecialKey(SpecialKey::FocusGained),
+                "FocusLost" => Key::SpecialKey(SpecialKey::FocusLost),
+                "Resize" => Key::SpecialKey(SpecialKey::Resize),
+                _ => Key::FunctionKey(base.strip_prefix('F')?.parse().ok()?),
+            },
+        };
+        Some(Self::with_modifiers(modifiers, key))
+    }
+
+    /// 带修饰键的按键，和终端发送的形式保持一致
+    /// 终端把Ctrl+字母和Alt+字符作为单独的按键发送
+    pub fn with_modifiers(modifiers: Modifiers, key: Key) -> Key {
+        match key {
+            Key::Char(c) if modifiers == (Modifiers { ctrl: true, ..Default::default() }) => {
+                match c.to_ascii_lowercase() {
+                    'i' => Key::ControlKey(ControlKey::Tab),
+                    'j' => Key::ControlKey(ControlKey::LF),
+                    'm' => Key::ControlKey(ControlKey::CR),
+                    '[' => Key::ControlKey(ControlKey::Escape),
+                    c => Key::ControlKey(ControlKey::Ctrl(c)),
+                }
+            }
+            Key::Char(c) if modifiers == (Modifiers { alt: true, ..Default::default() }) => {
+                Key::ControlKey(ControlKey::Alt(c))
+            }
+            key if modifiers.is_empty() => key,
+            key => Key::Modified(modifiers, Box::new(key)),
+        }
+    }
+
+    /// 按键在文本中对应的字符，Ctrl+字母等控制键对应控制字符
+    pub fn as_char(&self) -> Option<char> {
+        let c = match self {
+            Key::Char(c) => *c,
+            Key::ControlKey(ControlKey::Tab) => '\t',
+            Key::ControlKey(ControlKey::LF) => '\n',
+            Key::ControlKey(ControlKey::CR) => '\r',
+            Key::ControlKey(ControlKey::Escape) => '\u{1B}',
+            Key::ControlKey(ControlKey::Backspace) => '\u{7F}',
+            Key::ControlKey(ControlKey::Ctrl(c @ ('@' | 'a'..='z' | '[' | '\\' | ']' | '^' | '_'))) => {
+                char::from(c.to_ascii_uppercase() as u8 ^ 0x40)
+            }
+            _ => return None,
+        };
+        Some(c)
+    }
+
+    /// 文本中的字符对应的按键，和从终端读到这个字符时得到的按键相同
+    pub fn from_char(c: char) -> Key {
+        match c {
+            '\t' => Key::ControlKey(ControlKey::Tab),
+            '\n' => Key::ControlKey(ControlKey::LF),
+            '\r' => Key::ControlKey(ControlKey::CR),
+            '\u{1B}' => Key::ControlKey(ControlKey::Escape),
+            '\u{7F}' => Key::ControlKey(ControlKey::Backspace),
+            '\u{0}'..='\u{1F}' => Key::ControlKey(ControlKey::Ctrl(char::from(c as u8 ^ 0x40).to_ascii_lowercase())),
+            c => Key::Char(c),
+        }
+    }
+
+    pub fn get_display_width(&self, tab_width: usize) -> usize {
+        match self {
+            // 宽字符占两列，组合字符至少算一列，保证可以插入
+            Key::Char(c) => c.width().unwrap_or(1).max(1),
+            Key::Text(text) => text.width(),
+            Key::ControlKey(ControlKey::Tab) => tab_width,
+            _ => 0,
+        }
+    }
+}
+
+/// 修饰键，可以同时按下多个
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub alt: bool,
+    pub ctrl: bool,
+}
+
+impl Modifiers {
+    /// 从转义序列中的修饰参数解析，参数等于1加上各修饰键的位
+    /// Shift是1，Alt是2，Ctrl是4
+    pub fn from_param(param: u8) -> Self {
+        let bits = param.saturating_sub(1);
+        Self {
+            shift: bits & 1 != 0,
+            alt: bits & 2 != 0,
+            ctrl: bits & 4 != 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !(self.shift || self.alt || self.ctrl)
+    }
+
+    /// 按键名称前的修饰键前缀，比如S-、C-、S-A-C-
+    pub fn prefix(&self) -> String {
+        let mut prefix = String::new();
+        for (on, name) in [(self.shift, "S-"), (self.alt, "A-"), (self.ctrl, "C-")] {
+            if on {
+                prefix.push_str(name);
+            }
+        }
+        prefix
+    }
+
+    /// 解析名称前的修饰键前缀，返回修饰键和剩下的名称
+    pub fn parse_prefix(name: &str) -> (Self, &str) {
+        let mut modifiers = Self::default();
+        let mut base = name;
+        // 最后一个字符本身可能是-，比如<C-->
+        while base.len() > 2 {
+            let flag = match &base[..2] {
+                "S-" => &mut modifiers.shift,
+                "A-" => &mut modifiers.alt,
+                "C-" => &mut modifiers.ctrl,
+                _ => break,
+            };
+            *flag = true;
+            base = &base[2..];
+        }
+        (modifiers, base)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ControlKey {
+    Ctrl(char), // Ctrl+字母/数字
+    Alt(char),  // Alt+字符
+    Tab,
+    LF,
+    CR,
+    // Enter,
+    Escape,
+    Backspace,
+    Delete,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SpecialKey {
+    CapsLock,
+    NumLock,
+    ScrollLock,
+    PrintScreen,
+    PauseBreak,
+    Menu,
+    /// 括号粘贴的开始和结束，终端会用它们包住粘贴的内容
+    PasteStart,
+    PasteEnd,
+    /// 终端窗口获得和失去焦点
+    FocusGained,
+    FocusLost,
+    /// 终端的大小改变了
+    Resize,
+}
+
+/// 鼠标事件，坐标从0开始
+/// 按钮0是左键，1是中键，2是右键
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MouseEvent {
+    Click(u8, u16, u16),    // 按钮, x, y
+    Drag(u8, u16, u16),     // 按钮, x, y
+    Release(u8, u16, u16),  // 按钮, x, y
+    Scroll(i8, u16, u16),   // 滚动方向, x, y，-1向上，1向下
+    HScroll(i8, u16, u16),  // 水平滚动方向, x, y，-1向左，1向右
+    Move(u16, u16),         // x, y
+}
@@ -0,0 +1,18 @@
+//! fim的输入管道和文本存储，可以在fim之外单独使用
+//!
+//! - [`ByteStream`]包装任意`AsyncRead`，负责缓冲和预读
+//! - [`Decoder`]把字节流按指定编码解码为字符
+//! - [`KeyStream`]把解码后的终端输入解析为[`Key`]，包括转义序列、鼠标和粘贴
+//! - [`TextBuffer`]按块存储文本的行，克隆时共享没有修改的块
+//!
+//! 出错时统一返回[`error::EditorError`]
+
+pub mod error;
+pub mod key;
+pub mod reader;
+pub mod text;
+
+pub use error::{EditorError, Result};
+pub use key::Key;
+pub use reader::{ByteStream, Decoder, KeyStream};
+pub use text::TextBuffer;
@@ -0,0 +1,15 @@
+pub mod byte_stream;
+pub mod key_stream;
+pub mod decoder;
+pub mod detect;
+pub mod events;
+#[cfg(any(test, feature = "testing"))]
+pub mod fixture;
+pub mod record;
+pub mod stream;
+
+pub use byte_stream::ByteStream;
+pub use decoder::{Decoder, DecoderBuilder};
+pub use detect::{Bom, detect_encoding};
+pub use key_stream::{InputBackend, KeyStream};
+pub use stream::{Keys, LineStream};
\ No newline at end of file
@@ -0,0 +1,323 @@
+use std::collections::VecDeque;
+use std::marker::Unpin;
+
+use std::io::SeekFrom;
+
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tracing::{debug, error, instrument, trace};
+
+use super::detect::Bom;
+use crate::error::{EditorError, Result};
+
+/// 默认每次读取的字节数
+const BUFFER_SIZE: usize = 1024;
+/// 默认最多缓冲的字节数，预读超过这个数量时返回错误
+const MAX_BUFFER_SIZE: usize = 1 << 20;
+
+/// 读取原始字节数据
+/// 负责缓冲和管理IO
+pub struct ByteStream<R: AsyncReadExt + Unpin> {
+    /// 读取器
+    reader: R,
+    /// 字节缓冲区，用于预读和缓存
+    byte_buffer: VecDeque<u8>,
+    /// 读取缓冲区，减少系统调用次数
+    // 可以直接去掉，这样由传入的reader控制
+    // 如果reader可以自带buffer机制或者不带
+    // 用上也没有什么开销，如果自带buffer多的开销只是拷贝了一次
+    read_buffer: Vec<u8>,
+    /// 已经读取的字节数，即下一个字节在输入中的偏移量
+    position: usize,
+    /// 每次从reader读取的字节数
+    capacity: usize,
+    /// 缓冲区最多可以增长到的字节数
+    limit: usize,
+}
+
+impl<R: AsyncReadExt + Unpin> ByteStream<R> {
+    /// 从reader读取字节，每次读取1024个字节
+    pub fn new(reader: R) -> Self {
+        Self::with_capacity(reader, BUFFER_SIZE)
+    }
+
+    /// 每次读取capacity个字节，读取大文件时可以设置得大一些减少系统调用
+    #[instrument(skip(reader))]
+    pub fn with_capacity(reader: R, capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        debug!("Creating new ByteStream with buffer sizes: {}", capacity);
+
+        Self {
+            reader,
+            byte_buffer: VecDeque::with_capacity(capacity),
+            read_buffer: Vec::with_capacity(capacity),
+            position: 0,
+            capacity,
+            limit: MAX_BUFFER_SIZE.max(capacity),
+        }
+    }
+
+    /// 设置预读时缓冲区最多增长到的字节数，不能小于每次读取的字节数
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit.max(self.capacity);
+        self
+    }
+
+    /// 读取单个字节
+    ///
+    /// # Returns
+    /// - `Ok(Some(byte))` - 成功读取到字节
+    /// - `Ok(None)` - 输入流关闭
+    /// - `Err(error)` - 读取过程中发生错误
+    #[instrument(skip(self))]
+    pub async fn read_next_byte(&mut self) -> Result<Option<u8>> {
+        // 优先从缓冲区读取，读到直接返回
+        if let Some(byte) = self.byte_buffer.pop_front() {
+            self.position += 1;
+            trace!(
+                "Read byte from buffer: 0x{:02X} ( '{} )",
+                byte,
+                if byte.is_ascii_graphic() {
+                    byte as char
+                } else {
+                    '.'
+                }
+            );
+            return Ok(Some(byte));
+        }
+
+        // 没有数据时填充缓冲区
+        self.fill_buffer().await.map_err(|e| {
+            error!("Failed to fill buffer: {}", e);
+            e
+        })?;
+
+        let result = self.byte_buffer.pop_front();
+        if let Some(byte) = result {
+            self.position += 1;
+            trace!(
+                "Read byte after buffer fill: 0x{:02X} ('{}')",
+                byte,
+                if byte.is_ascii_graphic() {
+                    byte as char
+                } else {
+                    '.'
+                }
+            );
+        } else {
+            debug!("Input stream closed");
+        }
+
+        Ok(result)
+    }
+
+    /// 等待直到缓冲区中有数据或者输入流关闭
+    /// 读取到的数据只会放入缓冲区，所以future被取消时不会丢失数据
+    pub async fn wait_data(&mut self) -> Result<()> {
+        self.fill_buffer().await
+    }
+
+    /// 缓冲区为空时填充缓冲区
+    #[instrument(skip(self))]
+    async fn fill_buffer(&mut self) -> Result<()> {
+        if !self.byte_buffer.is_empty() {
+            trace!(
+                "Buffer not empty, skipping fill (current size: {})",
+                self.byte_buffer.len()
+            );
+            return Ok(());
+        }
+
+        // 切片的长度是read_buffer的len长度，所以只需要调整长度就行，避免内存分配
+        self.read_buffer.resize(self.capacity, 0);
+
+        match self.reader.read(&mut self.read_buffer).await {
+            Ok(0) => {
+                debug!("Input stream closed");
+            }
+            Ok(size) => {
+                self.byte_buffer.extend(&self.read_buffer[..size]);
+                trace!("Buffer filled with {} bytes", size);
+            }
+            Err(e) => {
+                error!("I/O error during buffer fill: {}", e);
+                return Err(EditorError::Io { source: e });
+            }
+        }
+        Ok(())
+    }
+
+    /// 预读指定数量的字节，不从缓冲区移除
+    /// 用于转义序列解析等需要前瞻的场景
+    ///
+    /// # Arguments
+    /// * `count` - 需要预读的字节数量
+    ///
+    /// # Returns
+    /// 返回可用的字节切片，长度可能小于请求的数量（如遇到EOF）
+    /// 缓冲区需要增长到超过限制时返回`ResourceExhausted`错误
+    #[instrument(skip(self))]
+    pub async fn peek_ahead(&mut self, count: usize) -> Result<&[u8]> {
+        // 限制预读数量以避免过度缓冲
+        if count > self.limit {
+            error!("Peek of {} bytes exceeds buffer limit {}", count, self.limit);
+            return Err(EditorError::resource_exhausted("byte stream buffer", self.limit));
+        }
+
+        while self.byte_buffer.len() < count {
+            // read一般会从索引0覆盖写入，可以不用clear
+            // self.read_buffer.clear();
+            // 每次最多读取capacity个字节，缓冲区按需增长
+            self.read_buffer
+                .resize((count - self.byte_buffer.len()).min(self.capacity), 0);
+
+            // 切片的长度是read_buffer的len长度，所以只需要调整长度就行，避免内存分配
+            match self.reader.read(&mut self.read_buffer).await? {
+                0 => break,
+                size => {
+                    self.byte_buffer.extend(&self.read_buffer[..size]);
+                }
+            }
+        }
+
+        let available_count = self.byte_buffer.len().min(count);
+
+        // slice和self.byte_buffer.make_contiguous()即便没有同时存在
+        // 但是它们的生命周期都是与返回值的生命周期相同
+        // 所以rust编译器会认为借用冲突
+        // {
+        //     let slice = self.byte_buffer.as_slices().0;
+
+        //     if slice.len() >= available_count {
+        //         return Ok(&slice[..available_count]);
+        //     }
+        // }
+        // Ok(self.byte_buffer.make_contiguous())
+
+        // 不可变借用会在作用域结束时drop
+        let need_contiguous = {
+            // VecDeque使用环形缓冲区存储数据，其内部维护一个 逻辑上的连续序列
+            // 如果数据被环形缓冲区分割，两个切片分别对应前半段和后半段
+            let slice = self.byte_buffer.as_slices().0;
+            slice.len() < available_count
+        };
+
+        // if分支，两者不可能同时存在
+        if need_contiguous {
+            // 重整数据
+            Ok(self.byte_buffer.make_contiguous())
+        } else {
+            let (first_slice, _) = self.byte_buffer.as_slices();
+            Ok(&first_slice[..available_count])
+        }
+    }
+
+    /// 预读开头的一整块数据，用于检测编码
+    pub async fn peek_chunk(&mut self) -> Result<&[u8]> {
+        self.peek_ahead(self.capacity).await
+    }
+
+    /// 检查开头的BOM，和encoding对应时从流中去掉
+    /// 编码不对应时保留这些字节，交给解码器处理
+    pub async fn take_bom(&mut self, encoding: &str) -> Result<Option<Bom>> {
+        let bom = Bom::detect(self.peek_ahead(3).await?)
+            .filter(|bom| bom.encoding().eq_ignore_ascii_case(encoding));
+        if let Some(bom) = bom {
+            for _ in bom.bytes() {
+                self.read_next_byte().await?;
+            }
+        }
+        Ok(bom)
+    }
+
+    /// 下一个字节在输入中的偏移量
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// 获取缓冲区中的字节数量
+    pub fn buffered_count(&self) -> usize {
+        self.byte_buffer.len()
+    }
+}
+
+/// 读取器可以定位时（比如文件）支持回到之前的位置重新读取
+/// 可以换一种编码重新解码，不需要重新打开文件
+impl<R: AsyncReadExt + AsyncSeekExt + Unpin> ByteStream<R> {
+    /// 跳转到输入中的偏移量pos，丢弃已经预读的字节
+    #[instrument(skip(self))]
+    pub async fn seek(&mut self, pos: usize) -> Result<()> {
+        self.reader.seek(SeekFrom::Start(pos as u64)).await?;
+        self.byte_buffer.clear();
+        self.position = pos;
+        debug!("Seeked to byte {}", pos);
+        Ok(())
+    }
+
+    /// 回到输入的开头
+    pub async fn rewind(&mut self) -> Result<()> {
+        self.seek(0).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn bytes() -> Vec<u8> {
+        (0..=255).collect()
+    }
+
+    async fn read(stream: &mut ByteStream<Cursor<Vec<u8>>>, count: usize) -> Vec<u8> {
+        let mut read = Vec::new();
+        for _ in 0..count {
+            read.push(stream.read_next_byte().await.unwrap().unwrap());
+        }
+        read
+    }
+
+    #[tokio::test]
+    async fn seek_discards_buffered_bytes_and_reads_again() {
+        // 每次读取16个字节，读到20时缓冲区里还有21到31
+        let mut stream = ByteStream::with_capacity(Cursor::new(bytes()), 16);
+        assert_eq!(read(&mut stream, 20).await, (0..20).collect::<Vec<u8>>());
+        assert_eq!(stream.buffered_count(), 12);
+
+        // 回到前面，连续读取时要跨过两次重新填充
+        stream.seek(3).await.unwrap();
+        assert_eq!(stream.position(), 3);
+        assert_eq!(stream.buffered_count(), 0);
+        assert_eq!(read(&mut stream, 30).await, (3..33).collect::<Vec<u8>>());
+        assert_eq!(stream.position(), 33);
+
+        // 预读的字节也会被丢弃
+        assert_eq!(stream.peek_ahead(4).await.unwrap(), &[33, 34, 35, 36]);
+        stream.seek(200).await.unwrap();
+        assert_eq!(read(&mut stream, 2).await, [200, 201]);
+
+        stream.rewind().await.unwrap();
+        assert_eq!(stream.position(), 0);
+        assert_eq!(read(&mut stream, 256).await, bytes());
+        assert_eq!(stream.read_next_byte().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn peek_fails_only_beyond_the_limit() {
+        let mut stream = ByteStream::with_capacity(Cursor::new(bytes()), 4).with_limit(16);
+        // 刚好等于限制时可以预读，缓冲区分多次填充
+        assert_eq!(stream.peek_ahead(16).await.unwrap(), &bytes()[..16]);
+        assert_eq!(stream.buffered_count(), 16);
+        match stream.peek_ahead(17).await {
+            Err(EditorError::ResourceExhausted { limit, .. }) => assert_eq!(limit, 16),
+            other => panic!("expected ResourceExhausted, got {other:?}"),
+        }
+        // 出错之后已经预读的字节还在
+        assert_eq!(read(&mut stream, 3).await, [0, 1, 2]);
+
+        // 限制不能小于每次读取的字节数
+        let mut stream = ByteStream::with_capacity(Cursor::new(bytes()), 32).with_limit(8);
+        assert_eq!(stream.peek_ahead(32).await.unwrap().len(), 32);
+        assert!(stream.peek_ahead(33).await.is_err());
+    }
+}
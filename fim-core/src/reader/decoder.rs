@@ -0,0 +1,290 @@
+pub mod acsii;
+#[cfg(feature = "cjk")]
+pub mod cjk;
+pub mod utf16;
+pub mod utf8;
+
+use std::cmp::{Eq, PartialEq};
+use std::fmt;
+use std::marker::Unpin;
+
+use bon::bon;
+use tokio::io::AsyncReadExt;
+use tracing::warn;
+
+use crate::error::{EditorError, Result};
+use crate::reader::ByteStream;
+pub use acsii::AsciiDecoder;
+#[cfg(feature = "cjk")]
+pub use cjk::CjkDecoder;
+pub use utf16::Utf16Decoder;
+pub use utf8::Utf8Decoder;
+
+/// 具体编码的解码器
+pub enum DecoderKind<R: AsyncReadExt + Unpin> {
+    Utf8(Utf8Decoder<R>),
+    Ascii(AsciiDecoder<R>),
+    Utf16Le(Utf16Decoder<R>),
+    Utf16Be(Utf16Decoder<R>),
+    #[cfg(feature = "cjk")]
+    Gbk(CjkDecoder<R>),
+    #[cfg(feature = "cjk")]
+    ShiftJis(CjkDecoder<R>),
+}
+
+pub struct Decoder<R: AsyncReadExt + Unpin> {
+    kind: DecoderKind<R>,
+    /// 有损模式下遇到无效字节不报错，而是解码为U+FFFD
+    lossy: bool,
+    /// 有损模式下被替换的字节序列的起始偏移量
+    damage: Vec<usize>,
+    /// 下一个字符所在的行，从0开始
+    line: usize,
+    /// 下一个字符在行中的位置，按字符计算，从0开始
+    column: usize,
+}
+
+impl<R: AsyncReadExt + Unpin> fmt::Display for Decoder<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.get_name())
+    }
+}
+
+impl<R: AsyncReadExt + Unpin> PartialEq for Decoder<R> {
+    fn eq(&self, other: &Self) -> bool {
+        self.get_name() == other.get_name()
+    }
+}
+
+// Eq没有方法
+impl<R: AsyncReadExt + Unpin> Eq for Decoder<R> {}
+
+#[bon]
+impl<R: AsyncReadExt + Unpin> Decoder<R> {
+    /// 按encoding解码byte_stream，编码名不区分大小写，不支持时返回`UnsupportedEncoding`
+    /// lossy为true时无效的字节解码为U+FFFD而不是返回错误
+    #[builder]
+    pub fn new(
+        encoding: String,
+        byte_stream: ByteStream<R>,
+        #[builder(default)] lossy: bool,
+    ) -> Result<Self> {
+        let kind = match encoding.to_ascii_lowercase().as_str() {
+            "utf-8" => DecoderKind::Utf8(Utf8Decoder::new(byte_stream)),
+            "ascii" => DecoderKind::Ascii(AsciiDecoder::new(byte_stream)),
+            "utf-16le" => DecoderKind::Utf16Le(Utf16Decoder::le(byte_stream)),
+            "utf-16be" => DecoderKind::Utf16Be(Utf16Decoder::be(byte_stream)),
+            #[cfg(feature = "cjk")]
+            "gbk" => DecoderKind::Gbk(CjkDecoder::gbk(byte_stream)),
+            #[cfg(feature = "cjk")]
+            "shift-jis" | "shift_jis" | "sjis" => {
+                DecoderKind::ShiftJis(CjkDecoder::shift_jis(byte_stream))
+            }
+            _ => {
+                return Err(crate::error::EditorError::UnsupportedEncoding {
+                    encoding,
+                    available: Decoder::<R>::get_list(),
+                });
+            }
+        };
+        Ok(Self {
+            kind,
+            lossy,
+            damage: Vec::new(),
+            line: 0,
+            column: 0,
+        })
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self.kind {
+            DecoderKind::Utf8(_) => "UTF-8",
+            DecoderKind::Ascii(_) => "ASCII",
+            DecoderKind::Utf16Le(_) => "UTF-16LE",
+            DecoderKind::Utf16Be(_) => "UTF-16BE",
+            #[cfg(feature = "cjk")]
+            DecoderKind::Gbk(_) => "GBK",
+            #[cfg(feature = "cjk")]
+            DecoderKind::ShiftJis(_) => "Shift-JIS",
+        }
+    }
+
+    // pub fn get_name(&self) -> &'static str {
+    //     match self {
+    //         Decoder::Utf8(decoder) => decoder.get_name(),
+    //         Decoder::Ascii(decoder) => decoder.get_name(),
+    //     }
+    // }
+
+    pub fn get_list() -> Vec<&'static str> {
+        #[allow(unused_mut)]
+        let mut list = vec!["UTF-8", "ASCII", "UTF-16LE", "UTF-16BE"];
+        #[cfg(feature = "cjk")]
+        list.extend(["GBK", "Shift-JIS"]);
+        list
+    }
+
+    pub fn is_lossy(&self) -> bool {
+        self.lossy
+    }
+
+    /// 取出目前为止被替换的字节序列的偏移量
+    pub fn take_damage(&mut self) -> Vec<usize> {
+        std::mem::take(&mut self.damage)
+    }
+
+    /// 下一个字节在输入中的偏移量
+    pub fn position(&self) -> usize {
+        match &self.kind {
+            DecoderKind::Utf8(decoder) => decoder.position(),
+            DecoderKind::Ascii(decoder) => decoder.position(),
+            DecoderKind::Utf16Le(decoder) | DecoderKind::Utf16Be(decoder) => decoder.position(),
+            #[cfg(feature = "cjk")]
+            DecoderKind::Gbk(decoder) | DecoderKind::ShiftJis(decoder) => decoder.position(),
+        }
+    }
+
+    /// 下一个字符所在的行和列，都从0开始，列按字符计算
+    /// 和position()一起可以在字节偏移量和字符位置之间对应
+    pub fn line_column(&self) -> (usize, usize) {
+        (self.line, self.column)
+    }
+
+    /// 已经读入但还没有解码的字节数，大于0时说明还有输入紧跟在后面
+    pub fn buffered_count(&self) -> usize {
+        match &self.kind {
+            DecoderKind::Utf8(decoder) => decoder.buffered_count(),
+            DecoderKind::Ascii(decoder) => decoder.buffered_count(),
+            DecoderKind::Utf16Le(decoder) | DecoderKind::Utf16Be(decoder) => decoder.buffered_count(),
+            #[cfg(feature = "cjk")]
+            DecoderKind::Gbk(decoder) | DecoderKind::ShiftJis(decoder) => decoder.buffered_count(),
+        }
+    }
+
+    pub fn take_stream(self) -> ByteStream<R> {
+        match self.kind {
+            DecoderKind::Utf8(decoder) => decoder.take_stream(),
+            DecoderKind::Ascii(decoder) => decoder.take_stream(),
+            DecoderKind::Utf16Le(decoder) | DecoderKind::Utf16Be(decoder) => decoder.take_stream(),
+            #[cfg(feature = "cjk")]
+            DecoderKind::Gbk(decoder) | DecoderKind::ShiftJis(decoder) => decoder.take_stream(),
+        }
+    }
+
+    pub fn switch_to_encoding(self, encoding: String) -> Result<Self> {
+        if encoding.eq_ignore_ascii_case(self.get_name()) {
+            return Ok(self);
+        }
+
+        let (lossy, line, column) = (self.lossy, self.line, self.column);
+        let byte_stream = self.take_stream();
+        let mut decoder = Self::builder()
+            .encoding(encoding)
+            .byte_stream(byte_stream)
+            .lossy(lossy)
+            .build()?;
+        // 换编码后继续从同一个位置解码
+        decoder.line = line;
+        decoder.column = column;
+        Ok(decoder)
+    }
+
+    pub async fn decode_char(&mut self) -> Result<Option<char>> {
+        let start = self.position();
+        let result = match &mut self.kind {
+            DecoderKind::Utf8(decoder) => decoder.decode_char().await,
+            DecoderKind::Ascii(decoder) => decoder.decode_char().await,
+            DecoderKind::Utf16Le(decoder) | DecoderKind::Utf16Be(decoder) => decoder.decode_char().await,
+            #[cfg(feature = "cjk")]
+            DecoderKind::Gbk(decoder) | DecoderKind::ShiftJis(decoder) => {
+                decoder.decode_char().await
+            }
+        };
+        let result = match result {
+            // 无效的字节已经被消耗，替换为U+FFFD后继续解码
+            Err(
+                EditorError::InvalidEncoding { .. }
+                | EditorError::OverlongEncoding { .. }
+                | EditorError::SurrogateEncoding { .. }
+                | EditorError::CodePointOutOfRange { .. }
+                | EditorError::UnexpectedEof { .. },
+            ) if self.lossy =>
+            {
+                warn!(
+                    "{} decoder: replaced invalid bytes at offset {} (line {}, column {})",
+                    self.get_name(),
+                    start,
+                    self.line + 1,
+                    self.column + 1
+                );
+                self.damage.push(start);
+                Ok(Some(char::REPLACEMENT_CHARACTER))
+            }
+            result => result,
+        };
+        match result {
+            Ok(Some('\n')) => {
+                self.line += 1;
+                self.column = 0;
+            }
+            Ok(Some(_)) => self.column += 1,
+            _ => {}
+        }
+        result
+    }
+
+    pub async fn is_next_esc(&mut self) -> bool {
+        match &mut self.kind {
+            DecoderKind::Utf8(decoder) => decoder.is_next_esc().await,
+            DecoderKind::Ascii(decoder) => decoder.is_next_esc().await,
+            DecoderKind::Utf16Le(decoder) | DecoderKind::Utf16Be(decoder) => decoder.is_next_esc().await,
+            #[cfg(feature = "cjk")]
+            DecoderKind::Gbk(decoder) | DecoderKind::ShiftJis(decoder) => {
+                decoder.is_next_esc().await
+            }
+        }
+    }
+
+    /// 等待直到有输入可以解码，可以安全地取消
+    pub async fn wait_input(&mut self) -> Result<()> {
+        match &mut self.kind {
+            DecoderKind::Utf8(decoder) => decoder.wait_input().await,
+            DecoderKind::Ascii(decoder) => decoder.wait_input().await,
+            DecoderKind::Utf16Le(decoder) | DecoderKind::Utf16Be(decoder) => decoder.wait_input().await,
+            #[cfg(feature = "cjk")]
+            DecoderKind::Gbk(decoder) | DecoderKind::ShiftJis(decoder) => {
+                decoder.wait_input().await
+            }
+        }
+    }
+
+    pub async fn read_line(&mut self) -> Result<Option<String>> {
+        // 逐字符解码，这样有损模式也对read_line生效
+        let mut line = String::new();
+        loop {
+            match self.decode_char().await? {
+                Some('\n') => break,
+                // 忽略回车符
+                Some('\r') => continue,
+                Some(c) => line.push(c),
+                None if line.is_empty() => return Ok(None),
+                None => break,
+            }
+        }
+        Ok(Some(line))
+    }
+}
+
+/// 将文本按指定编码转换为字节，保存文件时使用
+pub fn encode(encoding: &str, text: &str) -> Result<Vec<u8>> {
+    match encoding.to_ascii_lowercase().as_str() {
+        "utf-16le" => return Ok(text.encode_utf16().flat_map(u16::to_le_bytes).collect()),
+        "utf-16be" => return Ok(text.encode_utf16().flat_map(u16::to_be_bytes).collect()),
+        _ => {}
+    }
+    #[cfg(feature = "cjk")]
+    if let Some(bytes) = cjk::encode(encoding, text) {
+        return bytes;
+    }
+    Ok(text.as_bytes().to_vec())
+}
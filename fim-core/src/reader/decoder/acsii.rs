@@ -17,6 +17,7 @@ impl<R: AsyncReadExt + Unpin> AsciiDecoder<R> {
 
     #[instrument(skip(self))]
     pub async fn decode_char(&mut self) -> Result<Option<char>> {
+        let start = self.byte_stream.position();
         let Some(byte) = self.byte_stream.read_next_byte().await? else {
             trace!("ASCII decoder: reached EOF");
             return Ok(None);
@@ -25,7 +26,7 @@ impl<R: AsyncReadExt + Unpin> AsciiDecoder<R> {
         if byte > 127 {
             error!("ASCII decoder: invalid byte 0x{:02X} (> 127)", byte);
             Err(EditorError::invalid_encoding(
-                0,
+                start,
                 format!("Byte 0x{:02X} is not valid ASCII (must be <= 127)", byte),
                 vec![byte],
             ))
@@ -37,21 +38,30 @@ impl<R: AsyncReadExt + Unpin> AsciiDecoder<R> {
     }
 
     pub async fn is_next_esc(&mut self) -> bool {
-        if let Ok(byte) = self.byte_stream.peek_ahead(1).await {
-            byte[0] == 0x1B
-        } else {
-            false
-        }
+        // 输入结束时预读到的是空切片
+        matches!(self.byte_stream.peek_ahead(1).await, Ok([0x1B, ..]))
     }
 
     // pub fn get_name(&self) -> &'static str {
     //     "ASCII"
     // }
 
+    pub fn position(&self) -> usize {
+        self.byte_stream.position()
+    }
+
+    pub fn buffered_count(&self) -> usize {
+        self.byte_stream.buffered_count()
+    }
+
     pub fn take_stream(self) -> ByteStream<R> {
         self.byte_stream
     }
 
+    pub async fn wait_input(&mut self) -> Result<()> {
+        self.byte_stream.wait_data().await
+    }
+
     pub async fn read_line(&mut self) -> Result<Option<String>> {
         let mut line = String::new();
         loop {
@@ -0,0 +1,185 @@
+use std::marker::Unpin;
+
+use encoding_rs::{Encoding, GBK, SHIFT_JIS};
+use tokio::io::AsyncReadExt;
+use tracing::{error, instrument, trace};
+
+use crate::{
+    error::{EditorError, Result},
+    reader::byte_stream::ByteStream,
+};
+
+/// 东亚多字节编码的解码器，目前支持GBK和Shift-JIS
+/// 先根据编码规则确定一个字符占几个字节，再交给encoding_rs解码
+pub struct CjkDecoder<R: AsyncReadExt + Unpin> {
+    byte_stream: ByteStream<R>,
+    encoding: &'static Encoding,
+}
+
+impl<R: AsyncReadExt + Unpin> CjkDecoder<R> {
+    pub fn gbk(byte_stream: ByteStream<R>) -> Self {
+        Self {
+            byte_stream,
+            encoding: GBK,
+        }
+    }
+
+    pub fn shift_jis(byte_stream: ByteStream<R>) -> Self {
+        Self {
+            byte_stream,
+            encoding: SHIFT_JIS,
+        }
+    }
+
+    /// 根据第一个字节确定字符需要的字节数，0表示无效的起始字节
+    // GBK：0x00-0x7F单字节，0x80是欧元符号，0x81-0xFE是双字节的首字节
+    // GBK解码时兼容GB18030的四字节序列，第二个字节是0x30-0x39
+    // Shift-JIS：0x00-0x7F和0xA1-0xDF（半角片假名）单字节
+    // 0x81-0x9F和0xE0-0xFC是双字节的首字节
+    fn calculate_byte_count(&self, first_byte: u8) -> u8 {
+        if self.encoding == GBK {
+            match first_byte {
+                0x00..=0x80 => 1,
+                0x81..=0xFE => 2,
+                _ => 0,
+            }
+        } else {
+            match first_byte {
+                0x00..=0x7F | 0xA1..=0xDF => 1,
+                0x81..=0x9F | 0xE0..=0xFC => 2,
+                _ => 0,
+            }
+        }
+    }
+
+    #[instrument(skip(self))]
+    pub async fn decode_char(&mut self) -> Result<Option<char>> {
+        let name = self.encoding.name();
+        let start = self.byte_stream.position();
+        let Some(leading_byte) = self.byte_stream.read_next_byte().await? else {
+            trace!("{} decoder: reached EOF", name);
+            return Ok(None);
+        };
+
+        let byte_count = self.calculate_byte_count(leading_byte);
+        if byte_count == 0 {
+            error!("{} decoder: invalid leading byte 0x{:02X}", name, leading_byte);
+            return Err(EditorError::invalid_encoding(
+                start,
+                format!("Invalid {} leading byte 0x{:02X}", name, leading_byte),
+                vec![leading_byte],
+            ));
+        }
+
+        let mut bytes = vec![leading_byte];
+        if byte_count == 2 {
+            // 尾字节最小是0x30，更小的控制字符比如换行不消耗，有损解码时保留下来
+            let Some(&byte) = self.byte_stream.peek_ahead(1).await?.first() else {
+                return Err(EditorError::unexpected_eof(
+                    start,
+                    format!("{} trail byte 1 of 2", name),
+                    1,
+                ));
+            };
+            bytes.push(byte);
+            if byte < 0x30 {
+                return Err(EditorError::invalid_encoding(
+                    start,
+                    format!("Invalid {} trail byte 0x{:02X}", name, byte),
+                    bytes,
+                ));
+            }
+            self.byte_stream.read_next_byte().await?;
+        }
+        // GB18030的四字节序列
+        if self.encoding == GBK && bytes.len() == 2 && (0x30..=0x39).contains(&bytes[1]) {
+            for i in 2..4 {
+                let Some(byte) = self.byte_stream.read_next_byte().await? else {
+                    return Err(EditorError::unexpected_eof(
+                        start,
+                        format!("{} trail byte {} of 4", name, i),
+                        i,
+                    ));
+                };
+                bytes.push(byte);
+            }
+        }
+
+        let decoded = self
+            .encoding
+            .decode_without_bom_handling_and_without_replacement(&bytes);
+        match decoded.as_deref().map(|s| s.chars().collect::<Vec<_>>()) {
+            Some(chars) if chars.len() == 1 => {
+                trace!("{} decoder: decoded character '{}'", name, chars[0]);
+                Ok(Some(chars[0]))
+            }
+            _ => {
+                error!("{} decoder: invalid byte sequence {:02X?}", name, bytes);
+                Err(EditorError::invalid_encoding(
+                    start,
+                    format!("Invalid {} byte sequence {:02X?}", name, bytes),
+                    bytes,
+                ))
+            }
+        }
+    }
+
+    pub fn position(&self) -> usize {
+        self.byte_stream.position()
+    }
+
+    pub fn buffered_count(&self) -> usize {
+        self.byte_stream.buffered_count()
+    }
+
+    pub fn take_stream(self) -> ByteStream<R> {
+        self.byte_stream
+    }
+
+    pub async fn wait_input(&mut self) -> Result<()> {
+        self.byte_stream.wait_data().await
+    }
+
+    pub async fn is_next_esc(&mut self) -> bool {
+        matches!(self.byte_stream.peek_ahead(1).await, Ok([0x1B, ..]))
+    }
+
+    pub async fn read_line(&mut self) -> Result<Option<String>> {
+        let mut line = String::new();
+        loop {
+            match self.decode_char().await? {
+                Some('\n') => break,
+                // 忽略回车符
+                Some('\r') => continue,
+                Some(c) => line.push(c),
+                // EOF reached
+                None if line.is_empty() => return Ok(None),
+                None => break,
+            }
+        }
+        Ok(Some(line))
+    }
+}
+
+/// 按GBK或Shift-JIS编码文本，不是这两种编码时返回None
+/// 遇到无法表示的字符时返回错误，而不是写入替换字符
+pub fn encode(encoding: &str, text: &str) -> Option<Result<Vec<u8>>> {
+    let target = match encoding.to_ascii_lowercase().as_str() {
+        "gbk" => GBK,
+        "shift-jis" | "shift_jis" | "sjis" => SHIFT_JIS,
+        _ => return None,
+    };
+    let (bytes, _, had_errors) = target.encode(text);
+    if !had_errors {
+        return Some(Ok(bytes.into_owned()));
+    }
+    let (position, c) = text
+        .char_indices()
+        .find(|(_, c)| target.encode(c.encode_utf8(&mut [0; 4])).2)
+        .unwrap_or((0, '\0'));
+    Some(Err(EditorError::invalid_encoding(
+        position,
+        format!("'{c}' cannot be encoded as {}", target.name()),
+        c.to_string().into_bytes(),
+    )))
+}
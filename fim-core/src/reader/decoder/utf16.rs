@@ -0,0 +1,119 @@
+use std::marker::Unpin;
+
+use tokio::io::AsyncReadExt;
+use tracing::{error, instrument, trace};
+
+use crate::{
+    error::{EditorError, Result},
+    reader::byte_stream::ByteStream,
+};
+
+/// UTF-16解码器，每个码元两个字节，字节序由big_endian决定
+pub struct Utf16Decoder<R: AsyncReadExt + Unpin> {
+    byte_stream: ByteStream<R>,
+    big_endian: bool,
+}
+
+impl<R: AsyncReadExt + Unpin> Utf16Decoder<R> {
+    pub fn le(byte_stream: ByteStream<R>) -> Self {
+        Self {
+            byte_stream,
+            big_endian: false,
+        }
+    }
+
+    pub fn be(byte_stream: ByteStream<R>) -> Self {
+        Self {
+            byte_stream,
+            big_endian: true,
+        }
+    }
+
+    fn unit(&self, bytes: [u8; 2]) -> u16 {
+        if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        }
+    }
+
+    /// 读取一个码元，只读到一个字节就结束时返回错误
+    async fn read_unit(&mut self) -> Result<Option<u16>> {
+        let start = self.byte_stream.position();
+        let Some(first) = self.byte_stream.read_next_byte().await? else {
+            return Ok(None);
+        };
+        let Some(second) = self.byte_stream.read_next_byte().await? else {
+            return Err(EditorError::unexpected_eof(start, "UTF-16 second byte", 1));
+        };
+        Ok(Some(self.unit([first, second])))
+    }
+
+    // 0xD800-0xDBFF是高代理，后面必须跟着0xDC00-0xDFFF的低代理
+    // 两个代理一起表示U+10000以上的字符
+    #[instrument(skip(self))]
+    pub async fn decode_char(&mut self) -> Result<Option<char>> {
+        let start = self.byte_stream.position();
+        let Some(unit) = self.read_unit().await? else {
+            trace!("UTF-16 decoder: reached EOF");
+            return Ok(None);
+        };
+
+        let code_point = match unit {
+            0xD800..=0xDBFF => {
+                // 先预读，不是低代理时不消耗，有损解码时保留下一个字符
+                let low = match self.byte_stream.peek_ahead(2).await? {
+                    &[a, b] => self.unit([a, b]),
+                    _ => 0,
+                };
+                if !(0xDC00..=0xDFFF).contains(&low) {
+                    error!("UTF-16 decoder: unpaired high surrogate 0x{:04X}", unit);
+                    return Err(EditorError::invalid_encoding(
+                        start,
+                        format!("Unpaired UTF-16 high surrogate 0x{:04X}", unit),
+                        unit.to_be_bytes().to_vec(),
+                    ));
+                }
+                self.read_unit().await?;
+                0x10000 + ((unit as u32 - 0xD800) << 10) + (low as u32 - 0xDC00)
+            }
+            0xDC00..=0xDFFF => {
+                error!("UTF-16 decoder: unpaired low surrogate 0x{:04X}", unit);
+                return Err(EditorError::invalid_encoding(
+                    start,
+                    format!("Unpaired UTF-16 low surrogate 0x{:04X}", unit),
+                    unit.to_be_bytes().to_vec(),
+                ));
+            }
+            _ => unit as u32,
+        };
+
+        // 代理已经排除，剩下的码点都是合法的字符
+        let ch = char::from_u32(code_point).unwrap_or(char::REPLACEMENT_CHARACTER);
+        trace!("UTF-16 decoder: decoded character '{}' (U+{:04X})", ch, code_point);
+        Ok(Some(ch))
+    }
+
+    pub fn position(&self) -> usize {
+        self.byte_stream.position()
+    }
+
+    pub fn buffered_count(&self) -> usize {
+        self.byte_stream.buffered_count()
+    }
+
+    pub fn take_stream(self) -> ByteStream<R> {
+        self.byte_stream
+    }
+
+    pub async fn wait_input(&mut self) -> Result<()> {
+        self.byte_stream.wait_data().await
+    }
+
+    pub async fn is_next_esc(&mut self) -> bool {
+        match self.byte_stream.peek_ahead(2).await {
+            Ok(&[a, b]) => self.unit([a, b]) == 0x1B,
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,307 @@
+use std::marker::Unpin;
+
+use tokio::io::AsyncReadExt;
+use tracing::{error, instrument, trace};
+
+use crate::{
+    error::{EditorError, Result},
+    reader::byte_stream::ByteStream,
+};
+
+pub struct Utf8Decoder<R: AsyncReadExt + Unpin> {
+    byte_stream: ByteStream<R>,
+}
+
+impl<R: AsyncReadExt + Unpin> Utf8Decoder<R> {
+    pub fn new(byte_stream: ByteStream<R>) -> Self {
+        Self { byte_stream }
+    }
+
+    /// 根据第一个字节确定UTF-8字符需要的字节数
+    // 如果第一个字节以0开头，则是单字节字符
+    // 0b是Rust中的二进制字面量前缀（binary literal prefix），表示后面跟着的是二进制数字
+    // UTF-8编码规则：
+    // 1字节：0xxxxxxx
+    // 2字节：110xxxxx 10xxxxxx
+    // 3字节：1110xxxx 10xxxxxx 10xxxxxx
+    // 4字节：11110xxx 10xxxxxx 10xxxxxx 10xxxxxx
+    // 如果要判断该位是否为1，可以让该位与1进行与运算，如果结果为1则该位为1，否则为0
+    // 如果要判断该位是否为0，可以让该位与1进行与运算，如果结果为0则该位为0，否则为1
+    // 其余位可以为任意值，与0想与运结果固定为0
+    fn calculate_byte_count(&self, first_byte: u8) -> u8 {
+        match first_byte {
+            b if b & 0b1000_0000 == 0 => 1,
+            b if b & 0b1110_0000 == 0b1100_0000 => 2,
+            b if b & 0b1111_0000 == 0b1110_0000 => 3,
+            b if b & 0b1111_1000 == 0b1111_0000 => 4,
+            _ => 0, // 无效的UTF-8起始字节
+        }
+    }
+
+    /// 按照Unicode的规则检查第二个字节，排除过长编码、代理和超过U+10FFFF的码点
+    /// E0、F0开头时第二个字节太小是过长编码，ED开头时太大是代理，F4开头时太大超过U+10FFFF
+    fn check_second_byte(&self, leading_byte: u8, byte: u8) -> Option<fn(usize, Vec<u8>) -> EditorError> {
+        match (leading_byte, byte) {
+            (0xE0, 0x80..=0x9F) | (0xF0, 0x80..=0x8F) => Some(EditorError::overlong_encoding),
+            (0xED, 0xA0..=0xBF) => Some(EditorError::surrogate_encoding),
+            (0xF4, 0x90..=0xBF) => Some(EditorError::code_point_out_of_range),
+            _ => None,
+        }
+    }
+
+    /// 检查是否为UTF-8续字节 (10xxxxxx)
+    fn is_continuation_byte(&self, byte: u8) -> bool {
+        byte & 0b1100_0000 == 0b1000_0000
+    }
+
+    #[instrument(skip(self))]
+    pub async fn decode_char(&mut self) -> Result<Option<char>> {
+        // 出错时报告整个字节序列的起始偏移量
+        let start = self.byte_stream.position();
+        let Some(leading_byte) = self.byte_stream.read_next_byte().await? else {
+            trace!("UTF-8 decoder: reached EOF");
+            return Ok(None);
+        };
+
+        let byte_count = self.calculate_byte_count(leading_byte);
+        trace!(
+            "UTF-8 decoder: leading byte 0x{:02X} requires {} bytes",
+            leading_byte, byte_count
+        );
+
+        // 只消耗首字节，后面的续字节在下次解码时作为无效的首字节处理
+        if matches!(leading_byte, 0xC0 | 0xC1) {
+            // 两个字节的序列至少表示U+0080，C0和C1开头的只能表示更小的码点
+            error!("UTF-8 decoder: overlong leading byte 0x{:02X}", leading_byte);
+            return Err(EditorError::overlong_encoding(start, vec![leading_byte]));
+        }
+        if matches!(leading_byte, 0xF5..=0xF7) {
+            error!("UTF-8 decoder: leading byte 0x{:02X} above U+10FFFF", leading_byte);
+            return Err(EditorError::code_point_out_of_range(start, vec![leading_byte]));
+        }
+
+        if byte_count == 1 {
+            // 单字节ASCII字符
+            let ch = leading_byte as char;
+            trace!("UTF-8 decoder: decoded ASCII character '{}'", ch);
+            Ok(Some(ch))
+        } else if byte_count > 1 {
+            // U+0000是空字符（NUL），即'\0'，其UTF-8编码为0x00
+            // 空字符表示无或者空
+            // 移除控制信息，保留数据位，解码为Unicode码点
+            let mut unicode_point = (leading_byte & (0xFF >> (byte_count + 1))) as u32;
+            let mut bytes_collected = vec![leading_byte];
+
+            for i in 1..byte_count {
+                // 先预读，不是续字节时不消耗它，有损解码时就不会吞掉后面的字符
+                let Some(&continuation_byte) = self.byte_stream.peek_ahead(1).await?.first() else {
+                    error!(
+                        "UTF-8 decoder: unexpected EOF while reading continuation byte {} of {}",
+                        i, byte_count
+                    );
+                    return Err(EditorError::unexpected_eof(
+                        start,
+                        format!("UTF-8 continuation byte {} of {}", i, byte_count),
+                        i as usize,
+                    ));
+                };
+
+                bytes_collected.push(continuation_byte);
+
+                if !self.is_continuation_byte(continuation_byte) {
+                    error!(
+                        "UTF-8 decoder: invalid continuation byte 0x{:02X} at position {}",
+                        continuation_byte, i
+                    );
+                    return Err(EditorError::invalid_encoding(
+                        start,
+                        format!(
+                            "Expected UTF-8 continuation byte (10xxxxxx), got 0x{:02X}",
+                            continuation_byte
+                        ),
+                        bytes_collected,
+                    ));
+                }
+                if i == 1
+                    && let Some(error) = self.check_second_byte(leading_byte, continuation_byte)
+                {
+                    error!("UTF-8 decoder: invalid sequence {:02X?}", bytes_collected);
+                    return Err(error(start, bytes_collected));
+                }
+                self.byte_stream.read_next_byte().await?;
+
+                // 移除控制信息提取6位数据并合并到Unicode码点
+                unicode_point = unicode_point << 6 | (continuation_byte & 0b0011_1111) as u32;
+            }
+
+            // 将Unicode码点转换为字符
+            match std::char::from_u32(unicode_point) {
+                Some(ch) => {
+                    trace!(
+                        "UTF-8 decoder: successfully decoded character '{}' (U+{:04X}) from {} bytes",
+                        ch, unicode_point, byte_count
+                    );
+                    Ok(Some(ch))
+                }
+                None => {
+                    error!(
+                        "UTF-8 decoder: invalid Unicode code point U+{:08X}",
+                        unicode_point
+                    );
+                    Err(EditorError::invalid_encoding(
+                        start,
+                        format!("Invalid Unicode code point U+{:08X}", unicode_point),
+                        bytes_collected,
+                    ))
+                }
+            }
+        } else {
+            error!("UTF-8 decoder: invalid leading byte 0x{:02X}", leading_byte);
+            Err(EditorError::invalid_encoding(
+                start,
+                format!("Invalid UTF-8 leading byte 0x{:02X}", leading_byte),
+                vec![leading_byte],
+            ))
+        }
+    }
+
+    pub fn position(&self) -> usize {
+        self.byte_stream.position()
+    }
+
+    pub fn buffered_count(&self) -> usize {
+        self.byte_stream.buffered_count()
+    }
+
+    pub fn take_stream(self) -> ByteStream<R> {
+        self.byte_stream
+    }
+
+    pub async fn wait_input(&mut self) -> Result<()> {
+        self.byte_stream.wait_data().await
+    }
+
+    pub async fn is_next_esc(&mut self) -> bool {
+        // 输入结束时预读到的是空切片
+        matches!(self.byte_stream.peek_ahead(1).await, Ok([0x1B, ..]))
+    }
+
+    pub async fn read_line(&mut self) -> Result<Option<String>> {
+        let mut line = String::new();
+        loop {
+            match self.decode_char().await? {
+                Some(c) => {
+                    if c == '\n' {
+                        break;
+                    } else if c == '\r' {
+                        // 忽略回车符
+                        continue;
+                    } else {
+                        line.push(c);
+                    }
+                }
+                None => {
+                    // EOF reached
+                    if line.is_empty() {
+                        return Ok(None);
+                    } else {
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(Some(line))
+    }
+
+    // pub fn get_name(&self) -> &'static str {
+    //     "UTF-8"
+    // }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::fixture::ChunkedReader;
+
+    const CHUNKS: [usize; 4] = [1, 2, 3, 64];
+
+    fn decoder(bytes: &[u8], chunk: usize) -> Utf8Decoder<ChunkedReader> {
+        Utf8Decoder::new(ByteStream::with_capacity(ChunkedReader::new(bytes, chunk), chunk))
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_sequences_with_their_offset() {
+        type Variant = fn(&EditorError) -> bool;
+        let overlong: Variant = |e| matches!(e, EditorError::OverlongEncoding { .. });
+        let surrogate: Variant = |e| matches!(e, EditorError::SurrogateEncoding { .. });
+        let out_of_range: Variant = |e| matches!(e, EditorError::CodePointOutOfRange { .. });
+        let cases: &[(&[u8], Variant)] = &[
+            (&[0xC0, 0xAF], overlong),
+            (&[0xC1, 0xBF], overlong),
+            (&[0xE0, 0x80, 0xAF], overlong),
+            (&[0xE0, 0x9F, 0xBF], overlong),
+            (&[0xF0, 0x80, 0x80, 0x80], overlong),
+            (&[0xF0, 0x8F, 0xBF, 0xBF], overlong),
+            (&[0xED, 0xA0, 0x80], surrogate),
+            (&[0xED, 0xBF, 0xBF], surrogate),
+            (&[0xF4, 0x90, 0x80, 0x80], out_of_range),
+            (&[0xF5, 0x80, 0x80, 0x80], out_of_range),
+            (&[0xF7, 0xBF, 0xBF, 0xBF], out_of_range),
+        ];
+        for &(sequence, is_variant) in cases {
+            // 前面放两个ASCII字符，错误的位置应该是2
+            let bytes = [b"ab", sequence].concat();
+            for chunk in CHUNKS {
+                let mut decoder = decoder(&bytes, chunk);
+                assert_eq!(decoder.decode_char().await.unwrap(), Some('a'));
+                assert_eq!(decoder.decode_char().await.unwrap(), Some('b'));
+                let error = decoder.decode_char().await.unwrap_err();
+                assert!(is_variant(&error), "{sequence:02X?}: {error:?}");
+                let position = match error {
+                    EditorError::OverlongEncoding { position, .. }
+                    | EditorError::SurrogateEncoding { position, .. }
+                    | EditorError::CodePointOutOfRange { position, .. } => position,
+                    _ => unreachable!(),
+                };
+                assert_eq!(position, 2, "{sequence:02X?} read {chunk} bytes at a time");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_truncated_sequence_offset() {
+        for chunk in CHUNKS {
+            let mut decoder = decoder(&[0x61, 0x62, 0x63, 0xE4, 0xB8], chunk);
+            for c in "abc".chars() {
+                assert_eq!(decoder.decode_char().await.unwrap(), Some(c));
+            }
+            match decoder.decode_char().await {
+                Err(EditorError::UnexpectedEof { position, .. }) => assert_eq!(position, 3),
+                other => panic!("expected UnexpectedEof, got {other:?}"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn accepts_shortest_form_boundaries() {
+        let cases: &[(&[u8], char)] = &[
+            (&[0x7F], '\u{7F}'),
+            (&[0xC2, 0x80], '\u{80}'),
+            (&[0xDF, 0xBF], '\u{7FF}'),
+            (&[0xE0, 0xA0, 0x80], '\u{800}'),
+            (&[0xED, 0x9F, 0xBF], '\u{D7FF}'),
+            (&[0xEE, 0x80, 0x80], '\u{E000}'),
+            (&[0xEF, 0xBF, 0xBF], '\u{FFFF}'),
+            (&[0xF0, 0x90, 0x80, 0x80], '\u{10000}'),
+            (&[0xF4, 0x8F, 0xBF, 0xBF], '\u{10FFFF}'),
+        ];
+        for &(bytes, expected) in cases {
+            for chunk in CHUNKS {
+                let mut decoder = decoder(bytes, chunk);
+                assert_eq!(decoder.decode_char().await.unwrap(), Some(expected), "{bytes:02X?}");
+                assert_eq!(decoder.decode_char().await.unwrap(), None);
+                assert_eq!(decoder.position(), bytes.len());
+            }
+        }
+    }
+}
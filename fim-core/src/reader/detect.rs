@@ -0,0 +1,153 @@
+//! 打开文件时猜测文件的编码
+//! 先检查BOM，再根据开头一段字节的特征判断
+
+/// 文件开头的字节序标记
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bom {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Bom {
+    /// 检查bytes是否以BOM开头
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        [Bom::Utf8, Bom::Utf16Le, Bom::Utf16Be]
+            .into_iter()
+            .find(|bom| bytes.starts_with(bom.bytes()))
+    }
+
+    /// 编码对应的BOM，不支持BOM的编码返回None
+    pub fn for_encoding(encoding: &str) -> Option<Self> {
+        match encoding.to_ascii_lowercase().as_str() {
+            "utf-8" => Some(Bom::Utf8),
+            "utf-16le" => Some(Bom::Utf16Le),
+            "utf-16be" => Some(Bom::Utf16Be),
+            _ => None,
+        }
+    }
+
+    pub fn bytes(self) -> &'static [u8] {
+        match self {
+            Bom::Utf8 => b"\xEF\xBB\xBF",
+            Bom::Utf16Le => b"\xFF\xFE",
+            Bom::Utf16Be => b"\xFE\xFF",
+        }
+    }
+
+    pub fn encoding(self) -> &'static str {
+        match self {
+            Bom::Utf8 => "UTF-8",
+            Bom::Utf16Le => "UTF-16LE",
+            Bom::Utf16Be => "UTF-16BE",
+        }
+    }
+}
+
+/// 根据文件开头的字节猜测编码，返回的名称可以直接用来构造Decoder
+/// 无法判断时返回UTF-8，读取出错时再由用户用-e指定
+pub fn detect_encoding(sample: &[u8]) -> &'static str {
+    if let Some(bom) = Bom::detect(sample) {
+        return bom.encoding();
+    }
+    if is_utf8(sample) {
+        return "UTF-8";
+    }
+    #[cfg(feature = "cjk")]
+    if let Some(encoding) = guess_cjk(sample) {
+        return encoding;
+    }
+    "UTF-8"
+}
+
+/// 是否是合法的UTF-8
+/// sample是文件的一部分，末尾可能截断在字符中间，这种情况也算合法
+fn is_utf8(sample: &[u8]) -> bool {
+    match std::str::from_utf8(sample) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    }
+}
+
+/// 分别按GBK和Shift-JIS解码，选择错误较少的
+/// 两者都没有错误时，有假名的认为是Shift-JIS，否则是GBK
+/// 错误不比UTF-8少时返回None，这样个别字节损坏的UTF-8文件不会被误认为GBK
+#[cfg(feature = "cjk")]
+fn guess_cjk(sample: &[u8]) -> Option<&'static str> {
+    use encoding_rs::{Encoding, GBK, SHIFT_JIS, UTF_8};
+
+    let score = |encoding: &'static Encoding| {
+        let (text, _) = encoding.decode_without_bom_handling(sample);
+        // 末尾截断的字符会被解码为一个替换字符，不计入错误
+        let text = text.strip_suffix('\u{FFFD}').unwrap_or(&text).to_string();
+        let errors = text.matches('\u{FFFD}').count();
+        let kana = text
+            .chars()
+            .filter(|c| matches!(c, '\u{3040}'..='\u{30FF}' | '\u{FF66}'..='\u{FF9F}'))
+            .count();
+        (errors, kana)
+    };
+
+    let (utf8_errors, _) = score(UTF_8);
+    let (gbk_errors, _) = score(GBK);
+    let (sjis_errors, sjis_kana) = score(SHIFT_JIS);
+    let errors = gbk_errors.min(sjis_errors);
+    // 错误太多时说明两者都不是，比如二进制文件
+    if errors >= utf8_errors || errors * 20 > sample.len() {
+        return None;
+    }
+    if sjis_errors < gbk_errors || (sjis_errors == gbk_errors && sjis_kana > 0) {
+        Some("Shift-JIS")
+    } else {
+        Some("GBK")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bom_wins_over_content() {
+        for bom in [Bom::Utf8, Bom::Utf16Le, Bom::Utf16Be] {
+            let sample = [bom.bytes(), b"\xFF\xFF plain"].concat();
+            assert_eq!(Bom::detect(&sample), Some(bom));
+            assert_eq!(detect_encoding(&sample), bom.encoding());
+            assert_eq!(Bom::for_encoding(bom.encoding()), Some(bom));
+        }
+        // 只有BOM的一部分不算
+        assert_eq!(Bom::detect(b"\xEF\xBB"), None);
+    }
+
+    #[test]
+    fn ascii_and_utf8_are_utf8() {
+        assert_eq!(detect_encoding(b""), "UTF-8");
+        assert_eq!(detect_encoding(b"fn main() {}\r\n"), "UTF-8");
+        assert_eq!(detect_encoding("中文と日本語".as_bytes()), "UTF-8");
+        // 样本截断在字符中间
+        assert_eq!(detect_encoding(&"中文".as_bytes()[..5]), "UTF-8");
+    }
+
+    #[cfg(feature = "cjk")]
+    #[test]
+    fn guesses_gbk_and_shift_jis() {
+        use encoding_rs::{GBK, SHIFT_JIS};
+
+        let (gbk, _, _) = GBK.encode("这是一段用于测试编码检测的简体中文文本。");
+        assert_eq!(detect_encoding(&gbk), "GBK");
+        let (sjis, _, _) = SHIFT_JIS.encode("これはエンコーディング検出のテスト用の日本語です。");
+        assert_eq!(detect_encoding(&sjis), "Shift-JIS");
+        // 样本在双字节字符中间截断
+        assert_eq!(detect_encoding(&gbk[..gbk.len() - 1]), "GBK");
+    }
+
+    #[cfg(feature = "cjk")]
+    #[test]
+    fn short_or_binary_input_falls_back() {
+        // 两个字节既是GBK的"你"，也是Shift-JIS的半角假名加截断的字符，有假名时认为是Shift-JIS
+        assert_eq!(detect_encoding(b"\xC4\xE3"), "Shift-JIS");
+        // 错误不比UTF-8少时不猜测
+        assert_eq!(detect_encoding(b"\xFF"), "UTF-8");
+        assert_eq!(detect_encoding(&[0x00, 0xFF, 0xFE, 0x80, 0xFF, 0x81, 0xFD, 0xFF]), "UTF-8");
+    }
+}
@@ -0,0 +1,96 @@
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEventKind};
+
+use crate::key::{ControlKey, Direction, Key, Modifiers, MouseEvent, SpecialKey};
+
+/// crossterm的终端事件对应的按键，和自带的转义序列解析得到的按键相同
+/// 粘贴的内容前后加上PasteStart和PasteEnd，不需要的事件返回空列表
+pub fn event_keys(event: Event) -> Vec<Key> {
+    match event {
+        Event::Key(key) => key_event(key).into_iter().collect(),
+        Event::Mouse(mouse) => vec![mouse_event(mouse)],
+        Event::Paste(text) => {
+            // 和终端直接发送的一样，换行用回车表示
+            let text = text.replace("\r\n", "\r").replace('\n', "\r");
+            let mut keys = vec![Key::SpecialKey(SpecialKey::PasteStart)];
+            keys.extend(text.chars().map(Key::from_char));
+            keys.push(Key::SpecialKey(SpecialKey::PasteEnd));
+            keys
+        }
+        Event::FocusGained => vec![Key::SpecialKey(SpecialKey::FocusGained)],
+        Event::FocusLost => vec![Key::SpecialKey(SpecialKey::FocusLost)],
+        Event::Resize(..) => vec![Key::SpecialKey(SpecialKey::Resize)],
+    }
+}
+
+fn key_event(event: KeyEvent) -> Option<Key> {
+    // 只有支持kitty键盘协议的终端才会报告按键释放
+    if event.kind == KeyEventKind::Release {
+        return None;
+    }
+    let mut modifiers = Modifiers {
+        shift: event.modifiers.contains(KeyModifiers::SHIFT),
+        alt: event.modifiers.contains(KeyModifiers::ALT),
+        ctrl: event.modifiers.contains(KeyModifiers::CONTROL),
+    };
+    let key = match event.code {
+        KeyCode::Char(c) => {
+            // 字符本身已经是按下Shift之后的字符
+            modifiers.shift = false;
+            // 终端把Ctrl+空格发送为NUL
+            if modifiers.ctrl && c == ' ' { Key::Char('@') } else { Key::Char(c) }
+        }
+        KeyCode::Backspace => Key::ControlKey(ControlKey::Backspace),
+        KeyCode::Enter => Key::ControlKey(ControlKey::CR),
+        KeyCode::Left => Key::ArrowKey(Direction::Left),
+        KeyCode::Right => Key::ArrowKey(Direction::Right),
+        KeyCode::Up => Key::ArrowKey(Direction::Up),
+        KeyCode::Down => Key::ArrowKey(Direction::Down),
+        KeyCode::Home => Key::ControlKey(ControlKey::Home),
+        KeyCode::End => Key::ControlKey(ControlKey::End),
+        KeyCode::PageUp => Key::ControlKey(ControlKey::PageUp),
+        KeyCode::PageDown => Key::ControlKey(ControlKey::PageDown),
+        KeyCode::Tab => Key::ControlKey(ControlKey::Tab),
+        KeyCode::BackTab => {
+            modifiers.shift = true;
+            Key::ControlKey(ControlKey::Tab)
+        }
+        KeyCode::Delete => Key::ControlKey(ControlKey::Delete),
+        KeyCode::Insert => Key::ControlKey(ControlKey::Insert),
+        KeyCode::F(n) => Key::FunctionKey(n),
+        KeyCode::Null => Key::ControlKey(ControlKey::Ctrl('@')),
+        KeyCode::Esc => Key::ControlKey(ControlKey::Escape),
+        KeyCode::CapsLock => Key::SpecialKey(SpecialKey::CapsLock),
+        KeyCode::ScrollLock => Key::SpecialKey(SpecialKey::ScrollLock),
+        KeyCode::NumLock => Key::SpecialKey(SpecialKey::NumLock),
+        KeyCode::PrintScreen => Key::SpecialKey(SpecialKey::PrintScreen),
+        KeyCode::Pause => Key::SpecialKey(SpecialKey::PauseBreak),
+        KeyCode::Menu => Key::SpecialKey(SpecialKey::Menu),
+        KeyCode::KeypadBegin | KeyCode::Media(_) | KeyCode::Modifier(_) => return None,
+    };
+    Some(Key::with_modifiers(modifiers, key))
+}
+
+fn mouse_event(event: event::MouseEvent) -> Key {
+    let (x, y) = (event.column, event.row);
+    let modifiers = Modifiers {
+        shift: event.modifiers.contains(KeyModifiers::SHIFT),
+        alt: event.modifiers.contains(KeyModifiers::ALT),
+        ctrl: event.modifiers.contains(KeyModifiers::CONTROL),
+    };
+    let button = |button: MouseButton| match button {
+        MouseButton::Left => 0,
+        MouseButton::Middle => 1,
+        MouseButton::Right => 2,
+    };
+    let event = match event.kind {
+        MouseEventKind::Down(b) => MouseEvent::Click(button(b), x, y),
+        MouseEventKind::Up(b) => MouseEvent::Release(button(b), x, y),
+        MouseEventKind::Drag(b) => MouseEvent::Drag(button(b), x, y),
+        MouseEventKind::Moved => MouseEvent::Move(x, y),
+        MouseEventKind::ScrollUp => MouseEvent::Scroll(-1, x, y),
+        MouseEventKind::ScrollDown => MouseEvent::Scroll(1, x, y),
+        MouseEventKind::ScrollLeft => MouseEvent::HScroll(-1, x, y),
+        MouseEventKind::ScrollRight => MouseEvent::HScroll(1, x, y),
+    };
+    Key::with_modifiers(modifiers, Key::MouseEvent(event))
+}
@@ -0,0 +1,307 @@
+//! 把内存中的字节当作输入驱动解码器和按键解析，检查解码结果
+//! 用于验证编码的边界情况（过长编码、代理、被截断的序列）和模糊测试
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, ReadBuf};
+
+use super::{ByteStream, Decoder, KeyStream};
+use crate::key::Key;
+use crate::error::Result;
+
+/// 内存中的输入，每次最多读出chunk个字节
+/// 模拟终端和管道分多次到达的数据，多字节字符和转义序列可能被拆到两次读取中
+pub struct ChunkedReader {
+    bytes: Vec<u8>,
+    offset: usize,
+    chunk: usize,
+}
+
+impl ChunkedReader {
+    pub fn new(bytes: impl Into<Vec<u8>>, chunk: usize) -> Self {
+        Self {
+            bytes: bytes.into(),
+            offset: 0,
+            chunk: chunk.max(1),
+        }
+    }
+}
+
+impl AsyncRead for ChunkedReader {
+    fn poll_read(mut self: Pin<&mut Self>, _: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        let end = self.bytes.len().min(self.offset + self.chunk.min(buf.remaining()));
+        let offset = self.offset;
+        buf.put_slice(&self.bytes[offset..end]);
+        self.offset = end;
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// 读取内存中字节的解码器，每次读取chunk个字节
+pub fn decoder(encoding: &str, bytes: &[u8], chunk: usize, lossy: bool) -> Result<Decoder<ChunkedReader>> {
+    Decoder::builder()
+        .encoding(encoding.to_owned())
+        .byte_stream(ByteStream::with_capacity(ChunkedReader::new(bytes, chunk), chunk))
+        .lossy(lossy)
+        .build()
+}
+
+/// 用encoding解码全部字节，每次读取chunk个字节，遇到无效的字节时返回错误
+pub async fn decode_chars(encoding: &str, bytes: &[u8], chunk: usize, lossy: bool) -> Result<String> {
+    let mut decoder = decoder(encoding, bytes, chunk, lossy)?;
+    let mut text = String::new();
+    while let Some(c) = decoder.decode_char().await? {
+        text.push(c);
+    }
+    Ok(text)
+}
+
+/// 把全部字节当作终端的输入，解析出所有按键
+pub async fn parse_keys(bytes: &[u8], chunk: usize) -> Result<Vec<Key>> {
+    let mut key_stream = KeyStream::new(decoder("utf-8", bytes, chunk, false)?);
+    let mut keys = Vec::new();
+    while let Some(key) = key_stream.next_key().await? {
+        keys.push(key);
+    }
+    Ok(keys)
+}
+
+/// 按1个字节到整块的各种读取大小解码，结果都要和expected相同
+/// expected为None时期望解码出错，返回第一个不符合期望的情况
+pub async fn check_decode(encoding: &str, bytes: &[u8], expected: Option<&str>) -> std::result::Result<(), String> {
+    for chunk in chunk_sizes(bytes) {
+        let decoded = decode_chars(encoding, bytes, chunk, false).await;
+        match (&decoded, expected) {
+            (Ok(text), Some(expected)) if text == expected => {}
+            (Err(_), None) => {}
+            _ => {
+                return Err(format!(
+                    "{encoding} {bytes:02X?} read {chunk} bytes at a time: expected {expected:?}, got {decoded:?}"
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 按各种读取大小解析按键，结果都要和expected相同
+pub async fn check_keys(bytes: &[u8], expected: &[Key]) -> std::result::Result<(), String> {
+    for chunk in chunk_sizes(bytes) {
+        match parse_keys(bytes, chunk).await {
+            Ok(keys) if keys == expected => {}
+            parsed => return Err(format!("{bytes:02X?} read {chunk} bytes at a time: expected {expected:?}, got {parsed:?}")),
+        }
+    }
+    Ok(())
+}
+
+/// 模糊测试UTF-8解码器：任意输入都不能panic
+/// 有损模式下必须消耗全部字节，被替换的位置都在输入范围内，解码出的字符重新编码后不能比输入长
+pub async fn fuzz_decode(bytes: &[u8]) {
+    let Ok(mut decoder) = decoder("utf-8", bytes, bytes.len(), true) else {
+        return;
+    };
+    let mut encoded_len = 0;
+    while let Ok(Some(c)) = decoder.decode_char().await {
+        if c != char::REPLACEMENT_CHARACTER {
+            encoded_len += c.len_utf8();
+        }
+    }
+    assert_eq!(decoder.position(), bytes.len());
+    assert!(encoded_len <= bytes.len());
+    assert!(decoder.take_damage().iter().all(|&offset| offset < bytes.len()));
+}
+
+/// 模糊测试转义序列的解析：任意输入都不能panic，一次读取和逐字节读取都能读到输入结束
+pub async fn fuzz_keys(bytes: &[u8]) {
+    for chunk in [1, bytes.len()] {
+        let _ = parse_keys(bytes, chunk).await;
+    }
+}
+
+/// 逐字节、两个字节、三个字节和一次读完
+fn chunk_sizes(bytes: &[u8]) -> impl Iterator<Item = usize> {
+    let mut sizes = vec![1, 2, 3, bytes.len().max(1)];
+    sizes.dedup();
+    sizes.into_iter()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::{ControlKey, Direction, Modifiers, MouseEvent, SpecialKey};
+    use crate::reader::record;
+
+    #[tokio::test]
+    async fn decodes_valid_input() {
+        let cases: &[(&str, &[u8], &str)] = &[
+            ("utf-8", b"plain ascii\r\n", "plain ascii\r\n"),
+            ("utf-8", "é中😀".as_bytes(), "é中😀"),
+            ("utf-16le", &[0x41, 0x00, 0x3D, 0xD8, 0x00, 0xDE], "A😀"),
+            ("utf-16be", &[0x00, 0x41, 0xD8, 0x3D, 0xDE, 0x00], "A😀"),
+            ("ascii", b"\x1b[A~", "\x1b[A~"),
+        ];
+        for &(encoding, bytes, expected) in cases {
+            check_decode(encoding, bytes, Some(expected)).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_input() {
+        let cases: &[(&str, &[u8])] = &[
+            // 过长编码
+            ("utf-8", &[0xC0, 0xAF]),
+            ("utf-8", &[0xE0, 0x80, 0xAF]),
+            ("utf-8", &[0xF0, 0x80, 0x80, 0xAF]),
+            // 代理
+            ("utf-8", &[0xED, 0xA0, 0x80]),
+            ("utf-8", &[0xED, 0xBF, 0xBF]),
+            // 超过U+10FFFF
+            ("utf-8", &[0xF4, 0x90, 0x80, 0x80]),
+            ("utf-8", &[0xF5, 0x80, 0x80, 0x80]),
+            // 被截断的序列
+            ("utf-8", &[0x61, 0xE4, 0xB8]),
+            ("utf-8", &[0xF0, 0x9F, 0x98]),
+            // 续字节单独出现
+            ("utf-8", &[0x80]),
+            ("utf-8", &[0xE4, 0x41, 0x41]),
+            ("utf-16le", &[0x41, 0x00, 0x3D]),
+            ("utf-16le", &[0x3D, 0xD8, 0x41, 0x00]),
+            ("utf-16le", &[0x00, 0xDE]),
+            ("ascii", &[0x80]),
+        ];
+        for &(encoding, bytes) in cases {
+            check_decode(encoding, bytes, None).await.unwrap();
+        }
+    }
+
+    #[cfg(feature = "cjk")]
+    #[tokio::test]
+    async fn decodes_cjk_input() {
+        check_decode("gbk", &[0xD6, 0xD0, 0x41], Some("中A")).await.unwrap();
+        check_decode("shift-jis", &[0x82, 0xA0, 0x41], Some("あA")).await.unwrap();
+        check_decode("gbk", &[0xD6], None).await.unwrap();
+        check_decode("gbk", &[0x81, 0x30, 0x81], None).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn lossy_decoding_replaces_each_invalid_sequence() {
+        for chunk in [1, 2, 3, 64] {
+            let text = decode_chars("utf-8", &[0x61, 0xC0, 0xAF, 0x62, 0xE4, 0xB8], chunk, true).await.unwrap();
+            assert_eq!(text, "a\u{FFFD}\u{FFFD}b\u{FFFD}");
+        }
+    }
+
+    #[tokio::test]
+    async fn parses_key_sequences() {
+        let ctrl = Modifiers { ctrl: true, ..Modifiers::default() };
+        let shift = Modifiers { shift: true, ..Modifiers::default() };
+        let cases: Vec<(&[u8], Vec<Key>)> = vec![
+            // CSI
+            (b"\x1b[A\x1b[D", vec![Key::ArrowKey(Direction::Up), Key::ArrowKey(Direction::Left)]),
+            (b"\x1b[3~", vec![Key::ControlKey(ControlKey::Delete)]),
+            (b"\x1b[1;5C", vec![Key::Modified(ctrl, Box::new(Key::ArrowKey(Direction::Right)))]),
+            (b"\x1b[15;2~", vec![Key::Modified(shift, Box::new(Key::FunctionKey(5)))]),
+            // F13到F24
+            (
+                b"\x1b[25~\x1b[34~\x1b[42~\x1b[45;5~",
+                vec![
+                    Key::FunctionKey(13),
+                    Key::FunctionKey(20),
+                    Key::FunctionKey(21),
+                    Key::Modified(ctrl, Box::new(Key::FunctionKey(24))),
+                ],
+            ),
+            // CSI u，Ctrl+字母和传统编码一样，Ctrl+Shift+字母保留修饰键
+            (
+                b"\x1b[117;6u\x1b[97;5u\x1b[57376u\x1b[57387;2u",
+                vec![
+                    Key::Modified(Modifiers { shift: true, ctrl: true, ..Modifiers::default() }, Box::new(Key::Char('u'))),
+                    Key::ControlKey(ControlKey::Ctrl('a')),
+                    Key::FunctionKey(13),
+                    Key::Modified(shift, Box::new(Key::FunctionKey(24))),
+                ],
+            ),
+            // SS3
+            (b"\x1bOP\x1bO2Q", vec![Key::FunctionKey(1), Key::Modified(shift, Box::new(Key::FunctionKey(2)))]),
+            // SGR鼠标
+            (
+                b"\x1b[<0;10;5M\x1b[<0;10;5m\x1b[<65;1;1M",
+                vec![
+                    Key::MouseEvent(MouseEvent::Click(0, 9, 4)),
+                    Key::MouseEvent(MouseEvent::Release(0, 9, 4)),
+                    Key::MouseEvent(MouseEvent::Scroll(1, 0, 0)),
+                ],
+            ),
+            // 修饰键的位：4是Shift，8是Alt，16是Ctrl
+            (
+                b"\x1b[<16;3;2M\x1b[<4;3;2M\x1b[<28;1;1M",
+                vec![
+                    Key::Modified(ctrl, Box::new(Key::MouseEvent(MouseEvent::Click(0, 2, 1)))),
+                    Key::Modified(shift, Box::new(Key::MouseEvent(MouseEvent::Click(0, 2, 1)))),
+                    Key::Modified(
+                        Modifiers { shift: true, alt: true, ctrl: true },
+                        Box::new(Key::MouseEvent(MouseEvent::Click(0, 0, 0))),
+                    ),
+                ],
+            ),
+            // 水平滚轮
+            (
+                b"\x1b[<66;1;1M\x1b[<67;1;1M",
+                vec![
+                    Key::MouseEvent(MouseEvent::HScroll(-1, 0, 0)),
+                    Key::MouseEvent(MouseEvent::HScroll(1, 0, 0)),
+                ],
+            ),
+            // 括号粘贴
+            (
+                b"\x1b[200~a\rb\x1b[201~",
+                vec![
+                    Key::SpecialKey(SpecialKey::PasteStart),
+                    Key::Char('a'),
+                    Key::ControlKey(ControlKey::CR),
+                    Key::Char('b'),
+                    Key::SpecialKey(SpecialKey::PasteEnd),
+                ],
+            ),
+            // 焦点事件
+            (
+                b"\x1b[I\x1b[Oa",
+                vec![
+                    Key::SpecialKey(SpecialKey::FocusGained),
+                    Key::SpecialKey(SpecialKey::FocusLost),
+                    Key::Char('a'),
+                ],
+            ),
+            // ESC ESC是两次Escape，ESC加按键是Alt
+            (b"\x1b\x1b", vec![Key::ControlKey(ControlKey::Escape), Key::ControlKey(ControlKey::Escape)]),
+            (b"\x1b\x1b[A", vec![Key::ControlKey(ControlKey::Escape), Key::ArrowKey(Direction::Up)]),
+            (b"\x1bx", vec![Key::ControlKey(ControlKey::Alt('x'))]),
+            (b"\x1b", vec![Key::ControlKey(ControlKey::Escape)]),
+        ];
+        for (bytes, expected) in cases {
+            check_keys(bytes, &expected).await.unwrap();
+            // 录制的按键回放时得到相同的按键
+            let recording: String = expected.iter().map(|key| record::format_key(key) + "\n").collect();
+            assert_eq!(record::parse_recording(&recording).unwrap(), expected, "{recording}");
+        }
+    }
+
+    #[tokio::test]
+    async fn fuzz_entry_points_accept_edge_cases() {
+        let inputs: &[&[u8]] = &[
+            b"",
+            &[0xFF],
+            &[0xF0, 0x9F, 0x41, 0xFF, 0xE4],
+            &[0xED, 0xA0, 0x80, 0xC0],
+            b"\x1b[1;5\x1b\x1bO",
+            b"\x1b[<999999;1;1M",
+            b"\x1b[200~\x1b",
+        ];
+        for bytes in inputs {
+            fuzz_decode(bytes).await;
+            fuzz_keys(bytes).await;
+        }
+    }
+}
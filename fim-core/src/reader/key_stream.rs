@@ -0,0 +1,558 @@
+use std::collections::VecDeque;
+use std::io::Write;
+use std::time::Duration;
+
+use crossterm::event::EventStream;
+use futures::StreamExt;
+use serde::Deserialize;
+use tokio::io::AsyncReadExt;
+use tokio::time;
+use tracing::{debug, instrument, warn};
+
+use super::decoder::Decoder;
+use super::{events, record};
+use crate::key::{ControlKey, Direction, Key, Modifiers, MouseEvent, SpecialKey};
+use crate::error::{EditorError, Result};
+
+// 按键解析状态
+// #[derive(Debug, Clone, PartialEq)]
+// pub enum SequenceState {
+//     /// 正常状态，处理普通字符
+//     Normal,
+//     /// 转义序列状态，处理所有以ESC开头的序列
+//     EscapeSequence,
+// }
+
+/// 转义序列的最大长度，用于预分配缓冲区
+const MAX_ESCAPE_SEQUENCE_LENGTH: usize = 32;
+/// 字符缓冲区的初始容量
+// const CHAR_BUFFER_CAPACITY: usize = 32;
+/// 转义序列超时时间（毫秒）
+const BUFFER_SIZE: usize = 10;
+const ESCAPE_SEQUENCE_TIMEOUT: Duration = Duration::from_millis(10);
+/// kitty键盘协议中F13和F24的码位
+const CSI_U_F13: u32 = 57376;
+const CSI_U_F24: u32 = 57387;
+
+/// 读取终端按键的方式，在配置文件中选择
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InputBackend {
+    /// 解码标准输入并自己解析转义序列
+    #[default]
+    Builtin,
+    /// 使用crossterm的事件流
+    Crossterm,
+}
+
+/// 按键的来源
+enum Input<R: AsyncReadExt + Unpin> {
+    /// 解码终端的输入并自己解析转义序列
+    Decoder(Decoder<R>),
+    /// crossterm解析好的终端事件
+    Events(EventStream),
+}
+
+pub struct KeyStream<R: AsyncReadExt + Unpin> {
+    input: Input<R>,
+    // state: SequenceState,
+    buffer: VecDeque<Key>,
+    // 记录读到的按键
+    recorder: Option<Box<dyn Write + Send>>,
+}
+
+impl<R: AsyncReadExt + Unpin> KeyStream<R> {
+    /// 解析decoder解码出的终端输入，ESC开头的字符按转义序列解析
+    #[instrument(skip(decoder))]
+    pub fn new(decoder: Decoder<R>) -> Self {
+        debug!(
+            "Creating new KeyStream with buffer capacities: {}",
+            BUFFER_SIZE
+        );
+        Self::with_input(Input::Decoder(decoder))
+    }
+
+    /// 使用crossterm读取终端事件，不需要解码器
+    /// 自带的转义序列解析不能正确处理某些终端的输入时使用
+    pub fn with_events() -> Self {
+        debug!("Creating new KeyStream backed by crossterm events");
+        Self::with_input(Input::Events(EventStream::new()))
+    }
+
+    fn with_input(input: Input<R>) -> Self {
+        Self {
+            input,
+            buffer: VecDeque::with_capacity(BUFFER_SIZE),
+            recorder: None,
+        }
+    }
+
+    /// 当前使用的编码，crossterm总是按UTF-8解码
+    pub fn encoding(&self) -> &'static str {
+        match &self.input {
+            Input::Decoder(decoder) => decoder.get_name(),
+            Input::Events(_) => "UTF-8",
+        }
+    }
+
+    /// 取出有损解码时被替换的字节偏移量
+    pub fn take_damage(&mut self) -> Vec<usize> {
+        match &mut self.input {
+            Input::Decoder(decoder) => decoder.take_damage(),
+            Input::Events(_) => Vec::new(),
+        }
+    }
+
+    /// 把之后读到的每个按键写入writer，每行一个，可以用record::parse_recording读回来重放
+    pub fn record(&mut self, writer: impl Write + Send + 'static) {
+        self.recorder = Some(Box::new(writer));
+    }
+
+    /// 先依次读到keys，然后才读取终端的输入，用于重放记录的按键
+    pub fn replay(&mut self, keys: impl IntoIterator<Item = Key>) {
+        self.buffer.extend(keys);
+    }
+
+    /// 把合成的按键放到最前面，下一次读取时先返回，在终端的输入和之前放入的按键之前
+    pub fn push_front(&mut self, key: Key) {
+        self.buffer.push_front(key);
+    }
+
+    /// 把多个按键按原来的顺序放到最前面，读取时依次返回
+    pub fn push_keys_front(&mut self, keys: impl IntoIterator<Item = Key>) {
+        let keys: Vec<Key> = keys.into_iter().collect();
+        for key in keys.into_iter().rev() {
+            self.buffer.push_front(key);
+        }
+    }
+
+    /// 还有没读取的按键，读取时不需要等待终端的输入
+    pub fn has_pending(&self) -> bool {
+        !self.buffer.is_empty()
+    }
+
+    /// 读取下一个按键，开启记录时同时写入记录
+    #[instrument(skip(self))]
+    pub async fn next_key(&mut self) -> Result<Option<Key>> {
+        let key = self.read_key().await?;
+        if let (Some(recorder), Some(key)) = (&mut self.recorder, &key) {
+            // 每个按键都立即写入，程序崩溃时也能保留崩溃前的按键
+            let written = writeln!(recorder, "{}", record::format_key(key)).and_then(|_| recorder.flush());
+            if let Err(e) = written {
+                warn!("stop recording keys: {}", e);
+                self.recorder = None;
+            }
+        }
+        Ok(key)
+    }
+
+    /// 解析字符为按键事件
+    ///
+    /// # Returns
+    /// - `Ok(Some(Key))` - 成功解析出一个按键
+    /// - `Ok(None)` - 需要更多字符才能完成解析
+    /// - `Err(error)` - 解析过程中发生错误
+    async fn read_key(&mut self) -> Result<Option<Key>> {
+        if !self.buffer.is_empty() {
+            return Ok(self.buffer.pop_front());
+        }
+
+        match &mut self.input {
+            Input::Decoder(decoder) => match decoder.decode_char().await? {
+                Some(c) if c.is_ascii() => Self::push_char(decoder, &mut self.buffer, c).await,
+                Some(c) => Self::read_text(decoder, &mut self.buffer, c).await?,
+                None => {}
+            },
+            Input::Events(events) => Self::read_event(events, &mut self.buffer).await?,
+        }
+        Ok(self.buffer.pop_front())
+    }
+
+    /// 读取一个终端事件，转换成按键放入缓冲区，输入结束时不放入任何按键
+    async fn read_event(events: &mut EventStream, buffer: &mut VecDeque<Key>) -> Result<()> {
+        // 按键释放等没有对应按键的事件会被跳过
+        while buffer.is_empty() {
+            match events.next().await {
+                Some(event) => buffer.extend(events::event_keys(event?)),
+                None => break,
+            }
+        }
+        Ok(())
+    }
+
+    /// 把读到的字符转换为按键放入缓冲区，ESC开头时解析转义序列
+    async fn push_char(decoder: &mut Decoder<R>, buffer: &mut VecDeque<Key>, c: char) {
+        if c != '\u{001B}' {
+            buffer.push_back(Self::convert_char_to_key(c));
+        } else if let Some(key) = Self::process_escape(decoder, buffer).await {
+            buffer.push_back(key);
+        }
+    }
+
+    /// 输入法提交的字符串会一次性到达，把紧跟在非ASCII字符后面、已经读入的可打印字符合并成一个Text按键
+    /// 这样整个字符串是一次插入，也只产生一个撤销步骤
+    /// ASCII字符开头的输入不合并，不影响按键映射
+    async fn read_text(decoder: &mut Decoder<R>, buffer: &mut VecDeque<Key>, first: char) -> Result<()> {
+        let mut text = String::from(first);
+        let mut result = Ok(None);
+        while decoder.buffered_count() > 0 {
+            match decoder.decode_char().await {
+                Ok(Some(c)) if !c.is_control() => text.push(c),
+                next => {
+                    result = next;
+                    break;
+                }
+            }
+        }
+        if text.len() == first.len_utf8() {
+            buffer.push_back(Key::Char(first));
+        } else {
+            debug!("grouped {} chars of committed text", text.chars().count());
+            buffer.push_back(Key::Text(text));
+        }
+        // 打断合并的字符按原来的方式处理
+        // 解码出错时先返回错误，合并的文本留在缓冲区中，下次读取时返回
+        if let Some(c) = result? {
+            Self::push_char(decoder, buffer, c).await;
+        }
+        Ok(())
+    }
+
+    /// 读取文件内容时使用，每个字符对应一个按键，不解析转义序列
+    pub async fn next_text_key(&mut self) -> Result<Option<Key>> {
+        match &mut self.input {
+            Input::Decoder(decoder) => Ok(decoder.decode_char().await?.map(Key::from_char)),
+            Input::Events(_) => self.read_key().await,
+        }
+    }
+
+    /// 等待直到有输入，不会消耗任何按键
+    /// 和next_key不同，这个future可以安全地在select!中取消
+    pub async fn wait_input(&mut self) -> Result<()> {
+        if !self.buffer.is_empty() {
+            return Ok(());
+        }
+        match &mut self.input {
+            Input::Decoder(decoder) => decoder.wait_input().await,
+            // 读到的事件放入缓冲区，取消时事件还留在crossterm的队列中
+            Input::Events(events) => Self::read_event(events, &mut self.buffer).await,
+        }
+    }
+
+    #[instrument(skip(decoder, buffer))]
+    async fn process_escape(decoder: &mut Decoder<R>, buffer: &mut VecDeque<Key>) -> Option<Key> {
+        let mut sequence = vec!['\u{001B}'];
+        // 如果next是esc，那么说明当前转义序列是失败的
+        // 且可能产生新的转义序列
+        // 如果不及时终止当前转义序列的处理
+        // 且不去处理新的可能的转义序列
+        // 则新的转义序列会和当前的一起识别为失败的转义序列，转换为普通字符
+        loop {
+            let Ok(is_next_esc) =
+                time::timeout(ESCAPE_SEQUENCE_TIMEOUT, decoder.is_next_esc()).await
+            else {
+                warn!(
+                    "escape sequence timeout after {}ms, flushing buffer",
+                    ESCAPE_SEQUENCE_TIMEOUT.as_millis()
+                );
+                break;
+            };
+
+            if is_next_esc {
+                break;
+            }
+
+            let Ok(Some(next)) = decoder.decode_char().await else {
+                break;
+            };
+
+            sequence.push(next);
+
+            if sequence.len() == MAX_ESCAPE_SEQUENCE_LENGTH {
+                warn!("KeyParser: escape sequence too long, flushing");
+                break;
+            }
+
+            match Self::parse_escape_sequence(&sequence) {
+                Err(_) => break,
+                Ok(None) => {}
+                Ok(Some(key)) => {
+                    return Some(key);
+                }
+            }
+        }
+
+        // ESC [和ESC O后面没有更多输入时是Alt+[和Alt+O
+        if let ['\u{001B}', c @ ('[' | 'O')] = sequence[..] {
+            return Some(Key::ControlKey(ControlKey::Alt(c)));
+        }
+
+        for c in sequence {
+            buffer.push_back(Self::convert_char_to_key(c));
+        }
+
+        None
+    }
+
+    fn parse_escape_sequence(sequence: &[char]) -> Result<Option<Key>> {
+        if sequence.len() < 2 {
+            return Ok(None);
+        }
+        match sequence[1] {
+            // CSI序列
+            '[' => Self::parse_csi_sequence(sequence),
+            // SS3序列
+            'O' => Self::parse_ss3_key(sequence),
+            // 终端把Alt+按键发送为ESC加上这个按键，在超时时间内紧跟着ESC到达
+            c if sequence.len() == 2 => {
+                let alt = Modifiers { alt: true, ..Modifiers::default() };
+                Ok(Some(Key::with_modifiers(alt, Self::convert_char_to_key(c))))
+            }
+            _ => Err(EditorError::invalid_sequence(
+                sequence.iter().collect::<String>(),
+                sequence.len(),
+            )),
+        }
+    }
+
+    fn parse_csi_sequence(sequence: &[char]) -> Result<Option<Key>> {
+        if sequence.len() < 3 {
+            return Ok(None);
+        }
+
+        match sequence[2] {
+            'A' => Ok(Some(Key::ArrowKey(Direction::Up))),
+            'B' => Ok(Some(Key::ArrowKey(Direction::Down))),
+            'C' => Ok(Some(Key::ArrowKey(Direction::Right))),
+            'D' => Ok(Some(Key::ArrowKey(Direction::Left))),
+            'H' => Ok(Some(Key::ControlKey(ControlKey::Home))),
+            'F' => Ok(Some(Key::ControlKey(ControlKey::End))),
+            // 开启焦点事件报告后，终端窗口获得和失去焦点
+            'I' => Ok(Some(Key::SpecialKey(SpecialKey::FocusGained))),
+            'O' => Ok(Some(Key::SpecialKey(SpecialKey::FocusLost))),
+            // Shift+Tab
+            'Z' => {
+                let shift = Modifiers { shift: true, ..Modifiers::default() };
+                Ok(Some(Key::Modified(shift, Box::new(Key::ControlKey(ControlKey::Tab)))))
+            }
+            // SGR(1006)格式的鼠标事件
+            '<' => Self::parse_mouse_event(sequence),
+            '0'..='9' => Self::parse_csi_with_number(sequence),
+            _ => Err(EditorError::invalid_sequence(
+                sequence.iter().collect::<String>(),
+                sequence.len(),
+            )),
+        }
+    }
+
+    /// 解析SS3序列，一些终端把修饰参数放在O和按键之间，比如Shift+F1是 ESC O 2 P
+    fn parse_ss3_key(sequence: &[char]) -> Result<Option<Key>> {
+        let last = sequence[sequence.len() - 1];
+        if sequence.len() < 3 || (sequence.len() == 3 && last.is_ascii_digit()) {
+            return Ok(None);
+        }
+        let modifiers = match sequence[2..] {
+            [param, _] => param.to_digit(10).map(|param| Modifiers::from_param(param as u8)),
+            [_] => Some(Modifiers::default()),
+            _ => None,
+        };
+
+        let key = match (modifiers, last) {
+            (Some(_), 'P') => Key::FunctionKey(1),
+            (Some(_), 'Q') => Key::FunctionKey(2),
+            (Some(_), 'R') => Key::FunctionKey(3),
+            (Some(_), 'S') => Key::FunctionKey(4),
+            _ => {
+                return Err(EditorError::invalid_sequence(
+                    sequence.iter().collect::<String>(),
+                    sequence.len(),
+                ));
+            }
+        };
+        Ok(Some(Key::with_modifiers(modifiers.unwrap_or_default(), key)))
+    }
+
+    /// 解析SGR鼠标事件：ESC [ < 按钮 ; x ; y M，释放按钮时以m结尾
+    /// 坐标从1开始，转换为从0开始
+    fn parse_mouse_event(sequence: &[char]) -> Result<Option<Key>> {
+        let invalid = || {
+            EditorError::invalid_sequence(sequence.iter().collect::<String>(), sequence.len())
+        };
+        let last = sequence[sequence.len() - 1];
+        if last.is_ascii_digit() || last == ';' || last == '<' {
+            return Ok(None);
+        }
+        if last != 'M' && last != 'm' {
+            return Err(invalid());
+        }
+
+        let params: String = sequence[3..sequence.len() - 1].iter().collect();
+        let params = params
+            .split(';')
+            .map(|p| p.parse::<u16>())
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|_| invalid())?;
+        let [code, x, y] = params[..] else {
+            return Err(invalid());
+        };
+        let (x, y) = (x.saturating_sub(1), y.saturating_sub(1));
+
+        // 低两位是按钮，32表示移动，64表示滚轮，4、8、16是Shift、Alt、Ctrl
+        // 滚轮的按钮0、1是上下滚动，2、3是左右滚动
+        let button = (code & 0b11) as u8;
+        let modifiers = Modifiers {
+            shift: code & 4 != 0,
+            alt: code & 8 != 0,
+            ctrl: code & 16 != 0,
+        };
+        let event = if code & 64 != 0 {
+            let direction = if button & 1 == 0 { -1 } else { 1 };
+            if button < 2 {
+                MouseEvent::Scroll(direction, x, y)
+            } else {
+                MouseEvent::HScroll(direction, x, y)
+            }
+        } else if last == 'm' {
+            MouseEvent::Release(button, x, y)
+        } else if code & 32 != 0 {
+            if button == 3 {
+                MouseEvent::Move(x, y)
+            } else {
+                MouseEvent::Drag(button, x, y)
+            }
+        } else {
+            MouseEvent::Click(button, x, y)
+        };
+        Ok(Some(Key::with_modifiers(modifiers, Key::MouseEvent(event))))
+    }
+
+    /// 解析以数字开头的CSI序列
+    /// 比如 ESC [ 3 ~ 是Delete，ESC [ 1 ; 5 C 是Ctrl+Right，分号后面是修饰参数
+    /// ESC [ 15 ; 2 ~ 是Shift+F5，ESC [ 25 ~ 是F13
+    /// ESC [ 117 ; 6 u 是Ctrl+Shift+U
+    fn parse_csi_with_number(sequence: &[char]) -> Result<Option<Key>> {
+        let len = sequence.len();
+        let last = sequence[len - 1];
+        // 参数还没有结束
+        if last.is_ascii_digit() || last == ';' {
+            return Ok(None);
+        }
+        let invalid = || {
+            EditorError::invalid_sequence(sequence.iter().collect::<String>(), sequence.len())
+        };
+
+        let params = &sequence[2..len - 1];
+        let (number_chars, modifiers) = match params.iter().position(|&c| c == ';') {
+            Some(i) => {
+                let param: String = params[i + 1..].iter().collect();
+                let param = param.parse::<u8>().map_err(|_| invalid())?;
+                (&params[..i], Modifiers::from_param(param))
+            }
+            None => (params, Modifiers::default()),
+        };
+
+        // CSI u：按键用码位表示，可以报告Ctrl+Shift+字母这样传统编码无法区分的组合
+        // F13到F24使用私有区的码位
+        if last == 'u' {
+            let code: String = number_chars.iter().collect();
+            let code = code.parse::<u32>().map_err(|_| invalid())?;
+            let key = match code {
+                CSI_U_F13..=CSI_U_F24 => Key::FunctionKey((code - CSI_U_F13 + 13) as u8),
+                code => Self::convert_char_to_key(char::from_u32(code).ok_or_else(invalid)?),
+            };
+            return Ok(Some(Key::with_modifiers(modifiers, key)));
+        }
+
+        let key = match (last, number_chars) {
+            ('A', ['1']) => Key::ArrowKey(Direction::Up),
+            ('B', ['1']) => Key::ArrowKey(Direction::Down),
+            ('C', ['1']) => Key::ArrowKey(Direction::Right),
+            ('D', ['1']) => Key::ArrowKey(Direction::Left),
+            ('H', ['1']) => Key::ControlKey(ControlKey::Home),
+            ('F', ['1']) => Key::ControlKey(ControlKey::End),
+            // 带修饰键的F1到F4，比如Shift+F3是 ESC [ 1 ; 2 R
+            ('P', ['1']) => Key::FunctionKey(1),
+            ('Q', ['1']) => Key::FunctionKey(2),
+            ('R', ['1']) => Key::FunctionKey(3),
+            ('S', ['1']) => Key::FunctionKey(4),
+            ('~', ['1']) => Key::ControlKey(ControlKey::Home),
+            ('~', ['2']) => Key::ControlKey(ControlKey::Insert),
+            ('~', ['3']) => Key::ControlKey(ControlKey::Delete),
+            ('~', ['4']) => Key::ControlKey(ControlKey::End),
+            ('~', ['5']) => Key::ControlKey(ControlKey::PageUp),
+            ('~', ['6']) => Key::ControlKey(ControlKey::PageDown),
+            ('~', ['1', '1']) => Key::FunctionKey(1),
+            ('~', ['1', '2']) => Key::FunctionKey(2),
+            ('~', ['1', '3']) => Key::FunctionKey(3),
+            ('~', ['1', '4']) => Key::FunctionKey(4),
+            ('~', ['1', '5']) => Key::FunctionKey(5),
+            ('~', ['1', '7']) => Key::FunctionKey(6),
+            ('~', ['1', '8']) => Key::FunctionKey(7),
+            ('~', ['1', '9']) => Key::FunctionKey(8),
+            ('~', ['2', '0']) => Key::FunctionKey(9),
+            ('~', ['2', '1']) => Key::FunctionKey(10),
+            ('~', ['2', '3']) => Key::FunctionKey(11),
+            ('~', ['2', '4']) => Key::FunctionKey(12),
+            // VT220的F13到F20，xterm也用它们报告Shift+F3到Shift+F10
+            ('~', ['2', '5']) => Key::FunctionKey(13),
+            ('~', ['2', '6']) => Key::FunctionKey(14),
+            ('~', ['2', '8']) => Key::FunctionKey(15),
+            ('~', ['2', '9']) => Key::FunctionKey(16),
+            ('~', ['3', '1']) => Key::FunctionKey(17),
+            ('~', ['3', '2']) => Key::FunctionKey(18),
+            ('~', ['3', '3']) => Key::FunctionKey(19),
+            ('~', ['3', '4']) => Key::FunctionKey(20),
+            // rxvt的F21到F24
+            ('~', ['4', '2']) => Key::FunctionKey(21),
+            ('~', ['4', '3']) => Key::FunctionKey(22),
+            ('~', ['4', '4']) => Key::FunctionKey(23),
+            ('~', ['4', '5']) => Key::FunctionKey(24),
+            ('~', ['2', '0', '0']) => Key::SpecialKey(SpecialKey::PasteStart),
+            ('~', ['2', '0', '1']) => Key::SpecialKey(SpecialKey::PasteEnd),
+            _ => return Err(invalid()),
+        };
+
+        Ok(Some(Key::with_modifiers(modifiers, key)))
+    }
+
+    fn convert_char_to_key(c: char) -> Key {
+        match c {
+            '\u{001B}' => Key::ControlKey(ControlKey::Escape),
+            '\r' => Key::ControlKey(ControlKey::CR),
+            // 换行 Line Feed
+            // raw mode下，enter键发送的是\r
+            // ctrl+J发送的是\n, ctrl+M发送的是\r
+            '\n' => Key::ControlKey(ControlKey::LF),
+            '\t' => Key::ControlKey(ControlKey::Tab),
+            // 在ascii码表中，127是Del，而8是BS
+            // 在现代计算机中，BS通常映射为127
+            // 而Del是转义序列 <esc>[3~
+            '\u{007F}' => Key::ControlKey(ControlKey::Backspace),
+            c @ '\u{0000}'..='\u{001F}' => {
+                Key::ControlKey(ControlKey::Ctrl(Self::ctrl_key_reverse(c).unwrap()))
+            },
+            // 回车 Carriage Return
+            _ => Key::Char(c),
+        }
+    }
+
+    /// 将 Ctrl+字符 转换回基础字符
+    fn ctrl_key_reverse(ctrl_char: char) -> Option<char> {
+        match ctrl_char as u8 {
+            0 => Some('@'),
+            // Ctrl+A 到 Ctrl+Z → a 到 z
+            1..=26 => Some((ctrl_char as u8 - 1 + b'a') as char),
+
+            // 特殊符号
+            27 => Some('['),  // Ctrl+[
+            28 => Some('\\'), // Ctrl+\
+            29 => Some(']'),  // Ctrl+]
+            30 => Some('^'),  // Ctrl+^
+            31 => Some('_'),  // Ctrl+_
+
+            // 无效控制字符
+            _ => None,
+        }
+    }
+}
+
@@ -0,0 +1,58 @@
+use crate::key::{Key, Modifiers, MouseEvent};
+
+/// 记录中的一个按键，和Key::notation相同，鼠标事件额外记录按钮和坐标
+/// 比如 <Mouse:Click,0,10,5>、<Mouse:Move,10,5>，带修饰键时是 <Mouse:C-Click,0,10,5>
+pub fn format_key(key: &Key) -> String {
+    let (modifiers, Key::MouseEvent(event)) = key.modified() else {
+        return key.notation();
+    };
+    let fields = match event {
+        MouseEvent::Click(button, x, y) => format!("Click,{},{},{}", button, x, y),
+        MouseEvent::Drag(button, x, y) => format!("Drag,{},{},{}", button, x, y),
+        MouseEvent::Release(button, x, y) => format!("Release,{},{},{}", button, x, y),
+        MouseEvent::Scroll(direction, x, y) => format!("Scroll,{},{},{}", direction, x, y),
+        MouseEvent::HScroll(direction, x, y) => format!("HScroll,{},{},{}", direction, x, y),
+        MouseEvent::Move(x, y) => format!("Move,{},{}", x, y),
+    };
+    format!("<Mouse:{}{}>", modifiers.prefix(), fields)
+}
+
+fn parse_mouse(fields: &str) -> Option<Key> {
+    let (modifiers, fields) = Modifiers::parse_prefix(fields);
+    let fields: Vec<&str> = fields.split(',').collect();
+    let event = match fields.as_slice() {
+        ["Click", button, x, y] => MouseEvent::Click(button.parse().ok()?, x.parse().ok()?, y.parse().ok()?),
+        ["Drag", button, x, y] => MouseEvent::Drag(button.parse().ok()?, x.parse().ok()?, y.parse().ok()?),
+        ["Release", button, x, y] => MouseEvent::Release(button.parse().ok()?, x.parse().ok()?, y.parse().ok()?),
+        ["Scroll", direction, x, y] => MouseEvent::Scroll(direction.parse().ok()?, x.parse().ok()?, y.parse().ok()?),
+        ["HScroll", direction, x, y] => MouseEvent::HScroll(direction.parse().ok()?, x.parse().ok()?, y.parse().ok()?),
+        ["Move", x, y] => MouseEvent::Move(x.parse().ok()?, y.parse().ok()?),
+        _ => return None,
+    };
+    Some(Key::with_modifiers(modifiers, Key::MouseEvent(event)))
+}
+
+/// 解析KeyStream::record写入的按键，每行一个按键，空行被忽略
+/// 一行中有多个字符时是一个Text按键
+/// 出错时返回出错的行号和原因
+pub fn parse_recording(text: &str) -> Result<Vec<Key>, String> {
+    let mut keys = Vec::new();
+    for (i, line) in text.lines().enumerate().filter(|(_, line)| !line.is_empty()) {
+        let key = match line.strip_prefix("<Mouse:").and_then(|l| l.strip_suffix('>')) {
+            Some(fields) => parse_mouse(fields),
+            None => match Key::parse_notation(line) {
+                Ok(parsed) if parsed.len() == 1 => parsed.into_iter().next(),
+                // 输入法提交的文本记录为一行中的多个字符
+                Ok(parsed) => parsed
+                    .iter()
+                    .map(|key| if let Key::Char(c) = key { Some(*c) } else { None })
+                    .collect::<Option<String>>()
+                    .map(Key::Text),
+                _ => None,
+            },
+        };
+        let key = key.ok_or_else(|| format!("line {}: invalid key {}", i + 1, line))?;
+        keys.push(key);
+    }
+    Ok(keys)
+}
@@ -0,0 +1,159 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::Stream;
+use futures::stream::{self, BoxStream, StreamExt};
+use tokio::io::AsyncReadExt;
+
+use super::{Decoder, KeyStream};
+use crate::error::Result;
+use crate::key::Key;
+
+/// 逐行读取解码后的文本，每一项是一行，不包含换行符
+/// CRLF、单独的CR和LF都是换行，最后一行没有换行符时也会返回
+/// 出错之后流结束，不再继续读取
+pub struct LineStream {
+    inner: BoxStream<'static, Result<String>>,
+}
+
+impl LineStream {
+    pub fn new<R: AsyncReadExt + Unpin + Send + 'static>(decoder: Decoder<R>) -> Self {
+        let inner = stream::unfold(Some((decoder, None)), |state| async move {
+            let (mut decoder, mut pending) = state?;
+            match Self::read_line(&mut decoder, &mut pending).await {
+                Ok(Some(line)) => Some((Ok(line), Some((decoder, pending)))),
+                Ok(None) => None,
+                Err(e) => Some((Err(e), None)),
+            }
+        });
+        Self { inner: inner.boxed() }
+    }
+
+    /// 读取一行，CR后面不是LF时，读到的字符放入pending，作为下一行的开头
+    async fn read_line<R: AsyncReadExt + Unpin>(
+        decoder: &mut Decoder<R>,
+        pending: &mut Option<char>,
+    ) -> Result<Option<String>> {
+        let mut line = String::new();
+        loop {
+            let c = match pending.take() {
+                Some(c) => Some(c),
+                None => decoder.decode_char().await?,
+            };
+            match c {
+                Some('\n') => break,
+                Some('\r') => {
+                    if let Some(c) = decoder.decode_char().await?
+                        && c != '\n'
+                    {
+                        *pending = Some(c);
+                    }
+                    break;
+                }
+                Some(c) => line.push(c),
+                None if line.is_empty() => return Ok(None),
+                None => break,
+            }
+        }
+        Ok(Some(line))
+    }
+}
+
+impl Stream for LineStream {
+    type Item = Result<String>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_unpin(cx)
+    }
+}
+
+/// KeyStream读到的按键，输入结束或者出错之后流结束
+pub struct Keys {
+    inner: BoxStream<'static, Result<Key>>,
+}
+
+impl Keys {
+    pub fn new<R: AsyncReadExt + Unpin + Send + 'static>(key_stream: KeyStream<R>) -> Self {
+        let inner = stream::unfold(Some(key_stream), |key_stream| async move {
+            let mut key_stream = key_stream?;
+            match key_stream.next_key().await {
+                Ok(Some(key)) => Some((Ok(key), Some(key_stream))),
+                Ok(None) => None,
+                Err(e) => Some((Err(e), None)),
+            }
+        });
+        Self { inner: inner.boxed() }
+    }
+}
+
+impl Stream for Keys {
+    type Item = Result<Key>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_unpin(cx)
+    }
+}
+
+impl<R: AsyncReadExt + Unpin + Send + 'static> Decoder<R> {
+    /// 转换为逐行读取的流
+    pub fn into_lines(self) -> LineStream {
+        LineStream::new(self)
+    }
+}
+
+impl<R: AsyncReadExt + Unpin + Send + 'static> KeyStream<R> {
+    /// 转换为按键的流，可以和其他流的组合子一起使用
+    pub fn into_keys(self) -> Keys {
+        Keys::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::EditorError;
+    use crate::key::{ControlKey, Direction};
+    use crate::reader::fixture;
+
+    async fn lines(bytes: &[u8], chunk: usize) -> Vec<Result<String>> {
+        fixture::decoder("utf-8", bytes, chunk, false).unwrap().into_lines().collect().await
+    }
+
+    #[tokio::test]
+    async fn splits_lines_on_crlf_cr_and_lf() {
+        for chunk in [1, 2, 64] {
+            let lines: Vec<String> = lines(b"crlf\r\ncr\rlf\n\r\n\rlast", chunk)
+                .await
+                .into_iter()
+                .collect::<Result<_>>()
+                .unwrap();
+            assert_eq!(lines, ["crlf", "cr", "lf", "", "", "last"], "read {chunk} bytes at a time");
+        }
+        // 最后的换行符后面没有空行
+        assert_eq!(lines(b"a\r", 1).await.len(), 1);
+        assert!(lines(b"", 1).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn ends_after_a_decode_error() {
+        let lines = lines(b"ok\nbad\xFFline\nnever\n", 1).await;
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].as_ref().unwrap(), "ok");
+        assert!(matches!(lines[1], Err(EditorError::InvalidEncoding { position: 6, .. })), "{:?}", lines[1]);
+    }
+
+    #[tokio::test]
+    async fn yields_keys_until_the_end_or_an_error() {
+        let keys = |bytes: &'static [u8]| async move {
+            let decoder = fixture::decoder("utf-8", bytes, 1, false).unwrap();
+            KeyStream::new(decoder).into_keys().collect::<Vec<_>>().await
+        };
+        let parsed: Vec<Key> = keys(b"a\x1b[A\r").await.into_iter().collect::<Result<_>>().unwrap();
+        assert_eq!(parsed, [Key::Char('a'), Key::ArrowKey(Direction::Up), Key::ControlKey(ControlKey::CR)]);
+
+        let parsed = keys(b"a\xFFb").await;
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(*parsed[0].as_ref().unwrap(), Key::Char('a'));
+        assert!(parsed[1].is_err());
+    }
+}
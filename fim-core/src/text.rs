@@ -0,0 +1,354 @@
+use std::ops::{Index, IndexMut, Range};
+use std::sync::Arc;
+
+/// 每个块最多保存的行数，超过时分成两块
+const MAX_CHUNK_LEN: usize = 1024;
+
+/// 按块存储的行，T是一行的内容，编辑器中是一行的文本
+/// 插入和删除行只需要移动所在块中的行，而不是整个文件的行，
+/// 这样编辑很大的文件时不会因为频繁移动内存而卡顿
+/// 块在克隆之间共享，修改时才复制所在的块，撤销历史中保存的快照只占用修改过的块
+#[derive(Clone)]
+pub struct TextBuffer<T> {
+    chunks: Vec<Arc<Vec<T>>>,
+    /// 每个块第一行的行号，用来二分查找行所在的块
+    starts: Vec<usize>,
+    len: usize,
+}
+
+impl<T> Default for TextBuffer<T> {
+    fn default() -> Self {
+        Self {
+            chunks: Vec::new(),
+            starts: Vec::new(),
+            len: 0,
+        }
+    }
+}
+
+impl<T: Clone> TextBuffer<T> {
+    /// 创建空的缓冲区，有了全部的行时用`TextBuffer::from(rows)`一次分好块
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// 第index行所在的块和在块中的位置
+    /// index不小于len时返回最后一个块的末尾，用于在末尾插入
+    fn locate(&self, index: usize) -> (usize, usize) {
+        if index >= self.len {
+            return match self.chunks.len() {
+                0 => (0, 0),
+                n => (n - 1, self.chunks[n - 1].len()),
+            };
+        }
+        let chunk = self.starts.partition_point(|&start| start <= index) - 1;
+        (chunk, index - self.starts[chunk])
+    }
+
+    /// 块的行数改变之后重新计算从chunk开始的每个块的起始行号
+    fn reindex(&mut self, chunk: usize) {
+        self.starts.truncate(chunk);
+        let mut start = chunk.checked_sub(1).map_or(0, |i| self.starts[i] + self.chunks[i].len());
+        for rows in &self.chunks[chunk..] {
+            self.starts.push(start);
+            start += rows.len();
+        }
+    }
+
+    /// 把rows分成不超过MAX_CHUNK_LEN行的块，分开的块大小相近
+    fn split_chunks(rows: Vec<T>) -> Vec<Arc<Vec<T>>> {
+        let count = rows.len().div_ceil(MAX_CHUNK_LEN).max(1);
+        let size = rows.len().div_ceil(count).max(1);
+        let mut rows = rows.into_iter().peekable();
+        let mut chunks = Vec::with_capacity(count);
+        while rows.peek().is_some() {
+            chunks.push(Arc::new(rows.by_ref().take(size).collect()));
+        }
+        chunks
+    }
+
+    /// 删除之后相邻的两个块放得下时合并，避免留下很多很小的块
+    fn merge_at(&mut self, chunk: usize) {
+        if chunk + 1 < self.chunks.len()
+            && self.chunks[chunk].len() + self.chunks[chunk + 1].len() <= MAX_CHUNK_LEN
+        {
+            let next = self.chunks.remove(chunk + 1);
+            Arc::make_mut(&mut self.chunks[chunk]).extend(next.iter().cloned());
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if index >= self.len {
+            return None;
+        }
+        let (chunk, offset) = self.locate(index);
+        Some(&self.chunks[chunk][offset])
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        if index >= self.len {
+            return None;
+        }
+        let (chunk, offset) = self.locate(index);
+        Some(&mut Arc::make_mut(&mut self.chunks[chunk])[offset])
+    }
+
+    pub fn last_mut(&mut self) -> Option<&mut T> {
+        self.chunks.last_mut().and_then(|c| Arc::make_mut(c).last_mut())
+    }
+
+    pub fn insert(&mut self, index: usize, row: T) {
+        assert!(index <= self.len, "insert index out of range");
+        if self.chunks.is_empty() {
+            self.chunks.push(Arc::default());
+        }
+        let (chunk, offset) = self.locate(index);
+        let rows = Arc::make_mut(&mut self.chunks[chunk]);
+        rows.insert(offset, row);
+        self.len += 1;
+        // 块太大时从中间分开
+        if rows.len() > MAX_CHUNK_LEN {
+            let second = rows.split_off(rows.len() / 2);
+            self.chunks.insert(chunk + 1, Arc::new(second));
+        }
+        self.reindex(chunk);
+    }
+
+    pub fn push(&mut self, row: T) {
+        self.insert(self.len, row);
+    }
+
+    pub fn remove(&mut self, index: usize) -> T {
+        assert!(index < self.len, "remove index out of range");
+        let (chunk, offset) = self.locate(index);
+        let row = Arc::make_mut(&mut self.chunks[chunk]).remove(offset);
+        if self.chunks[chunk].is_empty() {
+            self.chunks.remove(chunk);
+        }
+        self.len -= 1;
+        self.reindex(chunk);
+        row
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        (!self.is_empty()).then(|| self.remove(self.len - 1))
+    }
+
+    /// 删除一段连续的行，中间完整的块直接去掉，只修改首尾两个块
+    pub fn remove_range(&mut self, range: Range<usize>) {
+        assert!(range.end <= self.len, "remove range out of range");
+        if range.is_empty() {
+            return;
+        }
+        let (first, start) = self.locate(range.start);
+        let (last, end) = self.locate(range.end - 1);
+        if first == last {
+            Arc::make_mut(&mut self.chunks[first]).drain(start..=end);
+        } else {
+            Arc::make_mut(&mut self.chunks[first]).truncate(start);
+            Arc::make_mut(&mut self.chunks[last]).drain(..=end);
+            self.chunks.drain(first + 1..last);
+        }
+        self.chunks.retain(|rows| !rows.is_empty());
+        self.len -= range.len();
+        self.reindex(first.min(self.chunks.len()));
+        // 首尾的块可能已经被去掉，合并删除位置前后的两个块
+        if !self.chunks.is_empty() {
+            let chunk = self.locate(range.start.saturating_sub(1)).0;
+            self.merge_at(chunk);
+            self.reindex(chunk);
+        }
+    }
+
+    /// 在index处依次插入多行，插入位置所在的块和新的行一起重新分块
+    pub fn insert_rows(&mut self, index: usize, rows: impl IntoIterator<Item = T>) {
+        assert!(index <= self.len, "insert index out of range");
+        let rows: Vec<T> = rows.into_iter().collect();
+        if rows.is_empty() {
+            return;
+        }
+        self.len += rows.len();
+        if self.chunks.is_empty() {
+            self.chunks = Self::split_chunks(rows);
+            self.reindex(0);
+            return;
+        }
+        let (chunk, offset) = self.locate(index);
+        let mut combined = Arc::unwrap_or_clone(std::mem::take(&mut self.chunks[chunk]));
+        let tail = combined.split_off(offset);
+        combined.extend(rows);
+        combined.extend(tail);
+        self.chunks.splice(chunk..=chunk, Self::split_chunks(combined));
+        self.reindex(chunk);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.chunks.iter().flat_map(|c| c.iter())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.chunks.iter_mut().flat_map(|c| Arc::make_mut(c).iter_mut())
+    }
+
+    /// 从第start行开始遍历，跳过前面的块而不是逐行跳过
+    pub fn iter_from(&self, start: usize) -> impl Iterator<Item = &T> {
+        let (chunk, offset) = self.locate(start.min(self.len));
+        let first = self.chunks.get(chunk).map_or(&[][..], |c| &c[offset..]);
+        first.iter().chain(self.chunks.iter().skip(chunk + 1).flat_map(|c| c.iter()))
+    }
+
+    pub fn range(&self, range: Range<usize>) -> impl Iterator<Item = &T> {
+        self.iter_from(range.start).take(range.len())
+    }
+
+    /// 只复制range所在的块
+    pub fn range_mut(&mut self, range: Range<usize>) -> impl Iterator<Item = &mut T> {
+        let (chunk, offset) = self.locate(range.start.min(self.len));
+        self.chunks
+            .iter_mut()
+            .skip(chunk)
+            .flat_map(|c| Arc::make_mut(c).iter_mut())
+            .skip(offset)
+            .take(range.len())
+    }
+}
+
+impl<T: Clone> From<Vec<T>> for TextBuffer<T> {
+    fn from(rows: Vec<T>) -> Self {
+        let len = rows.len();
+        let mut chunks = Vec::with_capacity(len.div_ceil(MAX_CHUNK_LEN));
+        let mut rows = rows.into_iter().peekable();
+        while rows.peek().is_some() {
+            chunks.push(Arc::new(rows.by_ref().take(MAX_CHUNK_LEN).collect()));
+        }
+        let mut text = Self { chunks, starts: Vec::new(), len };
+        text.reindex(0);
+        text
+    }
+}
+
+impl<T: Clone> Index<usize> for TextBuffer<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        self.get(index).expect("row index out of range")
+    }
+}
+
+impl<T: Clone> IndexMut<usize> for TextBuffer<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        self.get_mut(index).expect("row index out of range")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(range: Range<usize>) -> Vec<String> {
+        range.map(|i| i.to_string()).collect()
+    }
+
+    fn check(text: &TextBuffer<String>, expected: &[String]) {
+        assert_eq!(text.iter().cloned().collect::<Vec<_>>(), expected);
+        assert_eq!(text.len(), expected.len());
+        for (i, line) in expected.iter().enumerate() {
+            assert_eq!(&text[i], line);
+        }
+        assert!(text.chunks.iter().all(|rows| !rows.is_empty() && rows.len() <= MAX_CHUNK_LEN));
+    }
+
+    #[test]
+    fn edits_match_a_plain_vec() {
+        let mut text = TextBuffer::from(rows(0..5000));
+        let mut model = rows(0..5000);
+        check(&text, &model);
+
+        // 跨过多个块删除
+        text.remove_range(700..3300);
+        model.drain(700..3300);
+        check(&text, &model);
+
+        // 一次插入超过一个块的行
+        text.insert_rows(100, rows(10000..12500));
+        model.splice(100..100, rows(10000..12500));
+        check(&text, &model);
+
+        for (index, row) in [(0, "a"), (model.len(), "b"), (1234, "c")] {
+            text.insert(index, row.to_string());
+            model.insert(index, row.to_string());
+        }
+        text.remove(50);
+        model.remove(50);
+        check(&text, &model);
+
+        text.remove_range(0..10);
+        model.drain(0..10);
+        let end = model.len();
+        text.remove_range(end - 700..end);
+        model.drain(end - 700..end);
+        check(&text, &model);
+
+        text.remove_range(0..model.len());
+        check(&text, &[]);
+        text.insert_rows(0, rows(0..3));
+        check(&text, &rows(0..3));
+    }
+
+    #[test]
+    fn edits_at_chunk_boundaries() {
+        let mut text = TextBuffer::from(rows(0..2 * MAX_CHUNK_LEN));
+        let mut model = rows(0..2 * MAX_CHUNK_LEN);
+        assert_eq!(text.starts, [0, MAX_CHUNK_LEN]);
+
+        // 在两个块的交界处插入，插入到后一个块的开头
+        text.insert(MAX_CHUNK_LEN, "a".to_string());
+        model.insert(MAX_CHUNK_LEN, "a".to_string());
+        check(&text, &model);
+        // 后一个块超过上限时从中间分开
+        assert_eq!(text.starts, [0, MAX_CHUNK_LEN, MAX_CHUNK_LEN + MAX_CHUNK_LEN.div_ceil(2)]);
+
+        // 删除跨过交界的行，剩下的两个块合并
+        let boundary = text.starts[2];
+        text.remove_range(boundary - 1..boundary + 1);
+        model.drain(boundary - 1..boundary + 1);
+        check(&text, &model);
+        assert_eq!(text.chunks.len(), 2);
+
+        // 删除一个块的第一行和最后一行
+        let boundary = text.starts[1];
+        text.remove(boundary);
+        model.remove(boundary);
+        text.remove(boundary - 1);
+        model.remove(boundary - 1);
+        check(&text, &model);
+
+        // 在交界处一次插入多行，和所在的块一起重新分块
+        let boundary = text.starts[1];
+        text.insert_rows(boundary, rows(10000..10000 + MAX_CHUNK_LEN));
+        model.splice(boundary..boundary, rows(10000..10000 + MAX_CHUNK_LEN));
+        check(&text, &model);
+        for (chunk, start) in text.starts.iter().enumerate() {
+            let expected: usize = text.chunks[..chunk].iter().map(|c| c.len()).sum();
+            assert_eq!(*start, expected);
+        }
+        assert_eq!(text.range(boundary - 1..boundary + 2).cloned().collect::<Vec<_>>(), model[boundary - 1..boundary + 2]);
+    }
+
+    #[test]
+    fn clones_share_unmodified_chunks() {
+        let text = TextBuffer::from(rows(0..3000));
+        let mut copy = text.clone();
+        copy.remove_range(10..20);
+        assert!(Arc::ptr_eq(&text.chunks[2], &copy.chunks[2]));
+        check(&text, &rows(0..3000));
+    }
+}
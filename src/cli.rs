@@ -0,0 +1,122 @@
+use std::path::PathBuf;
+
+use crate::editor::OpenOptions;
+use crate::error::{EditorError, Result};
+use crate::reader::Decoder;
+
+pub const USAGE: &str = "\
+Usage: fim [OPTIONS] [+N] [FILE]...
+
+Arguments:
+  [FILE]...                  Files to open, each in its own buffer
+  +N                         Open the first file at line N
+
+Options:
+  -R, --readonly             Open files read-only
+  -f, --follow               Keep reading lines appended to the files, like tail -f
+  -e, --encoding <ENCODING>  Encoding used to read and write files
+                             [default: detected from the file]
+  -s, --script <FILE>        Run the commands in FILE without the interface,
+                             - reads them from standard input
+      --headless             Same as --script -
+      --record <FILE>        Write every key read from the terminal to FILE
+      --replay <FILE>        Read the keys recorded in FILE before the terminal
+      --tutor                Open a copy of the tutorial before the other files
+  -h, --help                 Print help
+  -V, --version              Print version";
+
+/// 命令行参数
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Args {
+    pub files: Vec<PathBuf>,
+    /// 第一个文件打开后跳转到的行，从1开始
+    pub line: Option<usize>,
+    pub readonly: bool,
+    pub follow: bool,
+    pub encoding: Option<String>,
+    /// 不进入界面执行的脚本，-表示从标准输入读取
+    pub script: Option<PathBuf>,
+    /// 记录按键的文件
+    pub record: Option<PathBuf>,
+    /// 启动后先重放的按键记录
+    pub replay: Option<PathBuf>,
+    /// 打开教程的副本
+    pub tutor: bool,
+    pub help: bool,
+    pub version: bool,
+}
+
+impl Args {
+    /// 解析命令行参数，不包括程序名
+    // 参数比较简单，+N也不符合常见的参数格式，所以手动解析
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter();
+        // --之后的参数全部当作文件名
+        let mut only_files = false;
+
+        while let Some(arg) = args.next() {
+            if only_files {
+                parsed.files.push(PathBuf::from(arg));
+                continue;
+            }
+            match arg.as_str() {
+                "--" => only_files = true,
+                "-h" | "--help" => parsed.help = true,
+                "-V" | "--version" => parsed.version = true,
+                "-R" | "--readonly" => parsed.readonly = true,
+                "-f" | "--follow" => parsed.follow = true,
+                "--tutor" => parsed.tutor = true,
+                "--headless" => parsed.script = Some(PathBuf::from("-")),
+                "-s" | "--script" => parsed.script = Some(Self::value(&arg, &mut args)?.into()),
+                "--record" => parsed.record = Some(Self::value(&arg, &mut args)?.into()),
+                "--replay" => parsed.replay = Some(Self::value(&arg, &mut args)?.into()),
+                _ if arg.starts_with("--script=") => {
+                    parsed.script = Some(PathBuf::from(&arg["--script=".len()..]));
+                }
+                "-e" | "--encoding" => {
+                    let encoding = Self::value(&arg, &mut args)?;
+                    parsed.encoding = Some(Self::check_encoding(encoding)?);
+                }
+                _ if arg.starts_with("--encoding=") => {
+                    let encoding = arg["--encoding=".len()..].to_string();
+                    parsed.encoding = Some(Self::check_encoding(encoding)?);
+                }
+                _ if arg.starts_with('+') => {
+                    let line = arg[1..]
+                        .parse::<usize>()
+                        .map_err(|e| EditorError::invalid_argument(&arg, e.to_string()))?;
+                    parsed.line = Some(line);
+                }
+                _ if arg.starts_with('-') && arg.len() > 1 => {
+                    return Err(EditorError::invalid_argument(arg, "unknown option"));
+                }
+                _ => parsed.files.push(PathBuf::from(arg)),
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// 选项后面的值
+    fn value(arg: &str, args: &mut impl Iterator<Item = String>) -> Result<String> {
+        args.next().ok_or_else(|| EditorError::invalid_argument(arg, "missing value"))
+    }
+
+    fn check_encoding(encoding: String) -> Result<String> {
+        let available = Decoder::<tokio::fs::File>::get_list();
+        if available.iter().any(|e| e.eq_ignore_ascii_case(&encoding)) {
+            Ok(encoding)
+        } else {
+            Err(fim_core::EditorError::unsupported_encoding(encoding, available).into())
+        }
+    }
+
+    pub fn open_options(&self) -> OpenOptions {
+        OpenOptions {
+            encoding: self.encoding.clone(),
+            readonly: self.readonly,
+            line: self.line,
+            follow: self.follow,
+        }
+    }
+}
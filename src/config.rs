@@ -0,0 +1,209 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::editor::{Binding, Keymap, LineNumbers, Mode, StatusSegment, DEFAULT_LEADER};
+use crate::editor::key::TAB_STOP;
+use crate::error::{EditorError, Result};
+use crate::reader::InputBackend;
+use crate::theme::Theme;
+
+/// 编辑器配置，从 ~/.config/fim/config.toml 加载
+/// 配置文件中没有出现的选项使用默认值
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// tab的显示宽度
+    pub tab_width: usize,
+    /// 按下tab时是否插入空格
+    pub expand_tab: bool,
+    /// 打开文件时是否根据内容检测缩进方式，检测到时代替expand_tab
+    pub detect_indent: bool,
+    /// 换行时是否保持上一行的缩进，并在{、:等后面增加缩进
+    pub auto_indent: bool,
+    /// 输入括号和引号时是否自动补上另一半
+    pub auto_pairs: bool,
+    /// 主题名称
+    pub theme: String,
+    /// 覆盖主题中的颜色，比如 status_bar = "bold #ffffff bg:#005f87"
+    pub colors: BTreeMap<String, String>,
+    /// 按键序列中<Leader>代表的按键
+    pub leader: String,
+    /// 覆盖默认的按键绑定，比如 [keymap.normal] 中的 "<C-s>" = "save"、"gg" = "file_start"
+    /// 也可以绑定一组操作和以:开头的命令，比如 "<Leader>w" = ["save", ":messages"]
+    pub keymap: BTreeMap<Mode, BTreeMap<String, Binding>>,
+    /// 等待多个按键组成的绑定的下一个按键的毫秒数
+    pub keymap_timeout: u64,
+    /// 行号显示模式：off、absolute、relative
+    pub line_numbers: LineNumbers,
+    /// 长行是否折行显示，关闭时水平滚动
+    pub wrap: bool,
+    /// 显示竖线的列，比如 [80, 100]，竖线在这么多个字符的后面
+    pub rulers: Vec<usize>,
+    /// 是否在每一级缩进处显示缩进线
+    pub indent_guides: bool,
+    /// 光标上下至少保留的行数，滚动屏幕时生效，也可以写作scrolloff
+    #[serde(alias = "scrolloff")]
+    pub scroll_margin: usize,
+    /// 翻页时是否分几帧平滑滚动
+    pub smooth_scroll: bool,
+    /// 鼠标滚轮每次滚动的行数
+    pub wheel_scroll_lines: usize,
+    /// 重新打开文件时是否回到上次关闭时光标的位置
+    pub remember_position: bool,
+    /// 每隔多少秒把有修改的buffer写入交换文件，0表示不写
+    pub autosave_interval: u64,
+    /// 累积了这么多次修改之后不等定时器，立即写入交换文件，0表示只按时间写入
+    pub autosave_changes: u64,
+    /// 是否把撤销历史保存在配置目录中，关闭文件之后再打开仍然可以撤销
+    pub undo_file: bool,
+    /// 格式化命令，从stdin读取内容并把结果写到stdout，键是文件类型或者扩展名
+    /// 比如 rust = "rustfmt --edition 2024"
+    pub formatters: BTreeMap<String, String>,
+    /// 保存时是否用formatters中的命令格式化
+    pub format_on_save: bool,
+    /// 是否在注释、字符串和纯文本文件中标出拼写错误的单词
+    pub spell_check: bool,
+    /// 拼写检查使用的词典，每行一个单词，没有设置时使用系统词典
+    pub dictionary: Option<PathBuf>,
+    /// :make执行的编译命令，输出中的 文件:行:列 显示在结果列表中
+    pub make_command: String,
+    /// 是否在光标所在行的后面显示git blame信息
+    pub inline_blame: bool,
+    /// 保存时是否删除行尾空白
+    pub strip_trailing_whitespace: bool,
+    /// 保存前是否把原来的内容备份到 文件名~
+    pub backup: bool,
+    /// 备份文件所在的目录，没有设置时放在文件旁边
+    pub backup_dir: Option<PathBuf>,
+    /// 最多保留的备份数量，更早的备份会被删除，为0时不备份
+    pub backup_count: usize,
+    /// 自定义的二合字母，键是两个字符，比如 "e=" = "€"，和内置的相同时覆盖内置的
+    pub digraphs: BTreeMap<String, char>,
+    /// 状态栏左边显示的内容，比如 ["file_name", "modified"]
+    pub status_left: Vec<StatusSegment>,
+    /// 状态栏右边显示的内容
+    pub status_right: Vec<StatusSegment>,
+    /// 读取按键的方式：builtin自己解析终端的转义序列，crossterm使用crossterm解析的事件
+    /// 自带的解析不能正确识别某些终端的按键时使用crossterm
+    pub input: InputBackend,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            tab_width: TAB_STOP,
+            expand_tab: false,
+            detect_indent: true,
+            auto_indent: true,
+            auto_pairs: true,
+            theme: "default".to_string(),
+            colors: BTreeMap::new(),
+            leader: DEFAULT_LEADER.to_string(),
+            keymap: BTreeMap::new(),
+            keymap_timeout: 1000,
+            line_numbers: LineNumbers::Off,
+            wrap: false,
+            rulers: Vec::new(),
+            indent_guides: false,
+            scroll_margin: 0,
+            smooth_scroll: false,
+            wheel_scroll_lines: 3,
+            remember_position: true,
+            autosave_interval: 4,
+            autosave_changes: 200,
+            undo_file: false,
+            formatters: BTreeMap::new(),
+            format_on_save: false,
+            spell_check: false,
+            dictionary: None,
+            make_command: "make".to_string(),
+            inline_blame: false,
+            strip_trailing_whitespace: false,
+            backup: false,
+            backup_dir: None,
+            backup_count: 1,
+            digraphs: BTreeMap::new(),
+            status_left: StatusSegment::default_left(),
+            status_right: StatusSegment::default_right(),
+            input: InputBackend::Builtin,
+        }
+    }
+}
+
+impl Config {
+    /// 配置目录，历史记录等文件也放在这里
+    /// 优先使用 $XDG_CONFIG_HOME，否则使用 $HOME/.config
+    pub fn dir() -> Option<PathBuf> {
+        let config_home = match env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        };
+        Some(config_home.join("fim"))
+    }
+
+    /// 配置文件的路径
+    pub fn path() -> Option<PathBuf> {
+        Some(Self::dir()?.join("config.toml"))
+    }
+
+    /// 加载配置文件，文件不存在时返回默认配置
+    pub fn load() -> Result<Self> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                debug!("Config file {} not found, using defaults", path.display());
+                return Ok(Self::default());
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Self::parse(&content).map_err(|e| match e {
+            EditorError::InvalidConfig { details, .. } => {
+                EditorError::invalid_config(path.display().to_string(), details)
+            }
+            e => e,
+        })
+    }
+
+    /// 解析配置内容并校验
+    pub fn parse(content: &str) -> Result<Self> {
+        let config: Self = toml::from_str(content)
+            .map_err(|e| EditorError::invalid_config("<string>", e.message()))?;
+
+        if config.tab_width == 0 {
+            return Err(EditorError::invalid_config(
+                "<string>",
+                "tab_width must be greater than 0",
+            ));
+        }
+        if let Some(name) = config.digraphs.keys().find(|name| name.chars().count() != 2) {
+            return Err(EditorError::invalid_config(
+                "<string>",
+                format!("digraph {:?} must be two characters", name),
+            ));
+        }
+        if let Err(e) = Theme::load(&config.theme, &config.colors) {
+            return Err(EditorError::invalid_config("<string>", e));
+        }
+        if let Err(e) = Keymap::new(&config.keymap, &config.leader) {
+            return Err(EditorError::invalid_config("<string>", e));
+        }
+        Ok(config)
+    }
+
+    /// 配置的主题，颜色在解析配置时已经校验过
+    pub fn theme(&self) -> Theme {
+        Theme::load(&self.theme, &self.colors).unwrap_or_default()
+    }
+
+    /// 配置的按键绑定，在解析配置时已经校验过
+    pub fn keymap(&self) -> Keymap {
+        Keymap::new(&self.keymap, &self.leader).unwrap_or_default()
+    }
+}
@@ -1,624 +1,940 @@
-pub mod key;
-
-use std::io::Write;
-use std::ops::Drop;
-use std::path::Path;
+mod brackets;
+mod buffer;
+mod case;
+mod command;
+mod completion;
+mod cursors;
+mod digraph;
+mod event;
+mod focus;
+mod format;
+mod git;
+mod grep;
+mod guides;
+mod help;
+mod history;
+mod idle;
+mod indent;
+mod json;
+mod jumps;
+mod literal;
+pub use fim_core::key;
+mod keymap;
+mod lines;
+mod macros;
+mod message;
+mod motion;
+mod mouse;
+mod overlay;
+mod pairs;
+mod pattern;
+#[cfg(feature = "lua")]
+mod plugin;
+mod positions;
+mod preview;
+mod prompt;
+mod quickfix;
+mod registers;
+mod render;
+mod save;
+mod script;
+mod screen;
+mod scroll;
+mod search;
+mod selection;
+mod shell;
+mod signals;
+mod snippet;
+mod spell;
+mod stats;
+mod status;
+mod symbols;
+mod tags;
+mod text;
+mod undo;
+mod view;
+mod watch;
+mod whitespace;
+mod wrap;
+
+use std::collections::VecDeque;
+use std::mem;
+use std::ops::{Drop, Range};
 use std::path::PathBuf;
-use std::time::Instant;
-use std::u16;
+use std::sync::MutexGuard;
+use std::thread;
 
-use crossterm::{ExecutableCommand, QueueableCommand, cursor, terminal};
-use tokio::fs::File;
-use tokio::io::AsyncWriteExt;
+use serde::Deserialize;
 use tokio::io::AsyncReadExt;
-
-use super::error::{EditorError, Result};
-use super::utils;
-use crate::reader::ByteStream;
-use crate::reader::Decoder;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::time::Interval;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use super::error::Result;
+use crate::config::Config;
+use crate::term::Capabilities;
+use crate::terminal::{self, Terminal};
 use crate::reader::KeyStream;
-use crate::utils::color;
-use utils::find_subsequence;
+use crate::theme::Theme;
+
+use buffer::{Buffer, LoadEvent};
+use indent::Indent;
+use jumps::JumpList;
+use git::{GitEvent, LineBlame};
+use grep::{GrepEvent, ResultsPane};
+use completion::Completion;
+use event::Subscriber;
+use history::{History, HistoryKind};
+#[cfg(feature = "lua")]
+use plugin::{Hook, Plugins};
+use signals::Signals;
+use macros::Macros;
+use message::Message;
+use overlay::Overlay;
+use positions::Positions;
+use registers::Registers;
+use screen::Screen;
+use search::SearchState;
+use snippet::{SnippetSession, Snippets};
+use spell::Dictionary;
+pub use buffer::OpenOptions;
+pub use key::{ControlKey, Direction, Key, Modifiers, MouseEvent, SpecialKey};
+use selection::SelectionKind;
+pub use keymap::{Action, Binding, Keymap, Mode, Step, DEFAULT_LEADER};
+pub use status::StatusSegment;
+use view::{Layout, View};
+pub use view::SplitDirection;
+
+/// 一行文本，每个字符对应一个key，raw索引就是字符的索引
+/// 只存储文本本身，按键和渲染后的内容在显示或者计算列的时候临时生成
+/// 这样没有显示过的行只占用文本的内存
+#[derive(Clone)]
+struct Row {
+    text: String,
+    // tab的显示宽度
+    tab_width: usize,
+}
 
-pub use key::{ControlKey, Direction, Key};
+/// 一行渲染后的内容，只在需要时生成，不随行保存
+struct Rendered {
+    text: String,
+    // text中的每个字素簇，用于在列和raw之间转换
+    cells: Vec<Cell>,
+    // 行的字符数
+    len: usize,
+}
 
-struct Row {
-    // 是否需要存储为string
-    // 如果存储key每次保存都需要转换
-    // 但是可以保留原始输入
-    raw: Vec<Key>,
-    rendered: String,
+/// 屏幕上不可分割的一段内容，对应一个字素簇
+/// 宽字符占两列，组合字符和前面的字符在同一个cell中
+#[derive(Debug, Clone)]
+struct Cell {
+    // 在raw中的起始位置
+    raw: usize,
+    // 在rendered中的字节范围
+    text: Range<usize>,
+    col: usize,
+    width: usize,
+}
+
+impl Rendered {
+    fn display_len(&self) -> usize {
+        self.cells.last().map_or(0, |c| c.col + c.width)
+    }
+
+    /// cell和它显示的内容
+    fn cells(&self) -> impl Iterator<Item = (&Cell, &str)> {
+        self.cells.iter().map(|c| (c, &self.text[c.text.clone()]))
+    }
+
+    /// raw_index处的key所在的cell
+    fn cell(&self, raw_index: usize) -> &Cell {
+        let i = self.cells.partition_point(|c| c.raw <= raw_index);
+        &self.cells[i - 1]
+    }
+
+    fn get_render_index(&self, raw_index: usize) -> (usize, usize) {
+        let cell = self.cell(raw_index);
+        (cell.col, cell.col + cell.width)
+    }
+
+    /// raw_index处的key开始的列，超出行尾时返回行的显示宽度
+    fn column_of(&self, raw_index: usize) -> usize {
+        if raw_index >= self.len {
+            self.display_len()
+        } else {
+            self.get_render_index(raw_index).0
+        }
+    }
+
+    /// 覆盖render_index列的字素簇在raw中的起始位置
+    fn get_raw_index(&self, render_index: usize) -> usize {
+        let i = self.cells.partition_point(|c| c.col + c.width <= render_index);
+        self.cells.get(i).map_or(self.len, |c| c.raw)
+    }
 }
 
 impl Row {
-    fn new(raw: Vec<Key>) -> Self {
-        let rendered = String::new();
-        let mut row = Self { raw, rendered };
-        row.render();
-        row
+    fn new(raw: Vec<Key>, tab_width: usize) -> Self {
+        Self::from_text(Self::raw_str(&raw), tab_width)
     }
 
-    fn display_len(&self) -> usize {
-        self.rendered.len()
+    /// 用文本创建一行
+    fn from_text(text: impl Into<String>, tab_width: usize) -> Self {
+        Self {
+            text: text.into(),
+            tab_width,
+        }
     }
 
-    fn append(&mut self, other: &Row) {
-        self.raw.extend_from_slice(&other.raw);
-        self.rendered.push_str(&other.rendered);
+    /// 修改tab宽度，之后渲染时使用新的宽度
+    fn set_tab_width(&mut self, tab_width: usize) {
+        self.tab_width = tab_width;
+    }
+
+    /// 字符数，也就是raw索引的上限
+    fn len(&self) -> usize {
+        self.text.chars().count()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.text.is_empty()
     }
 
-    fn chars(&self) -> std::str::Chars<'_> {
-        self.rendered.chars()
+    fn text(&self) -> &str {
+        &self.text
     }
 
-    fn raw(&self) -> String {
-        Self::raw_str(&self.raw)
+    /// 每个字符对应的key
+    fn keys(&self) -> Vec<Key> {
+        Self::raw_keys(&self.text)
     }
 
+    /// raw_index处的key
+    fn key(&self, raw_index: usize) -> Option<Key> {
+        self.text.chars().nth(raw_index).map(Key::from_char)
+    }
+
+    /// raw中range范围内的key
+    fn keys_in(&self, range: Range<usize>) -> Vec<Key> {
+        Self::raw_keys(&self.text[self.byte_range(range)])
+    }
+
+    /// raw_index处的字符在text中的字节位置，超出行尾时是text的长度
+    fn byte_index(&self, raw_index: usize) -> usize {
+        self.text.char_indices().nth(raw_index).map_or(self.text.len(), |(i, _)| i)
+    }
+
+    fn byte_range(&self, range: Range<usize>) -> Range<usize> {
+        let start = self.byte_index(range.start);
+        let end = self.text[start..]
+            .char_indices()
+            .nth(range.end.saturating_sub(range.start))
+            .map_or(self.text.len(), |(i, _)| start + i);
+        start..end
+    }
+
+    fn display_len(&self) -> usize {
+        self.render().display_len()
+    }
+
+    fn append(&mut self, other: &Row) {
+        self.text.push_str(&other.text);
+    }
+
+    /// 控制键转换成对应的控制字符，保存时和读取时的内容相同
     fn raw_str(keys: &[Key]) -> String {
-        let mut raw = String::new();
-        for key in keys {
-            match key {
-                Key::ControlKey(ControlKey::Tab) => {
-                    raw.push('\t');
-                }
-                Key::Char(c) => {
-                    raw.push(*c);
-                }
-                _ => {}
-            }
-        }
-        raw
+        keys.iter().filter_map(Key::as_char).collect()
     }
 
-    fn render(&mut self) {
-        for key in &self.raw {
-            match key {
-                Key::ControlKey(ControlKey::Backspace) => {
-                    // render函数只不可变借用了raw字段
-                    // backspace函数只可变借用了rendered字段
-                    // 但是借用检察器只查看函数签名，认为backspace函数可变借用了self
-                    // self.backspace();
-                    if self.rendered.is_empty() {
-                        return;
-                    }
-                    let key = self.raw.last().unwrap();
-                    for _ in 0..key.get_display_width() {
-                        self.rendered.pop();
-                    }
-                }
-                _ => {
-                    let s = key.render();
-                    if s.is_empty() {
-                        continue;
-                    }
-                    self.rendered.push_str(&s);
+    /// 文本中每个字符对应的key，和raw_str相反
+    fn raw_keys(text: &str) -> Vec<Key> {
+        text.chars().map(Key::from_char).collect()
+    }
+
+    /// 按字素簇切分后渲染，tab的宽度和所在的列有关
+    /// 每个key对应文本中的一个字符，控制字符显示成^A这样的两列
+    fn render(&self) -> Rendered {
+        let mut rendered = String::with_capacity(self.text.len());
+        let mut cells = Vec::new();
+        let (mut raw, mut col) = (0, 0);
+        for grapheme in self.text.graphemes(true) {
+            let start = rendered.len();
+            let width = if grapheme == "\t" {
+                // tab延伸到下一个tab stop
+                let width = self.tab_width - col % self.tab_width;
+                rendered.extend(std::iter::repeat_n(' ', width));
+                width
+            } else if let Some(c) = grapheme.chars().next().filter(char::is_ascii_control) {
+                rendered.push('^');
+                rendered.push(char::from(c as u8 ^ 0x40));
+                2
+            } else {
+                let width = grapheme.width();
+                // 单独的零宽字符附着在空格上显示，保证光标可以停在上面
+                if width == 0 {
+                    rendered.push(' ');
                 }
-            }
+                rendered.push_str(grapheme);
+                width.max(1)
+            };
+            cells.push(Cell {
+                raw,
+                text: start..rendered.len(),
+                col,
+                width,
+            });
+            raw += grapheme.chars().count();
+            col += width;
+        }
+        Rendered {
+            text: rendered,
+            cells,
+            len: raw,
         }
     }
 
-    fn backspace(&mut self, at: usize) -> usize{
-        if at >= self.rendered.len() {
-            let last_key = self.raw.pop().unwrap();
-            let width = last_key.get_display_width();
-            for _ in 0..width {
-                self.rendered.pop();
-            }
-            width
+    /// 删除at列之前的字素簇，返回它的宽度
+    fn backspace(&mut self, at: usize) -> usize {
+        let rendered = self.render();
+        let raw_index = if at >= rendered.display_len() {
+            rendered.len - 1
         } else {
-            let raw_index = self.get_raw_index(at - 1);
-            let (start, end) = self.get_render_index(raw_index);
-            self.rendered.drain(start..end);
-            self.raw.remove(raw_index);
-            end - start
-        }
+            rendered.get_raw_index(at - 1)
+        };
+        let cell = rendered.cell(raw_index);
+        let (start, width) = (cell.raw, cell.width);
+        let end = rendered
+            .cells
+            .iter()
+            .find(|c| c.raw > start)
+            .map_or(rendered.len, |c| c.raw);
+        let bytes = self.byte_range(start..end);
+        self.text.replace_range(bytes, "");
+        width
     }
 
     fn get_render_index(&self, raw_index: usize) -> (usize, usize) {
-        let mut render_index = 0;
-        for key in &self.raw[..raw_index] {
-            render_index += key.get_display_width();
-        }
-        (
-            render_index,
-            render_index + &self.raw[raw_index].get_display_width(),
-        )
+        self.render().get_render_index(raw_index)
     }
 
-    fn push(&mut self, key: Key) {
-        let rendered = key.render();
-        if !rendered.is_empty() {
-            self.raw.push(key);
-            self.rendered.push_str(&rendered);
-        }
+    /// raw_index处的key开始的列，超出行尾时返回行的显示宽度
+    fn column_of(&self, raw_index: usize) -> usize {
+        self.render().column_of(raw_index)
     }
 
+    /// 覆盖render_index列的字素簇在raw中的起始位置
     fn get_raw_index(&self, render_index: usize) -> usize {
-        let mut current_render_index = 0;
-        for (i, key) in self.raw.iter().enumerate() {
-            let key_width = key.get_display_width();
-            if current_render_index + key_width > render_index {
-                return i;
-            }
-            current_render_index += key_width;
-        }
-        self.raw.len()
+        self.render().get_raw_index(render_index)
     }
 
     fn split(&mut self, at: usize) -> Row {
-        if at >= self.rendered.len() {
-            return Row::new(Vec::new());
-        }
         let raw_index = self.get_raw_index(at);
-        let new_raw = self.raw.split_off(raw_index);
-        let new_row = Row::new(new_raw);
-        self.rendered.truncate(at);
-        new_row
+        let new_text = self.text.split_off(self.byte_index(raw_index));
+        Row::from_text(new_text, self.tab_width)
     }
 
-    fn insert(&mut self, at: usize, key: Key) -> bool {
-        if at >= self.rendered.len() {
-            let appended = key.render();
-            if appended.is_empty() {
-                return false;
-            }
-            self.raw.push(key);
-            self.rendered.push_str(&appended);
-        } else {
-            let inserted = key.render();
-            if inserted.is_empty() {
-                return false;
-            }
-            let raw_index = self.get_raw_index(at);
-            self.raw.insert(raw_index, key);
-            self.rendered.insert_str(at, &inserted);
-        }
-        true
+    /// 用keys替换raw中指定范围的内容
+    fn replace(&mut self, range: Range<usize>, keys: Vec<Key>) {
+        let bytes = self.byte_range(range);
+        self.text.replace_range(bytes, &Self::raw_str(&keys));
+    }
+
+    /// 在at列插入key，返回插入后光标应该在的列
+    /// 组合字符会和前面的字符合并，光标不一定前进
+    fn insert(&mut self, at: usize, key: Key) -> Option<usize> {
+        let c = key.as_char().filter(|&c| c != '\n')?;
+        let raw_index = self.get_raw_index(at);
+        self.text.insert(self.byte_index(raw_index), c);
+        Some(self.get_render_index(raw_index).1)
     }
 }
 
-struct Message {
-    text: String,
-    time: Instant,
+/// 行号显示模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineNumbers {
+    Off,
+    /// 显示绝对行号
+    Absolute,
+    /// 当前行显示绝对行号，其余行显示与当前行的距离
+    Relative,
 }
 
-impl Message {
-    fn new(text: String) -> Self {
-        Self {
-            text,
-            time: Instant::now(),
+impl LineNumbers {
+    fn next(self) -> Self {
+        match self {
+            LineNumbers::Off => LineNumbers::Absolute,
+            LineNumbers::Absolute => LineNumbers::Relative,
+            LineNumbers::Relative => LineNumbers::Off,
         }
     }
 }
 
-pub struct Editor<R: AsyncReadExt + Unpin, W: Write> {
-    writer: W,
-    // cursor横坐标
-    cx: u16,
-    // cursor纵坐标
-    cy: u16,
-    // 行偏移量
-    row_offset: usize,
-    // 列偏移量
-    col_offset: usize,
+pub struct Editor<R: AsyncReadExt + Unpin, W: Terminal> {
+    // 在后台任务中输出到终端
+    screen: Screen<W>,
+    // 当前窗口文本区域的宽度，等于窗口宽度减去行号栏宽度
     max_col: u16,
+    // 当前窗口文本区域的高度，等于窗口高度减去状态栏
     max_row: u16,
-    // rows应该存储key而不是string
-    // 显示的时候再进行渲染
-    // 这样可以控制比如tab等的渲染方式
-    // 同时也可以按照原始输入存储
-    // 每个key占据的宽度可能不一样，列数是渲染后的宽度
-    // 真的需要存储key吗？
-    // 如果希望tab转换为空格，那么存储时是不是也希望将tab存储为空格呢？
-    // 使用key的灵活性更高，可以设置一个开关，决定是否将tab转换为空格
-    // 如果不转换，则按空格显示，但是按tab存储
-    // 如果转换，则按空格存储和显示
-    // rows: Vec<String>,
-    rows: Vec<Row>,
-    current_file: Option<PathBuf>,
+    // 终端宽度
+    screen_cols: u16,
+    // 终端高度
+    screen_rows: u16,
+    line_numbers: LineNumbers,
+    // 长行折行显示，不水平滚动
+    wrap: bool,
+    // 显示竖线的列，为空时不显示
+    rulers: Vec<usize>,
+    // 是否显示缩进线
+    indent_guides: bool,
+    // 是否在光标所在行的后面显示blame信息
+    inline_blame: bool,
+    line_blame: Option<LineBlame>,
+    // 当前正在编辑的buffer
+    buffer: Buffer,
+    // 其余打开的buffer，按切换顺序排列
+    // 切换到下一个buffer时，当前buffer放到队尾，队首的buffer成为当前buffer
+    buffers: VecDeque<Buffer>,
+    // 下一个buffer的编号
+    next_buffer_id: usize,
+    // 所有窗口，焦点窗口的光标保存在当前buffer中
+    views: Vec<View>,
+    layout: Layout,
+    // 获得焦点的窗口在views中的索引
+    focus: usize,
+    // 正在接收终端的括号粘贴，这时不自动缩进
+    pasting: bool,
+    // 终端窗口是否有焦点，没有焦点时状态栏显示为不活动
+    terminal_focused: bool,
+    // 复制或删除的内容
+    registers: Registers,
+    // 正在显示的补全菜单
+    completion: Option<Completion>,
+    // 浮在文本上面的对话框或者列表
+    overlay: Option<Overlay>,
+    // 配置目录中的代码片段
+    snippets: Snippets,
+    // 展开后还在编辑占位符的代码片段
+    snippet: Option<SnippetSession>,
+    // 开启拼写检查时使用的词典
+    spell: Option<Dictionary>,
+    // 后台加载文件的任务通过channel把读取到的行发回来
+    load_sender: UnboundedSender<LoadEvent>,
+    load_receiver: UnboundedReceiver<LoadEvent>,
+    // 定时把有修改的buffer写入交换文件
+    autosave: Interval,
+    // 定时检查文件是否被其他程序修改
+    watch: Interval,
+    // 没有输入时定时更新界面
+    idle: Interval,
+    // 要求退出的信号
+    signals: Signals,
+    // 异常退出时写入了有修改的buffer的交换文件，恢复终端之后输出
+    recovered: Vec<PathBuf>,
     message: Option<Message>,
-    // 可以将dirty设置为一个整数，可以反映该文件到底有脏
-    is_dirty: bool,
+    // 正在输入提示时光标在消息栏中的列
+    prompt_cursor: Option<u16>,
+    // 选择区域变化时更新消息栏中的统计，保存的是整个buffer的统计
+    live_statistics: Option<String>,
+    // 显示过的消息，最新的在最后
+    message_log: VecDeque<Message>,
+    // 搜索提示、在所有文件中搜索和命令行的历史记录
+    search_history: History,
+    grep_history: History,
+    command_history: History,
+    // 每个文件上次关闭时光标的位置
+    positions: Positions,
+    // 跳到定义之前的位置，可以后退和前进
+    jumps: JumpList,
+    search_state: SearchState,
+    // 在所有文件中搜索的结果列表，没有搜索过时为None
+    results: Option<ResultsPane>,
+    // 每次在所有文件中搜索的编号，用于丢弃已经被替换掉的搜索发来的结果
+    grep_id: usize,
+    grep_sender: UnboundedSender<GrepEvent>,
+    grep_receiver: UnboundedReceiver<GrepEvent>,
+    // 后台读取HEAD和比较的结果
+    git_sender: UnboundedSender<GitEvent>,
+    git_receiver: UnboundedReceiver<GitEvent>,
     key_stream: KeyStream<R>,
+    // 播放宏时等待执行的按键，先于终端的输入读取
+    pending_keys: VecDeque<Key>,
+    macros: Macros,
+    keymap: Keymap,
+    #[cfg(feature = "lua")]
+    plugins: Plugins,
+    // 按订阅顺序处理编辑器事件的函数
+    subscribers: Vec<Subscriber<R, W>>,
+    // 执行了退出操作
+    quit: bool,
+    config: Config,
+    // 根据配置生成的颜色，已经转换为终端支持的颜色
+    theme: Theme,
+    // 终端支持的颜色和功能
+    capabilities: Capabilities,
 }
 
-impl<R: AsyncReadExt + Unpin, W: Write> Editor<R, W> {
-    pub async fn new(key_stream: KeyStream<R>, writer: W) -> Self {
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    pub async fn new(key_stream: KeyStream<R>, terminal: W, config: Config) -> Self {
+        let (load_sender, load_receiver) = mpsc::unbounded_channel();
+        let (grep_sender, grep_receiver) = mpsc::unbounded_channel();
+        let (git_sender, git_receiver) = mpsc::unbounded_channel();
+        let capabilities = terminal.capabilities();
         Self {
-            cx: 0,
-            cy: 0,
-            row_offset: 0,
-            col_offset: 0,
-            writer,
+            screen: Screen::new(terminal),
             max_col: 0,
+            screen_cols: 0,
+            screen_rows: 0,
+            line_numbers: config.line_numbers,
+            wrap: config.wrap,
+            rulers: config.rulers.clone(),
+            indent_guides: config.indent_guides,
+            inline_blame: config.inline_blame,
+            line_blame: None,
             // 留给状态栏和消息栏
             max_row: 0,
-            rows: Vec::new(),
-            current_file: None,
+            buffer: Buffer::new(1),
+            buffers: VecDeque::new(),
+            next_buffer_id: 2,
+            views: vec![View {
+                buffer_id: 1,
+                cursor: (0, 0, 0, 0),
+                preview: false,
+            }],
+            layout: Layout::View(0),
+            focus: 0,
+            pasting: false,
+            terminal_focused: true,
+            registers: Registers::default(),
+            completion: None,
+            overlay: None,
+            snippets: Snippets::default(),
+            snippet: None,
+            spell: None,
+            load_sender,
+            load_receiver,
+            autosave: save::autosave_timer(config.autosave_interval),
+            watch: watch::watch_timer(),
+            idle: idle::idle_timer(),
+            signals: Signals::new(),
+            recovered: Vec::new(),
             message: None,
-            is_dirty: false,
+            prompt_cursor: None,
+            live_statistics: None,
+            message_log: VecDeque::new(),
+            search_history: History::load(HistoryKind::Search),
+            grep_history: History::load(HistoryKind::Grep),
+            command_history: History::load(HistoryKind::Command),
+            positions: Positions::load(),
+            jumps: JumpList::default(),
+            search_state: SearchState::default(),
+            results: None,
+            grep_id: 0,
+            grep_sender,
+            grep_receiver,
+            git_sender,
+            git_receiver,
             key_stream,
+            pending_keys: VecDeque::new(),
+            macros: Macros::default(),
+            keymap: config.keymap(),
+            #[cfg(feature = "lua")]
+            plugins: Plugins::new(),
+            subscribers: Self::builtin_subscribers(),
+            quit: false,
+            theme: config.theme().adapt(capabilities.colors),
+            capabilities,
+            config,
         }
     }
 
-    pub async fn start(&mut self, file: Option<&str>) {
-        // 进入原始模式
-        terminal::enable_raw_mode().unwrap();
+    /// 编辑器输出到的终端，测试时可以用来检查屏幕的内容
+    /// 帧在后台输出，最新的一帧可能还没有输出到终端
+    pub fn terminal(&self) -> MutexGuard<'_, W> {
+        self.screen.lock()
+    }
 
-        let (max_col, max_row) = terminal::size().unwrap();
+    /// 进入原始模式并打开文件
+    /// 每个文件打开到单独的buffer中，第一个文件成为当前buffer
+    pub async fn start(&mut self, files: &[PathBuf], options: &OpenOptions) {
+        let (max_col, max_row) = self.screen.lock().size().unwrap();
 
+        self.screen_cols = max_col;
+        self.screen_rows = max_row;
         self.max_col = max_col;
-        self.max_row = max_row - 2;
-
-        self
-            .writer
-            // 进入备用屏幕
-            .queue(terminal::EnterAlternateScreen)
-            .unwrap()
-            // 设置标题
-            .queue(terminal::SetTitle("editor"))
-            .unwrap();
-
-        self.current_file = file.map(|f| PathBuf::from(f));
+        self.max_row = max_row.saturating_sub(2);
+
+        {
+            // 进入原始模式和备用屏幕
+            let mut terminal = self.screen.lock();
+            terminal.enter_alternate_screen().unwrap();
+            terminal.set_title("editor").unwrap();
+            // 和原始模式一起开启鼠标事件报告，不支持的终端会把转义序列显示出来
+            if self.capabilities.mouse {
+                terminal.write_styled(mouse::ENABLE_MOUSE).unwrap();
+                terminal.write_styled(indent::ENABLE_BRACKETED_PASTE).unwrap();
+                terminal.write_styled(focus::ENABLE_FOCUS_EVENTS).unwrap();
+            }
+        }
 
-        if let Some(file) = file {
-            self.open_file(file).await.unwrap();
+        // 插件需要在打开文件之前注册事件
+        #[cfg(feature = "lua")]
+        self.load_plugins();
+        self.load_snippets();
+        self.load_dictionary();
+        for (i, file) in files.iter().enumerate() {
+            // 只有第一个文件跳转到指定行
+            let options = OpenOptions {
+                line: if i == 0 { options.line } else { None },
+                ..options.clone()
+            };
+            if let Err(e) = self.open_file(file, &options).await {
+                let text = format!("Error opening {}: {}", file.display(), e);
+                self.show_message(Message::error(text, &e));
+            }
+        }
+        // 最后打开的文件是当前buffer，切换一次回到第一个文件
+        if !self.buffers.is_empty() {
+            let next = self.buffers.pop_front().unwrap();
+            let last = mem::replace(&mut self.buffer, next);
+            self.buffers.push_back(last);
         }
         self.refresh_screen().unwrap();
     }
 
-    pub fn refresh_screen(&mut self) -> Result<()> {
-        // execute会隐式调用flush，queue不会
-        // 刷新屏幕之前隐藏光标，刷新完成之后显示，这样可以防止光标闪烁
-        self.writer.execute(cursor::Hide)?;
-
-        self.writer
-            // 终端的光标起始位置以1开始
-            // crossterm的光标起始位置以0开始
-            // 将光标移到左上角开始绘制
-            .queue(cursor::MoveTo(0, 0))?;
-
-        // 清除屏幕内容
-        self.writer
-            .queue(terminal::Clear(terminal::ClearType::All))?;
-
-        self.draw_rows()?;
-
-        self.writer
-            // 将光标移动回来
-            // cx和cy是rows中的坐标，所以需要减去偏移量
-            .queue(cursor::MoveTo(
-                self.cx - self.col_offset as u16,
-                self.cy - self.row_offset as u16,
-            ))?
-            .execute(cursor::Show)?;
-
-        Ok(())
+    /// 行号栏的宽度，包括git标记和行号后面的一个空格
+    fn gutter_width(&self, buffer: &Buffer) -> u16 {
+        let signs = u16::from(buffer.git.is_some());
+        if self.line_numbers == LineNumbers::Off {
+            return signs;
+        }
+        // 至少保留3位，避免行数变化时频繁改变宽度
+        let digits = buffer.rows.len().max(1).to_string().len().max(3);
+        signs + digits as u16 + 1
     }
 
-    fn draw_rows(&mut self) -> Result<()> {
-        for i in self.row_offset..self.max_row as usize + self.row_offset {
-            if i < self.rows.len() {
-                let row = &self.rows[i];
-                for (i, c) in row.chars().enumerate() {
-                    if i < self.col_offset {
-                        continue;
-                    }
-
-                    write!(&mut self.writer, "{c}")?;
-
-                    if i + 1 == self.col_offset + self.max_col as usize {
-                        break;
-                    }
-                }
-            } else {
-                write!(&mut self.writer, "~")?;
+    /// 重新加载配置文件并立即应用
+    pub fn reload_config(&mut self) {
+        match Config::load() {
+            Ok(config) => {
+                self.apply_config(config);
+                self.show_message(Message::new("Config reloaded".to_string()));
+            }
+            Err(e) => {
+                self.show_message(Message::error(format!("Error loading config: {}", e), &e));
             }
-
-            if i + 1 == self.max_row as usize / 3 && self.rows.is_empty() {
-                let mut welcome = format!("fim -- version: {}", utils::get_version_from_env());
-                // 如果欢迎字符串的宽度超过终端宽带，则截断
-                if welcome.len() > self.max_col as usize {
-                    let bytes = welcome.as_bytes();
-                    let len = std::cmp::min(bytes.len(), self.max_col as usize);
-                    // 安全：因为我们知道welcome中是ASCII，所以可以直接从字节重建字符串
-                    welcome = unsafe { String::from_utf8_unchecked(bytes[..len].to_vec()) };
-                }
-                // welcome足够短，u16不会丢失信息
-                // 计算边距
-                let margin = (self.max_col - welcome.len() as u16) / 2;
-                self.writer.queue(cursor::MoveToColumn(margin))?;
-                self.writer.write(welcome.as_bytes())?;
-            }
-
-            // 最后一行不打印\r\n
-            // 如果最后一行打印\r\n会导致屏幕滚动到下一行
-            // 这样最后一行没有~
-            // 有了状态栏便不是最后一行了
-            // 如果动态调整，那么就不需要考虑最后一行的问题
-            // 由bar自己添加换行符
-            // 状态栏应该常驻
-            // if i + 1 < self.row_offset + self.max_row as usize {
-            //     write!(&mut self.writer, "\r\n")?;
-            // }
-            write!(&mut self.writer, "\r\n")?;
         }
-
-        // let message = Message::new(format!("{}x{}", self.max_col, self.max_row));
-        // self.message = Some(message);
-        self.draw_status_bar()?;
-        self.draw_message_bar()?;
-        Ok(())
     }
 
-    fn draw_status_bar(&mut self) -> Result<()> {
-        // self.writer
-        //     .queue(cursor::MoveTo(0, self.max_row))?;
+    fn apply_config(&mut self, config: Config) {
+        let buffers = std::iter::once(&mut self.buffer).chain(self.buffers.iter_mut());
+        for row in buffers.flat_map(|b| b.rows.iter_mut()) {
+            row.set_tab_width(config.tab_width);
+        }
+        self.line_numbers = config.line_numbers;
+        self.wrap = config.wrap;
+        self.rulers = config.rulers.clone();
+        self.indent_guides = config.indent_guides;
+        self.inline_blame = config.inline_blame;
+        self.theme = config.theme().adapt(self.capabilities.colors);
+        // 运行时用map修改的绑定会被配置文件中的绑定代替
+        self.keymap = config.keymap();
+        if config.autosave_interval != self.config.autosave_interval {
+            self.autosave = save::autosave_timer(config.autosave_interval);
+        }
+        self.config = config;
+        // 插件绑定的按键也需要重新注册
+        #[cfg(feature = "lua")]
+        self.load_plugins();
+        self.load_snippets();
+        self.load_dictionary();
+        // tab宽度变化后光标可能不在字符边界上
+        self.clamp_cursor_x();
+        self.scroll();
+    }
 
-        // 可以使用magical_rs检测文件类型
-        let filename = match &self.current_file {
-            // 当Option是Some时，and_then应用闭包返回新的Option
-            // 如果是None，则直接返回None
-            Some(name) => name
-                .file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("[No Name]"),
-            None => "[No Name]",
+    pub fn toggle_line_numbers(&mut self) {
+        self.line_numbers = self.line_numbers.next();
+        let text = match self.line_numbers {
+            LineNumbers::Off => "Line numbers: off",
+            LineNumbers::Absolute => "Line numbers: absolute",
+            LineNumbers::Relative => "Line numbers: relative",
         };
-        let modified = if self.is_dirty { "(modified)" } else { "" };
-        let mut content = format!(
-            "{}{} Ln {}/{}, Col {}",
-            filename,
-            modified,
-            self.cy + 1,
-            self.rows.len(),
-            self.cx + 1
-        );
-        // TODO: 后面优化显示效果
-        if content.len() > self.max_col as usize {
-            content.truncate(self.max_col as usize);
-        } else {
-            while content.len() < self.max_col as usize {
-                content.push(' ');
-            }
-        }
-        let status = format!("{}{}{}", color::BG_RED, content, color::RESET);
-        write!(&mut self.writer, "{}", status)?;
-        Ok(())
+        self.show_message(Message::new(text.to_string()));
     }
 
-    fn draw_message_bar(&mut self) -> Result<()> {
-        if let Some(message) = &self.message {
-            // 只在按键后才刷新屏幕，所以5秒后按下按键才会消失
-            if message.time.elapsed().as_secs() < 5 {
-                write!(&mut self.writer, "\r\n")?;
-                // 每次都会减去一行，不行，后续优化动态调整
-                // self.max_row -= 1;
-                let mut content = message.text.clone();
-                if content.len() > self.max_col as usize {
-                    content.truncate(self.max_col as usize);
-                } else {
-                    while content.len() < self.max_col as usize {
-                        content.push(' ');
-                    }
-                }
-                let message = format!("{}{}{}", color::BG_BLUE, content, color::RESET);
-                write!(&mut self.writer, "{}", message)?;
-            }
+    /// 调整偏移量，保证光标在屏幕内
+    fn scroll(&mut self) {
+        self.scroll_rows();
+        if self.wrap {
+            self.scroll_wrapped();
+            return;
+        }
+        let cx = self.buffer.cx as usize;
+        // 光标所在的宽字符需要完整显示在屏幕内
+        let end = cx + self.cursor_cell_width();
+        if cx < self.buffer.col_offset {
+            self.buffer.col_offset = cx;
+        } else if end > self.buffer.col_offset + self.max_col as usize {
+            self.buffer.col_offset = end.saturating_sub(self.max_col as usize);
         }
-        Ok(())
     }
 
+    /// 光标所在字素簇的显示宽度，在行尾时为1
+    fn cursor_cell_width(&self) -> usize {
+        let cx = self.buffer.cx as usize;
+        self.buffer
+            .rows
+            .get(self.buffer.cy as usize)
+            .filter(|row| cx < row.display_len())
+            .map_or(1, |row| {
+                let (start, end) = row.get_render_index(row.get_raw_index(cx));
+                end - start
+            })
+    }
 
-    fn search(&mut self, query: &[Key]) -> Result<()> {
-        for (i, r) in self.rows.iter().enumerate() {
-            if let Some(pos) = find_subsequence(&r.raw, query) {
-                self.cy = i as u16;
-                self.cx = pos as u16;
-                if self.cy < self.row_offset as u16 {
-                    self.row_offset = self.cy as usize;
-                } else if self.cy >= self.row_offset as u16 + self.max_row {
-                    self.row_offset = self.cy as usize - self.max_row as usize + 1;
-                }
-                if self.cx < self.col_offset as u16 {
-                    self.col_offset = self.cx as usize;
-                } else if self.cx >= self.col_offset as u16 + self.max_col {
-                    self.col_offset = self.cx as usize - self.max_col as usize + 1;
-                }
-                return Ok(());
-            }
+    /// 调整行偏移量，保证光标上下至少保留scroll_margin行
+    /// 到达文件首尾时不再保留
+    fn scroll_rows(&mut self) {
+        let max_row = self.max_row as usize;
+        if max_row == 0 {
+            return;
+        }
+        let cy = self.buffer.cy as usize;
+        // 边距不能超过屏幕的一半，否则光标无处可放
+        let margin = self.config.scroll_margin.min((max_row - 1) / 2);
+
+        if cy < self.buffer.row_offset + margin {
+            self.buffer.row_offset = cy.saturating_sub(margin);
+        } else if cy + margin >= self.buffer.row_offset + max_row {
+            // 光标可以在最后一行的后面，所以最多滚动到rows.len() + 1
+            let limit = (self.buffer.rows.len() + 1).saturating_sub(max_row);
+            let visible = (cy + 1).saturating_sub(max_row);
+            self.buffer.row_offset = (cy + margin + 1 - max_row).min(limit).max(visible);
         }
-        Err(EditorError::NotFound)
     }
 
-    async fn get_key(&mut self) -> Result<Key> {
-        if let Some(key) = self.key_stream.next_key().await? {
-            Ok(key)
-        } else {
-            Err(EditorError::UnexpectedEof { expected: "ESC OR CR".to_owned(), actual: 0 })
+    /// 先取等待执行的按键，没有时从终端读取，录制宏时记录读到的按键
+    async fn next_key(&mut self) -> Result<Option<Key>> {
+        if let Some(key) = self.pending_keys.pop_front() {
+            return Ok(Some(key));
         }
+        let key = self.key_stream.next_key().await?;
+        // 焦点和大小变化等事件不是用户按下的键，不录制到宏中
+        if let Some(key) = key.as_ref().filter(|key| !key.is_event()) {
+            self.macros.record(key);
+        }
+        Ok(key)
     }
 
-    async fn find(&mut self) {
-        let current_cx = self.cx;
-        let current_cy = self.cy;
-        let mut row = Row::new(Vec::new());
-        let prompt = "Search: ";
-        self.message = Some(Message::new(prompt.to_string()));
-        self.cy = self.max_row + 2 + self.row_offset as u16;
-        self.cx = prompt.len() as u16;
-
+    /// 等待用户按下一个键，期间的焦点事件直接处理，终端的其他事件被忽略
+    /// 等待时收到退出信号返回错误，调用者按取消处理，命令结束之后在run中退出
+    async fn get_key(&mut self) -> Result<Key> {
         loop {
-            self.refresh_screen().unwrap();
-            let key = match self.get_key().await {
-                Ok(key) => {
-                    key
-                },
-                Err(e) => {
-                    self.message = None;
-                    self.cy = current_cy;
-                    self.cx = current_cx;
-                    self.message = Some(Message::new(format!("Error reading Key: {}", e)));
-                    break;
+            if let Some(signal) = self.signals.received {
+                return Err(signals::interrupted(signal));
             }
-        };
-            match key {
-                Key::ControlKey(ControlKey::Escape) => {
-                        self.message = None;
-                        self.cy = current_cy;
-                        self.cx = current_cx;
-                        break;
-                    }
-                    Key::ControlKey(ControlKey::CR) => {
-                        self.message = None;
-                        break;
-                    },
-                    Key::ControlKey(ControlKey::Backspace) => {
-                        if !row.raw.is_empty() {
-                            row.backspace(self.cx as usize);
-                            if self.cx > prompt.len() as u16 {
-                                self.cx -= 1;
-                            }
-                            // TODO: bar的消息显示随着光标位置变化
-                            // 需要单独的偏移量，不能直接使用editor的偏移量
-                            self.message = Some(Message::new(format!("{}{}",
-                            prompt, &row.rendered)));
-                        }
-                    }
-                    Key::ArrowKey(Direction::Left) => {
-                        if self.cx > prompt.len() as u16 {
-                            self.cx -= 1;
-                        }
-                        self.message = Some(Message::new(format!("{}{}",
-                            prompt, &row.rendered)));
-                    }
-                    Key::ArrowKey(Direction::Right) => {
-                        if (self.cx as usize) < row.display_len() {
-                            self.cx += 1;
-                        }
-                        self.message = Some(Message::new(format!("{}{}",
-                            prompt, &row.rendered)));
-                    }
-                    _ => {
-                        row.push(key);
-                        if self.cx < self.max_col - 1 {
-                            self.cx += 1;
-                        }
-                        self.message = Some(Message::new(format!("{}{}",
-                            prompt, &row.rendered)));
+            if self.pending_keys.is_empty() {
+                tokio::select! {
+                    result = self.key_stream.wait_input() => result?,
+                    signal = self.signals.recv() => {
+                        self.signals.received = Some(signal);
+                        continue;
                     }
-            }
-            if let Err(_) = self.search(&row.raw) {
-                    self.cx = current_cx;
-                    self.cy = current_cy;
-                    self.message = Some(Message::new(format!("Not Found: {}", &row.rendered)));
                 }
+            }
+            let Some(key) = self.next_key().await? else {
+                return Err(fim_core::EditorError::unexpected_eof(0, "ESC OR CR", 0).into());
+            };
+            if let Some(focused) = focus::focus_change(&key) {
+                self.handle_focus(focused);
+            } else if !key.is_event() {
+                return Ok(key);
+            }
         }
     }
 
-    fn insert(&mut self, key: Key) {
-        let is_last_row = (self.cy as usize) == self.rows.len();
-        let row = if !is_last_row {
-            &mut self.rows[self.cy as usize]
-        } else {
-            // 如果光标在最后一行的后面，则添加新行
-            self.rows.push(Row::new(Vec::new()));
-            self.rows.last_mut().unwrap()
+    /// 在屏幕中间的对话框中询问用户，按y确认，其余按键取消
+    async fn confirm(&mut self, question: &str) -> bool {
+        self.overlay = Some(Overlay::dialog("Confirm", vec![question.to_string()]));
+        let confirmed = match self.refresh_screen() {
+            Ok(()) => matches!(self.get_key().await, Ok(Key::Char('y') | Key::Char('Y'))),
+            Err(_) => false,
         };
-        // raw mode下，enter键发送的是\r
-        if  key == Key::ControlKey(ControlKey::CR) {
-            self.message = Some(Message::new("".to_string()));
-            let new_row = row.split(self.cx as usize);
-            self.rows.insert(self.cy as usize + 1, new_row);
-            if is_last_row {
-                self.rows.pop();
+        self.overlay = None;
+        confirmed
+    }
+
+    fn history_mut(&mut self, kind: HistoryKind) -> &mut History {
+        match kind {
+            HistoryKind::Search => &mut self.search_history,
+            HistoryKind::Grep => &mut self.grep_history,
+            HistoryKind::Command => &mut self.command_history,
+        }
+    }
+
+    /// 在每个光标处插入按键
+    fn insert(&mut self, key: Key) {
+        if !self.check_writable() {
+            return;
+        }
+        self.at_cursors(true, |editor| {
+            if let Key::Text(text) = &key {
+                // 输入法提交的文本原样插入，不自动补全成对字符
+                for c in text.chars() {
+                    editor.insert_key(Key::Char(c));
+                }
+            } else if !editor.auto_pair(&key) {
+                editor.insert_key(key.clone());
+            }
+        });
+    }
+
+    /// 插入按键，不自动补全成对字符
+    fn insert_key(&mut self, key: Key) {
+        if let (Key::ControlKey(ControlKey::Tab), Indent::Spaces(width)) = (&key, self.indent()) {
+            // 补齐到下一级缩进
+            for _ in 0..width - self.buffer.cx as usize % width {
+                self.insert_key(Key::Char(' '));
             }
-            self.add_cy();
-            self.cx = 0;
-            self.col_offset = 0;
-            self.is_dirty = true;
             return;
         }
-        if let true = row.insert(self.cx as usize, key) {
-            self.is_dirty = true;
-            self.add_cx();
+        // raw mode下，enter键发送的是\r
+        if key != Key::ControlKey(ControlKey::CR) {
+            return self.insert_raw_key(key);
+        }
+        let is_last_row = (self.buffer.cy as usize) == self.buffer.rows.len();
+        let row = if !is_last_row {
+            &mut self.buffer.rows[self.buffer.cy as usize]
         } else {
-            if is_last_row {
-                self.rows.pop();
-            }
+            // 如果光标在最后一行的后面，则添加新行
+            self.buffer.rows.push(Row::new(Vec::new(), self.config.tab_width));
+            self.buffer.rows.last_mut().unwrap()
+        };
+        self.message = Some(Message::new("".to_string()));
+        let mut new_row = row.split(self.buffer.cx as usize);
+        let indent = self.new_line_indent(&mut new_row);
+        self.buffer.rows.insert(self.buffer.cy as usize + 1, new_row);
+        if is_last_row {
+            self.buffer.rows.pop();
         }
+        self.add_cy();
+        self.buffer.cx = indent as u16;
+        self.buffer.col_offset = 0;
+        self.scroll();
+        self.buffer.mark_dirty();
     }
 
-    pub async fn open_file(&mut self, filename: impl AsRef<Path>) -> Result<()> {
-        // file和stdin一样实现了read trait，可以用byte_stream包装
-        // decoder实现一个read_line和lines方法
-        // 这样可以支持不同编码的文件读取
-        let file = File::open(filename).await?;
-        // lines获取的行不会包含换行符
-        // 因为我们知道一个line代表一行，因此存储换行符是没有意义的
-        let byte_stream = ByteStream::new(file);
-        let decoder = Decoder::builder()
-            .encoding("utf-8".to_string())
-            .byte_stream(byte_stream)
-            .build()?;
-
-        let mut key_stream = KeyStream::new(decoder);
-
-        let mut key_line = Vec::new();
-
-        while let Some(key) = key_stream.next_key().await? {
-            if key == Key::ControlKey(ControlKey::CR) {
-                continue;
-            } else if key == Key::ControlKey(ControlKey::LF) {
-                let row = Row::new(key_line);
-                self.rows.push(row);
-                key_line = Vec::new();
-            } else {
-                key_line.push(key);
-            }
+    /// 把key原样插入到光标处，回车和tab也不做特殊处理
+    fn insert_raw_key(&mut self, key: Key) {
+        let is_last_row = (self.buffer.cy as usize) == self.buffer.rows.len();
+        if is_last_row {
+            self.buffer.rows.push(Row::new(Vec::new(), self.config.tab_width));
+        }
+        let row = &mut self.buffer.rows[self.buffer.cy as usize];
+        if let Some(cx) = row.insert(self.buffer.cx as usize, key) {
+            self.buffer.mark_dirty();
+            self.buffer.cx = cx as u16;
+            self.scroll();
+        } else if is_last_row {
+            self.buffer.rows.pop();
         }
-        Ok(())
     }
 
-    pub async fn save(&mut self) -> Result<()> {
-        let Some(path) = &self.current_file else {
-            let message = Message::new("No file name".to_string());
-            self.message = Some(message);
-            return Ok(());
-        };
-        let path = path.as_path();
-        // create会完全截断文件，使其变为空文件
-        // 然后写入新数据
-        // 如果文件不存在则创建新文件
-        // 更好的做法是将文件截断为计划写入的数据相同长度
-        // 如果长度不够则在文件末尾添加0使其达到指定长度
-        // 最佳做法是写入新的临时文件，然后将该文件重命名为用户想要覆盖的实际文件
-        let mut file = File::create(path).await?;
-        for row in &self.rows {
-            let raw = row.raw();
-            file.write_all(raw.as_bytes()).await?;
-            file.write_all(b"\n").await?;
-        }
-        let message = Message::new("File saved".to_string());
-        self.message = Some(message);
-        self.is_dirty = false;
-        Ok(())
+    /// 跳转到指定行，从1开始，超出范围时跳转到最后一行
+    pub fn goto_line(&mut self, line: usize) {
+        self.goto_position(line, 1);
+    }
+
+    /// 只读buffer和还在加载的buffer不能修改，返回false时在消息栏提示
+    fn check_writable(&mut self) -> bool {
+        if self.buffer.readonly {
+            self.show_message(Message::new("Buffer is read-only".to_string()));
+            return false;
+        }
+        if self.buffer.is_loading() {
+            self.show_message(Message::new("Buffer is still loading".to_string()));
+            return false;
+        }
+        true
     }
 
     pub async fn run(&mut self) {
         loop {
-        match self.key_stream.next_key().await {
+        // 提示或者对话框等待按键时收到了信号，命令已经取消
+        if let Some(signal) = self.signals.received.take() {
+            self.terminate(signal);
+            break;
+        }
+        // 等待按键的同时处理后台加载的内容
+        // wait_input和recv都可以安全地取消，不会丢失按键
+        // 还有宏的按键没有执行时不需要等待
+        if self.pending_keys.is_empty() {
+        self.update_git_diff();
+        self.update_inline_blame();
+        // 修改很多时不等定时器，尽早写入交换文件
+        if self.config.autosave_changes > 0 {
+            self.write_swap_files(self.config.autosave_changes).await;
+        }
+        tokio::select! {
+            _ = self.key_stream.wait_input() => {}
+            Some(event) = self.load_receiver.recv() => {
+                self.handle_load_event(event);
+                self.refresh_screen().unwrap();
+                continue;
+            }
+            Some(event) = self.grep_receiver.recv() => {
+                self.handle_grep_event(event);
+                self.refresh_screen().unwrap();
+                continue;
+            }
+            Some(event) = self.git_receiver.recv() => {
+                self.handle_git_event(event);
+                self.refresh_screen().unwrap();
+                continue;
+            }
+            _ = self.autosave.tick(), if self.config.autosave_interval > 0 => {
+                self.write_swap_files(1).await;
+                continue;
+            }
+            _ = self.watch.tick() => {
+                self.check_disk_changes().await;
+                self.read_repo_status();
+                self.refresh_screen().unwrap();
+                continue;
+            }
+            _ = self.idle.tick() => {
+                if self.update_on_idle() {
+                    self.refresh_screen().unwrap();
+                }
+                continue;
+            }
+            _ = self.screen.resized() => {
+                self.refresh_screen().unwrap();
+                continue;
+            }
+            signal = self.signals.recv() => {
+                self.terminate(signal);
+                break;
+            }
+        }
+        }
+        match self.next_key().await {
             Ok(Some(key)) =>  {
-                match key {
-                    Key::ControlKey(ControlKey::Ctrl('q')) => {
-                        // self.end();
-                        break;
-                    },
-                    _ => {
-                        self.handle_command(&key).await;
-                        self.refresh_screen().unwrap();
-                    }
+                let state = self.cursor_state();
+                self.handle_command(&key).await;
+                self.emit_cursor_events(state);
+                if self.quit {
+                    // 正常退出时不需要恢复
+                    self.remove_swap_files().await;
+                    self.remember_all_positions();
+                    break;
+                }
+                // 宏执行完再刷新屏幕
+                if self.pending_keys.is_empty() {
+                    self.refresh_screen().unwrap();
                 }
             },
             Ok(None) => {
@@ -633,84 +949,146 @@ impl<R: AsyncReadExt + Unpin, W: Write> Editor<R, W> {
     }
     }
 
+    /// 处理一个按键，按键造成的修改记录到撤销历史
     pub async fn handle_command(&mut self, key: &Key) {
-        match key {
-            // 必须使用括号分组，不然只绑定了'a'，是不完整的绑定
-            key @ (Key::ArrowKey(Direction::Left)
-            | Key::ArrowKey(Direction::Right)
-            | Key::ArrowKey(Direction::Up)
-            | Key::ArrowKey(Direction::Down)
-            | Key::ControlKey(ControlKey::Home)
-            | Key::ControlKey(ControlKey::End)) => {
-                self.move_cursor(key);
-            }
-            Key::FunctionKey(n) => {
-                println!("F{n}");
-            }
-            Key::ControlKey(ControlKey::Escape) => {
-                print!("esc");
+        let step = self.begin_undo_step(key);
+        self.process_key(key).await;
+        self.commit_undo_step(step);
+    }
+
+    async fn process_key(&mut self, key: &Key) {
+        // 焦点和大小变化不影响结果列表和块插入等正在进行的操作
+        // 处理完按键之后会按新的大小刷新屏幕
+        if let Some(focused) = focus::focus_change(key) {
+            return self.handle_focus(focused);
+        }
+        if key.is_event() {
+            return;
+        }
+        if self.results_command(key).await {
+            return;
+        }
+        if self.buffer.selection.is_some_and(|s| s.inserting) {
+            if self.block_insert_command(key) {
+                return;
             }
-            Key::ControlKey(ControlKey::PageUp) => {
-                self.scroll_srceen(self.cy as usize + self.row_offset, Direction::Up);
+            self.buffer.selection = None;
+        }
+        match key {
+            Key::MouseEvent(event) => {
+                self.handle_mouse(event).await;
             }
-            Key::ControlKey(ControlKey::PageDown) => {
-                self.scroll_srceen(self.rows.len() - self.cy as usize, Direction::Down);
+            Key::SpecialKey(SpecialKey::PasteStart) => {
+                self.pasting = true;
             }
-            Key::ControlKey(ControlKey::Backspace) => {
-                self.backspace();
+            Key::SpecialKey(SpecialKey::PasteEnd) => {
+                self.pasting = false;
             }
-            Key::ControlKey(ControlKey::Delete) => {
-                self.delete();
+            // 粘贴的内容不经过按键映射
+            _ if self.pasting => {
+                self.insert(key.clone());
             }
-            Key::ControlKey(ControlKey::Ctrl('f')) => {
-                self.find().await;
+            _ if self.snippet_command(key) => {}
+            #[cfg(feature = "lua")]
+            _ if self.run_hooks(Hook::Key, key.notation()) => {}
+            _ => {
+                self.dispatch_key(key.clone()).await;
+                self.sync_snippet();
             }
-            Key::ControlKey(ControlKey::Ctrl('s')) => {
-                if let Err(e) = self.save().await {
-                    let message = Message::new(format!("Error saving file: {}", e));
-                    self.message = Some(message);
-                }
+        }
+        self.update_statistics();
+    }
+
+    /// 带修饰键的按键
+    /// Ctrl+左右按单词移动，Ctrl+Backspace/Delete按单词删除，Ctrl+Home/End跳到文件首尾
+    /// Alt+上下移动当前行，Alt+左右移到单词开头，Alt+Backspace按单词删除，Shift+移动键扩展选择
+    /// 按键映射中没有绑定的带修饰键的按键才会到这里
+    fn handle_modified(&mut self, modifiers: Modifiers, key: &Key) {
+        if modifiers.alt {
+            match key {
+                Key::ArrowKey(Direction::Up) => self.move_line_up(),
+                Key::ArrowKey(Direction::Down) => self.move_line_down(),
+                Key::ArrowKey(Direction::Left) => self.word_left(),
+                Key::ArrowKey(Direction::Right) => self.next_word(),
+                Key::ControlKey(ControlKey::Backspace) => self.delete_word_before(),
+                _ => {}
             }
-            _ => {
-                self.insert(key.clone());
+            return;
+        }
+        if modifiers.ctrl {
+            match key {
+                Key::ControlKey(ControlKey::Backspace) => return self.delete_word_before(),
+                Key::ControlKey(ControlKey::Delete) => return self.delete_word_after(),
+                _ => {}
             }
         }
+        let is_motion = matches!(
+            key,
+            Key::ArrowKey(_) | Key::ControlKey(ControlKey::Home) | Key::ControlKey(ControlKey::End)
+        );
+        if !is_motion {
+            return;
+        }
+        if modifiers.shift && self.buffer.selection.is_none() {
+            self.toggle_selection(SelectionKind::Char);
+        }
+        match key {
+            Key::ArrowKey(Direction::Left) if modifiers.ctrl => self.word_left(),
+            Key::ArrowKey(Direction::Right) if modifiers.ctrl => self.word_right(),
+            Key::ControlKey(ControlKey::Home) if modifiers.ctrl => self.goto_start(),
+            Key::ControlKey(ControlKey::End) if modifiers.ctrl => self.goto_end(),
+            key => self.move_cursor(key),
+        }
     }
 
     fn delete(&mut self) {
-        self.add_cx();
-        self.backspace();
+        if !self.check_writable() {
+            return;
+        }
+        self.at_cursors(true, |editor| {
+            editor.add_cx();
+            editor.backspace_at_cursor();
+        });
     }
 
+    /// 在每个光标处删除前面的字符
     fn backspace(&mut self) {
-        // 如果是多线程，则is_dirty需要使用mutex保护
+        if !self.check_writable() {
+            return;
+        }
+        self.at_cursors(true, Self::backspace_at_cursor);
+    }
+
+    fn backspace_at_cursor(&mut self) {
+        self.delete_empty_pair();
+        // 如果是多线程，则修改次数需要使用mutex保护
                 // 整个代码块都是临界区
-                if self.cx != 0 && (self.cy as usize) < self.rows.len() {
-                    let row = &mut self.rows[self.cy as usize];
-                    let width = row.backspace(self.cx as usize);
+                if self.buffer.cx != 0 && (self.buffer.cy as usize) < self.buffer.rows.len() {
+                    let row = &mut self.buffer.rows[self.buffer.cy as usize];
+                    let width = row.backspace(self.buffer.cx as usize);
                     for _ in 0..width {
                         // sub_cx会使用cx计算raw_index，但是row已经被修改了
                         // cx没有修改，所以计算出来的raw_index是错误的
                         // self.sub_cx();
 
-                        self.cx -= 1;
-                        if (self.cx as usize)  < self.col_offset {
-                            self.col_offset -= 1;
+                        self.buffer.cx -= 1;
+                        if (self.buffer.cx as usize)  < self.buffer.col_offset {
+                            self.buffer.col_offset -= 1;
                         }
                     }
-                    self.is_dirty = true;
-                } else if (self.cy as usize) >= self.rows.len() {
+                    self.buffer.mark_dirty();
+                } else if (self.buffer.cy as usize) >= self.buffer.rows.len() {
                     self.sub_cx();
                 } else {
-                    if self.cy == 0 {
+                    if self.buffer.cy == 0 {
                         return;
                     }
-                    let current_cy = self.cy;
+                    let current_cy = self.buffer.cy;
                     self.sub_cx();
-                    let current_row = self.rows.remove(current_cy as usize);
-                    let prev_row = &mut self.rows[current_cy as usize - 1];
+                    let current_row = self.buffer.rows.remove(current_cy as usize);
+                    let prev_row = &mut self.buffer.rows[current_cy as usize - 1];
                     prev_row.append(&current_row);
-                    self.is_dirty = true;
+                    self.buffer.mark_dirty();
                 }
     }
 
@@ -718,6 +1096,8 @@ impl<R: AsyncReadExt + Unpin, W: Write> Editor<R, W> {
         match key {
             Key::ArrowKey(Direction::Left) => self.sub_cx(),
             Key::ArrowKey(Direction::Right) => self.add_cx(),
+            Key::ArrowKey(Direction::Up) if self.wrap => self.move_visual_line(true),
+            Key::ArrowKey(Direction::Down) if self.wrap => self.move_visual_line(false),
             Key::ArrowKey(Direction::Up) => self.sub_cy(),
             Key::ArrowKey(Direction::Down) => self.add_cy(),
             Key::ControlKey(ControlKey::Home) => {
@@ -730,125 +1110,108 @@ impl<R: AsyncReadExt + Unpin, W: Write> Editor<R, W> {
         }
     }
 
-    fn scroll_srceen(&mut self, nums: usize, direction: Direction) {
-        match direction {
-            Direction::Up => {
-                for _ in 0..nums {
-                    self.move_cursor(&Key::ArrowKey(Direction::Up));
-                }
-            }
-            Direction::Down => {
-                for _ in 0..nums {
-                    self.move_cursor(&Key::ArrowKey(Direction::Down));
-                }
-            }
-            Direction::Left => {}
-            Direction::Right => {}
-        }
-    }
-
     fn endx(&mut self) {
-        let row_len = if self.rows.is_empty() {
+        let row_len = if self.buffer.rows.is_empty() {
             0
         } else {
-            if (self.cy as usize) < self.rows.len() {
-                self.rows[self.cy as usize].display_len()
+            if (self.buffer.cy as usize) < self.buffer.rows.len() {
+                self.buffer.rows[self.buffer.cy as usize].display_len()
             } else {
                 0
             }
         };
 
         if row_len == 0 {
-            self.cx = 0;
+            self.buffer.cx = 0;
             return;
         }
 
         if row_len > self.max_col as usize {
             // 光标可以在最后一个字符的后面，可以插入
-            self.col_offset = row_len - self.max_col as usize + 1;
+            self.buffer.col_offset = row_len - self.max_col as usize + 1;
 
-            self.cx = self.max_col + self.col_offset as u16 - 1;
+            self.buffer.cx = self.max_col + self.buffer.col_offset as u16 - 1;
         } else {
-            // self.cx = row_len as u16 - 1;
-            // self.col_offset = 0;
-            self.cx = row_len as u16;
+            // self.buffer.cx = row_len as u16 - 1;
+            // self.buffer.col_offset = 0;
+            self.buffer.cx = row_len as u16;
         }
     }
 
     fn startx(&mut self) {
-        self.cx = 0;
-        self.col_offset = 0;
+        self.buffer.cx = 0;
+        self.buffer.col_offset = 0;
     }
 
     fn add_cx(&mut self) {
-        let row_len = if self.rows.is_empty() {
+        let row_len = if self.buffer.rows.is_empty() {
             0
         } else {
-            if (self.cy as usize) < self.rows.len() {
-                self.rows[self.cy as usize].display_len()
+            if (self.buffer.cy as usize) < self.buffer.rows.len() {
+                self.buffer.rows[self.buffer.cy as usize].display_len()
             } else {
                 0
             }
         };
 
         if row_len == 0 {
-            self.cx = 0;
+            self.buffer.cx = 0;
             return;
         }
 
-        let row = &self.rows[self.cy as usize];
+        let row = &self.buffer.rows[self.buffer.cy as usize];
 
         // 光标可以在最后一个字符的后面，可以插入
-        if (self.cx as usize) < row_len {
-            let raw_index = row.get_raw_index(self.cx as usize);
+        if (self.buffer.cx as usize) < row_len {
+            let raw_index = row.get_raw_index(self.buffer.cx as usize);
             let (_, end) = row.get_render_index(raw_index);
-            self.cx = end as u16;
-            // self.cx += 1;
+            self.buffer.cx = end as u16;
+            // self.buffer.cx += 1;
 
-            if self.cx as usize >= self.max_col as usize {
-                self.col_offset = self.cx as usize + 1 - self.max_col as usize;
+            if self.buffer.cx as usize >= self.max_col as usize {
+                self.buffer.col_offset = self.buffer.cx as usize + 1 - self.max_col as usize;
             }
         } else {
-            let pre_cy = self.cy;
+            let pre_cy = self.buffer.cy;
             self.add_cy();
-            if pre_cy != self.cy {
-                self.cx = 0;
-                self.col_offset = 0;
+            if pre_cy != self.buffer.cy {
+                self.buffer.cx = 0;
+                self.buffer.col_offset = 0;
             }
         }
     }
 
     fn sub_cx(&mut self) {
         // 注意：坐标不能小于0
-        if self.cx != 0 {
-            let row = &self.rows[self.cy as usize];
+        if self.buffer.cx != 0 {
+            let row = &self.buffer.rows[self.buffer.cy as usize];
 
-            let raw_index = row.get_raw_index(self.cx as usize - 1);
+            let raw_index = row.get_raw_index(self.buffer.cx as usize - 1);
             let (start, _) = row.get_render_index(raw_index);
-            let distance = self.cx as usize - start;
-            self.cx = start as u16;
-            // self.cx -= 1;
+            let distance = self.buffer.cx as usize - start;
+            self.buffer.cx = start as u16;
+            // self.buffer.cx -= 1;
 
             // col_offset代表屏幕左边第一个字符在行中的位置
             // cx代表光标在行中的位置
             // 如果cx小于col_offset，说明光标在屏幕左边第一个字符的左边
             // 需要将col_offset向左移动，保证光标在屏幕内
-            if (self.cx as usize) < self.col_offset {
-                if self.col_offset >= distance {
-                    self.col_offset -= distance;
+            if (self.buffer.cx as usize) < self.buffer.col_offset {
+                if self.buffer.col_offset >= distance {
+                    self.buffer.col_offset -= distance;
                 } else {
-                    self.col_offset = 0;
+                    self.buffer.col_offset = 0;
                 }
             }
-            // if self.cx as usize >= self.max_col as usize {
-            //     self.col_offset = self.cx as usize + 1 - self.max_col as usize;
+            // if self.buffer.cx as usize >= self.max_col as usize {
+            //     self.buffer.col_offset = self.buffer.cx as usize + 1 - self.max_col as usize;
             // } else {
-            //     self.col_offset = 0;
+            //     self.buffer.col_offset = 0;
             // }
         } else {
-            let pre_cy = self.cy;
+            let pre_cy = self.buffer.cy;
             self.sub_cy();
-            if pre_cy != self.cy {
+            if pre_cy != self.buffer.cy {
                 self.endx();
             }
         }
@@ -858,71 +1221,75 @@ impl<R: AsyncReadExt + Unpin, W: Write> Editor<R, W> {
         // crossterm的cursor左上角单元格是(0,0)
         // crossterm的size左上角单元格是(1,1)
         // 注意转换
-        if self.rows.is_empty() {
-            self.cy = 0;
+        if self.buffer.rows.is_empty() {
+            self.buffer.cy = 0;
             return;
         }
 
         // 光标可以在最后一行的后面，可以插入
-        if (self.cy as usize) < self.rows.len() {
-            self.cy += 1;
+        if (self.buffer.cy as usize) < self.buffer.rows.len() {
+            self.buffer.cy += 1;
 
             // 如果光标移动到屏幕底部，则滚动屏幕
-            if self.cy as usize - self.row_offset == self.max_row as usize {
-                self.row_offset += 1;
-            }
+            self.scroll_rows();
         }
         self.clamp_cursor_x();
     }
 
     fn sub_cy(&mut self) {
-        if self.cy != 0 {
-            self.cy -= 1;
+        if self.buffer.cy != 0 {
+            self.buffer.cy -= 1;
 
             // 如果光标移动到屏幕顶部，则滚动屏幕
-            if self.cy as usize + 1 - self.row_offset == 0 {
-                if self.row_offset != 0 {
-                    self.row_offset -= 1;
-                }
-            }
+            self.scroll_rows();
         }
         self.clamp_cursor_x();
     }
 
     fn clamp_cursor_x(&mut self) {
-        let row_len = if self.rows.is_empty() {
+        let row_len = if self.buffer.rows.is_empty() {
             0
         } else {
-            if (self.cy as usize) < self.rows.len() {
-                self.rows[self.cy as usize].display_len()
+            if (self.buffer.cy as usize) < self.buffer.rows.len() {
+                self.buffer.rows[self.buffer.cy as usize].display_len()
             } else {
                 0
             }
         };
 
         if row_len <= self.max_col as usize {
-            self.col_offset = 0;
+            self.buffer.col_offset = 0;
         }
 
         if row_len == 0 {
-            self.cx = 0;
+            self.buffer.cx = 0;
             return;
         }
 
-        if self.cx as usize > row_len {
-            self.cx = row_len as u16;
+        if self.buffer.cx as usize > row_len {
+            self.buffer.cx = row_len as u16;
         }
+        // 光标不能停在tab的中间，移到tab的开头
+        let row = &self.buffer.rows[self.buffer.cy as usize];
+        self.buffer.cx = row.column_of(row.get_raw_index(self.buffer.cx as usize)) as u16;
     }
 
+    /// 恢复终端，在panic时也会调用，所以忽略所有错误，避免再次panic
     fn end(&mut self) {
-        // 禁用终端的原始模式，恢复到规范模式（canonical mode）
-        terminal::disable_raw_mode().unwrap();
-        // 离开备用屏幕
-        self.writer.execute(terminal::LeaveAlternateScreen).unwrap();
+        // 恢复之后绘制任务不再输出
+        let mut terminal = self.screen.close();
+        if self.capabilities.mouse {
+            let _ = terminal.write_styled(mouse::DISABLE_MOUSE);
+            let _ = terminal.write_styled(indent::DISABLE_BRACKETED_PASTE);
+            let _ = terminal.write_styled(focus::DISABLE_FOCUS_EVENTS);
+        }
+        let _ = terminal.show_cursor();
+        // 禁用终端的原始模式，恢复到规范模式（canonical mode），并离开备用屏幕
+        let _ = terminal.leave_alternate_screen();
     }
 }
 
-impl<R: AsyncReadExt + Unpin, W: Write> Drop for Editor<R, W> {
+impl<R: AsyncReadExt + Unpin, W: Terminal> Drop for Editor<R, W> {
     // 当值不再需要时，Rust会自动运行析构函数
     // 析构函数分两部分：
     // 1.如果该类型实现了ops::Drop trait，调用其Drop::drop方法
@@ -930,7 +1297,68 @@ impl<R: AsyncReadExt + Unpin, W: Write> Drop for Editor<R, W> {
     // 不能主动调用该方法
     // 原因是drop(&mut self)不会移动其值，在析构后依旧可以使用该值，有危险
     // 显式析构使用mem::drop代替
+    // panic时也会在展开栈的过程中调用，这时先把有修改的buffer写入交换文件，恢复终端之后再输出panic的信息
     fn drop(&mut self) {
+        let panicking = thread::panicking();
+        if panicking {
+            self.recovered = self.write_recovery_files();
+        }
         self.end();
+        if panicking {
+            terminal::print_panic_report();
+        }
+        for path in &self.recovered {
+            eprintln!("Unsaved changes were written to {}", path.display());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::{ByteStream, Decoder};
+    use crate::terminal::MemoryTerminal;
+
+    /// 不读终端输入的编辑器，供各个模块的测试使用
+    pub(super) async fn editor(cols: u16, rows: u16) -> Editor<tokio::io::Empty, MemoryTerminal> {
+        let decoder = Decoder::builder()
+            .encoding("utf-8".to_owned())
+            .byte_stream(ByteStream::new(tokio::io::empty()))
+            .build()
+            .unwrap();
+        let mut editor = Editor::new(KeyStream::new(decoder), MemoryTerminal::new(cols, rows), Config::default()).await;
+        editor.start(&[], &OpenOptions::default()).await;
+        editor
+    }
+
+    #[test]
+    fn row_edits_keep_text_and_columns_in_sync() {
+        // tab宽度和所在的列有关，宽字符占两列
+        let mut row = Row::from_text("a\t中b", 4);
+        assert_eq!(row.len(), 4);
+        assert_eq!(row.display_len(), 7);
+        assert_eq!(row.get_render_index(2), (4, 6));
+        assert_eq!(row.get_raw_index(5), 2);
+
+        // 在宽字符前插入后tab变窄
+        assert_eq!(row.insert(1, Key::Char('x')), Some(2));
+        assert_eq!(row.text(), "ax\t中b");
+        assert_eq!(row.get_render_index(2), (2, 4));
+
+        // 删除宽字符前面的tab
+        assert_eq!(row.backspace(4), 2);
+        assert_eq!(row.text(), "ax中b");
+
+        // 从宽字符的中间分开时整个宽字符移到新的一行
+        let tail = row.split(3);
+        assert_eq!((row.text(), tail.text()), ("ax", "中b"));
+        assert_eq!(tail.display_len(), 3);
+
+        row.replace(1..2, vec![Key::ControlKey(ControlKey::Tab), Key::Char('y')]);
+        assert_eq!(row.keys(), [Key::Char('a'), Key::ControlKey(ControlKey::Tab), Key::Char('y')]);
+        assert_eq!(row.keys_in(1..3), row.keys()[1..3]);
+        row.append(&tail);
+        assert_eq!(row.text(), "a\ty中b");
+        assert_eq!(row.column_of(row.len()), row.display_len());
     }
 }
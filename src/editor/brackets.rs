@@ -0,0 +1,90 @@
+use tokio::io::AsyncReadExt;
+
+use super::text::TextBuffer;
+use super::{Editor, Message, Terminal};
+
+/// 查找配对括号时最多扫描的行数，避免每次刷新都扫描整个大文件
+const MAX_SCAN_LINES: usize = 1000;
+
+/// 括号对应的开括号、闭括号，以及是否需要向后查找
+fn bracket_pair(c: char) -> Option<(char, char, bool)> {
+    match c {
+        '(' => Some(('(', ')', true)),
+        '[' => Some(('[', ']', true)),
+        '{' => Some(('{', '}', true)),
+        ')' => Some(('(', ')', false)),
+        ']' => Some(('[', ']', false)),
+        '}' => Some(('{', '}', false)),
+        _ => None,
+    }
+}
+
+/// (行, raw索引)处的括号的配对括号的位置
+pub(super) fn find_match(rows: &TextBuffer, (row, at): (usize, usize)) -> Option<(usize, usize)> {
+    let c = rows.get(row)?.text().chars().nth(at)?;
+    let (open, close, forward) = bracket_pair(c)?;
+    let (this, other) = if forward { (open, close) } else { (close, open) };
+    let mut depth = 0usize;
+    // 从光标下的括号开始计数，深度回到0时就是配对的括号
+    let mut visit = |c: char| -> bool {
+        if c == this {
+            depth += 1;
+        } else if c == other {
+            depth -= 1;
+            return depth == 0;
+        }
+        false
+    };
+    if forward {
+        let end = rows.len().min(row + MAX_SCAN_LINES);
+        for r in row..end {
+            let start = if r == row { at } else { 0 };
+            for (i, c) in rows[r].text().chars().enumerate().skip(start) {
+                if visit(c) {
+                    return Some((r, i));
+                }
+            }
+        }
+    } else {
+        let end = row.saturating_sub(MAX_SCAN_LINES);
+        for r in (end..=row).rev() {
+            let chars: Vec<char> = rows[r].text().chars().collect();
+            let start = if r == row { at + 1 } else { chars.len() };
+            for (i, &c) in chars[..start].iter().enumerate().rev() {
+                if visit(c) {
+                    return Some((r, i));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// 光标下的括号和它的配对括号在屏幕上的位置，(行, 列)
+pub(super) fn matching_columns(rows: &TextBuffer, (cx, cy): (usize, usize)) -> Vec<(usize, usize)> {
+    let Some(row) = rows.get(cy) else {
+        return Vec::new();
+    };
+    let at = row.get_raw_index(cx);
+    let Some((r, i)) = find_match(rows, (cy, at)) else {
+        return Vec::new();
+    };
+    vec![(cy, row.column_of(at)), (r, rows[r].column_of(i))]
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 跳到配对的括号，光标不在括号上时使用光标后面的第一个括号
+    pub(super) fn jump_to_match(&mut self) {
+        let cy = self.buffer.cy as usize;
+        let Some(row) = self.buffer.rows.get(cy) else {
+            return;
+        };
+        let at = row.get_raw_index(self.buffer.cx as usize);
+        let bracket = row.text().chars().skip(at).position(|c| bracket_pair(c).is_some());
+        let target = bracket.and_then(|offset| find_match(&self.buffer.rows, (cy, at + offset)));
+        match target {
+            Some(position) => self.set_position(position),
+            None => self.show_message(Message::new("No matching bracket".to_string())),
+        }
+    }
+}
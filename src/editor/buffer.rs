@@ -0,0 +1,891 @@
+use std::mem;
+use std::path::{Path, PathBuf};
+
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::task::JoinHandle;
+use tokio::time::{self, Duration};
+
+use super::event::Event;
+use super::format::LineEnding;
+use super::git::{GitDiff, RepoStatus};
+use super::indent::{self, Indent};
+use super::prompt::complete_path;
+use super::save::Swap;
+use super::scroll::Align;
+use super::watch::{self, DiskState};
+use super::selection::Selection;
+use super::text::TextBuffer;
+use super::undo::UndoTree;
+use super::{ControlKey, Editor, Key, Message, Row, Terminal};
+use crate::error::{ErrorSeverity, Result};
+use crate::reader::{self, ByteStream, Decoder, KeyStream};
+
+/// 打开文件时的选项
+#[derive(Debug, Clone, Default)]
+pub struct OpenOptions {
+    /// 文件编码，None表示根据文件内容自动检测
+    pub encoding: Option<String>,
+    /// 是否以只读方式打开
+    pub readonly: bool,
+    /// 打开后光标所在的行，从1开始
+    pub line: Option<usize>,
+    /// 像tail -f一样在文件变长时把新的行追加到buffer中
+    pub follow: bool,
+}
+
+/// 光标位置和偏移量
+pub(super) type CursorState = (u16, u16, usize, usize);
+
+/// 后台加载文件时每次发送的行数
+const LOAD_BATCH_SIZE: usize = 4096;
+
+/// 跟随模式检查文件末尾是否有新内容的间隔
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 后台加载任务发给编辑器的事件，第一个字段是buffer编号
+pub(super) enum LoadEvent {
+    Rows(usize, Vec<Row>),
+    /// 加载完成，附带后台加载时有损解码替换的字节偏移量，和根据所有的行决定的换行符
+    Done(usize, Vec<usize>, LineEnding),
+    Failed(usize, String),
+    /// 跟随模式读到的新的行，为true时第一行接在buffer最后一行的后面
+    Appended(usize, Vec<Row>, bool),
+}
+
+/// 使用指定编码逐行读取文件
+struct LineReader {
+    // file和stdin一样实现了read trait，可以用byte_stream包装
+    // 这样可以支持不同编码的文件读取
+    key_stream: KeyStream<File>,
+    tab_width: usize,
+    /// 文件开头是否有BOM，BOM不会出现在读取的内容中
+    bom: bool,
+    /// 读到的以CRLF和LF结尾的行数
+    crlf_count: usize,
+    lf_count: usize,
+    /// 最后读到的一行没有换行符，文件还在写入时之后的内容属于同一行
+    unterminated: bool,
+}
+
+impl LineReader {
+    /// 没有指定编码时根据文件开头的内容检测
+    async fn new(file: File, encoding: Option<&str>, tab_width: usize) -> Result<Self> {
+        let mut byte_stream = ByteStream::new(file);
+        let encoding = match encoding {
+            Some(encoding) => encoding.to_string(),
+            None => reader::detect_encoding(byte_stream.peek_chunk().await?).to_string(),
+        };
+        // 无效的字节替换为U+FFFD，不会因为文件中有少量损坏而无法打开
+        let bom = byte_stream.take_bom(&encoding).await?.is_some();
+        let decoder = Decoder::builder()
+            .encoding(encoding)
+            .byte_stream(byte_stream)
+            .lossy(true)
+            .build()?;
+        Ok(Self {
+            key_stream: KeyStream::new(decoder),
+            tab_width,
+            bom,
+            crlf_count: 0,
+            lf_count: 0,
+            unterminated: false,
+        })
+    }
+
+    fn encoding(&self) -> &'static str {
+        self.key_stream.encoding()
+    }
+
+    /// 目前读到的行中占多数的换行符
+    fn line_ending(&self) -> LineEnding {
+        if self.crlf_count > self.lf_count {
+            LineEnding::Crlf
+        } else {
+            LineEnding::Lf
+        }
+    }
+
+    /// 读取下一行，不包含换行符，读到文件末尾时返回None
+    // 因为我们知道一个line代表一行，因此存储换行符是没有意义的
+    async fn read_row(&mut self) -> Result<Option<Row>> {
+        let (line, terminated) = self.read_line().await?;
+        // 最后一行可能没有换行符
+        if line.is_empty() && !terminated {
+            return Ok(None);
+        }
+        self.unterminated = !terminated;
+        Ok(Some(Row::from_text(line, self.tab_width)))
+    }
+
+    /// 读取到换行符或者文件末尾，返回的bool表示是否读到了换行符
+    /// 直接拼接成文本，没有显示过的行不需要按键和渲染的结果
+    /// 不在换行符前面的回车和其他控制字符一样保留在行中
+    async fn read_line(&mut self) -> Result<(String, bool)> {
+        let mut line = String::new();
+        while let Some(c) = self.key_stream.next_text_key().await?.as_ref().and_then(Key::as_char) {
+            if c == '\n' {
+                // 换行前是回车时是CRLF
+                if line.ends_with('\r') {
+                    line.pop();
+                    self.crlf_count += 1;
+                } else {
+                    self.lf_count += 1;
+                }
+                return Ok((line, true));
+            }
+            line.push(c);
+        }
+        Ok((line, false))
+    }
+
+    /// 在后台读取剩余的行，分批发给编辑器
+    async fn load_rest(mut self, id: usize, sender: UnboundedSender<LoadEvent>) {
+        self.read_rest(id, &sender).await;
+    }
+
+    /// 读取剩余的行，读到文件末尾时返回true
+    async fn read_rest(&mut self, id: usize, sender: &UnboundedSender<LoadEvent>) -> bool {
+        let mut rows = Vec::with_capacity(LOAD_BATCH_SIZE);
+        loop {
+            match self.read_row().await {
+                Ok(Some(row)) => {
+                    rows.push(row);
+                    if rows.len() == LOAD_BATCH_SIZE {
+                        let batch = mem::replace(&mut rows, Vec::with_capacity(LOAD_BATCH_SIZE));
+                        // 编辑器已经退出时直接结束
+                        if sender.send(LoadEvent::Rows(id, batch)).is_err() {
+                            return false;
+                        }
+                    }
+                }
+                Ok(None) => {
+                    let _ = sender.send(LoadEvent::Rows(id, rows));
+                    let damage = self.key_stream.take_damage();
+                    let line_ending = self.line_ending();
+                    return sender.send(LoadEvent::Done(id, damage, line_ending)).is_ok();
+                }
+                Err(e) => {
+                    let _ = sender.send(LoadEvent::Rows(id, rows));
+                    let _ = sender.send(LoadEvent::Failed(id, e.to_string()));
+                    return false;
+                }
+            }
+        }
+    }
+
+    /// 读完剩余的行之后定时从文件末尾继续读取，把新写入的行发给编辑器
+    /// 文件在读到末尾之后不会关闭，再次读取时可以读到之后追加的内容
+    async fn follow(mut self, id: usize, sender: UnboundedSender<LoadEvent>) {
+        if !self.read_rest(id, &sender).await {
+            return;
+        }
+        let mut timer = time::interval(FOLLOW_INTERVAL);
+        loop {
+            timer.tick().await;
+            let continues = self.unterminated;
+            let mut rows = Vec::new();
+            loop {
+                match self.read_row().await {
+                    Ok(Some(row)) => rows.push(row),
+                    Ok(None) => break,
+                    Err(e) => {
+                        let _ = sender.send(LoadEvent::Failed(id, e.to_string()));
+                        return;
+                    }
+                }
+            }
+            if rows.is_empty() {
+                continue;
+            }
+            if sender.send(LoadEvent::Appended(id, rows, continues)).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// 一个打开的文件及其编辑状态
+/// 每个文件对应一个buffer，切换buffer时保留各自的光标位置
+pub(super) struct Buffer {
+    /// buffer编号，从1开始，关闭后不会复用
+    pub(super) id: usize,
+    // rows应该存储key而不是string
+    // 显示的时候再进行渲染
+    // 这样可以控制比如tab等的渲染方式
+    // 同时也可以按照原始输入存储
+    // 每个key占据的宽度可能不一样，列数是渲染后的宽度
+    // 真的需要存储key吗？
+    // 如果希望tab转换为空格，那么存储时是不是也希望将tab存储为空格呢？
+    // 使用key的灵活性更高，可以设置一个开关，决定是否将tab转换为空格
+    // 如果不转换，则按空格显示，但是按tab存储
+    // 如果转换，则按空格存储和显示
+    // rows: Vec<String>,
+    // 按块存储，编辑大文件时插入删除行不需要移动所有行
+    pub(super) rows: TextBuffer,
+    pub(super) current_file: Option<PathBuf>,
+    /// 修改的次数，只会增加
+    pub(super) changes: u64,
+    /// 读取或保存文件时的修改次数，和changes不同时buffer有没有保存的修改
+    pub(super) saved_changes: u64,
+    /// 上一次写入交换文件时的修改次数
+    pub(super) swap_changes: u64,
+    pub(super) swap: Option<Swap>,
+    /// 读取或保存文件时文件在磁盘上的状态，用于发现外部修改
+    pub(super) disk_state: Option<DiskState>,
+    pub(super) readonly: bool,
+    /// 读写文件使用的编码
+    pub(super) encoding: String,
+    /// 保存时是否在开头写入BOM
+    pub(super) bom: bool,
+    /// 保存时使用的换行符
+    pub(super) line_ending: LineEnding,
+    // cursor横坐标
+    pub(super) cx: u16,
+    // cursor纵坐标
+    pub(super) cy: u16,
+    // 行偏移量
+    pub(super) row_offset: usize,
+    // 列偏移量
+    pub(super) col_offset: usize,
+    /// 正在进行的选择
+    pub(super) selection: Option<Selection>,
+    /// 主光标之外的其他光标，(行, raw索引)
+    pub(super) cursors: Vec<(usize, usize)>,
+    /// 后台加载剩余内容的任务，加载完成后为None
+    pub(super) loader: Option<JoinHandle<()>>,
+    /// 跟随模式下读取文件新内容的任务
+    pub(super) follower: Option<JoinHandle<()>>,
+    /// 读取时被替换为U+FFFD的无效字节的偏移量
+    pub(super) damage: Vec<usize>,
+    /// 不对应文件的buffer显示的名称，比如消息列表
+    pub(super) title: Option<String>,
+    /// 打开文件时检测到的缩进方式，None时使用配置
+    pub(super) indent: Option<Indent>,
+    /// 和HEAD比较的结果，文件不在git仓库中时为None
+    pub(super) git: Option<GitDiff>,
+    /// 文件所在仓库的分支和状态
+    pub(super) repo: Option<RepoStatus>,
+    pub(super) undo: UndoTree,
+}
+
+impl Buffer {
+    pub(super) fn new(id: usize) -> Self {
+        Self {
+            id,
+            rows: TextBuffer::new(),
+            current_file: None,
+            changes: 0,
+            saved_changes: 0,
+            swap_changes: 0,
+            swap: None,
+            disk_state: None,
+            readonly: false,
+            encoding: "UTF-8".to_string(),
+            bom: false,
+            line_ending: LineEnding::Lf,
+            cx: 0,
+            cy: 0,
+            row_offset: 0,
+            col_offset: 0,
+            selection: None,
+            cursors: Vec::new(),
+            loader: None,
+            follower: None,
+            damage: Vec::new(),
+            title: None,
+            indent: None,
+            git: None,
+            repo: None,
+            undo: UndoTree::default(),
+        }
+    }
+
+    /// 用于状态栏显示的文件名
+    pub(super) fn name(&self) -> &str {
+        if let Some(title) = &self.title {
+            return title;
+        }
+        match &self.current_file {
+            // 当Option是Some时，and_then应用闭包返回新的Option
+            // 如果是None，则直接返回None
+            Some(name) => name
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("[No Name]"),
+            None => "[No Name]",
+        }
+    }
+
+    pub(super) fn cursor(&self) -> CursorState {
+        (self.cx, self.cy, self.row_offset, self.col_offset)
+    }
+
+    pub(super) fn set_cursor(&mut self, cursor: CursorState) {
+        (self.cx, self.cy, self.row_offset, self.col_offset) = cursor;
+    }
+
+    /// 用换行符连接所有行
+    pub(super) fn text(&self, line_ending: &str) -> String {
+        let mut content = String::new();
+        for row in self.rows.iter() {
+            content.push_str(row.text());
+            content.push_str(line_ending);
+        }
+        content
+    }
+
+    pub(super) fn mark_dirty(&mut self) {
+        self.changes += 1;
+        if let Some(git) = &mut self.git {
+            git.stale = true;
+        }
+    }
+
+    pub(super) fn is_dirty(&self) -> bool {
+        self.changes != self.saved_changes
+    }
+
+    /// 上一次读取或保存之后的修改次数
+    pub(super) fn unsaved_changes(&self) -> u64 {
+        self.changes - self.saved_changes
+    }
+
+    pub(super) fn mark_saved(&mut self) {
+        self.saved_changes = self.changes;
+    }
+
+    /// 还没有写入交换文件的修改次数
+    pub(super) fn unswapped_changes(&self) -> u64 {
+        self.changes - self.swap_changes
+    }
+
+    pub(super) fn is_loading(&self) -> bool {
+        self.loader.is_some()
+    }
+
+    pub(super) fn is_following(&self) -> bool {
+        self.follower.is_some()
+    }
+
+    /// 文件中有无效字节时的警告
+    fn damage_warning(&self) -> Option<String> {
+        let first = self.damage.first()?;
+        Some(format!(
+            "Warning: {} invalid byte sequence(s) in {} replaced with U+FFFD, first at byte {}",
+            self.damage.len(),
+            self.name(),
+            first
+        ))
+    }
+}
+
+impl Drop for Buffer {
+    // 关闭buffer时停止后台加载
+    fn drop(&mut self) {
+        if let Some(loader) = self.loader.take() {
+            loader.abort();
+        }
+        if let Some(follower) = self.follower.take() {
+            follower.abort();
+        }
+    }
+}
+
+/// 用指定的编码读取文件的所有行
+pub(super) async fn read_rows(path: &Path, encoding: &str, tab_width: usize) -> Result<Vec<Row>> {
+    let file = File::open(path).await?;
+    let mut lines = LineReader::new(file, Some(encoding), tab_width).await?;
+    let mut rows = Vec::new();
+    while let Some(row) = lines.read_row().await? {
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 在新的buffer中打开文件，并切换到该buffer
+    /// 文件不存在时创建一个空buffer，保存时再创建文件
+    pub async fn open_file(
+        &mut self,
+        filename: impl AsRef<Path>,
+        options: &OpenOptions,
+    ) -> Result<()> {
+        let path = filename.as_ref();
+
+        // 文件已经打开时直接切换过去
+        let opened = |b: &Buffer| b.current_file.as_deref() == Some(path);
+        if let Some(id) = self.buffers.iter().find(|b| opened(b)).map(|b| b.id) {
+            self.switch_buffer(id);
+        }
+        if opened(&self.buffer) {
+            if let Some(line) = options.line {
+                self.goto_line(line);
+            }
+            return Ok(());
+        }
+
+        // 编号在push_buffer中分配
+        let mut buffer = Buffer::new(0);
+        // 先只读取足够显示第一屏的行，剩余的行在后台加载，这样打开很大的文件也不需要等待
+        let mut reader = None;
+        // 指定了行号时不恢复上次的位置
+        let restore = options.line.is_none().then(|| self.remembered_position(path)).flatten();
+        match File::open(path).await {
+            Ok(file) => {
+                buffer.disk_state = watch::disk_state(path).await;
+                let encoding = options.encoding.as_deref();
+                let mut lines = LineReader::new(file, encoding, self.config.tab_width).await?;
+                buffer.encoding = lines.encoding().to_ascii_uppercase();
+                buffer.bom = lines.bom;
+                let mut rows = Vec::new();
+                // 要跳转到的行也需要先读取
+                let line = options.line.or(restore.map(|(row, _)| row + 1));
+                let initial = self.screen_rows as usize + line.unwrap_or(0);
+                while rows.len() < initial.max(1) {
+                    match lines.read_row().await? {
+                        Some(row) => rows.push(row),
+                        None => break,
+                    }
+                }
+                buffer.damage = lines.key_stream.take_damage();
+                // 根据第一屏的内容决定换行符
+                buffer.line_ending = lines.line_ending();
+                // 跟随模式在读到末尾之后也要继续读取
+                if rows.len() == initial.max(1) || options.follow {
+                    reader = Some(lines);
+                }
+                buffer.rows = rows.into();
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                if let Some(encoding) = &options.encoding {
+                    buffer.encoding = encoding.to_ascii_uppercase();
+                }
+            }
+            Err(e) => return Err(e.into()),
+        };
+        // 上次没有正常退出，用交换文件中的内容代替
+        if let Some((rows, swap)) = self.recover_swap(path).await? {
+            buffer.rows = rows.into();
+            buffer.swap = Some(swap);
+            buffer.mark_dirty();
+            reader = None;
+        }
+        buffer.current_file = Some(path.to_path_buf());
+        // 跟随的文件还在被其他程序写入，保存会覆盖写入的内容
+        buffer.readonly = options.readonly || (options.follow && reader.is_some());
+
+        if let Some(warning) = buffer.damage_warning() {
+            self.show_message(Message::warning(warning));
+        }
+        self.push_buffer(buffer);
+        if let Some(reader) = reader {
+            let sender = self.load_sender.clone();
+            let id = self.buffer.id;
+            if options.follow {
+                self.buffer.follower = Some(tokio::spawn(reader.follow(id, sender)));
+                // 从文件末尾开始看
+                if options.line.is_none() {
+                    let last = self.buffer.rows.len().saturating_sub(1);
+                    self.set_position((last, 0));
+                }
+            } else {
+                self.buffer.loader = Some(tokio::spawn(reader.load_rest(id, sender)));
+            }
+        }
+        if let Some(line) = options.line {
+            self.goto_line(line);
+        }
+        if let Some(position) = restore.filter(|_| !self.buffer.is_following()) {
+            self.set_position(position);
+            self.align_cursor(Align::Center);
+        }
+        self.emit(Event::BufferOpened(self.buffer.id));
+        Ok(())
+    }
+
+    /// 将buffer设为当前buffer，原来的当前buffer放到队尾
+    /// 如果当前buffer是未修改的空白buffer，则直接替换掉并沿用它的编号
+    pub(super) fn push_buffer(&mut self, mut buffer: Buffer) {
+        let is_scratch = self.buffer.current_file.is_none()
+            && !self.buffer.is_dirty()
+            && self.buffer.rows.is_empty();
+        if is_scratch {
+            buffer.id = self.buffer.id;
+            self.buffer = buffer;
+        } else {
+            buffer.id = self.next_buffer_id;
+            self.next_buffer_id += 1;
+            let old = mem::replace(&mut self.buffer, buffer);
+            self.buffers.push_back(old);
+        }
+    }
+
+    /// 切换到下一个buffer
+    pub fn next_buffer(&mut self) {
+        let Some(next) = self.buffers.pop_front() else {
+            self.show_message(Message::new("No other buffers".to_string()));
+            return;
+        };
+        let old = mem::replace(&mut self.buffer, next);
+        self.buffers.push_back(old);
+        self.show_buffer();
+    }
+
+    /// 切换到上一个buffer
+    pub fn prev_buffer(&mut self) {
+        let Some(prev) = self.buffers.pop_back() else {
+            self.show_message(Message::new("No other buffers".to_string()));
+            return;
+        };
+        let old = mem::replace(&mut self.buffer, prev);
+        self.buffers.push_front(old);
+        self.show_buffer();
+    }
+
+    /// 切换到指定编号的buffer
+    pub fn switch_buffer(&mut self, id: usize) {
+        if self.buffer.id == id {
+            return;
+        }
+        if !self.rotate_to_buffer(id) {
+            self.show_message(Message::new(format!("No buffer {}", id)));
+            return;
+        }
+        self.show_buffer();
+    }
+
+    /// 依次轮转直到指定编号的buffer成为当前buffer，保持buffer之间的顺序不变
+    /// buffer不存在时返回false
+    pub(super) fn rotate_to_buffer(&mut self, id: usize) -> bool {
+        if self.buffer.id != id && !self.buffers.iter().any(|b| b.id == id) {
+            return false;
+        }
+        while self.buffer.id != id {
+            let next = self.buffers.pop_front().unwrap();
+            let old = mem::replace(&mut self.buffer, next);
+            self.buffers.push_back(old);
+        }
+        true
+    }
+
+    pub(super) fn find_buffer_mut(&mut self, id: usize) -> Option<&mut Buffer> {
+        if self.buffer.id == id {
+            Some(&mut self.buffer)
+        } else {
+            self.buffers.iter_mut().find(|b| b.id == id)
+        }
+    }
+
+    /// 重新读取当前buffer的文件，放弃内存中的修改
+    pub(super) async fn reload_buffer(&mut self) -> Result<()> {
+        let Some(path) = self.buffer.current_file.clone() else {
+            return Ok(());
+        };
+        let file = File::open(&path).await?;
+        let mut lines = LineReader::new(file, Some(&self.buffer.encoding), self.config.tab_width)
+            .await?;
+        let mut rows = Vec::new();
+        while let Some(row) = lines.read_row().await? {
+            rows.push(row);
+        }
+        self.buffer.bom = lines.bom;
+        self.buffer.line_ending = lines.line_ending();
+        self.buffer.damage = lines.key_stream.take_damage();
+        self.buffer.rows = rows.into();
+        self.buffer.selection = None;
+        self.buffer.undo.clear();
+        self.buffer.mark_saved();
+        self.buffer.swap_changes = self.buffer.changes;
+        if let Some(swap) = self.buffer.swap.take() {
+            swap.remove().await;
+        }
+        self.buffer.disk_state = watch::disk_state(&path).await;
+        self.buffer.cy = self.buffer.cy.min(self.buffer.rows.len() as u16);
+        self.clamp_cursor_x();
+        self.scroll();
+        self.show_message(Message::new(format!("Reloaded {}", self.buffer.name())));
+        Ok(())
+    }
+
+    /// 处理后台加载任务发来的事件
+    pub(super) fn handle_load_event(&mut self, event: LoadEvent) {
+        let id = match &event {
+            LoadEvent::Rows(id, _)
+            | LoadEvent::Done(id, ..)
+            | LoadEvent::Failed(id, _)
+            | LoadEvent::Appended(id, ..) => *id,
+        };
+        let detect_indent = self.config.detect_indent;
+        // buffer可能已经被关闭了
+        let Some(buffer) = self.find_buffer_mut(id) else {
+            return;
+        };
+        // 跟随模式下光标在最后一行时保持在最后一行，向上移动之后不再跟随
+        let pinned = buffer.is_following() && buffer.cy as usize + 1 >= buffer.rows.len();
+        match event {
+            LoadEvent::Rows(_, rows) => {
+                for row in rows {
+                    buffer.rows.push(row);
+                }
+            }
+            LoadEvent::Appended(_, rows, continues) => {
+                let mut rows = rows.into_iter();
+                if continues
+                    && let Some(last) = buffer.rows.len().checked_sub(1)
+                    && let Some(first) = rows.next()
+                {
+                    buffer.rows[last].append(&first);
+                }
+                for row in rows {
+                    buffer.rows.push(row);
+                }
+            }
+            LoadEvent::Done(_, damage, line_ending) => {
+                buffer.loader = None;
+                // 打开时只读取了第一屏，用完整的内容重新检测
+                // 加载时不能编辑，换行符不会被手动修改过
+                buffer.line_ending = line_ending;
+                if detect_indent {
+                    buffer.indent = indent::detect_indent(&buffer.rows);
+                }
+                if !damage.is_empty() {
+                    buffer.damage.extend(damage);
+                    if let Some(warning) = buffer.damage_warning() {
+                        self.show_message(Message::warning(warning));
+                    }
+                }
+            }
+            LoadEvent::Failed(_, e) => {
+                buffer.loader = None;
+                buffer.follower = None;
+                // 文件没有完整读取，避免保存时覆盖原文件
+                buffer.readonly = true;
+                let name = buffer.name().to_string();
+                self.show_message(Message::with_severity(
+                    format!("Error loading {}: {}", name, e),
+                    ErrorSeverity::Error,
+                ));
+            }
+        }
+        if pinned && let Some(buffer) = self.find_buffer_mut(id) {
+            buffer.cy = buffer.rows.len().saturating_sub(1) as u16;
+            buffer.cx = 0;
+            if self.buffer.id == id {
+                self.scroll();
+            }
+        }
+    }
+
+    /// 查找指定编号的buffer
+    pub(super) fn find_buffer(&self, id: usize) -> Option<&Buffer> {
+        if self.buffer.id == id {
+            Some(&self.buffer)
+        } else {
+            self.buffers.iter().find(|b| b.id == id)
+        }
+    }
+
+    /// 关闭当前buffer，切换到下一个buffer
+    /// 关闭最后一个buffer时留下一个空白buffer
+    /// buffer有未保存的修改时需要确认
+    pub async fn close_buffer(&mut self) {
+        if self.buffer.is_dirty() {
+            let question = format!(
+                "{} has unsaved changes, close anyway? (y/n)",
+                self.buffer.name()
+            );
+            if !self.confirm(&question).await {
+                self.message = None;
+                return;
+            }
+        }
+        let mut closed = match self.buffers.pop_front() {
+            Some(next) => mem::replace(&mut self.buffer, next),
+            None => {
+                let scratch = Buffer::new(self.next_buffer_id);
+                self.next_buffer_id += 1;
+                mem::replace(&mut self.buffer, scratch)
+            }
+        };
+        self.remember_position(&closed);
+        // 放弃了未保存的修改
+        if let Some(swap) = closed.swap.take() {
+            swap.remove().await;
+        }
+        self.show_message(Message::new(format!(
+            "Closed buffer {}: {}",
+            closed.id,
+            closed.name()
+        )));
+    }
+
+    /// 在消息栏中列出所有buffer，当前buffer用*标记，有修改的用+标记
+    pub fn list_buffers(&mut self) {
+        let mut buffers: Vec<&Buffer> = self.buffers.iter().collect();
+        buffers.push(&self.buffer);
+        buffers.sort_by_key(|b| b.id);
+        let list = buffers
+            .iter()
+            .map(|b| {
+                format!(
+                    "{}[{}] {}{}",
+                    if b.id == self.buffer.id { "*" } else { "" },
+                    b.id,
+                    b.name(),
+                    if b.is_dirty() { "+" } else { "" }
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("  ");
+        self.show_message(Message::new(list));
+    }
+
+    /// 在只读的buffer中显示列表，标题相同的buffer已经打开时更新内容并切换过去
+    pub(super) fn show_list(&mut self, title: &str, rows: Vec<Row>) {
+        let opened = |b: &Buffer| b.title.as_deref() == Some(title);
+        if let Some(id) = self.buffers.iter().find(|b| opened(b)).map(|b| b.id) {
+            self.switch_buffer(id);
+        }
+        if !opened(&self.buffer) {
+            let mut buffer = Buffer::new(0);
+            buffer.title = Some(title.to_string());
+            buffer.readonly = true;
+            self.push_buffer(buffer);
+        }
+        self.buffer.rows = rows.into();
+        self.buffer.selection = None;
+        self.set_position((0, 0));
+    }
+
+    /// 从消息栏输入文件名并在新的buffer中打开
+    pub async fn prompt_open_file(&mut self) {
+        let Some(input) = self.prompt_with_completion("Open file: ", complete_path).await else {
+            return;
+        };
+        let filename = Row::raw_str(&input);
+        if filename.is_empty() {
+            return;
+        }
+        let options = OpenOptions::default();
+        if let Err(e) = self.open_file(&filename, &options).await {
+            self.show_message(Message::error(format!("Error opening {}: {}", filename, e), &e));
+        } else {
+            self.show_buffer();
+        }
+    }
+
+    /// Ctrl+B之后的按键决定对buffer的操作
+    pub(super) async fn buffer_command(&mut self) {
+        self.message = Some(Message::new(
+            "Buffer: n(ext) p(rev) o(pen) d(elete) l(ist) m(essages) 1-9".to_string(),
+        ));
+        if self.refresh_screen().is_err() {
+            return;
+        }
+        let Ok(key) = self.get_key().await else {
+            return;
+        };
+        self.message = None;
+        match key {
+            Key::Char('n') | Key::ControlKey(ControlKey::Ctrl('b')) => self.next_buffer(),
+            Key::Char('p') => self.prev_buffer(),
+            Key::Char('o') => self.prompt_open_file().await,
+            Key::Char('d') => self.close_buffer().await,
+            Key::Char('l') => self.list_buffers(),
+            Key::Char('m') => self.show_messages(),
+            Key::Char(c @ '1'..='9') => self.switch_buffer(c as usize - '0' as usize),
+            _ => {}
+        }
+    }
+
+    fn show_buffer(&mut self) {
+        self.show_message(Message::new(format!(
+            "Buffer {}: {}",
+            self.buffer.id,
+            self.buffer.name()
+        )));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::editor::Direction;
+
+    /// 前三行以LF结尾，其余的行以CRLF结尾
+    fn write_file(name: &str, lines: usize) -> PathBuf {
+        let path = env::temp_dir().join(format!("fim-{}-{}.txt", name, process::id()));
+        let text: String = (0..lines)
+            .map(|i| format!("line {}{}", i, if i < 3 { "\n" } else { "\r\n" }))
+            .collect();
+        fs::write(&path, text).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn loads_the_rest_in_batches() {
+        let path = write_file("batches", 2 * LOAD_BATCH_SIZE + 1);
+        let mut lines = LineReader::new(File::open(&path).await.unwrap(), None, 4).await.unwrap();
+        for _ in 0..3 {
+            lines.read_row().await.unwrap();
+        }
+        // 第一屏只有LF
+        assert_eq!(lines.line_ending(), LineEnding::Lf);
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        lines.load_rest(1, sender).await;
+        fs::remove_file(&path).unwrap();
+
+        let mut batches = Vec::new();
+        let mut done = None;
+        while let Some(event) = receiver.recv().await {
+            match event {
+                LoadEvent::Rows(1, rows) => batches.push(rows),
+                LoadEvent::Done(1, damage, line_ending) => done = Some((damage, line_ending)),
+                _ => panic!("unexpected event"),
+            }
+        }
+        let sizes: Vec<usize> = batches.iter().map(Vec::len).collect();
+        assert_eq!(sizes, [LOAD_BATCH_SIZE, LOAD_BATCH_SIZE - 2]);
+        assert_eq!(batches[0][0].text(), "line 3");
+        assert_eq!(batches[1].last().unwrap().text(), format!("line {}", 2 * LOAD_BATCH_SIZE));
+        // 加载完成时按所有的行决定换行符
+        assert_eq!(done, Some((Vec::new(), LineEnding::Crlf)));
+    }
+
+    #[tokio::test]
+    async fn edits_wait_until_loading_is_done() {
+        let mut editor = super::super::tests::editor(40, 5).await;
+        let lines = LOAD_BATCH_SIZE + 10;
+        let path = write_file("editing", lines);
+        editor.open_file(&path, &OpenOptions::default()).await.unwrap();
+        assert!(editor.buffer.is_loading());
+        assert!(editor.buffer.rows.len() < lines);
+        assert_eq!(editor.buffer.line_ending, LineEnding::Lf);
+
+        // 加载时可以移动光标，但是不能修改内容
+        editor.handle_command(&Key::ArrowKey(Direction::Down)).await;
+        editor.handle_command(&Key::Char('x')).await;
+        assert_eq!(editor.buffer.cy, 1);
+        assert_eq!(editor.buffer.rows[1].text(), "line 1");
+        assert!(!editor.buffer.is_dirty());
+
+        while editor.buffer.is_loading() {
+            let event = editor.load_receiver.recv().await.unwrap();
+            editor.handle_load_event(event);
+        }
+        fs::remove_file(&path).unwrap();
+        assert_eq!(editor.buffer.rows.len(), lines);
+        assert_eq!(editor.buffer.rows[lines - 1].text(), format!("line {}", lines - 1));
+        assert_eq!(editor.buffer.line_ending, LineEnding::Crlf);
+
+        editor.handle_command(&Key::Char('x')).await;
+        assert_eq!(editor.buffer.rows[1].text(), "xline 1");
+        assert!(editor.buffer.is_dirty());
+    }
+}
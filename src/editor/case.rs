@@ -0,0 +1,182 @@
+use regex::Regex;
+use tokio::io::AsyncReadExt;
+
+use super::{Editor, Key, Message, Terminal};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Case {
+    Upper,
+    Lower,
+    /// 每个单词的第一个字母大写，其他字母小写
+    Title,
+}
+
+/// 转换按键中字符的大小写，一个字符可能转换成多个字符，比如ß转换成SS
+fn convert_case(keys: &[Key], case: Case) -> Vec<Key> {
+    let mut converted = Vec::with_capacity(keys.len());
+    let mut in_word = false;
+    for key in keys {
+        let Key::Char(c) = key else {
+            converted.push(key.clone());
+            in_word = false;
+            continue;
+        };
+        let upper = match case {
+            Case::Upper => true,
+            Case::Lower => false,
+            Case::Title => !in_word,
+        };
+        if upper {
+            converted.extend(c.to_uppercase().map(Key::Char));
+        } else {
+            converted.extend(c.to_lowercase().map(Key::Char));
+        }
+        // 单词中的撇号不开始新的单词，比如don't
+        in_word = c.is_alphanumeric() || (in_word && *c == '\'');
+    }
+    converted
+}
+
+/// 加上delta后的数字，保持原来的位数和十六进制字母的大小写
+fn add_to_number(number: &str, delta: i64) -> Option<String> {
+    if let Some(digits) = number.get(2..).filter(|_| number[..2].eq_ignore_ascii_case("0x")) {
+        let value = u64::from_str_radix(digits, 16).ok()?.wrapping_add_signed(delta);
+        let width = digits.len();
+        let hex = if digits.chars().any(|c| c.is_ascii_uppercase()) {
+            format!("{:0width$X}", value)
+        } else {
+            format!("{:0width$x}", value)
+        };
+        return Some(format!("{}{}", &number[..2], hex));
+    }
+    let value = number.parse::<i64>().ok()?.checked_add(delta)?;
+    let digits = number.trim_start_matches('-');
+    // 有前导零时保持位数，比如007加1是008
+    if digits.len() > 1 && digits.starts_with('0') {
+        let sign = if value < 0 { "-" } else { "" };
+        return Some(format!("{}{:0width$}", sign, value.unsigned_abs(), width = digits.len()));
+    }
+    Some(value.to_string())
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 转换选中的内容或者光标所在单词的大小写
+    pub(super) fn change_case(&mut self, case: Case) {
+        if !self.check_writable() {
+            return;
+        }
+        let Some(ranges) = self.selected_ranges() else {
+            self.at_cursors(true, |editor| editor.change_word_case(case));
+            return;
+        };
+        for (i, range) in &ranges {
+            let row = &mut self.buffer.rows[*i];
+            let converted = convert_case(&row.keys_in(range.clone()), case);
+            row.replace(range.clone(), converted);
+        }
+        self.buffer.selection = None;
+        self.buffer.mark_dirty();
+        if let Some((i, range)) = ranges.first() {
+            self.set_position((*i, range.start));
+        }
+    }
+
+    fn change_word_case(&mut self, case: Case) {
+        let Some(word) = self.word_under_cursor() else {
+            return;
+        };
+        let (cy, at) = (self.buffer.cy as usize, self.raw_cursor());
+        let row = &mut self.buffer.rows[cy];
+        let converted = convert_case(&row.keys_in(word.clone()), case);
+        let len = converted.len();
+        row.replace(word.clone(), converted);
+        self.buffer.mark_dirty();
+        self.set_position((cy, at.min(word.start + len)));
+    }
+
+    /// 把光标处或者光标后面的第一个数字加上delta，支持十进制和0x开头的十六进制
+    pub(super) fn increment_number(&mut self, delta: i64) {
+        if !self.check_writable() {
+            return;
+        }
+        self.at_cursors(true, |editor| editor.increment_at_cursor(delta));
+    }
+
+    fn increment_at_cursor(&mut self, delta: i64) {
+        let cy = self.buffer.cy as usize;
+        let at = self.raw_cursor();
+        let Some(row) = self.buffer.rows.get(cy) else {
+            return;
+        };
+        // 每个按键对应一个字符，这样字符的位置就是raw中的位置
+        let text = row.text();
+        let pattern = Regex::new(r"(?i)0x[0-9a-f]+|-?[0-9]+").expect("valid regex");
+        let index = |byte: usize| text[..byte].chars().count();
+        let Some(found) = pattern
+            .find_iter(text)
+            .map(|m| (index(m.start())..index(m.end()), m.as_str()))
+            .find(|(range, _)| range.end > at)
+        else {
+            self.show_message(Message::new("No number under or after cursor".to_string()));
+            return;
+        };
+        let (range, number) = found;
+        let Some(replaced) = add_to_number(number, delta) else {
+            self.show_message(Message::warning(format!("Number out of range: {}", number)));
+            return;
+        };
+        let keys: Vec<Key> = replaced.chars().map(Key::Char).collect();
+        let end = range.start + keys.len();
+        self.buffer.rows[cy].replace(range, keys);
+        self.buffer.mark_dirty();
+        self.set_position((cy, end - 1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::Row;
+
+    fn convert(text: &str, case: Case) -> String {
+        Row::raw_str(&convert_case(&Row::raw_keys(text), case))
+    }
+
+    #[test]
+    fn adds_across_zero_keeping_leading_zeros() {
+        assert_eq!(add_to_number("007", 1).as_deref(), Some("008"));
+        assert_eq!(add_to_number("007", -10).as_deref(), Some("-003"));
+        assert_eq!(add_to_number("-003", 10).as_deref(), Some("007"));
+        assert_eq!(add_to_number("099", 1).as_deref(), Some("100"));
+        // 没有前导零时不补零
+        assert_eq!(add_to_number("7", -10).as_deref(), Some("-3"));
+        assert_eq!(add_to_number("0", -1).as_deref(), Some("-1"));
+    }
+
+    #[test]
+    fn keeps_hex_case_and_width() {
+        assert_eq!(add_to_number("0x0f", 1).as_deref(), Some("0x10"));
+        assert_eq!(add_to_number("0X0F", 1).as_deref(), Some("0X10"));
+        assert_eq!(add_to_number("0xAb", 1).as_deref(), Some("0xAC"));
+        assert_eq!(add_to_number("0xff", 1).as_deref(), Some("0x100"));
+        // 十六进制按无符号数回绕
+        assert_eq!(add_to_number("0x00", -1).as_deref(), Some("0xffffffffffffffff"));
+    }
+
+    #[test]
+    fn overflow_is_not_a_number() {
+        assert_eq!(add_to_number("9223372036854775807", 1), None);
+        assert_eq!(add_to_number("-9223372036854775808", -1), None);
+        assert_eq!(add_to_number("99999999999999999999", 1), None);
+        assert_eq!(add_to_number("0x10000000000000000", 1), None);
+    }
+
+    #[test]
+    fn converts_case_of_special_letters() {
+        assert_eq!(convert("straße", Case::Upper), "STRASSE");
+        assert_eq!(convert("ß", Case::Title), "SS");
+        assert_eq!(convert("ΣΑΣ Ǆ", Case::Lower), "σασ ǆ");
+        // 撇号不分开单词，控制字符和空白一样分开单词
+        assert_eq!(convert("don't STOP\tnow", Case::Title), "Don't Stop\tNow");
+    }
+}
@@ -0,0 +1,156 @@
+use tokio::io::AsyncReadExt;
+
+use super::history::HistoryKind;
+use super::keymap::{Binding, Mode};
+use super::prompt::Prompt;
+use super::{Editor, Message, Row, Terminal};
+
+/// 命令行中可以补全的命令：名称、参数和说明，也用于帮助页面
+pub(super) const COMMANDS: &[(&str, &str, &str)] = &[
+    ("align", "<delimiter>", "Align the delimiter in the selected lines"),
+    ("digraphs", "", "List digraphs entered with <C-k>"),
+    ("help", "", "List key bindings and commands"),
+    ("json", "[format|minify]", "Check, format or minify JSON"),
+    ("make", "[args]", "Run make_command and list the errors"),
+    ("map", "<keys> <action>...", "Bind keys in normal mode"),
+    ("messages", "", "List recent messages"),
+    ("put-char", "<U+XXXX>", "Insert a character by its code point"),
+    ("redo", "", "Redo the last undone change"),
+    ("registers", "", "List registers"),
+    ("reverse", "", "Reverse the selected lines"),
+    ("smap", "<keys> <action>...", "Bind keys in select mode"),
+    ("sort", "[n]", "Sort the selected lines, sort! sorts in descending order"),
+    ("stats", "", "Show line, word, character and byte counts"),
+    ("sunmap", "<keys>", "Remove a select mode binding"),
+    ("tag", "<name>", "Jump to the definition of name"),
+    ("undo", "", "Undo the last change"),
+    ("undotree", "", "Browse the undo tree"),
+    ("uniq", "", "Remove adjacent duplicate lines"),
+    ("unmap", "<keys>", "Remove a normal mode binding"),
+];
+
+/// 补全命令名称，已经输入了参数时不补全
+fn complete_command(input: &str) -> Vec<String> {
+    let prefix = input.trim_start();
+    if prefix.contains(' ') {
+        return Vec::new();
+    }
+    COMMANDS
+        .iter()
+        .filter(|(name, _, _)| name.starts_with(prefix))
+        .map(|(name, _, _)| name.to_string())
+        .collect()
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 在消息栏中输入命令并执行
+    pub(super) async fn command_line(&mut self) {
+        let prompt = Prompt::new(self.config.tab_width)
+            .with_history(HistoryKind::Command)
+            .with_completer(complete_command);
+        let Some(input) = self.read_prompt(":", prompt, None).await else {
+            return;
+        };
+        let input = Row::raw_str(&input);
+        if let Err(e) = self.execute_command(input.trim()).await {
+            self.show_message(Message::warning(e));
+        }
+    }
+
+    /// 执行一条命令，除了run_command中的命令之外
+    /// !命令：在后台执行shell命令，输出显示在单独的buffer中
+    /// %!命令：把整个buffer通过shell命令过滤
+    /// tag 名称：用tags文件跳到名称的定义
+    /// undotree：浏览撤销树
+    pub(super) async fn execute_command(&mut self, input: &str) -> Result<(), String> {
+        if let Some(command) = input.strip_prefix("%!") {
+            if command.trim().is_empty() {
+                return Err("Usage: %!<command>".to_string());
+            }
+            self.filter_lines(0..self.buffer.rows.len(), command).await;
+        } else if let Some(command) = input.strip_prefix('!') {
+            if command.trim().is_empty() {
+                return Err("Usage: !<command>".to_string());
+            }
+            self.run_shell(command);
+        } else if let Some(name) = input.strip_prefix("tag ") {
+            self.goto_tag(name.trim()).await;
+        } else if input == "undotree" {
+            self.undo_tree().await;
+        } else {
+            self.run_command(input)?;
+        }
+        Ok(())
+    }
+
+    /// 执行一条命令
+    /// map 按键 操作...、smap 按键 操作...：在normal模式或select模式中绑定按键，可以依次执行多个操作
+    /// unmap 按键、sunmap 按键：删除绑定
+    /// help：列出按键绑定和命令
+    /// digraphs：列出二合字母
+    /// undo、redo：撤销和重做
+    /// put-char U+XXXX：插入码位对应的字符
+    /// messages、registers：列出消息和寄存器
+    /// stats：显示行数、单词数、字符数和字节数
+    /// sort[!] [n]、uniq、reverse：排序、删除相邻的重复行、反转选中的行或者整个buffer，!表示降序，n表示按数字排序
+    /// align 分隔符：让选中的行中的分隔符对齐
+    /// json [format|minify]：检查选中的行或者整个buffer是否是有效的JSON，并且格式化或者压缩
+    pub(super) fn run_command(&mut self, input: &str) -> Result<(), String> {
+        let mut parts = input.split_whitespace();
+        let Some(command) = parts.next() else {
+            return Ok(());
+        };
+        let args: Vec<&str> = parts.collect();
+        match (command, args.as_slice()) {
+            ("map", [keys, steps @ ..]) if !steps.is_empty() => {
+                self.map_keys(Mode::Normal, keys, steps)?
+            }
+            ("smap", [keys, steps @ ..]) if !steps.is_empty() => {
+                self.map_keys(Mode::Select, keys, steps)?
+            }
+            ("unmap", [keys]) => self.unmap_keys(Mode::Normal, keys)?,
+            ("sunmap", [keys]) => self.unmap_keys(Mode::Select, keys)?,
+            ("map" | "smap", _) => return Err(format!("Usage: {} <keys> <action>...", command)),
+            ("unmap" | "sunmap", _) => return Err(format!("Usage: {} <keys>", command)),
+            ("help", []) => self.show_help(),
+            ("digraphs", []) => self.show_digraphs(),
+            ("put-char", [code]) => self.put_char(code)?,
+            ("put-char", _) => return Err("Usage: put-char <U+XXXX>".to_string()),
+            ("undo", []) => self.undo(),
+            ("redo", []) => self.redo(),
+            ("messages", []) => self.show_messages(),
+            ("registers", []) => self.show_registers(),
+            ("make", args) => self.make(args),
+            ("stats", []) => self.show_statistics(),
+            ("sort" | "sort!", [] | ["n"]) => self.sort_lines(!args.is_empty(), command == "sort!"),
+            ("sort" | "sort!", _) => return Err(format!("Usage: {} [n]", command)),
+            ("uniq", []) => self.unique_lines(),
+            ("reverse", []) => self.reverse_lines(),
+            ("align", [delimiter]) => self.align_lines(delimiter),
+            ("align", _) => return Err("Usage: align <delimiter>".to_string()),
+            ("json", []) => self.json_command(None),
+            ("json", ["format"]) => self.json_command(Some(true)),
+            ("json", ["minify"]) => self.json_command(Some(false)),
+            ("json", _) => return Err("Usage: json [format|minify]".to_string()),
+            _ => return Err(format!("Unknown command: {}", input)),
+        }
+        Ok(())
+    }
+
+    fn map_keys(&mut self, mode: Mode, keys: &str, steps: &[&str]) -> Result<(), String> {
+        let parsed = self.keymap.parse_keys(keys)?;
+        let binding = Binding::parse(steps)?;
+        self.keymap.bind(mode, parsed, binding);
+        self.show_message(Message::new(format!("Mapped {} to {}", keys, steps.join(" "))));
+        Ok(())
+    }
+
+    fn unmap_keys(&mut self, mode: Mode, keys: &str) -> Result<(), String> {
+        let parsed = self.keymap.parse_keys(keys)?;
+        if !self.keymap.unbind(mode, &parsed) {
+            return Err(format!("No mapping for {}", keys));
+        }
+        self.show_message(Message::new(format!("Unmapped {}", keys)));
+        Ok(())
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::HashSet;
+
+use tokio::io::AsyncReadExt;
+
+use super::key::Direction;
+use super::motion::is_word;
+use super::overlay::{Anchor, Overlay};
+use super::{ControlKey, Editor, Key, Message, Modifiers, Row, Terminal};
+
+/// 收集单词时最多查看的行数，从光标附近开始
+const SCAN_LINES: usize = 5000;
+
+/// 正在显示的补全菜单
+pub(super) struct Completion {
+    /// 正在补全的单词在行中的起始位置
+    start: usize,
+    items: Vec<String>,
+    selected: usize,
+}
+
+impl Completion {
+    /// 其他功能也用补全菜单显示候选，start是被替换的单词的起始位置
+    pub(super) fn new(start: usize, items: Vec<String>, selected: usize) -> Self {
+        Self {
+            start,
+            items,
+            selected,
+        }
+    }
+}
+
+/// pattern中的字符按顺序出现在word中时返回分数，越小越好
+/// pattern中有大写字母时区分大小写
+fn fuzzy_score(word: &str, pattern: &str) -> Option<usize> {
+    let ignore_case = !pattern.chars().any(char::is_uppercase);
+    let eq = |a: char, b: char| {
+        if ignore_case {
+            a.to_lowercase().eq(b.to_lowercase())
+        } else {
+            a == b
+        }
+    };
+    let mut score = 0;
+    let mut last = None;
+    let mut chars = word.chars().enumerate();
+    for p in pattern.chars() {
+        let (i, _) = chars.by_ref().find(|&(_, c)| eq(c, p))?;
+        // 匹配的字符之间隔得越远分数越大
+        score += match last {
+            Some(last) => i - last - 1,
+            None => i,
+        };
+        last = Some(i);
+    }
+    Some(score)
+}
+
+/// 按分数排序的匹配的单词，分数相同时保持原来的顺序
+fn filter_words(words: &[String], pattern: &str) -> Vec<String> {
+    let mut matches: Vec<_> = words
+        .iter()
+        .filter(|w| w.as_str() != pattern)
+        .filter_map(|w| Some((fuzzy_score(w, pattern)?, w)))
+        .collect();
+    matches.sort_by_key(|(score, _)| *score);
+    matches.into_iter().map(|(_, w)| w.clone()).collect()
+}
+
+/// 把一行拆成单词加入words
+fn collect_words(raw: &[Key], seen: &mut HashSet<String>, words: &mut Vec<String>) {
+    for word in raw.split(|k| !is_word(k)).filter(|w| !w.is_empty()) {
+        let word = Row::raw_str(word);
+        if seen.insert(word.clone()) {
+            words.push(word);
+        }
+    }
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 所有buffer中的单词，当前buffer中离光标近的排在前面
+    fn buffer_words(&self, skip: (usize, usize)) -> Vec<String> {
+        let mut seen = HashSet::new();
+        let mut words = Vec::new();
+        let rows = &self.buffer.rows;
+        let cy = (self.buffer.cy as usize).min(rows.len());
+        for distance in 0..SCAN_LINES.min(rows.len()) {
+            let below = cy + distance;
+            let above = cy.checked_sub(distance + 1);
+            for i in [Some(below), above].into_iter().flatten() {
+                let Some(row) = rows.get(i) else {
+                    continue;
+                };
+                // 不要把正在输入的单词本身作为候选
+                if i == skip.0 {
+                    let (start, end) = (skip.1, row.get_raw_index(self.buffer.cx as usize));
+                    let mut raw = row.keys();
+                    raw.splice(start..end, []);
+                    collect_words(&raw, &mut seen, &mut words);
+                } else {
+                    collect_words(&row.keys(), &mut seen, &mut words);
+                }
+            }
+            if below >= rows.len() && above.is_none() {
+                break;
+            }
+        }
+        for buffer in &self.buffers {
+            for row in buffer.rows.iter().take(SCAN_LINES) {
+                collect_words(&row.keys(), &mut seen, &mut words);
+            }
+        }
+        words
+    }
+
+    /// 光标前面的单词的起始位置和光标的位置
+    fn word_before_cursor(&self) -> Option<(usize, usize)> {
+        let row = self.buffer.rows.get(self.buffer.cy as usize)?;
+        let end = row.get_raw_index(self.buffer.cx as usize);
+        let len = row.keys_in(0..end).iter().rev().take_while(|k| is_word(k)).count();
+        Some((end - len, end))
+    }
+
+    /// 用当前buffer和其他buffer中的单词补全光标前的单词
+    /// Tab、下或者Ctrl+N选择下一个，Shift+Tab、上或者Ctrl+P选择上一个，回车确认，Esc取消
+    /// 继续输入时按模糊匹配过滤候选，其他按键关闭菜单后照常处理
+    pub(super) async fn complete(&mut self) {
+        if !self.check_writable() {
+            return;
+        }
+        let Some((start, _)) = self.word_before_cursor() else {
+            return;
+        };
+        let cy = self.buffer.cy;
+        let words = self.buffer_words((cy as usize, start));
+        let mut selected = 0;
+        loop {
+            let end = match self.word_before_cursor() {
+                Some((s, end)) if s == start && self.buffer.cy == cy => end,
+                _ => break,
+            };
+            let pattern = Row::raw_str(&self.buffer.rows[cy as usize].keys_in(start..end));
+            let items = filter_words(&words, &pattern);
+            if items.is_empty() {
+                self.show_message(Message::new("No completions".to_string()));
+                break;
+            }
+            selected = selected.min(items.len() - 1);
+            self.completion = Some(Completion::new(start, items, selected));
+            if self.refresh_screen().is_err() {
+                break;
+            }
+            let Ok(key) = self.get_key().await else {
+                break;
+            };
+            let count = self.completion.as_ref().map_or(1, |c| c.items.len());
+            match key {
+                Key::ControlKey(ControlKey::Tab | ControlKey::Ctrl('n'))
+                | Key::ArrowKey(Direction::Down) => selected = (selected + 1) % count,
+                Key::ControlKey(ControlKey::Ctrl('p')) | Key::ArrowKey(Direction::Up) => {
+                    selected = (selected + count - 1) % count
+                }
+                Key::Modified(Modifiers { shift: true, .. }, ref key)
+                    if **key == Key::ControlKey(ControlKey::Tab) =>
+                {
+                    selected = (selected + count - 1) % count
+                }
+                Key::ControlKey(ControlKey::CR) => {
+                    self.accept_completion(end);
+                    break;
+                }
+                Key::ControlKey(ControlKey::Escape) => break,
+                Key::Char(_) if is_word(&key) => {
+                    self.insert(key);
+                    selected = 0;
+                }
+                Key::ControlKey(ControlKey::Backspace) if end > start => {
+                    self.backspace();
+                    selected = 0;
+                }
+                key => {
+                    self.pending_keys.push_front(key);
+                    break;
+                }
+            }
+        }
+        self.completion = None;
+    }
+
+    /// 在屏幕中间的列表中选择一项，输入的字符按模糊匹配过滤，返回选中的项在items中的索引
+    /// 上下键、Tab、Ctrl+N和Ctrl+P选择，回车确认，Esc取消
+    pub(super) async fn pick(&mut self, prompt: &str, items: &[String]) -> Option<usize> {
+        let mut pattern = String::new();
+        let mut selected = 0;
+        let choice = loop {
+            let mut matches: Vec<_> = items
+                .iter()
+                .enumerate()
+                .filter_map(|(i, item)| Some((fuzzy_score(item, &pattern)?, i)))
+                .collect();
+            matches.sort_by_key(|(score, _)| *score);
+            let shown: Vec<String> = matches.iter().map(|(_, i)| items[*i].clone()).collect();
+            selected = selected.min(shown.len().saturating_sub(1));
+            let title = format!("{}{}", prompt, pattern);
+            self.overlay = Some(Overlay::list(Anchor::Center, shown, selected).with_title(title));
+            if self.refresh_screen().is_err() {
+                break None;
+            }
+            let Ok(key) = self.get_key().await else {
+                break None;
+            };
+            let count = matches.len().max(1);
+            match key {
+                Key::ControlKey(ControlKey::Tab | ControlKey::Ctrl('n'))
+                | Key::ArrowKey(Direction::Down) => selected = (selected + 1) % count,
+                Key::ControlKey(ControlKey::Ctrl('p')) | Key::ArrowKey(Direction::Up) => {
+                    selected = (selected + count - 1) % count
+                }
+                Key::ControlKey(ControlKey::CR) => break matches.get(selected).map(|(_, i)| *i),
+                Key::ControlKey(ControlKey::Escape) => break None,
+                Key::ControlKey(ControlKey::Backspace) => {
+                    pattern.pop();
+                    selected = 0;
+                }
+                Key::Char(c) => {
+                    pattern.push(c);
+                    selected = 0;
+                }
+                Key::Text(text) => {
+                    pattern.push_str(&text);
+                    selected = 0;
+                }
+                _ => {}
+            }
+        };
+        self.overlay = None;
+        choice
+    }
+
+    /// 用选中的候选替换光标前的单词
+    fn accept_completion(&mut self, end: usize) {
+        let Some(completion) = self.completion.take() else {
+            return;
+        };
+        let word = &completion.items[completion.selected];
+        let keys: Vec<Key> = word.chars().map(Key::Char).collect();
+        let cy = self.buffer.cy as usize;
+        let at = completion.start + keys.len();
+        self.buffer.rows[cy].replace(completion.start..end, keys);
+        self.buffer.mark_dirty();
+        self.set_position((cy, at));
+    }
+
+    /// 补全菜单放在光标所在行的下面，和单词的开头对齐
+    pub(super) fn completion_overlay(&self) -> Option<Overlay> {
+        let completion = self.completion.as_ref()?;
+        let (x, y) = self.cursor_screen_position();
+        let start = self.buffer.rows.get(self.buffer.cy as usize).map_or(0, |r| r.column_of(completion.start));
+        let word_width = (self.buffer.cx as usize).saturating_sub(start);
+        let x = (x as usize).saturating_sub(word_width) as u16;
+        Some(Overlay::list(Anchor::Below(x, y), completion.items.clone(), completion.selected))
+    }
+}
@@ -0,0 +1,126 @@
+use std::mem;
+
+use tokio::io::AsyncReadExt;
+
+use super::motion::is_word;
+use super::{Editor, Key, Message, Terminal};
+
+/// raw中和word相同的完整单词的起始位置
+fn word_occurrences(raw: &[Key], word: &[Key]) -> Vec<usize> {
+    (0..raw.len())
+        .filter(|&i| {
+            raw[i..].starts_with(word)
+                && (i == 0 || !is_word(&raw[i - 1]))
+                && raw.get(i + word.len()).is_none_or(|k| !is_word(k))
+        })
+        .collect()
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 在最下面或者最上面的光标的下一行或者上一行添加光标，列和主光标相同
+    pub(super) fn add_cursor_vertical(&mut self, down: bool) {
+        let primary = (self.buffer.cy as usize, self.raw_cursor());
+        let rows = self.buffer.cursors.iter().map(|c| c.0).chain([primary.0]);
+        let row = if down {
+            rows.max().map(|r| r + 1)
+        } else {
+            rows.min().and_then(|r| r.checked_sub(1))
+        };
+        let Some(row) = row.and_then(|r| self.buffer.rows.get(r).map(|row| (r, row))) else {
+            self.show_message(Message::new("No more lines".to_string()));
+            return;
+        };
+        let (index, row) = row;
+        let at = row.get_raw_index((self.buffer.cx as usize).min(row.display_len()));
+        self.buffer.cursors.push((index, at));
+        self.show_cursor_count();
+    }
+
+    /// 在光标所在单词的下一个出现的位置添加光标，从最后添加的光标之后开始找，到末尾后从头开始
+    /// 新的光标在单词中的位置和主光标相同
+    pub(super) fn add_cursor_at_next_match(&mut self) {
+        let Some(word) = self.word_under_cursor() else {
+            self.show_message(Message::new("No word under cursor".to_string()));
+            return;
+        };
+        let primary = (self.buffer.cy as usize, self.raw_cursor());
+        let keys = self.buffer.rows[primary.0].keys_in(word.clone());
+        let offset = primary.1 - word.start;
+        let occurrences: Vec<(usize, usize)> = self
+            .buffer
+            .rows
+            .iter()
+            .enumerate()
+            .flat_map(|(i, row)| word_occurrences(&row.keys(), &keys).into_iter().map(move |s| (i, s + offset)))
+            .filter(|c| *c != primary && !self.buffer.cursors.contains(c))
+            .collect();
+        let from = self.buffer.cursors.last().copied().unwrap_or(primary);
+        let next = occurrences.iter().find(|c| **c > from).or(occurrences.first());
+        match next {
+            Some(&cursor) => {
+                self.buffer.cursors.push(cursor);
+                self.show_cursor_count();
+            }
+            None => self.show_message(Message::new("No more matches".to_string())),
+        }
+    }
+
+    /// 所有光标一起移动
+    pub(super) fn move_cursors(&mut self, key: &Key) {
+        self.at_cursors(false, |editor| editor.move_cursor(key));
+    }
+
+    fn show_cursor_count(&mut self) {
+        let count = self.buffer.cursors.len() + 1;
+        self.show_message(Message::new(format!("{} cursors", count)));
+    }
+
+    /// 删除主光标之外的光标，没有其他光标时返回false
+    pub(super) fn clear_cursors(&mut self) -> bool {
+        let cleared = !self.buffer.cursors.is_empty();
+        self.buffer.cursors.clear();
+        cleared
+    }
+
+    /// 在每个光标处执行op，只有主光标时直接执行
+    /// 从后往前执行，这样前面的修改不会影响还没有执行的光标
+    /// edit表示op会修改文本，这时已经执行过的光标跟着它们后面的文本移动
+    pub(super) fn at_cursors(&mut self, edit: bool, mut op: impl FnMut(&mut Self)) {
+        if self.buffer.cursors.is_empty() {
+            op(self);
+            return;
+        }
+        let primary = (self.buffer.cy as usize, self.raw_cursor());
+        let mut cursors = mem::take(&mut self.buffer.cursors);
+        cursors.push(primary);
+        cursors.sort();
+        cursors.dedup();
+        let mut done: Vec<((usize, usize), bool)> = Vec::new();
+        for &(row, at) in cursors.iter().rev() {
+            self.set_position((row, at));
+            let rows = self.buffer.rows.len();
+            let len = self.buffer.rows.get(row).map_or(0, |r| r.len());
+            op(self);
+            let moved = (self.buffer.cy as usize, self.raw_cursor());
+            if edit {
+                let new_len = self.buffer.rows.get(moved.0).map_or(0, |r| r.len());
+                for ((r, a), _) in &mut done {
+                    if *r == row && *a >= at {
+                        // 光标后面的文本跟着当前光标移动，到行尾的距离不变
+                        *a = new_len.saturating_sub(len.saturating_sub(*a));
+                        *r = moved.0;
+                    } else if *r > row {
+                        *r = (*r + self.buffer.rows.len()).saturating_sub(rows);
+                    }
+                }
+            }
+            done.push((moved, (row, at) == primary));
+        }
+        let primary = done.iter().find(|(_, p)| *p).map_or(primary, |(c, _)| *c);
+        let mut cursors: Vec<(usize, usize)> = done.into_iter().map(|(c, _)| c).filter(|c| *c != primary).collect();
+        cursors.sort();
+        cursors.dedup();
+        self.buffer.cursors = cursors;
+        self.set_position(primary);
+    }
+}
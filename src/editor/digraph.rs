@@ -0,0 +1,98 @@
+use std::collections::BTreeMap;
+
+use tokio::io::AsyncReadExt;
+
+use super::{Editor, Key, Message, Row, Terminal};
+
+/// 二合字母列表buffer的名称
+const DIGRAPHS_TITLE: &str = "[Digraphs]";
+
+/// 内置的二合字母，大多和RFC 1345以及Vim相同
+/// 第二个字符表示变音符号：: 分音符，' 尖音符，! 重音符，> 扬抑符，? 波浪号，, 软音符
+const DIGRAPHS: &[(&str, char)] = &[
+    ("a:", 'ä'), ("a'", 'á'), ("a!", 'à'), ("a>", 'â'), ("a?", 'ã'), ("aa", 'å'), ("ae", 'æ'),
+    ("A:", 'Ä'), ("A'", 'Á'), ("A!", 'À'), ("A>", 'Â'), ("A?", 'Ã'), ("AA", 'Å'), ("AE", 'Æ'),
+    ("c,", 'ç'), ("C,", 'Ç'),
+    ("e:", 'ë'), ("e'", 'é'), ("e!", 'è'), ("e>", 'ê'),
+    ("E:", 'Ë'), ("E'", 'É'), ("E!", 'È'), ("E>", 'Ê'),
+    ("i:", 'ï'), ("i'", 'í'), ("i!", 'ì'), ("i>", 'î'),
+    ("I:", 'Ï'), ("I'", 'Í'), ("I!", 'Ì'), ("I>", 'Î'),
+    ("n?", 'ñ'), ("N?", 'Ñ'),
+    ("o:", 'ö'), ("o'", 'ó'), ("o!", 'ò'), ("o>", 'ô'), ("o?", 'õ'), ("o/", 'ø'), ("oe", 'œ'),
+    ("O:", 'Ö'), ("O'", 'Ó'), ("O!", 'Ò'), ("O>", 'Ô'), ("O?", 'Õ'), ("O/", 'Ø'), ("OE", 'Œ'),
+    ("u:", 'ü'), ("u'", 'ú'), ("u!", 'ù'), ("u>", 'û'),
+    ("U:", 'Ü'), ("U'", 'Ú'), ("U!", 'Ù'), ("U>", 'Û'),
+    ("y:", 'ÿ'), ("y'", 'ý'), ("Y'", 'Ý'),
+    ("ss", 'ß'),
+    ("Eu", '€'), ("Pd", '£'), ("Ct", '¢'), ("Ye", '¥'),
+    ("Co", '©'), ("Rg", '®'), ("TM", '™'), ("SE", '§'), ("PI", '¶'),
+    ("DG", '°'), ("+-", '±'), ("*X", '×'), ("-:", '÷'), ("My", 'µ'),
+    ("12", '½'), ("14", '¼'), ("34", '¾'), ("1S", '¹'), ("2S", '²'), ("3S", '³'),
+    ("<<", '«'), (">>", '»'), ("!I", '¡'), ("?I", '¿'),
+    ("-N", '–'), ("-M", '—'), ("'6", '‘'), ("'9", '’'), ("\"6", '“'), ("\"9", '”'),
+    (".M", '·'), ("NS", '\u{A0}'),
+    ("->", '→'), ("<-", '←'), ("-!", '↑'), ("-v", '↓'), ("=>", '⇒'),
+    ("!=", '≠'), ("=<", '≤'), (">=", '≥'), ("?2", '≈'), ("00", '∞'), ("RT", '√'),
+    ("FA", '∀'), ("TE", '∃'), ("(-", '∈'),
+    ("a*", 'α'), ("b*", 'β'), ("g*", 'γ'), ("d*", 'δ'), ("e*", 'ε'), ("l*", 'λ'),
+    ("m*", 'μ'), ("p*", 'π'), ("s*", 'σ'), ("w*", 'ω'), ("D*", 'Δ'), ("W*", 'Ω'),
+    ("OK", '✓'), ("XX", '✗'),
+];
+
+/// 查找两个字符组成的二合字母，配置中的优先，找不到时交换两个字符再找一次
+fn lookup(custom: &BTreeMap<String, char>, first: char, second: char) -> Option<char> {
+    [[first, second], [second, first]].iter().find_map(|pair| {
+        let name: String = pair.iter().collect();
+        custom.get(&name).copied().or_else(|| {
+            DIGRAPHS.iter().find(|(n, _)| *n == name).map(|(_, c)| *c)
+        })
+    })
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 依次输入两个字符，插入它们组成的二合字母，比如 e' 插入é
+    pub(super) async fn digraph(&mut self) {
+        if !self.check_writable() {
+            return;
+        }
+        let mut chars = Vec::new();
+        while chars.len() < 2 {
+            let typed: String = chars.iter().collect();
+            self.message = Some(Message::new(format!("Digraph: {}", typed)));
+            if self.refresh_screen().is_err() {
+                return;
+            }
+            match self.get_key().await {
+                Ok(Key::Char(c)) => chars.push(c),
+                // 其他按键取消输入
+                _ => {
+                    self.message = None;
+                    return;
+                }
+            }
+        }
+        self.message = None;
+        match lookup(&self.config.digraphs, chars[0], chars[1]) {
+            Some(c) => self.insert_literal_key(Key::Char(c)),
+            None => {
+                let name: String = chars.iter().collect();
+                self.show_message(Message::new(format!("Unknown digraph: {}", name)));
+            }
+        }
+    }
+
+    /// 在只读buffer中列出所有二合字母，配置中的放在前面
+    pub(super) fn show_digraphs(&mut self) {
+        let custom = self.config.digraphs.iter().map(|(name, c)| (name.as_str(), *c));
+        let builtin = DIGRAPHS
+            .iter()
+            .filter(|(name, _)| !self.config.digraphs.contains_key(*name))
+            .copied();
+        let tab_width = self.config.tab_width;
+        let rows = custom
+            .chain(builtin)
+            .map(|(name, c)| Row::from_text(format!("{}  {}  U+{:04X}", name, c, c as u32), tab_width))
+            .collect();
+        self.show_list(DIGRAPHS_TITLE, rows);
+    }
+}
@@ -0,0 +1,67 @@
+use tokio::io::AsyncReadExt;
+
+use super::{Editor, Terminal};
+use super::keymap::Mode;
+
+/// 编辑器中发生的事情，触发时对应的buffer是当前buffer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum Event {
+    /// 打开了文件，参数是buffer的编号
+    BufferOpened(usize),
+    /// 即将写入文件，这时修改的内容也会被保存
+    BeforeSave(usize),
+    /// 文件已经写入
+    AfterSave(usize),
+    /// 处理完一个按键后光标的(行, 列)发生了变化
+    CursorMoved {
+        from: (usize, usize),
+        to: (usize, usize),
+    },
+    /// 开始或者取消了选择
+    ModeChanged(Mode),
+}
+
+/// 事件的处理函数，按订阅的顺序调用
+pub(super) type Subscriber<R, W> = fn(&mut Editor<R, W>, &Event);
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 内置功能订阅的事件
+    pub(super) fn builtin_subscribers() -> Vec<Subscriber<R, W>> {
+        vec![
+            Self::detect_indent_on_open,
+            Self::strip_whitespace_before_save,
+            Self::remove_swap_after_save,
+            Self::record_undo_after_save,
+            Self::read_git_head,
+            #[cfg(feature = "lua")]
+            Self::run_event_hooks,
+        ]
+    }
+
+    /// 依次通知所有订阅者
+    pub(super) fn emit(&mut self, event: Event) {
+        for subscriber in self.subscribers.clone() {
+            subscriber(self, &event);
+        }
+    }
+
+    /// 光标所在的位置和当前的模式
+    pub(super) fn cursor_state(&self) -> ((usize, usize), Mode) {
+        let mode = match self.buffer.selection {
+            Some(_) => Mode::Select,
+            None => Mode::Normal,
+        };
+        ((self.buffer.cy as usize, self.buffer.cx as usize), mode)
+    }
+
+    /// 和处理按键之前的状态比较，触发光标移动和模式变化的事件
+    pub(super) fn emit_cursor_events(&mut self, (from, old_mode): ((usize, usize), Mode)) {
+        let (to, mode) = self.cursor_state();
+        if to != from {
+            self.emit(Event::CursorMoved { from, to });
+        }
+        if mode != old_mode {
+            self.emit(Event::ModeChanged(mode));
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use tokio::io::AsyncReadExt;
+
+use super::{Editor, Key, SpecialKey, Terminal};
+
+/// 开启焦点事件报告，终端窗口获得和失去焦点时发送ESC [ I和ESC [ O
+pub(super) const ENABLE_FOCUS_EVENTS: &str = "\x1b[?1004h";
+pub(super) const DISABLE_FOCUS_EVENTS: &str = "\x1b[?1004l";
+/// 开启和停止光标闪烁
+const BLINKING_CURSOR: &str = "\x1b[?12h";
+const STEADY_CURSOR: &str = "\x1b[?12l";
+
+/// 焦点事件对应的焦点状态，其他按键返回None
+pub(super) fn focus_change(key: &Key) -> Option<bool> {
+    match key {
+        Key::SpecialKey(SpecialKey::FocusGained) => Some(true),
+        Key::SpecialKey(SpecialKey::FocusLost) => Some(false),
+        _ => None,
+    }
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 终端窗口失去焦点时状态栏显示为不活动，光标停止闪烁
+    /// 获得焦点时立即检查文件是否被其他程序修改，不等检查的定时器
+    pub(super) fn handle_focus(&mut self, focused: bool) {
+        self.terminal_focused = focused;
+        let cursor = if focused { BLINKING_CURSOR } else { STEADY_CURSOR };
+        let _ = self.screen.lock().write_styled(cursor);
+        if focused {
+            self.watch.reset_immediately();
+        }
+    }
+}
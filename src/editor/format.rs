@@ -0,0 +1,199 @@
+use tokio::io::AsyncReadExt;
+
+use super::indent::Indent;
+use super::{ControlKey, Editor, Key, Message, Row, Terminal, shell, status};
+use crate::reader::Bom;
+
+/// 文件的换行符
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum LineEnding {
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    pub(super) fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+        }
+    }
+
+    pub(super) fn label(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "LF",
+            LineEnding::Crlf => "CRLF",
+        }
+    }
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// Ctrl+E之后的按键决定对文件格式的操作
+    pub(super) async fn format_command(&mut self) {
+        self.message = Some(Message::new(
+            "Format: b(om) f(ormatter) l(ine ending) t(retab) w(hitespace)".to_string(),
+        ));
+        if self.refresh_screen().is_err() {
+            return;
+        }
+        let Ok(key) = self.get_key().await else {
+            return;
+        };
+        self.message = None;
+        match key {
+            Key::Char('b') => self.toggle_bom(),
+            Key::Char('f') => self.format_command_buffer().await,
+            Key::Char('l') => self.toggle_line_ending(),
+            Key::Char('t') => self.retab(),
+            Key::Char('w') => self.strip_command(),
+            _ => {}
+        }
+    }
+
+    /// 当前文件类型配置的格式化命令，formatters中可以用文件类型或者扩展名作为键
+    pub(super) fn formatter(&self) -> Option<&str> {
+        let path = self.buffer.current_file.as_deref()?;
+        let formatters = &self.config.formatters;
+        let by_type = status::file_type(path).and_then(|t| formatters.get(&t.to_ascii_lowercase()));
+        let by_extension = || formatters.get(path.extension()?.to_str()?);
+        by_type.or_else(by_extension).map(String::as_str)
+    }
+
+    /// 手动格式化，结果显示在消息栏
+    async fn format_command_buffer(&mut self) {
+        if !self.check_writable() {
+            return;
+        }
+        let message = match self.format_buffer().await {
+            Ok(true) => Message::new("Formatted".to_string()),
+            Ok(false) => Message::new("Already formatted".to_string()),
+            Err(e) => Message::warning(e),
+        };
+        self.show_message(message);
+    }
+
+    /// 把整个buffer通过格式化命令过滤，只替换有变化的行，返回是否有修改
+    /// 命令失败时不修改buffer
+    pub(super) async fn format_buffer(&mut self) -> Result<bool, String> {
+        let Some(command) = self.formatter().map(str::to_string) else {
+            return Err("No formatter for this file type".to_string());
+        };
+        if self.buffer.is_loading() {
+            return Err("File is still loading".to_string());
+        }
+        let text = self.buffer.text("\n");
+        let output = shell::pipe_through(&command, text)
+            .await
+            .map_err(|e| format!("{} failed: {}", command, e))?;
+        let tab_width = self.config.tab_width;
+        let formatted: Vec<Row> = output.lines().map(|l| Row::from_text(l, tab_width)).collect();
+        // 去掉首尾相同的行，中间的部分作为一次修改替换
+        let old_len = self.buffer.rows.len();
+        let new_len = formatted.len();
+        let prefix = self
+            .buffer
+            .rows
+            .iter()
+            .zip(&formatted)
+            .take_while(|(a, b)| a.text() == b.text())
+            .count();
+        let suffix = (0..(old_len - prefix).min(new_len - prefix))
+            .take_while(|i| self.buffer.rows[old_len - 1 - i].text() == formatted[new_len - 1 - i].text())
+            .count();
+        if prefix == old_len && prefix == new_len {
+            return Ok(false);
+        }
+        let changed = new_len - prefix - suffix;
+        let rows = formatted.into_iter().skip(prefix).take(changed);
+        self.buffer.rows.remove_range(prefix..old_len - suffix);
+        self.buffer.rows.insert_rows(prefix, rows);
+        self.buffer.mark_dirty();
+        // 光标留在原来的行，行数减少时移到最后一行
+        let cy = (self.buffer.cy as usize).min(self.buffer.rows.len().saturating_sub(1));
+        self.buffer.cy = cy as u16;
+        self.clamp_cursor_x();
+        self.scroll();
+        Ok(true)
+    }
+
+    /// 在LF和CRLF之间切换，保存时生效
+    fn toggle_line_ending(&mut self) {
+        if !self.check_writable() {
+            return;
+        }
+        self.buffer.line_ending = match self.buffer.line_ending {
+            LineEnding::Lf => LineEnding::Crlf,
+            LineEnding::Crlf => LineEnding::Lf,
+        };
+        self.buffer.mark_dirty();
+        self.show_message(Message::new(format!(
+            "Line endings: {}",
+            self.buffer.line_ending.label()
+        )));
+    }
+
+    /// 添加或去掉BOM，保存时生效
+    fn toggle_bom(&mut self) {
+        if !self.check_writable() {
+            return;
+        }
+        if Bom::for_encoding(&self.buffer.encoding).is_none() {
+            self.show_message(Message::new(format!(
+                "{} has no BOM",
+                self.buffer.encoding
+            )));
+            return;
+        }
+        self.buffer.bom = !self.buffer.bom;
+        self.buffer.mark_dirty();
+        let text = if self.buffer.bom { "BOM added" } else { "BOM removed" };
+        self.show_message(Message::new(text.to_string()));
+    }
+
+    /// 按缩进方式转换整个buffer
+    /// 用空格缩进时所有tab换成等宽的空格，否则行首的空白尽量换成tab
+    fn retab(&mut self) {
+        if !self.check_writable() {
+            return;
+        }
+        let expand = matches!(self.indent(), Indent::Spaces(_));
+        let mut changed = 0;
+        for row in self.buffer.rows.iter_mut() {
+            let raw = if expand { expand_tabs(row) } else { unexpand_indent(row) };
+            if raw != row.keys() {
+                row.replace(0..row.len(), raw);
+                changed += 1;
+            }
+        }
+        if changed > 0 {
+            self.buffer.mark_dirty();
+            self.clamp_cursor_x();
+        }
+        self.show_message(Message::new(format!("Retabbed {} lines", changed)));
+    }
+}
+
+fn expand_tabs(row: &Row) -> Vec<Key> {
+    let rendered = row.render();
+    let mut raw = Vec::with_capacity(row.len());
+    for (i, key) in row.keys().into_iter().enumerate() {
+        match key {
+            Key::ControlKey(ControlKey::Tab) => {
+                let (start, end) = rendered.get_render_index(i);
+                raw.extend(std::iter::repeat_n(Key::Char(' '), end - start));
+            }
+            key => raw.push(key),
+        }
+    }
+    raw
+}
+
+/// 行首空白的宽度用尽量多的tab表示，不足一个tab的部分用空格
+fn unexpand_indent(row: &Row) -> Vec<Key> {
+    let len = row.text().chars().take_while(|&c| c == ' ' || c == '\t').count();
+    let width = row.column_of(len);
+    let mut raw = vec![Key::ControlKey(ControlKey::Tab); width / row.tab_width];
+    raw.extend(std::iter::repeat_n(Key::Char(' '), width % row.tab_width));
+    raw.extend(row.keys_in(len..row.len()));
+    raw
+}
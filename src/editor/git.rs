@@ -0,0 +1,568 @@
+use std::ops::Range;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::process::Command;
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::buffer::Buffer;
+use super::event::Event;
+use super::{Editor, Message, Row, Terminal};
+use crate::utils::{self, color};
+
+/// 超过这个行数的文件不比较
+const MAX_DIFF_LINES: usize = 100_000;
+/// 修改太多时不再细分，中间的部分作为一个修改块
+const MAX_EDITS: usize = 2000;
+
+/// 后台任务发给编辑器的事件，第一个字段是buffer的编号
+pub(super) enum GitEvent {
+    /// HEAD中的文件内容，不在仓库中或者没有提交过时为None
+    Head(usize, Option<Vec<String>>),
+    /// 比较的结果，第二个字段是比较的序号，只使用最新一次的结果
+    Diff(usize, usize, Vec<Hunk>),
+    /// 文件所在仓库的分支和是否有未提交的修改
+    Status(usize, Option<RepoStatus>),
+    /// 行内显示的blame信息，第一个字段是请求时的(buffer编号, 行, 比较的序号)
+    Blame((usize, usize, usize), Option<String>),
+}
+
+/// 仓库的当前分支和工作区状态
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct RepoStatus {
+    branch: String,
+    dirty: bool,
+}
+
+impl RepoStatus {
+    /// 状态栏中显示的内容，有未提交的修改时加上*
+    pub(super) fn label(&self) -> String {
+        let dirty = if self.dirty { "*" } else { "" };
+        format!("{}{}", self.branch, dirty)
+    }
+}
+
+/// 光标所在行的blame信息，行或者内容变化后重新获取
+pub(super) struct LineBlame {
+    key: (usize, usize, usize),
+    text: Option<String>,
+}
+
+/// 和HEAD相比修改过的连续的行
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Hunk {
+    /// 在buffer中的起始行和行数，只删除了行时行数为0
+    start: usize,
+    len: usize,
+    /// HEAD中被替换或者删除的行
+    old: Vec<String>,
+}
+
+/// 行号栏中的标记
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sign {
+    Added,
+    Modified,
+    /// 标在被删除的行的上一行
+    Removed,
+}
+
+impl Hunk {
+    fn sign(&self) -> Sign {
+        if self.old.is_empty() {
+            Sign::Added
+        } else if self.len == 0 {
+            Sign::Removed
+        } else {
+            Sign::Modified
+        }
+    }
+
+    /// 显示标记的行
+    fn lines(&self) -> Range<usize> {
+        if self.len == 0 {
+            let line = self.start.saturating_sub(1);
+            line..line + 1
+        } else {
+            self.start..self.start + self.len
+        }
+    }
+}
+
+/// 文件在HEAD中的内容和比较结果
+pub(super) struct GitDiff {
+    head: Arc<Vec<String>>,
+    hunks: Vec<Hunk>,
+    /// 最近一次开始比较的序号
+    generation: usize,
+    /// 内容修改过，需要重新比较
+    pub(super) stale: bool,
+}
+
+/// 用Myers算法比较两组行，返回修改的(旧的范围, 新的范围)
+fn diff_lines<T: PartialEq>(old: &[T], new: &[T]) -> Vec<(Range<usize>, Range<usize>)> {
+    // 去掉首尾相同的行
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+    if a.is_empty() && b.is_empty() {
+        return Vec::new();
+    }
+    let whole = vec![(prefix..prefix + a.len(), prefix..prefix + b.len())];
+    if a.is_empty() || b.is_empty() {
+        return whole;
+    }
+
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    // v[k]是第k条对角线上走得最远的x，trace中保存每一步开始时-d..=d的部分
+    let offset = (n + m) as usize;
+    let mut v = vec![0isize; 2 * offset + 2];
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+    let mut found = false;
+    for d in 0..=(n + m).min(MAX_EDITS as isize) {
+        trace.push(v[(offset as isize - d) as usize..=(offset as isize + d) as usize].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let index = (offset as isize + k) as usize;
+            let mut x = if k == -d || (k != d && v[index - 1] < v[index + 1]) {
+                v[index + 1]
+            } else {
+                v[index - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[index] = x;
+            if x >= n && y >= m {
+                found = true;
+                break;
+            }
+        }
+        if found {
+            break;
+        }
+    }
+    if !found {
+        return whole;
+    }
+
+    // 从终点往回找，得到反向的编辑步骤
+    let (mut x, mut y) = (n, m);
+    let mut ops = Vec::new();
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let at = |k: isize| v[(k + d) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = if d == 0 { 0 } else { at(prev_k) };
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            ops.push((true, true));
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            if x == prev_x {
+                ops.push((false, true));
+                y -= 1;
+            } else {
+                ops.push((true, false));
+                x -= 1;
+            }
+        }
+    }
+
+    // 把连续的插入和删除合并成修改块
+    let mut hunks: Vec<(Range<usize>, Range<usize>)> = Vec::new();
+    let (mut i, mut j) = (prefix, prefix);
+    let mut changing = false;
+    for (old, new) in ops.into_iter().rev() {
+        if old && new {
+            changing = false;
+        } else {
+            if !changing {
+                hunks.push((i..i, j..j));
+                changing = true;
+            }
+            let hunk = hunks.last_mut().unwrap();
+            if old {
+                hunk.0.end += 1;
+            }
+            if new {
+                hunk.1.end += 1;
+            }
+        }
+        i += usize::from(old);
+        j += usize::from(new);
+    }
+    hunks
+}
+
+fn hunks(head: &[String], lines: &[String]) -> Vec<Hunk> {
+    diff_lines(head, lines)
+        .into_iter()
+        .map(|(old, new)| Hunk {
+            start: new.start,
+            len: new.len(),
+            old: head[old].to_vec(),
+        })
+        .collect()
+}
+
+/// 在文件所在的目录中执行git命令
+fn git_command(path: &Path) -> Option<(Command, &str)> {
+    let dir = path.parent()?;
+    let name = path.file_name()?.to_str()?;
+    let mut command = Command::new("git");
+    command.arg("-C").arg(dir).kill_on_drop(true);
+    Some((command, name))
+}
+
+/// 读取文件所在仓库的分支和状态，不在仓库中时返回None
+async fn read_status(path: &Path) -> Option<RepoStatus> {
+    let (mut command, _) = git_command(path)?;
+    let output = command
+        .args(["status", "--porcelain=v2", "--branch", "--untracked-files=no"])
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    let mut branch = None;
+    let mut oid = None;
+    let mut dirty = false;
+    for line in text.lines() {
+        if let Some(head) = line.strip_prefix("# branch.head ") {
+            branch = Some(head.to_string());
+        } else if let Some(id) = line.strip_prefix("# branch.oid ") {
+            oid = Some(id.to_string());
+        } else if !line.starts_with('#') {
+            dirty = true;
+        }
+    }
+    // 分离HEAD时显示提交的前几位
+    let branch = match branch? {
+        head if head == "(detached)" => format!("({})", &oid?[..7]),
+        head => head,
+    };
+    Some(RepoStatus { branch, dirty })
+}
+
+/// 多久以前，比如 3 days ago
+pub(super) fn format_age(time: u64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let seconds = now.saturating_sub(time);
+    let (count, unit) = match seconds {
+        0..60 => return "just now".to_string(),
+        60..3600 => (seconds / 60, "minute"),
+        3600..86400 => (seconds / 3600, "hour"),
+        86400..2592000 => (seconds / 86400, "day"),
+        2592000..31536000 => (seconds / 2592000, "month"),
+        _ => (seconds / 31536000, "year"),
+    };
+    let plural = if count == 1 { "" } else { "s" };
+    format!("{} {}{} ago", count, unit, plural)
+}
+
+/// 用git blame查看buffer中第line行(从0开始)最后一次修改的提交
+/// 内容从stdin传入，所以未保存的修改也能对应上
+async fn blame_line(path: &Path, contents: String, line: usize) -> Result<String, String> {
+    let (mut command, name) = git_command(path).ok_or("Not a file")?;
+    let mut child = command
+        .args(["blame", "--porcelain", "--contents", "-"])
+        .arg(format!("-L{},{}", line + 1, line + 1))
+        .args(["--", name])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let writer = tokio::spawn(async move {
+        let _ = stdin.write_all(contents.as_bytes()).await;
+    });
+    let output = child.wait_with_output().await.map_err(|e| e.to_string())?;
+    let _ = writer.await;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(stderr.lines().next().unwrap_or_default().to_string());
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    let mut lines = text.lines();
+    let commit = lines.next().and_then(|l| l.split(' ').next()).unwrap_or_default();
+    if commit.bytes().all(|b| b == b'0') {
+        return Ok("Not committed yet".to_string());
+    }
+    let (mut author, mut time, mut summary) = ("", 0, "");
+    for line in lines {
+        if let Some(value) = line.strip_prefix("author ") {
+            author = value;
+        } else if let Some(value) = line.strip_prefix("author-time ") {
+            time = value.parse().unwrap_or(0);
+        } else if let Some(value) = line.strip_prefix("summary ") {
+            summary = value;
+        }
+    }
+    Ok(format!(
+        "{} {}, {} · {}",
+        &commit[..commit.len().min(8)],
+        author,
+        format_age(time),
+        summary
+    ))
+}
+
+/// 读取文件在HEAD中的内容
+async fn read_head(path: &Path) -> Option<Vec<String>> {
+    let (mut command, name) = git_command(path)?;
+    let output = command
+        .arg("show")
+        .arg(format!("HEAD:./{}", name))
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    Some(text.lines().map(str::to_string).collect())
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 打开和保存文件后重新读取HEAD中的内容和仓库状态，保存时可能已经有了新的提交
+    pub(super) fn read_git_head(&mut self, event: &Event) {
+        let (Event::BufferOpened(id) | Event::AfterSave(id)) = *event else {
+            return;
+        };
+        let Some(path) = self.buffer.current_file.clone() else {
+            return;
+        };
+        let sender: UnboundedSender<GitEvent> = self.git_sender.clone();
+        tokio::spawn(async move {
+            let head = read_head(&path).await;
+            let _ = sender.send(GitEvent::Head(id, head));
+        });
+        self.read_repo_status();
+    }
+
+    /// 在后台读取当前buffer所在仓库的分支和状态，定时调用以发现外部的提交
+    pub(super) fn read_repo_status(&mut self) {
+        let Some(path) = self.buffer.current_file.clone() else {
+            return;
+        };
+        let id = self.buffer.id;
+        let sender = self.git_sender.clone();
+        tokio::spawn(async move {
+            let status = read_status(&path).await;
+            let _ = sender.send(GitEvent::Status(id, status));
+        });
+    }
+
+    pub(super) fn handle_git_event(&mut self, event: GitEvent) {
+        match event {
+            GitEvent::Head(id, head) => {
+                let Some(buffer) = self.find_buffer_mut(id) else {
+                    return;
+                };
+                // 保留原来的结果和序号，重新比较完之前继续显示
+                buffer.git = match (head, buffer.git.take()) {
+                    (Some(head), Some(git)) => Some(GitDiff {
+                        head: Arc::new(head),
+                        stale: true,
+                        ..git
+                    }),
+                    (Some(head), None) => Some(GitDiff {
+                        head: Arc::new(head),
+                        hunks: Vec::new(),
+                        generation: 0,
+                        stale: true,
+                    }),
+                    (None, _) => None,
+                };
+            }
+            GitEvent::Diff(id, generation, hunks) => {
+                let git = self.find_buffer_mut(id).and_then(|b| b.git.as_mut());
+                if let Some(git) = git.filter(|g| g.generation == generation) {
+                    git.hunks = hunks;
+                }
+            }
+            GitEvent::Status(id, status) => {
+                if let Some(buffer) = self.find_buffer_mut(id) {
+                    buffer.repo = status;
+                }
+            }
+            GitEvent::Blame(key, text) => {
+                if let Some(blame) = self.line_blame.as_mut().filter(|b| b.key == key) {
+                    blame.text = text;
+                }
+            }
+        }
+    }
+
+    /// 在消息栏中显示光标所在行的blame信息
+    pub(super) async fn blame_command(&mut self) {
+        let Some(path) = self.buffer.current_file.clone() else {
+            self.show_message(Message::warning("Buffer has no file".to_string()));
+            return;
+        };
+        if self.buffer.is_loading() {
+            self.show_message(Message::warning("File is still loading".to_string()));
+            return;
+        }
+        let line = self.buffer.cy as usize;
+        let contents = self.buffer.text("\n");
+        let message = match blame_line(&path, contents, line).await {
+            Ok(text) => Message::new(text),
+            Err(e) => Message::warning(format!("git blame failed: {}", e)),
+        };
+        self.show_message(message);
+    }
+
+    pub(super) fn toggle_inline_blame(&mut self) {
+        self.inline_blame = !self.inline_blame;
+        self.line_blame = None;
+        let text = if self.inline_blame { "Inline blame: on" } else { "Inline blame: off" };
+        self.show_message(Message::new(text.to_string()));
+    }
+
+    /// 开启行内blame时，光标换行或者内容修改后在后台重新获取
+    pub(super) fn update_inline_blame(&mut self) {
+        if !self.inline_blame {
+            return;
+        }
+        let buffer = &self.buffer;
+        let (Some(git), Some(path)) = (&buffer.git, &buffer.current_file) else {
+            return;
+        };
+        let line = buffer.cy as usize;
+        if line >= buffer.rows.len() || buffer.is_loading() {
+            return;
+        }
+        let key = (buffer.id, line, git.generation);
+        if self.line_blame.as_ref().is_some_and(|b| b.key == key) {
+            return;
+        }
+        self.line_blame = Some(LineBlame { key, text: None });
+        let (path, contents) = (path.clone(), buffer.text("\n"));
+        let sender = self.git_sender.clone();
+        tokio::spawn(async move {
+            let text = blame_line(&path, contents, line).await.ok();
+            let _ = sender.send(GitEvent::Blame(key, text));
+        });
+    }
+
+    /// 光标所在行后面显示的blame信息，最多占用width列
+    pub(super) fn render_inline_blame(&self, buffer: &Buffer, i: usize, width: usize) -> String {
+        let Some(blame) = &self.line_blame else {
+            return String::new();
+        };
+        let Some(text) = blame.text.as_ref().filter(|_| blame.key.0 == buffer.id && blame.key.1 == i) else {
+            return String::new();
+        };
+        // 和文本之间至少隔开几列
+        const GAP: usize = 4;
+        if width <= GAP {
+            return String::new();
+        }
+        let text = utils::fit_width(text, width - GAP);
+        format!("{}{}{}{}", " ".repeat(GAP), self.theme.comment, text, color::RESET)
+    }
+
+    /// 当前buffer修改过时在后台重新比较
+    pub(super) fn update_git_diff(&mut self) {
+        let buffer = &mut self.buffer;
+        let Some(git) = &mut buffer.git else {
+            return;
+        };
+        if !git.stale || buffer.loader.is_some() || buffer.rows.len() > MAX_DIFF_LINES {
+            return;
+        }
+        git.stale = false;
+        git.generation += 1;
+        let (id, generation, head) = (buffer.id, git.generation, git.head.clone());
+        let lines: Vec<String> = buffer.rows.iter().map(|r| r.text().to_string()).collect();
+        let sender = self.git_sender.clone();
+        tokio::task::spawn_blocking(move || {
+            let _ = sender.send(GitEvent::Diff(id, generation, hunks(&head, &lines)));
+        });
+    }
+
+    /// 行号栏中第i行的标记，文件不在仓库中时为空
+    pub(super) fn render_git_sign(&self, buffer: &Buffer, i: usize) -> String {
+        let Some(git) = &buffer.git else {
+            return String::new();
+        };
+        let sign = git.hunks.iter().find(|h| h.lines().contains(&i)).map(Hunk::sign);
+        let (mark, style) = match sign {
+            Some(Sign::Added) => ('+', &self.theme.git_added),
+            Some(Sign::Modified) => ('~', &self.theme.git_modified),
+            Some(Sign::Removed) => ('_', &self.theme.git_removed),
+            None => return " ".to_string(),
+        };
+        format!("{}{}{}", style, mark, color::RESET)
+    }
+
+    /// 跳到下一个或者上一个修改块，到文件末尾后从头开始
+    pub(super) fn goto_hunk(&mut self, forward: bool) {
+        let Some(git) = &self.buffer.git else {
+            self.show_message(Message::warning("Not a file in a git repository".to_string()));
+            return;
+        };
+        let cy = self.buffer.cy as usize;
+        let starts = git.hunks.iter().map(|h| h.lines().start);
+        let target = if forward {
+            starts.clone().find(|s| *s > cy).or_else(|| starts.min())
+        } else {
+            starts.clone().rev().find(|s| *s < cy).or_else(|| starts.max())
+        };
+        match target {
+            Some(line) => self.set_position((line, 0)),
+            None => self.show_message(Message::new("No changes".to_string())),
+        }
+    }
+
+    /// 把光标所在的修改块恢复成HEAD中的内容
+    pub(super) fn revert_hunk(&mut self) {
+        let Some(git) = &self.buffer.git else {
+            self.show_message(Message::warning("Not a file in a git repository".to_string()));
+            return;
+        };
+        let cy = self.buffer.cy as usize;
+        let Some(hunk) = git.hunks.iter().find(|h| h.lines().contains(&cy)).cloned() else {
+            self.show_message(Message::new("No change under cursor".to_string()));
+            return;
+        };
+        if !self.check_writable() {
+            return;
+        }
+        let tab_width = self.config.tab_width;
+        let rows: Vec<Row> = hunk.old.iter().map(|l| Row::from_text(l, tab_width)).collect();
+        let end = (hunk.start + hunk.len).min(self.buffer.rows.len());
+        self.buffer.rows.remove_range(hunk.start..end);
+        self.buffer.rows.insert_rows(hunk.start, rows);
+        self.buffer.mark_dirty();
+        self.set_position((hunk.start, 0));
+        self.show_message(Message::new(format!(
+            "Reverted {} lines",
+            hunk.len.max(hunk.old.len())
+        )));
+    }
+}
@@ -0,0 +1,421 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use ignore::WalkBuilder;
+use regex::Regex;
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::history::HistoryKind;
+use super::pattern::regex_error;
+use super::{ControlKey, Direction, Editor, Key, Message, OpenOptions, Row, Terminal};
+use crate::utils::{self, color};
+
+/// 最多收集的结果数量，超过后停止搜索
+const MAX_RESULTS: usize = 10_000;
+/// 结果列表最多占用的行数，不包括标题行
+const MAX_PANE_ROWS: usize = 10;
+
+/// 搜索到的一行，或者编译输出中的一个位置
+pub(super) struct GrepMatch {
+    path: PathBuf,
+    // 从1开始
+    line: usize,
+    // 匹配项在这一行中的字符索引
+    column: usize,
+    text: String,
+}
+
+impl GrepMatch {
+    pub(super) fn new(path: PathBuf, line: usize, column: usize, text: String) -> Self {
+        Self {
+            path,
+            line,
+            column,
+            text,
+        }
+    }
+}
+
+/// 后台搜索任务发给编辑器的事件，第一个字段是搜索的编号
+pub(super) enum GrepEvent {
+    Match(usize, GrepMatch),
+    /// 搜索结束，第二个字段表示是否因为结果太多提前停止
+    Done(usize, bool),
+    /// 无法开始，比如命令不存在
+    Failed(usize, String),
+}
+
+/// 列表中的结果来自哪里
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum ResultSource {
+    Grep,
+    /// 编译命令的输出
+    Make,
+}
+
+impl ResultSource {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Grep => "Grep",
+            Self::Make => "Make",
+        }
+    }
+
+    fn busy(self) -> &'static str {
+        match self {
+            Self::Grep => "searching...",
+            Self::Make => "running...",
+        }
+    }
+}
+
+/// 搜索结果列表，显示在所有窗口的下方
+pub(super) struct ResultsPane {
+    id: usize,
+    source: ResultSource,
+    query: String,
+    items: Vec<GrepMatch>,
+    selected: usize,
+    offset: usize,
+    // 后台任务还在搜索
+    searching: bool,
+    truncated: bool,
+    // 列表获得焦点时按键用于选择结果
+    focused: bool,
+    // 已经打开过选中的结果，跳到下一个结果时从它后面开始
+    opened: bool,
+    // 关闭列表或者开始新的搜索时通知后台任务停止
+    cancel: Arc<AtomicBool>,
+}
+
+impl ResultsPane {
+    /// 新的列表，结果由后台任务通过GrepEvent发来
+    pub(super) fn new(id: usize, source: ResultSource, query: String, cancel: Arc<AtomicBool>) -> Self {
+        Self {
+            id,
+            source,
+            query,
+            items: Vec::new(),
+            selected: 0,
+            offset: 0,
+            searching: true,
+            truncated: false,
+            focused: true,
+            opened: false,
+            cancel,
+        }
+    }
+}
+
+impl Drop for ResultsPane {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+/// 在后台线程中递归搜索当前目录，跳过.gitignore忽略的文件和隐藏文件
+fn grep_files(id: usize, regex: Regex, sender: UnboundedSender<GrepEvent>, cancel: Arc<AtomicBool>) {
+    let mut count = 0;
+    for entry in WalkBuilder::new(".").build().flatten() {
+        if cancel.load(Ordering::Relaxed) {
+            return;
+        }
+        if !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+        }
+        // 读取失败或者不是UTF-8的文件（多半是二进制文件）直接跳过
+        let Ok(content) = fs::read_to_string(entry.path()) else {
+            continue;
+        };
+        let path = entry.path().strip_prefix(".").unwrap_or(entry.path());
+        for (i, line) in content.lines().enumerate() {
+            let Some(m) = regex.find(line) else {
+                continue;
+            };
+            let item = GrepMatch {
+                path: path.to_path_buf(),
+                line: i + 1,
+                column: line[..m.start()].chars().count(),
+                text: line.to_string(),
+            };
+            if sender.send(GrepEvent::Match(id, item)).is_err() {
+                return;
+            }
+            count += 1;
+            if count >= MAX_RESULTS {
+                let _ = sender.send(GrepEvent::Done(id, true));
+                return;
+            }
+        }
+    }
+    let _ = sender.send(GrepEvent::Done(id, false));
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 在当前目录下的所有文件中搜索，结果显示在列表中
+    /// 和普通搜索一样用Ctrl+R切换正则
+    pub(super) async fn grep_command(&mut self) {
+        let query = self
+            .prompt_with_history("Grep: ", Some(Self::grep_callback), HistoryKind::Grep)
+            .await;
+        let Some(query) = query.filter(|query| !query.is_empty()) else {
+            return;
+        };
+        let query = Row::raw_str(&query);
+        let pattern = if self.search_state.regex {
+            query.clone()
+        } else {
+            regex::escape(&query)
+        };
+        let regex = match Regex::new(&pattern) {
+            Ok(regex) => regex,
+            Err(e) => {
+                self.show_message(Message::warning(regex_error(&e)));
+                return;
+            }
+        };
+
+        self.grep_id += 1;
+        let cancel = Arc::new(AtomicBool::new(false));
+        // 替换掉旧的列表时会通知旧的任务停止
+        self.results = Some(ResultsPane::new(self.grep_id, ResultSource::Grep, query, cancel.clone()));
+        let (id, sender) = (self.grep_id, self.grep_sender.clone());
+        tokio::task::spawn_blocking(move || grep_files(id, regex, sender, cancel));
+    }
+
+    fn grep_callback(&mut self, _: &[Key], key: &Key) {
+        if *key == Key::ControlKey(ControlKey::Ctrl('r')) {
+            self.search_state.regex = !self.search_state.regex;
+        }
+    }
+
+    /// 处理后台搜索发来的结果，一次取出所有已经到达的事件，避免每个结果都刷新一次屏幕
+    pub(super) fn handle_grep_event(&mut self, event: GrepEvent) {
+        let mut next = Some(event);
+        while let Some(event) = next {
+            let Some(results) = &mut self.results else {
+                return;
+            };
+            match event {
+                GrepEvent::Match(id, item) if id == results.id => results.items.push(item),
+                GrepEvent::Done(id, truncated) if id == results.id => {
+                    results.searching = false;
+                    results.truncated = truncated;
+                }
+                GrepEvent::Failed(id, e) if id == results.id => {
+                    results.searching = false;
+                    let text = format!("{} failed: {}", results.source.label(), e);
+                    self.show_message(Message::warning(text));
+                }
+                // 已经被替换掉的搜索
+                _ => {}
+            }
+            next = self.grep_receiver.try_recv().ok();
+        }
+    }
+
+    /// 结果列表占用的行数，包括标题行
+    pub(super) fn results_height(&self) -> u16 {
+        let Some(results) = &self.results else {
+            return 0;
+        };
+        let rows = results.items.len().clamp(1, MAX_PANE_ROWS) as u16 + 1;
+        // 至少给窗口留下一半的屏幕
+        rows.min(self.screen_rows.saturating_sub(1) / 2)
+    }
+
+    /// 列表的第一行在屏幕上的位置
+    fn results_top(&self) -> u16 {
+        self.screen_rows.saturating_sub(1 + self.results_height())
+    }
+
+    /// 渲染结果列表，第一行是标题
+    pub(super) fn render_results(&self) -> Vec<String> {
+        let Some(results) = &self.results else {
+            return Vec::new();
+        };
+        let width = self.screen_cols as usize;
+        let height = self.results_height() as usize;
+        let state = if results.searching {
+            format!(" ({})", results.source.busy())
+        } else if results.truncated {
+            " (truncated)".to_string()
+        } else {
+            String::new()
+        };
+        let title = format!(
+            "{}: {} - {} results{}",
+            results.source.label(),
+            results.query,
+            results.items.len(),
+            state
+        );
+        let title_color = if results.focused {
+            &self.theme.status_bar
+        } else {
+            &self.theme.inactive_status_bar
+        };
+        let mut lines = vec![format!(
+            "{}{}{}",
+            title_color,
+            utils::fit_width(&title, width),
+            color::RESET
+        )];
+        for i in results.offset..results.offset + height.saturating_sub(1) {
+            let Some(item) = results.items.get(i) else {
+                lines.push(" ".repeat(width));
+                continue;
+            };
+            // tab的宽度和所在的列有关，列表中直接显示为空格
+            let text = item.text.trim_start().replace('\t', " ");
+            let line = format!("{}:{}: {}", item.path.display(), item.line, text);
+            let line = utils::fit_width(&line, width);
+            if i == results.selected {
+                lines.push(format!("{}{}{}", self.theme.selection, line, color::RESET));
+            } else {
+                lines.push(line);
+            }
+        }
+        lines
+    }
+
+    /// 列表获得焦点时光标放在选中的结果上
+    pub(super) fn results_cursor(&self) -> Option<(u16, u16)> {
+        let results = self.results.as_ref().filter(|r| r.focused)?;
+        let row = (results.selected - results.offset) as u16;
+        Some((0, self.results_top() + 1 + row))
+    }
+
+    /// 把焦点切换到结果列表
+    pub(super) fn focus_results(&mut self) {
+        match &mut self.results {
+            Some(results) => results.focused = true,
+            None => self.show_message(Message::new("No results".to_string())),
+        }
+    }
+
+    /// 打开列表中的下一个或者上一个结果，列表不需要获得焦点
+    pub(super) async fn goto_result(&mut self, forward: bool) {
+        let Some(results) = &self.results else {
+            self.show_message(Message::new("No results".to_string()));
+            return;
+        };
+        let (selected, count) = (results.selected, results.items.len());
+        let index = if !results.opened {
+            Some(selected)
+        } else if forward {
+            Some(selected + 1).filter(|i| *i < count)
+        } else {
+            selected.checked_sub(1)
+        };
+        match index.filter(|i| *i < count) {
+            Some(index) => {
+                self.select_result(index);
+                self.open_result().await;
+                let text = match &self.results {
+                    Some(results) => format!("({}/{}) {}", index + 1, count, results.items[index].text.trim()),
+                    None => return,
+                };
+                self.show_message(Message::new(text));
+            }
+            None => self.show_message(Message::new("No more results".to_string())),
+        }
+    }
+
+    /// 列表获得焦点时处理按键，返回true表示按键已经处理
+    /// 上下键选择，回车打开，Tab回到编辑窗口，ESC或q关闭列表
+    pub(super) async fn results_command(&mut self, key: &Key) -> bool {
+        let page = (self.results_height() as usize).saturating_sub(1).max(1);
+        let Some(results) = self.results.as_mut().filter(|r| r.focused) else {
+            return false;
+        };
+        let last = results.items.len().saturating_sub(1);
+        let selected = results.selected;
+        let selected = match key {
+            Key::ArrowKey(Direction::Up) => selected.saturating_sub(1),
+            Key::ArrowKey(Direction::Down) => (selected + 1).min(last),
+            Key::ControlKey(ControlKey::PageUp) => selected.saturating_sub(page),
+            Key::ControlKey(ControlKey::PageDown) => (selected + page).min(last),
+            Key::ControlKey(ControlKey::Home) => 0,
+            Key::ControlKey(ControlKey::End) => last,
+            Key::ControlKey(ControlKey::Tab) => {
+                results.focused = false;
+                return true;
+            }
+            Key::ControlKey(ControlKey::Escape) | Key::Char('q') => {
+                self.results = None;
+                return true;
+            }
+            Key::ControlKey(ControlKey::CR) => {
+                self.open_result().await;
+                return true;
+            }
+            // 鼠标和其他窗口的操作照常处理
+            Key::MouseEvent(_) | Key::ControlKey(ControlKey::Ctrl(_)) => return false,
+            _ => return true,
+        };
+        self.select_result(selected);
+        true
+    }
+
+    /// 选中第index个结果，并滚动列表让它可见
+    fn select_result(&mut self, index: usize) {
+        let rows = (self.results_height() as usize).saturating_sub(1).max(1);
+        let Some(results) = &mut self.results else {
+            return;
+        };
+        results.selected = index;
+        if index < results.offset {
+            results.offset = index;
+        } else if index >= results.offset + rows {
+            results.offset = index + 1 - rows;
+        }
+    }
+
+    /// 点击结果列表时打开点击的结果，返回false表示点击的位置不在列表中
+    pub(super) async fn click_results(&mut self, y: u16) -> bool {
+        let top = self.results_top();
+        let Some(results) = &self.results else {
+            return false;
+        };
+        if y < top {
+            return false;
+        }
+        // 点击标题行只是让列表获得焦点
+        if y == top {
+            self.focus_results();
+            return true;
+        }
+        let index = results.offset + (y - top - 1) as usize;
+        if index < results.items.len() {
+            self.select_result(index);
+            self.open_result().await;
+        }
+        true
+    }
+
+    /// 打开选中的结果，光标移到匹配的位置，焦点回到编辑窗口
+    async fn open_result(&mut self) {
+        let Some(results) = &mut self.results else {
+            return;
+        };
+        let Some(item) = results.items.get(results.selected) else {
+            return;
+        };
+        results.focused = false;
+        results.opened = true;
+        let (path, line, column) = (item.path.clone(), item.line, item.column);
+        let options = OpenOptions {
+            line: Some(line),
+            ..Default::default()
+        };
+        if let Err(e) = self.open_file(&path, &options).await {
+            self.show_message(Message::error(format!("Error opening {}: {}", path.display(), e), &e));
+            return;
+        }
+        self.set_position((line - 1, column));
+    }
+}
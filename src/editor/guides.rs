@@ -0,0 +1,63 @@
+use tokio::io::AsyncReadExt;
+
+use super::buffer::Buffer;
+use super::indent::Indent;
+use super::{Editor, Message, Row, Terminal};
+
+/// 空行的缩进参考上下的非空行，最多查看的行数
+const SCAN_LINES: usize = 100;
+
+/// 行首空白的显示宽度，空白行返回None
+fn indent_width(row: &Row) -> Option<usize> {
+    let first = row.text().chars().position(|c| c != ' ' && c != '\t')?;
+    Some(row.column_of(first))
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 显示或者隐藏竖线，没有配置rulers时在第80列显示
+    pub(super) fn toggle_ruler(&mut self) {
+        if self.rulers.is_empty() {
+            self.rulers = self.config.rulers.clone();
+            if self.rulers.is_empty() {
+                self.rulers.push(80);
+            }
+        } else {
+            self.rulers.clear();
+        }
+        let text = if self.rulers.is_empty() {
+            "Ruler: off".to_string()
+        } else {
+            let columns: Vec<String> = self.rulers.iter().map(usize::to_string).collect();
+            format!("Ruler: {}", columns.join(", "))
+        };
+        self.show_message(Message::new(text));
+    }
+
+    pub(super) fn toggle_indent_guides(&mut self) {
+        self.indent_guides = !self.indent_guides;
+        let text = if self.indent_guides { "Indent guides: on" } else { "Indent guides: off" };
+        self.show_message(Message::new(text.to_string()));
+    }
+
+    /// 第i行需要画缩进线的列，每一级缩进一条
+    /// 空行使用上下非空行中较小的缩进，这样代码块中的空行不会打断缩进线
+    pub(super) fn indent_guides(&self, buffer: &Buffer, i: usize) -> Vec<usize> {
+        if !self.indent_guides {
+            return Vec::new();
+        }
+        let width = match indent_width(&buffer.rows[i]) {
+            Some(width) => width,
+            None => {
+                let above = (i.saturating_sub(SCAN_LINES)..i).rev().find_map(|j| indent_width(&buffer.rows[j]));
+                let end = (i + 1 + SCAN_LINES).min(buffer.rows.len());
+                let below = buffer.rows.range(i + 1..end).find_map(indent_width);
+                above.zip(below).map_or(0, |(a, b)| a.min(b))
+            }
+        };
+        let unit = match self.indent_of(buffer) {
+            Indent::Spaces(width) => width,
+            Indent::Tabs => self.config.tab_width,
+        };
+        (0..width).step_by(unit.max(1)).collect()
+    }
+}
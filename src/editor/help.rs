@@ -0,0 +1,51 @@
+use tokio::io::AsyncReadExt;
+
+use super::command::COMMANDS;
+use super::keymap::Mode;
+use super::{Editor, Row, Terminal};
+
+/// 帮助buffer的名称
+const HELP_TITLE: &str = "[Help]";
+
+/// 按键和命令列的宽度
+const KEY_WIDTH: usize = 24;
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 在只读的buffer中列出当前生效的按键绑定和命令行中可以执行的命令
+    /// 绑定从按键映射表生成，包括配置文件和map命令修改的绑定
+    pub(super) fn show_help(&mut self) {
+        let mut lines = Vec::new();
+        let mut mode = None;
+        for (binding_mode, keys, steps) in self.keymap.describe() {
+            if mode != Some(binding_mode) {
+                let title = match binding_mode {
+                    Mode::Normal => "Key bindings",
+                    Mode::Select => "Key bindings with a selection",
+                };
+                if mode.is_some() {
+                    lines.push(String::new());
+                }
+                lines.push(title.to_string());
+                mode = Some(binding_mode);
+            }
+            lines.push(format!("  {:<KEY_WIDTH$} {}", keys, steps));
+        }
+
+        lines.push(String::new());
+        lines.push("Commands (<F2>)".to_string());
+        for (name, args, description) in COMMANDS {
+            let usage = format!("{} {}", name, args);
+            lines.push(format!("  {:<KEY_WIDTH$} {}", usage.trim_end(), description));
+        }
+        let shell = [
+            ("!<command>", "Run a shell command and show its output"),
+            ("%!<command>", "Filter the buffer through a shell command"),
+        ];
+        for (usage, description) in shell {
+            lines.push(format!("  {:<KEY_WIDTH$} {}", usage, description));
+        }
+
+        let rows = lines.iter().map(|line| Row::from_text(line, self.config.tab_width)).collect();
+        self.show_list(HELP_TITLE, rows);
+    }
+}
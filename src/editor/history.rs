@@ -0,0 +1,84 @@
+use std::fs;
+use std::path::PathBuf;
+
+use tracing::warn;
+
+use crate::config::Config;
+
+/// 最多保留的历史记录条数
+const MAX_ENTRIES: usize = 100;
+
+/// 使用历史记录的提示
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum HistoryKind {
+    Search,
+    Grep,
+    Command,
+}
+
+impl HistoryKind {
+    /// 历史记录文件的名称，放在配置目录下
+    fn file_name(self) -> &'static str {
+        match self {
+            HistoryKind::Search => "search_history",
+            HistoryKind::Grep => "grep_history",
+            HistoryKind::Command => "command_history",
+        }
+    }
+}
+
+/// 提示输入的历史记录，最新的在最后，每行一条
+pub(super) struct History {
+    entries: Vec<String>,
+    path: Option<PathBuf>,
+}
+
+impl History {
+    /// 从配置目录加载历史记录，文件不存在时为空
+    pub(super) fn load(kind: HistoryKind) -> Self {
+        let path = Config::dir().map(|dir| dir.join(kind.file_name()));
+        let entries = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|content| content.lines().map(str::to_string).collect())
+            .unwrap_or_default();
+        Self { entries, path }
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(super) fn get(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(String::as_str)
+    }
+
+    /// 添加一条记录并写入文件，已经存在的相同记录移到最后
+    pub(super) fn push(&mut self, entry: String) {
+        // 历史记录按行保存，不能包含换行符
+        if entry.is_empty() || entry.contains('\n') {
+            return;
+        }
+        self.entries.retain(|e| *e != entry);
+        self.entries.push(entry);
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.remove(0);
+        }
+        self.save();
+    }
+
+    fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let mut content = self.entries.join("\n");
+        content.push('\n');
+        let result = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(path, content));
+        if let Err(e) = result {
+            warn!("Failed to save history to {}: {}", path.display(), e);
+        }
+    }
+}
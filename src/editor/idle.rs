@@ -0,0 +1,27 @@
+use std::time::Duration;
+
+use tokio::io::AsyncReadExt;
+use tokio::time::{self, Instant, Interval, MissedTickBehavior};
+
+use super::{Editor, Terminal};
+
+/// 没有输入时检查界面是否需要随时间更新的间隔
+const IDLE_INTERVAL: Duration = Duration::from_millis(500);
+
+pub(super) fn idle_timer() -> Interval {
+    let mut timer = time::interval_at(Instant::now() + IDLE_INTERVAL, IDLE_INTERVAL);
+    timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    timer
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 更新随时间变化的界面，返回是否需要刷新屏幕
+    /// 消息栏中的消息显示一段时间后消失
+    pub(super) fn update_on_idle(&mut self) -> bool {
+        if self.message.as_ref().is_some_and(|m| m.is_expired()) {
+            self.message = None;
+            return true;
+        }
+        false
+    }
+}
@@ -0,0 +1,165 @@
+use tokio::io::AsyncReadExt;
+
+use super::buffer::Buffer;
+use super::event::Event;
+use super::text::TextBuffer;
+use super::{ControlKey, Editor, Key, Row, Terminal};
+
+/// 检测缩进时最多查看的行数
+const DETECT_LINES: usize = 1000;
+
+/// 缩进方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Indent {
+    Tabs,
+    /// 每级缩进的空格数
+    Spaces(usize),
+}
+
+impl Indent {
+    pub(super) fn label(self) -> String {
+        match self {
+            Indent::Tabs => "Tabs".to_string(),
+            Indent::Spaces(width) => format!("Spaces: {}", width),
+        }
+    }
+}
+
+/// 根据行首的空白判断文件使用的缩进方式，没有缩进的行时返回None
+/// 用tab缩进的行更多时认为使用tab，否则取相邻两行缩进差最常见的值作为宽度
+pub(super) fn detect_indent(rows: &TextBuffer) -> Option<Indent> {
+    let mut tabs = 0;
+    let mut spaces = 0;
+    // 缩进差为1到8的次数
+    let mut deltas = [0usize; 9];
+    let mut previous = 0;
+    for row in rows.iter().take(DETECT_LINES) {
+        let keys = row.keys();
+        let leading = leading_whitespace(&keys);
+        // 空行不影响缩进
+        if leading.len() == row.len() {
+            continue;
+        }
+        match leading.first() {
+            Some(Key::ControlKey(ControlKey::Tab)) => tabs += 1,
+            Some(_) if leading.iter().all(|k| *k == Key::Char(' ')) => spaces += 1,
+            _ => {}
+        }
+        if leading.contains(&Key::ControlKey(ControlKey::Tab)) {
+            continue;
+        }
+        let width = leading.len();
+        if let Some(count) = deltas.get_mut(width.abs_diff(previous)) {
+            *count += 1;
+        }
+        previous = width;
+    }
+    if tabs == 0 && spaces == 0 {
+        return None;
+    }
+    if tabs > spaces {
+        return Some(Indent::Tabs);
+    }
+    // 次数相同时取较小的宽度
+    let (width, count) = (2..deltas.len())
+        .map(|width| (width, deltas[width]))
+        .fold((0, 0), |best, (width, count)| if count > best.1 { (width, count) } else { best });
+    (count > 0).then_some(Indent::Spaces(width))
+}
+
+/// 开启括号粘贴，粘贴的内容前后会有ESC [ 200 ~和ESC [ 201 ~
+pub(super) const ENABLE_BRACKETED_PASTE: &str = "\x1b[?2004h";
+pub(super) const DISABLE_BRACKETED_PASTE: &str = "\x1b[?2004l";
+
+fn is_whitespace(key: &Key) -> bool {
+    matches!(key, Key::Char(' ') | Key::ControlKey(ControlKey::Tab))
+}
+
+/// 行首的空白
+fn leading_whitespace(keys: &[Key]) -> &[Key] {
+    let len = keys.iter().take_while(|k| is_whitespace(k)).count();
+    &keys[..len]
+}
+
+/// 最后一个非空白字符
+fn last_char(keys: &[Key]) -> Option<char> {
+    keys.iter().rev().find(|k| !is_whitespace(k)).and_then(|k| match k {
+        Key::Char(c) => Some(*c),
+        _ => None,
+    })
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 打开文件时检测缩进方式，后台加载完成后还会用完整的内容重新检测
+    pub(super) fn detect_indent_on_open(&mut self, event: &Event) {
+        if let Event::BufferOpened(_) = event
+            && self.config.detect_indent
+        {
+            self.buffer.indent = detect_indent(&self.buffer.rows);
+        }
+    }
+
+    /// 当前buffer的缩进方式
+    pub(super) fn indent(&self) -> Indent {
+        self.indent_of(&self.buffer)
+    }
+
+    /// buffer的缩进方式，没有检测到时根据expand_tab决定
+    pub(super) fn indent_of(&self, buffer: &Buffer) -> Indent {
+        match buffer.indent {
+            Some(indent) => indent,
+            None if self.config.expand_tab => Indent::Spaces(self.config.tab_width),
+            None => Indent::Tabs,
+        }
+    }
+
+    /// 一级缩进
+    pub(super) fn indent_unit(&self) -> Vec<Key> {
+        match self.indent() {
+            Indent::Spaces(width) => vec![Key::Char(' '); width],
+            Indent::Tabs => vec![Key::ControlKey(ControlKey::Tab)],
+        }
+    }
+
+    /// 换行后给新行加上缩进，new_row是从光标处拆分出来的后半行
+    /// 返回缩进的显示宽度，也就是光标在新行中的位置
+    // 新行保持当前行的缩进，当前行以{、(、[、:结尾时再多缩进一级
+    // 光标在{}之间时，}放到单独的一行，和当前行对齐
+    pub(super) fn new_line_indent(&mut self, new_row: &mut Row) -> usize {
+        if !self.config.auto_indent || self.pasting {
+            return 0;
+        }
+        let current = self.buffer.rows[self.buffer.cy as usize].keys();
+        let base = leading_whitespace(&current).to_vec();
+        let mut indent = base.clone();
+        let opener = last_char(&current);
+        if matches!(opener, Some('{' | '(' | '[' | ':')) {
+            indent.extend(self.indent_unit());
+        }
+
+        // 去掉后半行开头的空白，避免和缩进叠加
+        let keys = new_row.keys();
+        let rest = keys[leading_whitespace(&keys).len()..].to_vec();
+        let closes = matches!(
+            (opener, rest.first()),
+            (Some('{'), Some(Key::Char('}')))
+                | (Some('('), Some(Key::Char(')')))
+                | (Some('['), Some(Key::Char(']')))
+        );
+        let tab_width = self.config.tab_width;
+        if closes {
+            // 闭括号单独成行，和当前行对齐，光标停在中间的空行上
+            let mut closing = base;
+            closing.extend(rest);
+            let closing = Row::new(closing, tab_width);
+            self.buffer.rows.insert(self.buffer.cy as usize + 1, closing);
+            *new_row = Row::new(indent.clone(), tab_width);
+        } else {
+            let mut raw = indent.clone();
+            raw.extend(rest);
+            *new_row = Row::new(raw, tab_width);
+        }
+
+        new_row.column_of(indent.len())
+    }
+}
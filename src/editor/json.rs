@@ -0,0 +1,308 @@
+use tokio::io::AsyncReadExt;
+
+use super::{Editor, Message, Row, Terminal};
+
+/// JSON的语法错误，行和列从1开始，列按字符计算
+struct JsonError {
+    line: usize,
+    column: usize,
+    message: String,
+}
+
+/// 逐字符检查JSON并按格式重新输出，字符串和数字保持原样，对象中键的顺序不变
+struct Formatter {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    column: usize,
+    /// 一级缩进，None时压缩成一行
+    indent: Option<String>,
+    out: String,
+}
+
+impl Formatter {
+    fn new(text: &str, indent: Option<String>) -> Self {
+        Self {
+            chars: text.chars().collect(),
+            pos: 0,
+            line: 1,
+            column: 1,
+            indent,
+            out: String::new(),
+        }
+    }
+
+    fn format(mut self) -> Result<String, JsonError> {
+        self.skip_whitespace();
+        self.value(0)?;
+        self.skip_whitespace();
+        if self.peek().is_some() {
+            return Err(self.error("Unexpected characters after JSON value"));
+        }
+        Ok(self.out)
+    }
+
+    fn error(&self, message: &str) -> JsonError {
+        JsonError {
+            line: self.line,
+            column: self.column,
+            message: message.to_string(),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    /// 把当前字符原样输出
+    fn copy(&mut self) {
+        if let Some(c) = self.bump() {
+            self.out.push(c);
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| matches!(c, ' ' | '\t' | '\n' | '\r')) {
+            self.bump();
+        }
+    }
+
+    /// 格式化时换行并缩进到depth级
+    fn newline(&mut self, depth: usize) {
+        if let Some(indent) = &self.indent {
+            self.out.push('\n');
+            self.out.push_str(&indent.repeat(depth));
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<(), JsonError> {
+        match self.peek() {
+            Some('{') => self.container(depth, '}'),
+            Some('[') => self.container(depth, ']'),
+            Some('"') => self.string(),
+            Some('-' | '0'..='9') => self.number(),
+            Some('t') => self.literal("true"),
+            Some('f') => self.literal("false"),
+            Some('n') => self.literal("null"),
+            Some(_) => Err(self.error("Expected a value")),
+            None => Err(self.error("Unexpected end of input")),
+        }
+    }
+
+    /// 对象或者数组，close是结束的括号
+    fn container(&mut self, depth: usize, close: char) -> Result<(), JsonError> {
+        self.copy();
+        self.skip_whitespace();
+        if self.peek() == Some(close) {
+            self.copy();
+            return Ok(());
+        }
+        loop {
+            self.newline(depth + 1);
+            if close == '}' {
+                if self.peek() != Some('"') {
+                    return Err(self.error("Expected a string key"));
+                }
+                self.string()?;
+                self.skip_whitespace();
+                if self.peek() != Some(':') {
+                    return Err(self.error("Expected ':'"));
+                }
+                self.copy();
+                if self.indent.is_some() {
+                    self.out.push(' ');
+                }
+                self.skip_whitespace();
+            }
+            self.value(depth + 1)?;
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => {
+                    self.copy();
+                    self.skip_whitespace();
+                }
+                Some(c) if c == close => {
+                    self.newline(depth);
+                    self.copy();
+                    return Ok(());
+                }
+                _ => return Err(self.error(&format!("Expected ',' or '{}'", close))),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<(), JsonError> {
+        self.copy();
+        loop {
+            match self.peek() {
+                None => return Err(self.error("Unterminated string")),
+                Some('"') => {
+                    self.copy();
+                    return Ok(());
+                }
+                Some('\\') => {
+                    self.copy();
+                    match self.peek() {
+                        Some('"' | '\\' | '/' | 'b' | 'f' | 'n' | 'r' | 't') => self.copy(),
+                        Some('u') => {
+                            self.copy();
+                            for _ in 0..4 {
+                                if !self.peek().is_some_and(|c| c.is_ascii_hexdigit()) {
+                                    return Err(self.error("Invalid unicode escape"));
+                                }
+                                self.copy();
+                            }
+                        }
+                        _ => return Err(self.error("Invalid escape")),
+                    }
+                }
+                Some(c) if (c as u32) < 0x20 => {
+                    return Err(self.error("Control character in string"));
+                }
+                Some(_) => self.copy(),
+            }
+        }
+    }
+
+    fn digits(&mut self) -> usize {
+        let mut count = 0;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.copy();
+            count += 1;
+        }
+        count
+    }
+
+    /// -?(0|[1-9][0-9]*)(.[0-9]+)?([eE][+-]?[0-9]+)?
+    fn number(&mut self) -> Result<(), JsonError> {
+        if self.peek() == Some('-') {
+            self.copy();
+        }
+        if self.peek() == Some('0') {
+            self.copy();
+        } else if self.digits() == 0 {
+            return Err(self.error("Invalid number"));
+        }
+        if self.peek() == Some('.') {
+            self.copy();
+            if self.digits() == 0 {
+                return Err(self.error("Expected digits after '.'"));
+            }
+        }
+        if matches!(self.peek(), Some('e' | 'E')) {
+            self.copy();
+            if matches!(self.peek(), Some('+' | '-')) {
+                self.copy();
+            }
+            if self.digits() == 0 {
+                return Err(self.error("Expected digits in exponent"));
+            }
+        }
+        Ok(())
+    }
+
+    fn literal(&mut self, word: &str) -> Result<(), JsonError> {
+        for expected in word.chars() {
+            if self.peek() != Some(expected) {
+                return Err(self.error(&format!("Expected '{}'", word)));
+            }
+            self.bump();
+        }
+        self.out.push_str(word);
+        Ok(())
+    }
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 检查选中的行或者整个buffer是否是JSON，出错时光标移到出错的位置
+    /// format为Some(true)时格式化，Some(false)时压缩成一行，None时只检查
+    pub(super) fn json_command(&mut self, format: Option<bool>) {
+        let lines = self.command_lines();
+        let text: Vec<String> = self.buffer.rows.range(lines.clone()).map(|r| r.text().to_string()).collect();
+        let indent = match format {
+            Some(true) => Some(Row::raw_str(&self.indent_unit())),
+            _ => None,
+        };
+        let formatted = match Formatter::new(&text.join("\n"), indent).format() {
+            Ok(formatted) => formatted,
+            Err(e) => {
+                let row = lines.start + e.line - 1;
+                self.set_position((row, e.column - 1));
+                let text = format!("JSON error at line {}, column {}: {}", row + 1, e.column, e.message);
+                self.show_message(Message::warning(text));
+                return;
+            }
+        };
+        let Some(format) = format else {
+            self.show_message(Message::new("Valid JSON".to_string()));
+            return;
+        };
+        let replaced = self.transform_lines(|_| {
+            formatted
+                .lines()
+                .map(Row::raw_keys)
+                .collect()
+        });
+        if let Some((_, count)) = replaced {
+            let text = if format { "Formatted JSON" } else { "Minified JSON" };
+            self.show_message(Message::new(format!("{}: {} lines", text, count)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(text: &str, indent: Option<&str>) -> Result<String, (usize, usize, String)> {
+        Formatter::new(text, indent.map(str::to_string))
+            .format()
+            .map_err(|e| (e.line, e.column, e.message))
+    }
+
+    #[test]
+    fn formats_and_minifies_valid_json() {
+        let text = "{ \"a\" : [1, -2.5e+3, true, null],\r\n\t\"b\": {}, \"c\": [ ], \"d\": \"x y\" }";
+        assert_eq!(
+            format(text, Some("  ")).unwrap(),
+            "{\n  \"a\": [\n    1,\n    -2.5e+3,\n    true,\n    null\n  ],\n  \"b\": {},\n  \"c\": [],\n  \"d\": \"x y\"\n}"
+        );
+        assert_eq!(format(text, None).unwrap(), r#"{"a":[1,-2.5e+3,true,null],"b":{},"c":[],"d":"x y"}"#);
+        assert_eq!(format(" 0 ", None).unwrap(), "0");
+    }
+
+    #[test]
+    fn reports_where_the_document_is_invalid() {
+        // 列按字符计算，宽字符也算一列
+        let error = format("{\n  \"中文\": 1,\n  \"b\" 2\n}", None).unwrap_err();
+        assert_eq!(error, (3, 7, "Expected ':'".to_string()));
+        assert_eq!(format("[1, 2", None).unwrap_err(), (1, 6, "Expected ',' or ']'".to_string()));
+        assert_eq!(format("[01]", None).unwrap_err().2, "Expected ',' or ']'");
+        assert_eq!(format("{} x", None).unwrap_err(), (1, 4, "Unexpected characters after JSON value".to_string()));
+        assert_eq!(format("\"a\nb\"", None).unwrap_err(), (1, 3, "Control character in string".to_string()));
+        assert_eq!(format("[tru]", None).unwrap_err().2, "Expected 'true'");
+        assert_eq!(format("", None).unwrap_err().2, "Unexpected end of input");
+    }
+
+    #[test]
+    fn keeps_unicode_escapes() {
+        // 转义序列原样保留，不解码成字符
+        let text = r#"["\u00e9\uD83D\ude00", "é", "\n\"\\\/"]"#;
+        assert_eq!(format(text, None).unwrap(), text.replace(", ", ","));
+        assert_eq!(format(r#""\u12G4""#, None).unwrap_err(), (1, 6, "Invalid unicode escape".to_string()));
+        assert_eq!(format(r#""\u12"#, None).unwrap_err().2, "Invalid unicode escape");
+        assert_eq!(format(r#""\x""#, None).unwrap_err(), (1, 3, "Invalid escape".to_string()));
+    }
+}
@@ -0,0 +1,89 @@
+use std::path::PathBuf;
+
+use tokio::io::AsyncReadExt;
+
+use super::{Editor, Message, OpenOptions, Terminal};
+
+/// 最多记住的跳转位置，超出时丢弃最早的
+const MAX_JUMPS: usize = 100;
+
+/// 跳转之前光标所在的位置，(行, raw索引)
+/// buffer关闭后按文件重新打开
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Jump {
+    buffer_id: usize,
+    file: Option<PathBuf>,
+    position: (usize, usize),
+}
+
+/// 跳转列表，index等于列表长度时表示不在列表中的某个位置
+#[derive(Default)]
+pub(super) struct JumpList {
+    entries: Vec<Jump>,
+    index: usize,
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    fn current_jump(&self) -> Jump {
+        Jump {
+            buffer_id: self.buffer.id,
+            file: self.buffer.current_file.clone(),
+            position: (self.buffer.cy as usize, self.raw_cursor()),
+        }
+    }
+
+    /// 跳转到别的位置之前记住光标所在的位置，丢弃后退之后还没有前进的位置
+    pub(super) fn push_jump(&mut self) {
+        let jump = self.current_jump();
+        let jumps = &mut self.jumps;
+        jumps.entries.truncate(jumps.index);
+        if jumps.entries.last() != Some(&jump) {
+            jumps.entries.push(jump);
+        }
+        if jumps.entries.len() > MAX_JUMPS {
+            jumps.entries.remove(0);
+        }
+        jumps.index = jumps.entries.len();
+    }
+
+    /// 回到上一个跳转之前的位置
+    pub(super) async fn jump_back(&mut self) {
+        if self.jumps.index == 0 {
+            self.show_message(Message::new("Already at oldest jump".to_string()));
+            return;
+        }
+        // 第一次后退时记住当前的位置，这样还能前进回来
+        if self.jumps.index == self.jumps.entries.len() {
+            let jump = self.current_jump();
+            self.jumps.entries.push(jump);
+        }
+        self.jumps.index -= 1;
+        self.goto_jump().await;
+    }
+
+    /// 后退之后再前进
+    pub(super) async fn jump_forward(&mut self) {
+        if self.jumps.index + 1 >= self.jumps.entries.len() {
+            self.show_message(Message::new("Already at newest jump".to_string()));
+            return;
+        }
+        self.jumps.index += 1;
+        self.goto_jump().await;
+    }
+
+    async fn goto_jump(&mut self) {
+        let jump = self.jumps.entries[self.jumps.index].clone();
+        if !self.rotate_to_buffer(jump.buffer_id) {
+            let Some(file) = &jump.file else {
+                self.show_message(Message::warning("Buffer has been closed".to_string()));
+                return;
+            };
+            if let Err(e) = self.open_file(file, &OpenOptions::default()).await {
+                self.show_message(Message::error(format!("Error opening {}: {}", file.display(), e), &e));
+                return;
+            }
+        }
+        self.set_position(jump.position);
+        self.center_cursor();
+    }
+}
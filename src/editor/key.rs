@@ -1,87 +0,0 @@
-const TAB_STOP: u8 = 8;
-
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum Key {
-    // 普通字符
-    Char(char),
-    // 方向键
-    ArrowKey(Direction),
-    // 功能键
-    FunctionKey(u8),
-    // 控制键
-    ControlKey(ControlKey),
-    /// 其他特殊键
-    SpecialKey(SpecialKey),
-    // 鼠标事件
-    // MouseEvent(MouseEvent),
-    // 未知或无法解析的输入
-    // Unknown(Vec<u8>),
-}
-
-impl Key {
-    pub fn render(&self) -> String {
-        match self{
-            Key::Char(c) => format!("{c}"),
-            Key::ControlKey(ControlKey::Tab) => {
-                let mut s = String::new();
-                for _ in 0..(TAB_STOP as usize) {
-                    s.push(' ');
-                }
-                s
-            },
-            _ => {
-                "".to_owned()
-            }
-        }
-    }
-
-    pub fn get_display_width(&self) -> usize {
-        match self {
-            Key::Char(_) => 1,
-            Key::ControlKey(ControlKey::Tab) => TAB_STOP as usize,
-            _ => 0,
-        }
-    }
-}
-
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum Direction {
-    Up,
-    Down,
-    Left,
-    Right,
-}
-
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum ControlKey {
-    Ctrl(char), // Ctrl+字母/数字
-    Alt(char),  // Alt+字符
-    Tab,
-    LF,
-    CR,
-    // Enter,
-    Escape,
-    Backspace,
-    Delete,
-    Home,
-    End,
-    PageUp,
-    PageDown,
-    Insert,
-}
-
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum SpecialKey {
-    CapsLock,
-    NumLock,
-    ScrollLock,
-    PrintScreen,
-    PauseBreak,
-    Menu,
-}
-
-// pub enum MouseEvent {
-//     Click(u8, u16, u16),    // 按钮, x, y
-//     Scroll(i8, u16, u16),   // 滚动方向, x, y
-//     Move(u16, u16),         // x, y
-// }
@@ -0,0 +1,681 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use serde::Deserialize;
+use tokio::io::AsyncReadExt;
+use tokio::time;
+
+use super::case::Case;
+use super::key::{ControlKey, Direction, Key};
+use super::selection::SelectionKind;
+use super::{Editor, Message, Terminal};
+
+/// 按键绑定生效的模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    /// 没有选择区域时
+    Normal,
+    /// 有选择区域时，没有绑定的按键按normal模式处理
+    Select,
+}
+
+/// 可以绑定到按键的操作，在配置文件中使用snake_case的名称
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// 什么也不做，用于取消默认的绑定
+    Nop,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    LineStart,
+    LineEnd,
+    FileStart,
+    FileEnd,
+    WordLeft,
+    WordRight,
+    PageUp,
+    PageDown,
+    HalfPageUp,
+    HalfPageDown,
+    Backspace,
+    DeleteChar,
+    DeleteWordBefore,
+    DeleteWordAfter,
+    MoveLineUp,
+    MoveLineDown,
+    JoinLines,
+    DuplicateLine,
+    Find,
+    Replace,
+    SearchNext,
+    SearchPrevious,
+    Grep,
+    FocusResults,
+    GotoLine,
+    JumpToBracket,
+    ToggleLineNumbers,
+    ToggleWrap,
+    ToggleRuler,
+    ToggleIndentGuides,
+    ReloadConfig,
+    BufferMenu,
+    WindowMenu,
+    FormatMenu,
+    ScrollMenu,
+    RegisterMenu,
+    OpenFile,
+    Save,
+    SaveAs,
+    Quit,
+    SelectChar,
+    SelectLine,
+    SelectBlock,
+    CancelSelection,
+    /// 复制选中的内容
+    Yank,
+    DeleteSelection,
+    /// 用寄存器中的内容替换选中的内容
+    PasteSelection,
+    Paste,
+    SelectRegister,
+    /// 在矩形选择的左边插入
+    BlockInsert,
+    /// 删除矩形选择的内容后插入
+    BlockChange,
+    Indent,
+    Outdent,
+    /// 用命令过滤选中的行
+    FilterSelection,
+    /// 用buffer中的单词补全光标前的单词
+    Complete,
+    /// 展开光标前的代码片段，没有匹配的片段时插入tab
+    ExpandSnippet,
+    ToggleSpellCheck,
+    NextMisspelling,
+    PreviousMisspelling,
+    /// 显示光标所在单词的拼写建议
+    SpellSuggestions,
+    /// 执行make_command，输出中的位置显示在结果列表中
+    Make,
+    /// 打开结果列表中的下一个或者上一个位置
+    NextResult,
+    PreviousResult,
+    /// 跳到和HEAD相比修改过的下一块或者上一块
+    NextHunk,
+    PreviousHunk,
+    /// 把光标所在的修改块恢复成HEAD中的内容
+    RevertHunk,
+    /// 在消息栏中显示光标所在行的git blame
+    Blame,
+    ToggleInlineBlame,
+    /// 用tags文件跳到光标所在标识符的定义
+    GotoDefinition,
+    /// 在菜单中选择当前buffer中的符号
+    SymbolPicker,
+    /// 在跳转列表中后退和前进
+    JumpBack,
+    JumpForward,
+    /// 在下一行或者上一行添加光标
+    AddCursorBelow,
+    AddCursorAbove,
+    /// 在光标所在单词下一次出现的位置添加光标
+    AddCursorAtNextMatch,
+    /// 只保留主光标
+    ClearCursors,
+    /// 在消息栏中显示行数、单词数、字符数和字节数
+    Statistics,
+    /// 转换选中的内容或者光标所在单词的大小写
+    UpperCase,
+    LowerCase,
+    TitleCase,
+    /// 光标处或者后面的数字加一或者减一
+    IncrementNumber,
+    DecrementNumber,
+    /// 在右边显示当前markdown buffer的预览
+    TogglePreview,
+    RecordMacro,
+    PlayMacro,
+    CommandLine,
+    ShowMessages,
+    ShowRegisters,
+    /// 列出按键绑定和命令
+    Help,
+    Undo,
+    Redo,
+    /// 把下一个按键按原样插入，包括控制键
+    InsertLiteral,
+    /// 输入码位插入字符
+    InsertUnicode,
+    /// 输入两个字符插入对应的二合字母
+    Digraph,
+    /// 在浮动窗口中浏览撤销树
+    UndoTree,
+}
+
+impl Action {
+    /// 按名称查找，比如 save_as
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        use serde::de::value::{Error, StrDeserializer};
+        use serde::de::IntoDeserializer;
+        let deserializer: StrDeserializer<Error> = name.into_deserializer();
+        Self::deserialize(deserializer).map_err(|e| e.to_string())
+    }
+
+    /// 配置文件中使用的名称
+    pub fn name(&self) -> String {
+        let mut name = String::new();
+        for c in format!("{:?}", self).chars() {
+            if c.is_ascii_uppercase() && !name.is_empty() {
+                name.push('_');
+            }
+            name.push(c.to_ascii_lowercase());
+        }
+        name
+    }
+}
+
+/// 绑定执行的一步：一个操作，或者一条命令
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    Action(Action),
+    /// 以:开头的命令，和F2中输入的一样
+    Command(String),
+    /// 插件用fim.map绑定的函数
+    #[cfg(feature = "lua")]
+    Plugin(usize),
+}
+
+impl Step {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text.strip_prefix(':') {
+            Some(command) => Ok(Self::Command(command.trim().to_string())),
+            None => Action::from_name(text).map(Self::Action),
+        }
+    }
+
+    /// 和配置文件中相同的写法
+    fn describe(&self) -> String {
+        match self {
+            Self::Action(action) => action.name(),
+            Self::Command(command) => format!(":{}", command),
+            #[cfg(feature = "lua")]
+            Self::Plugin(_) => "<plugin>".to_string(),
+        }
+    }
+}
+
+/// 按键绑定的内容，配置文件中可以写一个步骤，或者依次执行的一组步骤
+/// 比如 "save" 或者 ["save", ":messages"]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "BindingDef")]
+pub struct Binding(Vec<Step>);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BindingDef {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl TryFrom<BindingDef> for Binding {
+    type Error = String;
+
+    fn try_from(def: BindingDef) -> Result<Self, String> {
+        let steps = match def {
+            BindingDef::One(step) => vec![step],
+            BindingDef::Many(steps) => steps,
+        };
+        Self::parse(&steps)
+    }
+}
+
+impl Binding {
+    pub fn parse(steps: &[impl AsRef<str>]) -> Result<Self, String> {
+        if steps.is_empty() {
+            return Err("empty binding".to_string());
+        }
+        let steps = steps.iter().map(|s| Step::parse(s.as_ref())).collect::<Result<_, _>>()?;
+        Ok(Self(steps))
+    }
+}
+
+impl From<Step> for Binding {
+    fn from(step: Step) -> Self {
+        Self(vec![step])
+    }
+}
+
+impl From<Action> for Binding {
+    fn from(action: Action) -> Self {
+        Step::Action(action).into()
+    }
+}
+
+/// 默认的按键绑定
+const DEFAULT_BINDINGS: &[(Mode, &str, Action)] = &[
+    (Mode::Normal, "<Left>", Action::MoveLeft),
+    (Mode::Normal, "<Right>", Action::MoveRight),
+    (Mode::Normal, "<Up>", Action::MoveUp),
+    (Mode::Normal, "<Down>", Action::MoveDown),
+    (Mode::Normal, "<Home>", Action::LineStart),
+    (Mode::Normal, "<End>", Action::LineEnd),
+    (Mode::Normal, "<PageUp>", Action::PageUp),
+    (Mode::Normal, "<PageDown>", Action::PageDown),
+    (Mode::Normal, "<C-u>", Action::HalfPageUp),
+    (Mode::Normal, "<C-d>", Action::HalfPageDown),
+    (Mode::Normal, "<BS>", Action::Backspace),
+    (Mode::Normal, "<Del>", Action::DeleteChar),
+    // 大多数终端的Ctrl+Backspace发送的是Ctrl+H
+    (Mode::Normal, "<C-h>", Action::DeleteWordBefore),
+    (Mode::Normal, "<NL>", Action::JoinLines),
+    (Mode::Normal, "<C-x>", Action::DuplicateLine),
+    (Mode::Normal, "<C-f>", Action::Find),
+    (Mode::Normal, "<C-r>", Action::Replace),
+    (Mode::Normal, "<F3>", Action::SearchNext),
+    (Mode::Normal, "<S-F3>", Action::SearchPrevious),
+    (Mode::Normal, "<C-t>", Action::Grep),
+    (Mode::Normal, "<F4>", Action::FocusResults),
+    (Mode::Normal, "<C-g>", Action::GotoLine),
+    (Mode::Normal, "<C-]>", Action::JumpToBracket),
+    (Mode::Normal, "<C-n>", Action::ToggleLineNumbers),
+    (Mode::Normal, "<C-l>", Action::ToggleWrap),
+    (Mode::Normal, "<Leader>|", Action::ToggleRuler),
+    (Mode::Normal, "<Leader>I", Action::ToggleIndentGuides),
+    (Mode::Normal, "<F5>", Action::ReloadConfig),
+    (Mode::Normal, "<C-b>", Action::BufferMenu),
+    (Mode::Normal, "<C-w>", Action::WindowMenu),
+    (Mode::Normal, "<C-e>", Action::FormatMenu),
+    (Mode::Normal, "<Leader>S", Action::ScrollMenu),
+    (Mode::Normal, "<C-y>", Action::RegisterMenu),
+    (Mode::Normal, "<C-o>", Action::OpenFile),
+    (Mode::Normal, "<C-s>", Action::Save),
+    (Mode::Normal, "<C-a>", Action::SaveAs),
+    (Mode::Normal, "<C-q>", Action::Quit),
+    (Mode::Normal, "<C-v>", Action::SelectChar),
+    (Mode::Normal, "<C-p>", Action::Paste),
+    (Mode::Normal, "<F2>", Action::CommandLine),
+    // 终端的Ctrl+Space发送的是Ctrl+@
+    (Mode::Normal, "<C-@>", Action::Complete),
+    (Mode::Normal, "<Tab>", Action::ExpandSnippet),
+    (Mode::Normal, "<F9>", Action::ToggleSpellCheck),
+    (Mode::Normal, "<F6>", Action::NextMisspelling),
+    (Mode::Normal, "<S-F6>", Action::PreviousMisspelling),
+    (Mode::Normal, "<C-F6>", Action::SpellSuggestions),
+    (Mode::Normal, "<Leader>m", Action::Make),
+    (Mode::Normal, "<Leader>n", Action::NextResult),
+    (Mode::Normal, "<Leader>p", Action::PreviousResult),
+    (Mode::Normal, "<Leader>]", Action::NextHunk),
+    (Mode::Normal, "<Leader>[", Action::PreviousHunk),
+    (Mode::Normal, "<Leader>r", Action::RevertHunk),
+    (Mode::Normal, "<Leader>b", Action::Blame),
+    (Mode::Normal, "<Leader>B", Action::ToggleInlineBlame),
+    (Mode::Normal, "<Leader>d", Action::GotoDefinition),
+    (Mode::Normal, "<Leader>s", Action::SymbolPicker),
+    (Mode::Normal, "<Leader>o", Action::JumpBack),
+    (Mode::Normal, "<Leader>i", Action::JumpForward),
+    (Mode::Normal, "<Leader>j", Action::AddCursorBelow),
+    (Mode::Normal, "<Leader>k", Action::AddCursorAbove),
+    (Mode::Normal, "<Leader>w", Action::AddCursorAtNextMatch),
+    (Mode::Normal, "<Esc>", Action::ClearCursors),
+    (Mode::Normal, "<Leader>c", Action::Statistics),
+    (Mode::Normal, "<Leader>U", Action::UpperCase),
+    (Mode::Normal, "<Leader>u", Action::LowerCase),
+    (Mode::Normal, "<Leader>t", Action::TitleCase),
+    (Mode::Normal, "<Leader>a", Action::IncrementNumber),
+    (Mode::Normal, "<Leader>x", Action::DecrementNumber),
+    (Mode::Normal, "<Leader>P", Action::TogglePreview),
+    (Mode::Normal, "<F7>", Action::RecordMacro),
+    (Mode::Normal, "<F8>", Action::PlayMacro),
+    (Mode::Normal, "<F1>", Action::Help),
+    (Mode::Normal, "<C-z>", Action::Undo),
+    (Mode::Normal, "<Leader>z", Action::Redo),
+    (Mode::Normal, "<Leader>Z", Action::UndoTree),
+    (Mode::Normal, "<Leader>v", Action::InsertLiteral),
+    // 只有用CSI u报告按键的终端才能区分Ctrl+Shift+U和Ctrl+U
+    (Mode::Normal, "<C-S-u>", Action::InsertUnicode),
+    (Mode::Normal, "<C-k>", Action::Digraph),
+    (Mode::Select, "v", Action::SelectChar),
+    (Mode::Select, "V", Action::SelectLine),
+    (Mode::Select, "<C-v>", Action::SelectBlock),
+    (Mode::Select, "<Esc>", Action::CancelSelection),
+    (Mode::Select, "I", Action::BlockInsert),
+    (Mode::Select, "c", Action::BlockChange),
+    (Mode::Select, "y", Action::Yank),
+    (Mode::Select, "d", Action::DeleteSelection),
+    (Mode::Select, "x", Action::DeleteSelection),
+    (Mode::Select, "<BS>", Action::DeleteSelection),
+    (Mode::Select, "<Del>", Action::DeleteSelection),
+    (Mode::Select, "p", Action::PasteSelection),
+    (Mode::Select, "\"", Action::SelectRegister),
+    (Mode::Select, "<NL>", Action::JoinLines),
+    (Mode::Select, "<C-d>", Action::DuplicateLine),
+    (Mode::Select, ">", Action::Indent),
+    (Mode::Select, "<lt>", Action::Outdent),
+    (Mode::Select, "!", Action::FilterSelection),
+    (Mode::Select, "<Leader>c", Action::Statistics),
+    (Mode::Select, "<F2>", Action::CommandLine),
+    (Mode::Select, "U", Action::UpperCase),
+    (Mode::Select, "u", Action::LowerCase),
+    (Mode::Select, "T", Action::TitleCase),
+];
+
+/// 默认的leader键
+pub const DEFAULT_LEADER: &str = "<C-\\>";
+
+/// 按模式保存的按键绑定，一个绑定可以是多个按键组成的序列
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    bindings: HashMap<Mode, HashMap<Vec<Key>, Binding>>,
+    /// 按键序列中的<Leader>替换成的按键
+    leader: Vec<Key>,
+}
+
+impl Keymap {
+    /// 默认绑定加上配置文件中[keymap.模式]表覆盖的绑定
+    pub fn new(
+        overrides: &BTreeMap<Mode, BTreeMap<String, Binding>>,
+        leader: &str,
+    ) -> Result<Self, String> {
+        let leader = Key::parse_notation(leader).map_err(|e| format!("leader: {}", e))?;
+        let mut keymap = Self {
+            bindings: HashMap::new(),
+            leader,
+        };
+        for (mode, keys, action) in DEFAULT_BINDINGS {
+            keymap.bind(*mode, keymap.parse_keys(keys)?, (*action).into());
+        }
+        for (mode, bindings) in overrides {
+            for (keys, binding) in bindings {
+                let keys = keymap.parse_keys(keys).map_err(|e| format!("keymap: {}", e))?;
+                keymap.bind(*mode, keys, binding.clone());
+            }
+        }
+        Ok(keymap)
+    }
+
+    /// 解析按键序列，<Leader>替换成leader键
+    pub(super) fn parse_keys(&self, text: &str) -> Result<Vec<Key>, String> {
+        let mut keys = Vec::new();
+        for (i, part) in text.split("<Leader>").enumerate() {
+            if i > 0 {
+                keys.extend(self.leader.iter().cloned());
+            }
+            if !part.is_empty() {
+                keys.extend(Key::parse_notation(part)?);
+            }
+        }
+        if keys.is_empty() {
+            return Err("empty key sequence".to_string());
+        }
+        Ok(keys)
+    }
+
+    pub(super) fn bind(&mut self, mode: Mode, keys: Vec<Key>, binding: Binding) {
+        self.bindings.entry(mode).or_default().insert(keys, binding);
+    }
+
+    /// 删除绑定，没有这个绑定时返回false
+    pub(super) fn unbind(&mut self, mode: Mode, keys: &[Key]) -> bool {
+        self.bindings
+            .get_mut(&mode)
+            .is_some_and(|bindings| bindings.remove(keys).is_some())
+    }
+
+    /// 所有的绑定，按模式和按键排序，按键序列以leader键开头时写成<Leader>
+    pub(super) fn describe(&self) -> Vec<(Mode, String, String)> {
+        let mut bindings: Vec<_> = self
+            .bindings
+            .iter()
+            .flat_map(|(mode, bindings)| bindings.iter().map(move |(keys, binding)| (*mode, keys, binding)))
+            .map(|(mode, keys, binding)| {
+                let (prefix, rest) = match keys.strip_prefix(self.leader.as_slice()) {
+                    Some(rest) if !rest.is_empty() => ("<Leader>", rest),
+                    _ => ("", keys.as_slice()),
+                };
+                let keys: String = rest.iter().map(Key::notation).collect();
+                let steps: Vec<String> = binding.0.iter().map(Step::describe).collect();
+                (mode, format!("{}{}", prefix, keys), steps.join(", "))
+            })
+            .collect();
+        bindings.sort();
+        bindings
+    }
+
+    /// 按键序列绑定的操作，以及是否有以它开头的更长的绑定
+    fn lookup(&self, mode: Mode, keys: &[Key]) -> (Option<Binding>, bool) {
+        let Some(bindings) = self.bindings.get(&mode) else {
+            return (None, false);
+        };
+        let longer = bindings
+            .keys()
+            .any(|k| k.len() > keys.len() && k.starts_with(keys));
+        (bindings.get(keys).cloned(), longer)
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::new(&BTreeMap::new(), DEFAULT_LEADER).expect("default bindings are valid")
+    }
+}
+
+/// 有选择区域时，这些按键移动光标并扩展选择区域，其余没有绑定的按键会取消选择
+fn extends_selection(key: &Key) -> bool {
+    matches!(
+        key,
+        Key::ArrowKey(_)
+            | Key::ControlKey(ControlKey::Home)
+            | Key::ControlKey(ControlKey::End)
+            | Key::ControlKey(ControlKey::PageUp)
+            | Key::ControlKey(ControlKey::PageDown)
+            | Key::Modified(..)
+    )
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 按键映射表查找按键对应的操作并执行，没有绑定的按键作为文本插入
+    pub(super) async fn dispatch_key(&mut self, key: Key) {
+        if self.buffer.selection.is_some() {
+            if let Some(binding) = self.resolve_keys(Mode::Select, key.clone()).await
+                && self.run_binding(binding).await
+            {
+                return;
+            }
+            if !extends_selection(&key) {
+                self.buffer.selection = None;
+            }
+        }
+        if let Some(binding) = self.resolve_keys(Mode::Normal, key.clone()).await
+            && self.run_binding(binding).await
+        {
+            return;
+        }
+        match key {
+            Key::Modified(modifiers, key) => self.handle_modified(modifiers, &key),
+            Key::Char(_) | Key::Text(_) | Key::ControlKey(ControlKey::Tab | ControlKey::CR) => self.insert(key),
+            _ => {}
+        }
+    }
+
+    /// 从key开始读取按键，直到不再是任何绑定的前缀或者等待超时
+    /// 返回匹配的最长的绑定，多读的按键放回去重新处理
+    async fn resolve_keys(&mut self, mode: Mode, key: Key) -> Option<Binding> {
+        let mut keys = vec![key];
+        let mut matched = None;
+        loop {
+            let (binding, longer) = self.keymap.lookup(mode, &keys);
+            if let Some(binding) = binding {
+                matched = Some((keys.len(), binding));
+            }
+            if !longer {
+                break;
+            }
+            match self.next_key_timeout().await {
+                Some(key) => keys.push(key),
+                None => break,
+            }
+        }
+        let used = matched.as_ref().map_or(1, |(len, _)| *len);
+        for key in keys.drain(used..).rev() {
+            self.pending_keys.push_front(key);
+        }
+        matched.map(|(_, binding)| binding)
+    }
+
+    /// 在keymap_timeout毫秒内等待下一个按键，超时返回None
+    async fn next_key_timeout(&mut self) -> Option<Key> {
+        if self.pending_keys.is_empty() {
+            let timeout = Duration::from_millis(self.config.keymap_timeout);
+            // 序列的后半部分还没有输入时先刷新屏幕
+            let _ = self.refresh_screen();
+            // wait_input可以安全地取消，不会丢失按键
+            time::timeout(timeout, self.key_stream.wait_input()).await.ok()?.ok()?;
+        }
+        self.next_key().await.ok().flatten()
+    }
+
+    /// 依次执行绑定的步骤，只有一步时返回这一步的结果
+    async fn run_binding(&mut self, binding: Binding) -> bool {
+        if let [Step::Action(action)] = binding.0.as_slice() {
+            return self.run_action(*action).await;
+        }
+        for step in binding.0 {
+            match step {
+                Step::Action(action) => {
+                    self.run_action(action).await;
+                }
+                Step::Command(command) => {
+                    if let Err(e) = self.execute_command(&command).await {
+                        self.show_message(Message::warning(e));
+                        break;
+                    }
+                }
+                #[cfg(feature = "lua")]
+                Step::Plugin(index) => self.run_plugin_callback(index),
+            }
+        }
+        true
+    }
+
+    /// 执行操作，返回false表示操作不适用，按键按没有绑定处理
+    pub(super) async fn run_action(&mut self, action: Action) -> bool {
+        match action {
+            Action::Nop => {}
+            Action::MoveLeft => self.move_cursors(&Key::ArrowKey(Direction::Left)),
+            Action::MoveRight => self.move_cursors(&Key::ArrowKey(Direction::Right)),
+            Action::MoveUp => self.move_cursors(&Key::ArrowKey(Direction::Up)),
+            Action::MoveDown => self.move_cursors(&Key::ArrowKey(Direction::Down)),
+            Action::LineStart => self.move_cursors(&Key::ControlKey(ControlKey::Home)),
+            Action::LineEnd => self.move_cursors(&Key::ControlKey(ControlKey::End)),
+            Action::FileStart => self.goto_start(),
+            Action::FileEnd => self.goto_end(),
+            Action::WordLeft => self.at_cursors(false, Self::word_left),
+            Action::WordRight => self.at_cursors(false, Self::word_right),
+            Action::PageUp => self.scroll_page(-(self.full_page() as isize)).await,
+            Action::PageDown => self.scroll_page(self.full_page() as isize).await,
+            Action::HalfPageUp => self.scroll_page(-(self.half_page() as isize)).await,
+            Action::HalfPageDown => self.scroll_page(self.half_page() as isize).await,
+            Action::Backspace => self.backspace(),
+            Action::DeleteChar => self.delete(),
+            Action::DeleteWordBefore => self.delete_word_before(),
+            Action::DeleteWordAfter => self.delete_word_after(),
+            Action::MoveLineUp => self.move_line_up(),
+            Action::MoveLineDown => self.move_line_down(),
+            Action::JoinLines => self.join_lines(),
+            Action::DuplicateLine => self.duplicate(),
+            Action::Find => self.find().await,
+            Action::Replace => self.replace().await,
+            Action::SearchNext => self.search_next(true),
+            Action::SearchPrevious => self.search_next(false),
+            Action::Grep => self.grep_command().await,
+            Action::FocusResults => self.focus_results(),
+            Action::GotoLine => self.goto_command().await,
+            Action::JumpToBracket => self.jump_to_match(),
+            Action::ToggleLineNumbers => self.toggle_line_numbers(),
+            Action::ToggleWrap => self.toggle_wrap(),
+            Action::ToggleRuler => self.toggle_ruler(),
+            Action::ToggleIndentGuides => self.toggle_indent_guides(),
+            Action::ReloadConfig => self.reload_config(),
+            Action::BufferMenu => self.buffer_command().await,
+            Action::WindowMenu => self.window_command().await,
+            Action::FormatMenu => self.format_command().await,
+            Action::ScrollMenu => self.scroll_command().await,
+            Action::RegisterMenu => self.register_command().await,
+            Action::OpenFile => self.prompt_open_file().await,
+            Action::Save => {
+                if let Err(e) = self.save().await {
+                    let message = Message::error(format!("Error saving file: {}", e), &e);
+                    self.show_message(message);
+                }
+            }
+            Action::SaveAs => {
+                if let Err(e) = self.save_as().await {
+                    let message = Message::error(format!("Error saving file: {}", e), &e);
+                    self.show_message(message);
+                }
+            }
+            Action::Quit => self.quit = true,
+            Action::SelectChar => self.toggle_selection(SelectionKind::Char),
+            Action::SelectLine => self.toggle_selection(SelectionKind::Line),
+            Action::SelectBlock => self.toggle_selection(SelectionKind::Block),
+            Action::CancelSelection => self.buffer.selection = None,
+            Action::Yank => self.yank_selection(),
+            Action::DeleteSelection => self.cut_selection(),
+            Action::PasteSelection => self.paste_over_selection().await,
+            Action::Paste => self.paste().await,
+            Action::SelectRegister => {
+                self.select_register().await;
+            }
+            Action::BlockInsert => return self.block_insert(false),
+            Action::BlockChange => return self.block_insert(true),
+            Action::Indent => self.indent_selection(true),
+            Action::Outdent => self.indent_selection(false),
+            Action::FilterSelection => self.filter_selection().await,
+            Action::Complete => self.complete().await,
+            Action::ExpandSnippet => return self.expand_snippet(),
+            Action::ToggleSpellCheck => self.toggle_spell_check(),
+            Action::NextMisspelling => self.goto_misspelling(true),
+            Action::PreviousMisspelling => self.goto_misspelling(false),
+            Action::SpellSuggestions => self.spell_suggestions().await,
+            Action::Make => self.make(&[]),
+            Action::NextResult => self.goto_result(true).await,
+            Action::PreviousResult => self.goto_result(false).await,
+            Action::NextHunk => self.goto_hunk(true),
+            Action::PreviousHunk => self.goto_hunk(false),
+            Action::RevertHunk => self.revert_hunk(),
+            Action::Blame => self.blame_command().await,
+            Action::ToggleInlineBlame => self.toggle_inline_blame(),
+            Action::AddCursorBelow => self.add_cursor_vertical(true),
+            Action::AddCursorAbove => self.add_cursor_vertical(false),
+            Action::AddCursorAtNextMatch => self.add_cursor_at_next_match(),
+            Action::ClearCursors => return self.clear_cursors(),
+            Action::Statistics => self.show_statistics(),
+            Action::UpperCase => self.change_case(Case::Upper),
+            Action::LowerCase => self.change_case(Case::Lower),
+            Action::TitleCase => self.change_case(Case::Title),
+            Action::IncrementNumber => self.increment_number(1),
+            Action::DecrementNumber => self.increment_number(-1),
+            Action::TogglePreview => self.toggle_preview(),
+            Action::GotoDefinition => self.goto_definition().await,
+            Action::SymbolPicker => self.symbol_picker().await,
+            Action::JumpBack => self.jump_back().await,
+            Action::JumpForward => self.jump_forward().await,
+            Action::RecordMacro => self.record_command().await,
+            Action::PlayMacro => self.play_command().await,
+            Action::CommandLine => self.command_line().await,
+            Action::ShowMessages => self.show_messages(),
+            Action::ShowRegisters => self.show_registers(),
+            Action::Help => self.show_help(),
+            Action::Undo => self.undo(),
+            Action::Redo => self.redo(),
+            Action::InsertLiteral => self.insert_literal().await,
+            Action::InsertUnicode => self.insert_unicode().await,
+            Action::Digraph => self.digraph().await,
+            Action::UndoTree => self.undo_tree().await,
+        }
+        true
+    }
+}
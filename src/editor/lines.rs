@@ -0,0 +1,332 @@
+use std::cmp::Ordering;
+use std::ops::Range;
+
+use regex::Regex;
+use tokio::io::AsyncReadExt;
+
+use super::selection::SelectionKind;
+use super::{ControlKey, Editor, Key, Message, Row, Terminal};
+
+fn is_whitespace(key: &Key) -> bool {
+    matches!(key, Key::Char(' ') | Key::ControlKey(ControlKey::Tab))
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 选中的行，没有选择时是光标所在的行
+    pub(super) fn selected_lines(&self) -> Range<usize> {
+        let cy = self.buffer.cy as usize;
+        let (start, end) = match self.buffer.selection {
+            Some(selection) => {
+                let (start, end) = selection.bounds((cy, self.buffer.cx as usize));
+                (start.0, end.0)
+            }
+            None => (cy, cy),
+        };
+        let last = self.buffer.rows.len().saturating_sub(1);
+        start.min(last)..end.min(last) + 1
+    }
+
+    /// 选择区域和光标一起移动
+    fn shift_selection(&mut self, up: bool) {
+        if let Some(selection) = &mut self.buffer.selection {
+            selection.anchor.0 = if up {
+                selection.anchor.0 - 1
+            } else {
+                selection.anchor.0 + 1
+            };
+        }
+    }
+
+    /// 将当前行或者选中的行上移一行，光标跟着移动
+    pub(super) fn move_line_up(&mut self) {
+        let lines = self.selected_lines();
+        if lines.start == 0
+            || self.buffer.cy as usize >= self.buffer.rows.len()
+            || !self.check_writable()
+        {
+            return;
+        }
+        let row = self.buffer.rows.remove(lines.start - 1);
+        self.buffer.rows.insert(lines.end - 1, row);
+        self.shift_selection(true);
+        self.buffer.mark_dirty();
+        self.sub_cy();
+    }
+
+    /// 将当前行或者选中的行下移一行，光标跟着移动
+    pub(super) fn move_line_down(&mut self) {
+        let lines = self.selected_lines();
+        if lines.end >= self.buffer.rows.len() || !self.check_writable() {
+            return;
+        }
+        let row = self.buffer.rows.remove(lines.end);
+        self.buffer.rows.insert(lines.start, row);
+        self.shift_selection(false);
+        self.buffer.mark_dirty();
+        self.add_cy();
+    }
+
+    /// 把下一行合并到当前行，有选择时合并所有选中的行
+    /// 连接处的空白压缩成一个空格，光标停在连接处
+    pub(super) fn join_lines(&mut self) {
+        if !self.check_writable() {
+            return;
+        }
+        let lines = self.selected_lines();
+        // 只选中一行时和没有选择一样合并下一行
+        let end = lines.end.max(lines.start + 2).min(self.buffer.rows.len());
+        if lines.start + 1 >= end {
+            return;
+        }
+        let tab_width = self.config.tab_width;
+        let rows = &mut self.buffer.rows;
+        let mut joined = rows[lines.start].keys();
+        let mut at = joined.len();
+        for i in lines.start + 1..end {
+            while joined.last().is_some_and(is_whitespace) {
+                joined.pop();
+            }
+            let next = rows[i].keys();
+            let next = &next[next.iter().take_while(|k| is_whitespace(k)).count()..];
+            at = joined.len();
+            if !joined.is_empty() && !next.is_empty() {
+                joined.push(Key::Char(' '));
+            }
+            joined.extend_from_slice(next);
+        }
+        rows.remove_range(lines.start + 1..end);
+        rows[lines.start] = Row::new(joined, tab_width);
+        self.buffer.selection = None;
+        self.buffer.mark_dirty();
+        self.set_position((lines.start, at));
+    }
+
+    /// 复制当前行或者选中的行到下面
+    /// 在一行内按字符选择时，把选中的内容复制到选择区域后面
+    pub(super) fn duplicate(&mut self) {
+        if !self.check_writable() || self.buffer.rows.is_empty() {
+            return;
+        }
+        let cursor = (self.buffer.cy as usize, self.buffer.cx as usize);
+        let selection = self.buffer.selection;
+        if let Some(selection) = selection.filter(|s| s.kind == SelectionKind::Char) {
+            let (start, end) = selection.bounds(cursor);
+            if start.0 == end.0 && start.0 < self.buffer.rows.len() {
+                let row = &mut self.buffer.rows[start.0];
+                let range = row.get_raw_index(start.1)..row.get_raw_index(end.1);
+                let copied = row.keys_in(range.clone());
+                row.replace(range.end..range.end, copied);
+                self.buffer.mark_dirty();
+                return;
+            }
+        }
+        let lines = self.selected_lines();
+        let tab_width = self.config.tab_width;
+        let copies: Vec<Row> = self
+            .buffer
+            .rows
+            .range(lines.clone())
+            .map(|r| Row::new(r.keys(), tab_width))
+            .collect();
+        self.buffer.rows.insert_rows(lines.end, copies);
+        // 光标和选择区域移到复制出来的行上
+        let count = lines.len();
+        if let Some(selection) = &mut self.buffer.selection {
+            selection.anchor.0 += count;
+        }
+        self.buffer.cy = (self.buffer.cy as usize + count) as u16;
+        self.buffer.mark_dirty();
+        self.scroll();
+    }
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 命令作用的行：有选择时是选中的行，否则是整个buffer
+    pub(super) fn command_lines(&self) -> Range<usize> {
+        if self.buffer.selection.is_some() {
+            self.selected_lines()
+        } else {
+            0..self.buffer.rows.len()
+        }
+    }
+
+    /// 用f处理命令作用的行，一次替换整个范围，返回处理前后的行数
+    pub(super) fn transform_lines(&mut self, f: impl FnOnce(Vec<Vec<Key>>) -> Vec<Vec<Key>>) -> Option<(usize, usize)> {
+        if !self.check_writable() || self.buffer.rows.is_empty() {
+            return None;
+        }
+        let lines = self.command_lines();
+        let raws: Vec<Vec<Key>> = self.buffer.rows.range(lines.clone()).map(|r| r.keys()).collect();
+        let before = raws.len();
+        let raws = f(raws);
+        let after = raws.len();
+        let tab_width = self.config.tab_width;
+        self.buffer.rows.remove_range(lines.clone());
+        self.buffer
+            .rows
+            .insert_rows(lines.start, raws.into_iter().map(|raw| Row::new(raw, tab_width)));
+        self.buffer.selection = None;
+        self.buffer.mark_dirty();
+        let cy = (self.buffer.cy as usize).min(lines.start + after.saturating_sub(1));
+        self.set_position((cy, 0));
+        Some((before, after))
+    }
+
+    /// 排序选中的行或者整个buffer，numeric按行中的第一个数字排序，没有数字的行排在前面
+    /// 排序是稳定的，相同的行保持原来的顺序
+    pub(super) fn sort_lines(&mut self, numeric: bool, descending: bool) {
+        let sorted = self.transform_lines(|raws| {
+            let number = Regex::new(r"-?\d+(?:\.\d+)?").expect("valid regex");
+            let mut keyed: Vec<(Option<f64>, String, Vec<Key>)> = raws
+                .into_iter()
+                .map(|raw| {
+                    let text = Row::raw_str(&raw);
+                    let value = number.find(&text).and_then(|m| m.as_str().parse().ok());
+                    (value, text, raw)
+                })
+                .collect();
+            keyed.sort_by(|a, b| {
+                let ordering = if numeric {
+                    a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal)
+                } else {
+                    a.1.cmp(&b.1)
+                };
+                if descending { ordering.reverse() } else { ordering }
+            });
+            keyed.into_iter().map(|(_, _, raw)| raw).collect()
+        });
+        if let Some((count, _)) = sorted {
+            self.show_message(Message::new(format!("Sorted {} lines", count)));
+        }
+    }
+
+    /// 删除相邻的重复行，只保留第一行
+    /// 和uniq命令一样不删除不相邻的重复行，需要时先排序
+    pub(super) fn unique_lines(&mut self) {
+        let result = self.transform_lines(|mut raws| {
+            raws.dedup();
+            raws
+        });
+        if let Some((before, after)) = result {
+            self.show_message(Message::new(format!("Removed {} duplicate lines", before - after)));
+        }
+    }
+
+    pub(super) fn reverse_lines(&mut self) {
+        let result = self.transform_lines(|mut raws| {
+            raws.reverse();
+            raws
+        });
+        if let Some((count, _)) = result {
+            self.show_message(Message::new(format!("Reversed {} lines", count)));
+        }
+    }
+
+    /// 在分隔符前面补空格，让选中的行或者整个buffer中第一个分隔符对齐
+    /// 原来分隔符前面有空白时在对齐后保留一个空格，没有分隔符的行不变
+    pub(super) fn align_lines(&mut self, delimiter: &str) {
+        let delimiter: Vec<Key> = delimiter.chars().map(Key::Char).collect();
+        let tab_width = self.config.tab_width;
+        let mut count = 0;
+        let aligned = self.transform_lines(|raws| {
+            let split: Vec<Option<(Vec<Key>, bool, usize)>> = raws
+                .iter()
+                .map(|raw| {
+                    let at = raw.windows(delimiter.len()).position(|w| w == delimiter.as_slice())?;
+                    let mut before = raw[..at].to_vec();
+                    let spaced = before.last().is_some_and(is_whitespace);
+                    while before.last().is_some_and(is_whitespace) {
+                        before.pop();
+                    }
+                    Some((before, spaced, at))
+                })
+                .collect();
+            let width = |keys: &[Key]| Row::new(keys.to_vec(), tab_width).display_len();
+            let column = split.iter().flatten().map(|(before, ..)| width(before)).max().unwrap_or(0);
+            let gap = usize::from(split.iter().flatten().any(|(_, spaced, _)| *spaced));
+            raws.into_iter()
+                .zip(split)
+                .map(|(raw, split)| match split {
+                    Some((mut before, _, at)) => {
+                        count += 1;
+                        let padding = column - width(&before) + gap;
+                        before.extend(std::iter::repeat_n(Key::Char(' '), padding));
+                        before.extend_from_slice(&raw[at..]);
+                        before
+                    }
+                    None => raw,
+                })
+                .collect()
+        });
+        if aligned.is_some() {
+            self.show_message(Message::new(format!("Aligned {} lines", count)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::MemoryTerminal;
+
+    type TestEditor = Editor<tokio::io::Empty, MemoryTerminal>;
+
+    /// 对整个buffer执行f之后每一行的内容
+    async fn lines_after(lines: &[&str], f: impl FnOnce(&mut TestEditor)) -> Vec<String> {
+        let mut editor = super::super::tests::editor(40, 10).await;
+        editor.buffer.rows = lines.iter().map(|l| Row::from_text(*l, 4)).collect::<Vec<_>>().into();
+        f(&mut editor);
+        editor.buffer.rows.iter().map(|r| r.text().to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn sorts_stably() {
+        let lines = ["b 2", "a 10", "x", "c 2", "B 1", "y"];
+        // 按字符串排序，大写字母在小写字母前面
+        let sorted = lines_after(&lines, |e| e.sort_lines(false, false)).await;
+        assert_eq!(sorted, ["B 1", "a 10", "b 2", "c 2", "x", "y"]);
+        // 数字相同的行和没有数字的行保持原来的顺序，倒序时也一样
+        let sorted = lines_after(&lines, |e| e.sort_lines(true, false)).await;
+        assert_eq!(sorted, ["x", "y", "B 1", "b 2", "c 2", "a 10"]);
+        let sorted = lines_after(&lines, |e| e.sort_lines(true, true)).await;
+        assert_eq!(sorted, ["a 10", "b 2", "c 2", "B 1", "x", "y"]);
+        let sorted = lines_after(&["v -1.5", "v -2", "v 0.5"], |e| e.sort_lines(true, false)).await;
+        assert_eq!(sorted, ["v -2", "v -1.5", "v 0.5"]);
+    }
+
+    #[tokio::test]
+    async fn unique_removes_only_adjacent_duplicates() {
+        let lines = ["a", "a", "b", "a", "b", "b", "", ""];
+        let unique = lines_after(&lines, |e| e.unique_lines()).await;
+        assert_eq!(unique, ["a", "b", "a", "b", ""]);
+        // 行尾的空白不同时不是重复的行
+        let unique = lines_after(&["a", "a ", "a"], |e| e.unique_lines()).await;
+        assert_eq!(unique, ["a", "a ", "a"]);
+    }
+
+    #[tokio::test]
+    async fn reverses_lines() {
+        let reversed = lines_after(&["1", "2", "", "3"], |e| e.reverse_lines()).await;
+        assert_eq!(reversed, ["3", "", "2", "1"]);
+    }
+
+    #[tokio::test]
+    async fn aligns_by_display_width() {
+        // 宽字符占两列，按显示的宽度对齐而不是字符数
+        let aligned = lines_after(&["中文 = 1", "a = 2", "tab\t= 3"], |e| e.align_lines("=")).await;
+        assert_eq!(aligned, ["中文 = 1", "a    = 2", "tab  = 3"]);
+        // 原来没有空白时紧贴着对齐
+        let aligned = lines_after(&["中=1", "abc=2"], |e| e.align_lines("=")).await;
+        assert_eq!(aligned, ["中 =1", "abc=2"]);
+    }
+
+    #[tokio::test]
+    async fn leaves_lines_without_the_separator() {
+        let lines = ["key: value", "no separator", "", "longer_key: v", "x:y:z"];
+        let aligned = lines_after(&lines, |e| e.align_lines(":")).await;
+        // 只按每行的第一个分隔符对齐
+        assert_eq!(aligned, ["key       : value", "no separator", "", "longer_key: v", "x         :y:z"]);
+        let lines = ["a", "b"];
+        assert_eq!(lines_after(&lines, |e| e.align_lines("=>")).await, lines);
+    }
+}
@@ -0,0 +1,66 @@
+use tokio::io::AsyncReadExt;
+
+use super::{Editor, Key, Message, Row, Terminal};
+
+/// 解析码位，比如 U+00E9、u+e9、0xe9 或者 e9
+fn parse_code_point(text: &str) -> Option<char> {
+    let text = text.trim();
+    let hex = ["U+", "u+", "0x", "0X"]
+        .iter()
+        .find_map(|prefix| text.strip_prefix(prefix))
+        .unwrap_or(text);
+    char::from_u32(u32::from_str_radix(hex, 16).ok()?)
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 把下一个按键按原样插入，控制键插入对应的控制字符，比如Ctrl+A显示为^A
+    pub(super) async fn insert_literal(&mut self) {
+        if !self.check_writable() {
+            return;
+        }
+        self.message = Some(Message::new("Insert literal: press a key".to_string()));
+        if self.refresh_screen().is_err() {
+            return;
+        }
+        let Ok(key) = self.get_key().await else {
+            return;
+        };
+        self.message = None;
+        self.insert_literal_key(key);
+    }
+
+    /// 在每个光标处插入key，不自动补全成对字符，回车和tab也不做特殊处理
+    pub(super) fn insert_literal_key(&mut self, key: Key) {
+        // 换行符会在保存后把一行分成两行
+        if key.as_char().is_none_or(|c| c == '\n') {
+            self.show_message(Message::warning(format!("Cannot insert {} literally", key.notation())));
+            return;
+        }
+        self.at_cursors(true, |editor| editor.insert_raw_key(key.clone()));
+    }
+
+    /// 输入码位，插入对应的字符
+    pub(super) async fn insert_unicode(&mut self) {
+        if !self.check_writable() {
+            return;
+        }
+        let Some(input) = self.prompt("Code point: U+", None).await else {
+            self.message = None;
+            return;
+        };
+        self.message = None;
+        if let Err(e) = self.put_char(&Row::raw_str(&input)) {
+            self.show_message(Message::warning(e));
+        }
+    }
+
+    /// put-char U+XXXX命令
+    pub(super) fn put_char(&mut self, code: &str) -> Result<(), String> {
+        let c = parse_code_point(code).ok_or_else(|| format!("Invalid code point: {}", code))?;
+        if !self.check_writable() {
+            return Ok(());
+        }
+        self.insert_literal_key(Key::from_char(c));
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+
+use tokio::io::AsyncReadExt;
+
+use super::key::Key;
+use super::{Editor, Message, Terminal};
+
+/// 等待执行的按键最多的数量，防止宏递归调用自己时无限展开
+const MAX_PENDING_KEYS: usize = 100_000;
+
+/// 开始和停止录制宏的按键
+const RECORD_KEY: Key = Key::FunctionKey(7);
+
+/// 录制的宏，按寄存器名称保存
+#[derive(Debug, Default)]
+pub(super) struct Macros {
+    registers: BTreeMap<char, Vec<Key>>,
+    /// 正在录制的寄存器和已经录下的按键
+    recording: Option<(char, Vec<Key>)>,
+    /// 上一次播放的寄存器，再次按下播放键时重复
+    last_played: Option<char>,
+}
+
+impl Macros {
+    /// 记录从终端读到的按键，播放宏产生的按键不会经过这里
+    pub(super) fn record(&mut self, key: &Key) {
+        if let Some((_, keys)) = &mut self.recording {
+            keys.push(key.clone());
+        }
+    }
+
+    pub(super) fn iter(&self) -> impl Iterator<Item = (&char, &Vec<Key>)> {
+        self.registers.iter()
+    }
+
+    /// 正在录制的寄存器
+    pub(super) fn recording(&self) -> Option<char> {
+        self.recording.as_ref().map(|(register, _)| *register)
+    }
+}
+
+fn notation(keys: &[Key]) -> String {
+    keys.iter().map(Key::notation).collect()
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 没有在录制时询问寄存器并开始录制，正在录制时停止并保存
+    pub(super) async fn record_command(&mut self) {
+        if let Some((register, mut keys)) = self.macros.recording.take() {
+            // 去掉停止录制的按键本身
+            if keys.last() == Some(&RECORD_KEY) {
+                keys.pop();
+            }
+            let text = format!("Recorded @{}: {}", register, notation(&keys));
+            self.show_message(Message::new(text));
+            self.macros.registers.insert(register, keys);
+            return;
+        }
+        self.message = Some(Message::new("Record macro into register: a-z".to_string()));
+        if self.refresh_screen().is_err() {
+            return;
+        }
+        let Ok(key) = self.get_key().await else {
+            return;
+        };
+        self.message = None;
+        if let Key::Char(register @ 'a'..='z') = key {
+            self.macros.recording = Some((register, Vec::new()));
+            self.show_message(Message::new(format!("Recording @{}", register)));
+        }
+    }
+
+    /// 先输入重复次数，再输入寄存器名称，再按一次播放键重复上一次播放的宏
+    pub(super) async fn play_command(&mut self) {
+        let mut count: usize = 0;
+        loop {
+            let prompt = match count {
+                0 => "Play macro: [count] register a-z, F8 repeats last".to_string(),
+                n => format!("Play macro {} times: register a-z, F8 repeats last", n),
+            };
+            self.message = Some(Message::new(prompt));
+            if self.refresh_screen().is_err() {
+                return;
+            }
+            let Ok(key) = self.get_key().await else {
+                return;
+            };
+            self.message = None;
+            let register = match key {
+                Key::Char(c @ '0'..='9') => {
+                    count = count.saturating_mul(10).saturating_add(c as usize - '0' as usize);
+                    continue;
+                }
+                Key::Char(register @ 'a'..='z') => register,
+                Key::FunctionKey(8) => match self.macros.last_played {
+                    Some(register) => register,
+                    None => {
+                        self.show_message(Message::warning("No macro played yet".to_string()));
+                        return;
+                    }
+                },
+                _ => return,
+            };
+            return self.play_macro(register, count.max(1));
+        }
+    }
+
+    /// 把宏的按键放到等待执行的按键的最前面，在读取终端的输入之前执行
+    fn play_macro(&mut self, register: char, count: usize) {
+        let Some(keys) = self.macros.registers.get(&register) else {
+            self.show_message(Message::warning(format!("Register {} is empty", register)));
+            return;
+        };
+        let total = keys.len().saturating_mul(count);
+        if self.pending_keys.len().saturating_add(total) > MAX_PENDING_KEYS {
+            self.pending_keys.clear();
+            self.show_message(Message::warning("Macro too long, aborted".to_string()));
+            return;
+        }
+        let keys = keys.clone();
+        for _ in 0..count {
+            for key in keys.iter().rev() {
+                self.pending_keys.push_front(key.clone());
+            }
+        }
+        self.macros.last_played = Some(register);
+    }
+}
@@ -0,0 +1,100 @@
+use std::time::{Duration, Instant};
+
+use tokio::io::AsyncReadExt;
+
+use super::{Editor, Row, Terminal};
+use crate::error::{EditorError, ErrorSeverity};
+use crate::utils::{self, color};
+
+/// 消息记录最多保留的条数，更早的消息会被丢弃
+const MAX_MESSAGES: usize = 200;
+
+/// 消息列表buffer的名称
+const MESSAGES_TITLE: &str = "[Messages]";
+
+/// 消息在消息栏中显示的时间
+const MESSAGE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+pub(super) struct Message {
+    pub(super) text: String,
+    pub(super) time: Instant,
+    // None表示普通的提示
+    severity: Option<ErrorSeverity>,
+}
+
+impl Message {
+    pub(super) fn new(text: String) -> Self {
+        Self {
+            text,
+            time: Instant::now(),
+            severity: None,
+        }
+    }
+
+    pub(super) fn with_severity(text: String, severity: ErrorSeverity) -> Self {
+        Self {
+            severity: Some(severity),
+            ..Self::new(text)
+        }
+    }
+
+    pub(super) fn warning(text: String) -> Self {
+        Self::with_severity(text, ErrorSeverity::Warning)
+    }
+
+    /// 错误消息，严重程度由错误决定
+    pub(super) fn error(text: String, error: &EditorError) -> Self {
+        Self::with_severity(text, error.severity())
+    }
+
+    pub(super) fn is_expired(&self) -> bool {
+        self.time.elapsed() >= MESSAGE_TIMEOUT
+    }
+
+    /// 警告或者错误
+    pub(super) fn is_problem(&self) -> bool {
+        self.severity.is_some()
+    }
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 在消息栏中显示消息，并记录下来以便之后查看
+    /// 提示用户输入的内容直接设置self.message，不需要记录
+    pub(super) fn show_message(&mut self, message: Message) {
+        if self.message_log.len() == MAX_MESSAGES {
+            self.message_log.pop_front();
+        }
+        self.message_log.push_back(message.clone());
+        self.message = Some(message);
+    }
+
+    pub(super) fn render_message_bar(&self) -> String {
+        let width = self.screen_cols as usize;
+        // 没有输入时由空闲的定时器清除过期的消息
+        let Some(message) = self.message.as_ref().filter(|m| !m.is_expired()) else {
+            return " ".repeat(width);
+        };
+        let content = utils::fit_width(&message.text, width);
+        let color = match message.severity {
+            None => &self.theme.message_bar,
+            Some(ErrorSeverity::Warning) => &self.theme.warning_message,
+            Some(ErrorSeverity::Error | ErrorSeverity::Critical) => &self.theme.error_message,
+        };
+        format!("{}{}{}", color, content, color::RESET)
+    }
+
+    /// 在只读的buffer中列出记录的所有消息，最新的在最后
+    pub(super) fn show_messages(&mut self) {
+        let rows = self.message_log.iter().map(|message| {
+            let level = message.severity.map_or("INFO", |s| s.as_str());
+            let elapsed = message.time.elapsed().as_secs();
+            let text = format!("[{:>5}s ago] {:<8} {}", elapsed, level, message.text);
+            Row::from_text(&text, self.config.tab_width)
+        });
+        let rows: Vec<Row> = rows.collect();
+        let last = rows.len().saturating_sub(1);
+        self.show_list(MESSAGES_TITLE, rows);
+        self.set_position((last, 0));
+    }
+}
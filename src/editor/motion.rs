@@ -0,0 +1,252 @@
+use std::ops::Range;
+
+use tokio::io::AsyncReadExt;
+
+use super::{Editor, Key, Message, Rendered, Row, Terminal};
+
+/// 解析跳转的目标，返回从1开始的行和可选的列
+/// 支持 行、行:列、行,列，可以以:开头，gg是第一行，G是最后一行
+pub(super) fn parse_target(input: &str) -> Option<(usize, Option<usize>)> {
+    let input = input.trim();
+    let input = input.strip_prefix(':').unwrap_or(input);
+    match input {
+        "gg" => return Some((1, None)),
+        "G" => return Some((usize::MAX, None)),
+        _ => {}
+    }
+    let (line, column) = match input.split_once([':', ',']) {
+        Some((line, column)) => (line, Some(column.trim().parse().ok()?)),
+        None => (input, None),
+    };
+    Some((line.trim().parse().ok()?, column))
+}
+
+/// 单词由字母、数字和下划线组成
+pub(super) fn is_word(key: &Key) -> bool {
+    matches!(key, Key::Char(c) if c.is_alphanumeric() || *c == '_')
+}
+
+/// 单词边界按字素簇计算，组合字符跟着前面的字符
+/// 计算时临时渲染一次，按cell查找边界
+struct Words {
+    rendered: Rendered,
+    keys: Vec<Key>,
+}
+
+impl Words {
+    fn new(row: &Row) -> Self {
+        Self {
+            rendered: row.render(),
+            keys: row.keys(),
+        }
+    }
+
+    fn cells(&self) -> usize {
+        self.rendered.cells.len()
+    }
+
+    /// at所在的cell，在行尾时是cells的数量
+    fn cell_index(&self, at: usize) -> usize {
+        if at >= self.keys.len() {
+            self.cells()
+        } else {
+            self.rendered.cells.partition_point(|c| c.raw <= at) - 1
+        }
+    }
+
+    /// 第i个cell在raw中的起始位置
+    fn cell_start(&self, i: usize) -> usize {
+        self.rendered.cells.get(i).map_or(self.keys.len(), |c| c.raw)
+    }
+
+    fn is_word_cell(&self, i: usize) -> bool {
+        is_word(&self.keys[self.rendered.cells[i].raw])
+    }
+}
+
+impl Row {
+    /// at之后第一个单词的末尾
+    pub(super) fn word_end_after(&self, at: usize) -> usize {
+        let words = Words::new(self);
+        let mut i = words.cell_index(at);
+        while i < words.cells() && !words.is_word_cell(i) {
+            i += 1;
+        }
+        while i < words.cells() && words.is_word_cell(i) {
+            i += 1;
+        }
+        words.cell_start(i)
+    }
+
+    /// at之后下一个单词的开头，没有时是行尾
+    pub(super) fn word_start_after(&self, at: usize) -> usize {
+        let words = Words::new(self);
+        let mut i = words.cell_index(at);
+        while i < words.cells() && words.is_word_cell(i) {
+            i += 1;
+        }
+        while i < words.cells() && !words.is_word_cell(i) {
+            i += 1;
+        }
+        words.cell_start(i)
+    }
+
+    /// at之前第一个单词的开头
+    pub(super) fn word_start_before(&self, at: usize) -> usize {
+        let words = Words::new(self);
+        let mut i = words.cell_index(at);
+        while i > 0 && !words.is_word_cell(i - 1) {
+            i -= 1;
+        }
+        while i > 0 && words.is_word_cell(i - 1) {
+            i -= 1;
+        }
+        words.cell_start(i)
+    }
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 光标在当前行raw中的索引
+    pub(super) fn raw_cursor(&self) -> usize {
+        self.buffer
+            .rows
+            .get(self.buffer.cy as usize)
+            .map_or(0, |r| r.get_raw_index(self.buffer.cx as usize))
+    }
+
+    /// 光标所在单词在raw中的范围，光标在单词的后面时也算
+    pub(super) fn word_under_cursor(&self) -> Option<Range<usize>> {
+        let row = self.buffer.rows.get(self.buffer.cy as usize)?;
+        let at = self.raw_cursor();
+        let keys = row.keys();
+        let start = at - keys[..at].iter().rev().take_while(|k| is_word(k)).count();
+        let end = at + keys[at..].iter().take_while(|k| is_word(k)).count();
+        (start < end).then_some(start..end)
+    }
+
+    /// 在行尾时移到下一行的开头，然后用motion计算新的位置
+    fn word_forward(&mut self, motion: fn(&Row, usize) -> usize) {
+        let mut cy = self.buffer.cy as usize;
+        let mut at = self.raw_cursor();
+        let Some(mut row) = self.buffer.rows.get(cy) else {
+            return;
+        };
+        if at >= row.len() {
+            if cy + 1 >= self.buffer.rows.len() {
+                return;
+            }
+            cy += 1;
+            at = 0;
+            row = &self.buffer.rows[cy];
+        }
+        self.set_position((cy, motion(row, at)));
+    }
+
+    /// 移到下一个单词的末尾，在行尾时移到下一行
+    pub(super) fn word_right(&mut self) {
+        self.word_forward(Row::word_end_after);
+    }
+
+    /// 移到下一个单词的开头，在行尾时移到下一行
+    pub(super) fn next_word(&mut self) {
+        self.word_forward(Row::word_start_after);
+    }
+
+    /// 移到上一个单词的开头，在行首时移到上一行
+    pub(super) fn word_left(&mut self) {
+        let mut cy = self.buffer.cy as usize;
+        let mut at = self.raw_cursor();
+        if at == 0 {
+            if cy == 0 {
+                return;
+            }
+            cy -= 1;
+            at = self.buffer.rows[cy].len();
+        }
+        let Some(row) = self.buffer.rows.get(cy) else {
+            return;
+        };
+        self.set_position((cy, row.word_start_before(at)));
+    }
+
+    /// 删除光标前的单词，在行首时和上一行合并
+    pub(super) fn delete_word_before(&mut self) {
+        if !self.check_writable() {
+            return;
+        }
+        let cy = self.buffer.cy as usize;
+        let at = self.raw_cursor();
+        let Some(row) = self.buffer.rows.get_mut(cy).filter(|_| at > 0) else {
+            self.backspace_at_cursor();
+            return;
+        };
+        let start = row.word_start_before(at);
+        row.replace(start..at, Vec::new());
+        self.buffer.mark_dirty();
+        self.set_position((cy, start));
+    }
+
+    /// 删除光标后的单词，在行尾时和下一行合并
+    pub(super) fn delete_word_after(&mut self) {
+        if !self.check_writable() {
+            return;
+        }
+        let cy = self.buffer.cy as usize;
+        let at = self.raw_cursor();
+        let Some(row) = self.buffer.rows.get_mut(cy).filter(|r| at < r.len()) else {
+            self.delete();
+            return;
+        };
+        let end = row.word_end_after(at);
+        row.replace(at..end, Vec::new());
+        self.buffer.mark_dirty();
+        self.set_position((cy, at));
+    }
+
+    /// Ctrl+G询问跳转的行和列
+    pub(super) async fn goto_command(&mut self) {
+        let Some(input) = self.prompt("Go to line[:column]: ", None).await else {
+            self.message = None;
+            return;
+        };
+        match parse_target(&Row::raw_str(&input)) {
+            Some((line, column)) => {
+                self.goto_position(line, column.unwrap_or(1));
+                self.message = None;
+            }
+            None => {
+                self.show_message(Message::new("Invalid line number".to_string()));
+            }
+        }
+    }
+
+    /// 跳转到从1开始的行和列，超出范围时停在最近的位置，跳转后光标所在行居中
+    pub(super) fn goto_position(&mut self, line: usize, column: usize) {
+        let cy = line.saturating_sub(1).min(self.buffer.rows.len().saturating_sub(1));
+        let cx = match self.buffer.rows.get(cy) {
+            Some(row) => row.column_of(row.get_raw_index(column.saturating_sub(1))),
+            None => 0,
+        };
+        self.buffer.cy = cy as u16;
+        self.buffer.cx = cx as u16;
+        self.buffer.col_offset = 0;
+        self.center_cursor();
+    }
+
+    /// 滚动屏幕让光标所在行位于窗口中间
+    pub(super) fn center_cursor(&mut self) {
+        let half = self.max_row as usize / 2;
+        self.buffer.row_offset = (self.buffer.cy as usize).saturating_sub(half);
+        self.scroll();
+    }
+
+    /// 跳到文件开头
+    pub(super) fn goto_start(&mut self) {
+        self.goto_position(1, 1);
+    }
+
+    /// 跳到最后一行的行尾
+    pub(super) fn goto_end(&mut self) {
+        self.goto_position(usize::MAX, usize::MAX);
+    }
+}
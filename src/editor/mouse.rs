@@ -0,0 +1,105 @@
+use tokio::io::AsyncReadExt;
+
+use super::selection::SelectionKind;
+use super::view::Rect;
+use super::{Editor, MouseEvent, Terminal};
+
+/// 开启鼠标事件报告：1000报告按下和释放，1002报告拖动，1006使用SGR格式
+pub(super) const ENABLE_MOUSE: &str = "\x1b[?1000h\x1b[?1002h\x1b[?1006h";
+pub(super) const DISABLE_MOUSE: &str = "\x1b[?1006l\x1b[?1002l\x1b[?1000l";
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    pub(super) async fn handle_mouse(&mut self, event: &MouseEvent) {
+        match *event {
+            MouseEvent::Click(0, _, y) if self.click_results(y).await => {}
+            MouseEvent::Click(0, x, y) => {
+                if let Some(rect) = self.focus_view_at(x, y) {
+                    self.buffer.selection = None;
+                    self.move_cursor_to(rect, x, y);
+                }
+            }
+            MouseEvent::Drag(0, x, y) => {
+                // 从按下的位置开始选择，拖动时不切换窗口
+                if self.buffer.selection.is_none() {
+                    self.toggle_selection(SelectionKind::Char);
+                }
+                let (_, rect) = self.focused_rect();
+                self.move_cursor_to(rect, x, y);
+            }
+            MouseEvent::Release(0, _, _) => {
+                // 只是点击而没有拖动时不保留空的选择
+                if let Some(selection) = self.buffer.selection
+                    && selection.anchor == (self.buffer.cy as usize, self.buffer.cx as usize)
+                {
+                    self.buffer.selection = None;
+                }
+            }
+            MouseEvent::Scroll(direction, x, y) => {
+                if let Some(rect) = self.focus_view_at(x, y) {
+                    self.scroll_lines(rect, direction);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// 焦点移到(x, y)所在的窗口
+    fn focus_view_at(&mut self, x: u16, y: u16) -> Option<Rect> {
+        let (index, rect) = self.view_rects().into_iter().find(|(i, r)| {
+            !self.views[*i].preview
+                && (r.x..r.x + r.width).contains(&x)
+                && (r.y..r.y + r.height).contains(&y)
+        })?;
+        self.focus_view(index);
+        Some(rect)
+    }
+
+    /// 将光标移到屏幕上的(x, y)，位置超出文本时移到最近的字符
+    fn move_cursor_to(&mut self, rect: Rect, x: u16, y: u16) {
+        let gutter = self.gutter_width(&self.buffer);
+        let text_height = rect.height.saturating_sub(1);
+        // 拖动到窗口外时停在窗口边缘
+        let y = y.clamp(rect.y, rect.y + text_height.saturating_sub(1)) - rect.y;
+        let x = x.saturating_sub(rect.x + gutter);
+
+        let (row, cx) = if self.wrap {
+            self.wrapped_position_at(x as usize, y as usize)
+        } else {
+            let row = (self.buffer.row_offset + y as usize).min(self.buffer.rows.len());
+            let col = self.buffer.col_offset + x as usize;
+            let cx = match self.buffer.rows.get(row) {
+                Some(r) if col < r.display_len() => r.get_render_index(r.get_raw_index(col)).0,
+                Some(r) => r.display_len(),
+                None => 0,
+            };
+            (row, cx)
+        };
+        self.buffer.cy = row as u16;
+        self.buffer.cx = cx as u16;
+        self.scroll();
+    }
+
+    /// 滚动视图，光标不动，只在超出屏幕时移到窗口边缘
+    fn scroll_lines(&mut self, rect: Rect, direction: i8) {
+        let max_row = rect.height.saturating_sub(1).max(1) as usize;
+        let limit = (self.buffer.rows.len() + 1).saturating_sub(max_row);
+        let lines = self.config.wheel_scroll_lines;
+        self.buffer.row_offset = if direction < 0 {
+            self.buffer.row_offset.saturating_sub(lines)
+        } else {
+            (self.buffer.row_offset + lines).min(limit.max(self.buffer.row_offset))
+        };
+
+        let margin = self.config.scroll_margin.min(max_row.saturating_sub(1) / 2);
+        let top = if self.buffer.row_offset == 0 {
+            0
+        } else {
+            self.buffer.row_offset + margin
+        };
+        let bottom = (self.buffer.row_offset + max_row)
+            .saturating_sub(margin + 1)
+            .min(self.buffer.rows.len());
+        self.buffer.cy = (self.buffer.cy as usize).clamp(top, bottom.max(top)) as u16;
+        self.clamp_cursor_x();
+    }
+}
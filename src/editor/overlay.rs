@@ -0,0 +1,137 @@
+use unicode_width::UnicodeWidthStr;
+
+use super::view::Rect;
+use crate::theme::Theme;
+use crate::utils::{self, color};
+
+/// 选择列表最多显示的行数，超出时滚动让选中的行可见
+const MAX_VISIBLE: usize = 8;
+/// 选择列表中每一项最多显示的宽度，对话框只受屏幕宽度的限制
+const MAX_WIDTH: usize = 40;
+
+/// 浮动窗口在屏幕上的位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Anchor {
+    /// 在文本区域的中间
+    Center,
+    /// 左上角在(x, y)的下一行，下面放不下时放在(x, y)的上面
+    Below(u16, u16),
+}
+
+/// 浮在文本区域上面的窗口，比如补全菜单、模糊查找的列表和确认对话框
+/// 渲染时画在所有窗口之后，只覆盖它占用的单元格
+pub(super) struct Overlay {
+    anchor: Anchor,
+    /// 有标题时画出边框
+    title: Option<String>,
+    lines: Vec<String>,
+    /// 选择列表中选中的行
+    selected: Option<usize>,
+}
+
+impl Overlay {
+    /// 选择列表，选中的行总是可见
+    pub(super) fn list(anchor: Anchor, items: Vec<String>, selected: usize) -> Self {
+        Self {
+            anchor,
+            title: None,
+            lines: items,
+            selected: Some(selected),
+        }
+    }
+
+    /// 在中间显示的带边框的对话框
+    pub(super) fn dialog(title: &str, lines: Vec<String>) -> Self {
+        Self {
+            anchor: Anchor::Center,
+            title: Some(title.to_string()),
+            lines,
+            selected: None,
+        }
+    }
+
+    pub(super) fn with_title(mut self, title: String) -> Self {
+        self.title = Some(title);
+        self
+    }
+
+    /// 在bounds中渲染，返回每一行和它在屏幕上的起始位置
+    pub(super) fn render(&self, theme: &Theme, bounds: Rect) -> Vec<((u16, u16), String)> {
+        let border = usize::from(self.title.is_some());
+        let (limit, max_width) = if self.selected.is_some() {
+            (MAX_VISIBLE, MAX_WIDTH)
+        } else {
+            (usize::MAX, usize::MAX)
+        };
+        let room = (bounds.height as usize).saturating_sub(2 * border);
+        let visible = self.lines.len().min(limit).min(room);
+        let title_width = self.title.as_deref().map_or(0, |t| t.width() + 2);
+        let content_width = self
+            .lines
+            .iter()
+            .map(|line| line.width())
+            .max()
+            .unwrap_or(0)
+            .max(title_width)
+            .min(max_width);
+        // 左右各留一个空格，有边框时再加上两边的边框
+        let width = (content_width + 2 + 2 * border).min(bounds.width as usize);
+        let height = visible + 2 * border;
+        let (x, y) = self.position(bounds, width, height);
+
+        let inner = width.saturating_sub(2 + 2 * border);
+        // 选中的行总是可见
+        let first = self.selected.map_or(0, |s| (s + 1).saturating_sub(visible));
+        let mut rows = Vec::with_capacity(height);
+        if let Some(title) = &self.title {
+            let title = utils::fit_width(&format!(" {} ", title), width.saturating_sub(2));
+            let fill = "─".repeat(width.saturating_sub(2 + title.width()));
+            rows.push(format!("{}┌{}{}┐{}", theme.popup, title, fill, color::RESET));
+        }
+        for (i, line) in self.lines.iter().enumerate().skip(first).take(visible) {
+            let style = if self.selected == Some(i) {
+                &theme.popup_selected
+            } else {
+                &theme.popup
+            };
+            let text = utils::fit_width(line, inner);
+            if border == 1 {
+                let edge = format!("{}│{}", theme.popup, color::RESET);
+                rows.push(format!("{}{} {} {}{}", edge, style, text, color::RESET, edge));
+            } else {
+                rows.push(format!("{} {} {}", style, text, color::RESET));
+            }
+        }
+        if border == 1 {
+            let fill = "─".repeat(width.saturating_sub(2));
+            rows.push(format!("{}└{}┘{}", theme.popup, fill, color::RESET));
+        }
+        rows.into_iter()
+            .enumerate()
+            .map(|(i, row)| ((x, y + i as u16), row))
+            .collect()
+    }
+
+    /// 窗口左上角的位置，保证窗口在bounds中
+    fn position(&self, bounds: Rect, width: usize, height: usize) -> (u16, u16) {
+        let right = (bounds.x + bounds.width) as usize;
+        let bottom = (bounds.y + bounds.height) as usize;
+        let (x, y) = match self.anchor {
+            Anchor::Center => (
+                bounds.x as usize + (bounds.width as usize).saturating_sub(width) / 2,
+                bounds.y as usize + (bounds.height as usize).saturating_sub(height) / 2,
+            ),
+            Anchor::Below(x, y) => {
+                let (x, y) = (x as usize, y as usize);
+                if y + 1 + height <= bottom {
+                    (x, y + 1)
+                } else {
+                    (x, y.saturating_sub(height))
+                }
+            }
+        };
+        let x = x.min(right.saturating_sub(width)).max(bounds.x as usize);
+        let y = y.max(bounds.y as usize);
+        (x as u16, y as u16)
+    }
+}
@@ -0,0 +1,79 @@
+use tokio::io::AsyncReadExt;
+
+use super::{Editor, Key, Terminal};
+
+/// 自动补全的成对字符
+const PAIRS: [(char, char); 5] = [('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\'')];
+
+fn closing(open: char) -> Option<char> {
+    PAIRS.iter().find(|(o, _)| *o == open).map(|(_, c)| *c)
+}
+
+fn is_closing(c: char) -> bool {
+    PAIRS.iter().any(|(_, close)| *close == c)
+}
+
+fn is_word(c: Option<char>) -> bool {
+    c.is_some_and(|c| c.is_alphanumeric() || c == '_')
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 光标前后的字符
+    fn chars_around_cursor(&self) -> (Option<char>, Option<char>) {
+        let Some(row) = self.buffer.rows.get(self.buffer.cy as usize) else {
+            return (None, None);
+        };
+        let at = row.get_raw_index(self.buffer.cx as usize);
+        let char_at = |i: usize| match row.key(i) {
+            Some(Key::Char(c)) => Some(c),
+            _ => None,
+        };
+        (at.checked_sub(1).and_then(char_at), char_at(at))
+    }
+
+    /// 输入成对字符的一半时自动补上另一半，返回true表示按键已经处理
+    /// 输入的闭合字符和光标后的字符相同时跳过它，不重复插入
+    /// TODO: 有语法高亮之后，在字符串和注释中不补全
+    pub(super) fn auto_pair(&mut self, key: &Key) -> bool {
+        let Key::Char(c) = *key else {
+            return false;
+        };
+        if !self.config.auto_pairs || self.pasting {
+            return false;
+        }
+        let (before, after) = self.chars_around_cursor();
+        if is_closing(c) && after == Some(c) {
+            self.add_cx();
+            return true;
+        }
+        let Some(close) = closing(c) else {
+            return false;
+        };
+        // 光标后面紧跟着单词时不补全，引号前面是单词时多半是撇号
+        let quote = c == close;
+        if is_word(after) || (quote && is_word(before)) {
+            return false;
+        }
+        self.insert_key(Key::Char(c));
+        let cx = self.buffer.cx;
+        self.insert_key(Key::Char(close));
+        self.buffer.cx = cx;
+        true
+    }
+
+    /// 光标在一对空的成对字符之间时，退格之前先删除闭合字符
+    pub(super) fn delete_empty_pair(&mut self) {
+        if !self.config.auto_pairs {
+            return;
+        }
+        let (Some(open), after) = self.chars_around_cursor() else {
+            return;
+        };
+        if after.is_none() || closing(open) != after {
+            return;
+        }
+        let row = &mut self.buffer.rows[self.buffer.cy as usize];
+        let at = row.get_raw_index(self.buffer.cx as usize);
+        row.replace(at..at + 1, Vec::new());
+    }
+}
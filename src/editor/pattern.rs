@@ -0,0 +1,189 @@
+use std::ops::Range;
+
+use regex::{Captures, Regex};
+
+use super::{Key, Row};
+use crate::utils::find_subsequence;
+
+/// 搜索的内容，普通模式逐个比较key，正则模式在行的文本上匹配
+pub(super) enum Pattern {
+    Plain(Vec<Key>),
+    Regex(Regex),
+}
+
+impl Row {
+    /// text中byte位置的字符在raw中的索引
+    fn raw_index_of_byte(&self, byte: usize) -> usize {
+        self.text[..byte].chars().count()
+    }
+
+    /// text中的字节范围对应的raw范围，正则的匹配项总是在字符边界上
+    fn raw_range_of(&self, bytes: Range<usize>) -> Range<usize> {
+        let start = self.raw_index_of_byte(bytes.start);
+        start..start + self.text[bytes].chars().count()
+    }
+}
+
+/// 捕获组的内容，不存在或者没有参与匹配的组为空
+fn group(caps: &Captures, name: &str) -> Vec<Key> {
+    let m = match name.parse::<usize>() {
+        Ok(i) => caps.get(i),
+        Err(_) => caps.name(name),
+    };
+    m.map_or_else(Vec::new, |m| Row::raw_keys(m.as_str()))
+}
+
+/// 正则的语法错误有多行，消息栏只显示最后一行的错误原因
+pub(super) fn regex_error(e: &regex::Error) -> String {
+    let text = e.to_string();
+    let reason = text.lines().last().unwrap_or_default();
+    format!("Invalid regex: {}", reason.trim_start_matches("error: "))
+}
+
+impl Pattern {
+    pub(super) fn new(query: &[Key], regex: bool) -> Result<Self, regex::Error> {
+        if regex {
+            Regex::new(&Row::raw_str(query)).map(Pattern::Regex)
+        } else {
+            Ok(Pattern::Plain(query.to_vec()))
+        }
+    }
+
+    /// 从raw索引at开始查找，返回匹配项在raw中的范围
+    pub(super) fn find(&self, row: &Row, at: usize) -> Option<Range<usize>> {
+        match self {
+            Pattern::Plain(query) => {
+                if query.is_empty() {
+                    return None;
+                }
+                let start = at + find_subsequence(&row.keys_in(at..row.len()), query)?;
+                Some(start..start + query.len())
+            }
+            Pattern::Regex(regex) => {
+                let m = regex.find_at(row.text(), row.byte_index(at))?;
+                Some(row.raw_range_of(m.range()))
+            }
+        }
+    }
+
+    /// 一行中所有不重叠的匹配项
+    pub(super) fn find_all(&self, row: &Row) -> Vec<Range<usize>> {
+        let mut matches = Vec::new();
+        let mut at = 0;
+        while at <= row.len()
+            && let Some(range) = self.find(row, at)
+        {
+            // 空的匹配项之后至少前进一个字符
+            at = range.end + range.is_empty() as usize;
+            matches.push(range);
+        }
+        matches
+    }
+
+    /// 匹配项替换后的内容
+    /// 正则模式下$1和${name}引用捕获组，$$表示$本身，普通模式原样使用
+    pub(super) fn expand(&self, row: &Row, range: Range<usize>, template: &[Key]) -> Vec<Key> {
+        let Pattern::Regex(regex) = self else {
+            return template.to_vec();
+        };
+        let Some(caps) = regex.captures_at(row.text(), row.byte_index(range.start)) else {
+            return template.to_vec();
+        };
+        let mut keys = Vec::new();
+        let mut i = 0;
+        while i < template.len() {
+            if template[i] != Key::Char('$') {
+                keys.push(template[i].clone());
+                i += 1;
+                continue;
+            }
+            let rest = &template[i + 1..];
+            let digits = rest
+                .iter()
+                .take_while(|k| matches!(k, Key::Char(c) if c.is_ascii_digit()))
+                .count();
+            let close = rest.iter().position(|k| *k == Key::Char('}'));
+            match (rest.first(), close) {
+                (Some(Key::Char('$')), _) => {
+                    keys.push(Key::Char('$'));
+                    i += 2;
+                }
+                (Some(Key::Char('{')), Some(close)) => {
+                    let name = Row::raw_str(&rest[1..close]);
+                    keys.extend(group(&caps, &name));
+                    i += close + 2;
+                }
+                _ if digits > 0 => {
+                    let name = Row::raw_str(&rest[..digits]);
+                    keys.extend(group(&caps, &name));
+                    i += digits + 1;
+                }
+                _ => {
+                    keys.push(Key::Char('$'));
+                    i += 1;
+                }
+            }
+        }
+        keys
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn regex(query: &str) -> Pattern {
+        Pattern::new(&Row::raw_keys(query), true).unwrap()
+    }
+
+    /// 替换第一个匹配项后的文本
+    fn expand(pattern: &Pattern, line: &str, template: &str) -> String {
+        let row = Row::from_text(line, 4);
+        let range = pattern.find(&row, 0).unwrap();
+        Row::raw_str(&pattern.expand(&row, range, &Row::raw_keys(template)))
+    }
+
+    #[test]
+    fn expands_groups_and_dollars() {
+        let pattern = regex(r"(?P<key>\w+)=(\d+)?");
+        assert_eq!(expand(&pattern, "x = a=1", "$2:$1"), "1:a");
+        assert_eq!(expand(&pattern, "a=1", "${key}-${2}"), "a-1");
+        assert_eq!(expand(&pattern, "a=1", "$$1 costs $"), "$1 costs $");
+        // 没有参与匹配的组和不存在的组都是空的
+        assert_eq!(expand(&pattern, "a=", "[$2][$9][${nope}]"), "[][][]");
+        // 没有闭合的花括号按原样保留
+        assert_eq!(expand(&pattern, "a=1", "${key"), "${key");
+        // 普通搜索时模板原样使用
+        let plain = Pattern::new(&Row::raw_keys("a"), false).unwrap();
+        assert_eq!(expand(&plain, "a=1", "$1"), "$1");
+    }
+
+    #[test]
+    fn rejects_invalid_regexes() {
+        for query in ["(", "a{2,1}", "[z-a]", r"\p{Nope}"] {
+            let error = Pattern::new(&Row::raw_keys(query), true).err().unwrap();
+            let message = regex_error(&error);
+            assert!(message.starts_with("Invalid regex: "), "{message}");
+            assert!(!message.contains('\n'), "{message}");
+        }
+        // 普通搜索不解析正则
+        assert!(Pattern::new(&Row::raw_keys("("), false).is_ok());
+    }
+
+    #[test]
+    fn maps_matches_back_to_raw_indices() {
+        // tab和宽字符在渲染后占多列，匹配项的范围仍然是字符的索引
+        let row = Row::from_text("\t中文\tab中b", 4);
+        let pattern = regex("ab|中");
+        assert_eq!(pattern.find_all(&row), [1..2, 4..6, 6..7]);
+        assert_eq!(pattern.find(&row, 2), Some(4..6));
+        assert_eq!(regex(r"\t").find_all(&row), [0..1, 3..4]);
+        assert_eq!(regex("文.a").find(&row, 0), Some(2..5));
+        assert_eq!(row.raw_range_of(row.byte_index(2)..row.byte_index(5)), 2..5);
+        // 空的匹配项每个位置一个，包括行尾
+        assert_eq!(regex("").find_all(&Row::from_text("中a", 4)), [0..0, 1..1, 2..2]);
+        // 普通搜索和正则搜索的结果相同
+        let plain = Pattern::new(&Row::raw_keys("b中"), false).unwrap();
+        assert_eq!(plain.find_all(&row), regex("b中").find_all(&row));
+    }
+}
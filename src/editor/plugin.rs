@@ -0,0 +1,302 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use mlua::{Function, IntoLuaMulti, Lua, Table, Value};
+use tokio::io::AsyncReadExt;
+
+use super::event::Event;
+use super::keymap::{Binding, Mode, Step};
+use super::{Editor, Message, Row, Terminal};
+use crate::config::Config;
+
+/// 插件可以用fim.on注册的事件
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(super) enum Hook {
+    /// 打开文件之后，参数是文件路径
+    Open,
+    /// 写入文件之前，参数是文件路径，这时修改的内容也会被保存
+    BeforeSave,
+    /// 写入文件之后，参数是文件路径
+    AfterSave,
+    /// 光标移动之后，参数是行号和字符下标
+    Cursor,
+    /// 开始或者取消选择之后，参数是normal或者select
+    Mode,
+    /// 按键经过按键映射之前，参数是按键的写法，返回true表示已经处理
+    Key,
+}
+
+impl Hook {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "open" => Some(Self::Open),
+            "before_save" => Some(Self::BeforeSave),
+            "after_save" => Some(Self::AfterSave),
+            "cursor" => Some(Self::Cursor),
+            "mode" => Some(Self::Mode),
+            "key" => Some(Self::Key),
+            _ => None,
+        }
+    }
+}
+
+/// 配置目录的plugins中的Lua插件
+pub(super) struct Plugins {
+    lua: Lua,
+    /// 全局的fim表，插件通过它调用编辑器的功能
+    api: Table,
+    hooks: HashMap<Hook, Vec<Function>>,
+    /// 绑定到按键的函数，Step::Plugin中是这里的下标
+    callbacks: Vec<Function>,
+}
+
+impl Plugins {
+    pub(super) fn new() -> Self {
+        let lua = Lua::new();
+        let api = lua.create_table().expect("failed to create plugin api");
+        lua.globals()
+            .set("fim", &api)
+            .expect("failed to create plugin api");
+        Self {
+            lua,
+            api,
+            hooks: HashMap::new(),
+            callbacks: Vec::new(),
+        }
+    }
+
+    /// 插件目录，每个.lua文件是一个插件
+    fn dir() -> Option<PathBuf> {
+        Some(Config::dir()?.join("plugins"))
+    }
+}
+
+fn parse_mode(mode: Option<String>) -> mlua::Result<Mode> {
+    match mode.as_deref() {
+        None | Some("normal") => Ok(Mode::Normal),
+        Some("select") => Ok(Mode::Select),
+        Some(mode) => Err(mlua::Error::runtime(format!("unknown mode: {}", mode))),
+    }
+}
+
+/// 插件中的行号从1开始
+fn line_index(line: usize) -> mlua::Result<usize> {
+    line.checked_sub(1)
+        .ok_or_else(|| mlua::Error::runtime("line numbers start at 1"))
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 按文件名的顺序加载所有插件，之前注册的事件和按键都会被清除
+    pub(super) fn load_plugins(&mut self) {
+        self.plugins = Plugins::new();
+        let Some(dir) = Plugins::dir() else {
+            return;
+        };
+        let Ok(entries) = fs::read_dir(&dir) else {
+            return;
+        };
+        let mut paths: Vec<_> = entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "lua"))
+            .collect();
+        paths.sort();
+        for path in paths {
+            let name = path.display().to_string();
+            let result = match fs::read_to_string(&path) {
+                Ok(source) => self.with_plugin_api(|lua| lua.load(source).set_name(&name).exec()),
+                Err(e) => Err(mlua::Error::external(e)),
+            };
+            if let Err(e) = result {
+                self.show_message(Message::warning(format!("Error loading {}: {}", name, e)));
+            }
+        }
+    }
+
+    /// 把编辑器事件转发给插件
+    pub(super) fn run_event_hooks(&mut self, event: &Event) {
+        let path = || {
+            let path = self.buffer.current_file.as_ref();
+            path.map(|p| p.display().to_string())
+        };
+        match event {
+            Event::BufferOpened(_) => self.run_hooks(Hook::Open, path()),
+            Event::BeforeSave(_) => self.run_hooks(Hook::BeforeSave, path()),
+            Event::AfterSave(_) => self.run_hooks(Hook::AfterSave, path()),
+            Event::CursorMoved { to: (cy, cx), .. } => {
+                let at = self.buffer.rows.get(*cy).map_or(0, |r| r.get_raw_index(*cx));
+                self.run_hooks(Hook::Cursor, (cy + 1, at))
+            }
+            Event::ModeChanged(Mode::Normal) => self.run_hooks(Hook::Mode, "normal"),
+            Event::ModeChanged(Mode::Select) => self.run_hooks(Hook::Mode, "select"),
+        };
+    }
+
+    /// 依次调用注册了该事件的函数，有函数返回true时返回true
+    pub(super) fn run_hooks(&mut self, hook: Hook, args: impl IntoLuaMulti + Clone) -> bool {
+        let Some(functions) = self.plugins.hooks.get(&hook).cloned() else {
+            return false;
+        };
+        let mut handled = false;
+        let result = self.with_plugin_api(|_| {
+            for function in &functions {
+                handled |= function.call::<Option<bool>>(args.clone())?.unwrap_or(false);
+            }
+            Ok(())
+        });
+        if let Err(e) = result {
+            self.show_message(Message::warning(format!("Plugin error: {}", e)));
+        }
+        handled
+    }
+
+    /// 调用用fim.map绑定到按键的函数
+    pub(super) fn run_plugin_callback(&mut self, index: usize) {
+        let Some(function) = self.plugins.callbacks.get(index).cloned() else {
+            return;
+        };
+        if let Err(e) = self.with_plugin_api(|_| function.call::<()>(())) {
+            self.show_message(Message::warning(format!("Plugin error: {}", e)));
+        }
+    }
+
+    /// 在fim表中放入可以访问编辑器的函数后执行f，f返回后这些函数失效
+    fn with_plugin_api<T>(&mut self, f: impl FnOnce(&Lua) -> mlua::Result<T>) -> mlua::Result<T> {
+        let lua = self.plugins.lua.clone();
+        let api = self.plugins.api.clone();
+        let editor = RefCell::new(&mut *self);
+        lua.scope(|scope| {
+            // fim.on(事件, 函数)
+            api.set(
+                "on",
+                scope.create_function(|_, (event, function): (String, Function)| {
+                    let hook = Hook::from_name(&event)
+                        .ok_or_else(|| mlua::Error::runtime(format!("unknown event: {}", event)))?;
+                    let mut editor = editor.borrow_mut();
+                    editor.plugins.hooks.entry(hook).or_default().push(function);
+                    Ok(())
+                })?,
+            )?;
+            // fim.map(按键, 函数或者操作名称, 模式)
+            api.set(
+                "map",
+                scope.create_function(|_, (keys, target, mode): (String, Value, Option<String>)| {
+                    let mode = parse_mode(mode)?;
+                    let mut editor = editor.borrow_mut();
+                    let keys = editor.keymap.parse_keys(&keys).map_err(mlua::Error::runtime)?;
+                    let binding = match target {
+                        Value::Function(function) => {
+                            editor.plugins.callbacks.push(function);
+                            Binding::from(Step::Plugin(editor.plugins.callbacks.len() - 1))
+                        }
+                        Value::String(step) => Binding::parse(&[step.to_str()?.to_string()])
+                            .map_err(mlua::Error::runtime)?,
+                        Value::Table(steps) => {
+                            let steps = steps.sequence_values::<String>().collect::<mlua::Result<Vec<_>>>()?;
+                            Binding::parse(&steps).map_err(mlua::Error::runtime)?
+                        }
+                        _ => return Err(mlua::Error::runtime("expected a function or action names")),
+                    };
+                    editor.keymap.bind(mode, keys, binding);
+                    Ok(())
+                })?,
+            )?;
+            api.set(
+                "line_count",
+                scope.create_function(|_, ()| Ok(editor.borrow().buffer.rows.len()))?,
+            )?;
+            api.set(
+                "get_line",
+                scope.create_function(|_, line: usize| {
+                    let editor = editor.borrow();
+                    Ok(editor.buffer.rows.get(line_index(line)?).map(|r| r.text().to_string()))
+                })?,
+            )?;
+            api.set(
+                "set_line",
+                scope.create_function(|_, (line, text): (usize, String)| {
+                    let mut editor = editor.borrow_mut();
+                    let index = line_index(line)?;
+                    if index >= editor.buffer.rows.len() || !editor.check_writable() {
+                        return Ok(false);
+                    }
+                    editor.buffer.rows[index] = Row::from_text(&text, editor.config.tab_width);
+                    editor.buffer.mark_dirty();
+                    editor.clamp_cursor_x();
+                    Ok(true)
+                })?,
+            )?;
+            api.set(
+                "insert_line",
+                scope.create_function(|_, (line, text): (usize, String)| {
+                    let mut editor = editor.borrow_mut();
+                    let index = line_index(line)?;
+                    if index > editor.buffer.rows.len() || !editor.check_writable() {
+                        return Ok(false);
+                    }
+                    let row = Row::from_text(&text, editor.config.tab_width);
+                    editor.buffer.rows.insert(index, row);
+                    editor.buffer.mark_dirty();
+                    Ok(true)
+                })?,
+            )?;
+            api.set(
+                "delete_line",
+                scope.create_function(|_, line: usize| {
+                    let mut editor = editor.borrow_mut();
+                    let index = line_index(line)?;
+                    if index >= editor.buffer.rows.len() || !editor.check_writable() {
+                        return Ok(false);
+                    }
+                    editor.buffer.rows.remove(index);
+                    editor.buffer.mark_dirty();
+                    let cy = (editor.buffer.cy as usize).min(editor.buffer.rows.len().saturating_sub(1));
+                    editor.buffer.cy = cy as u16;
+                    editor.clamp_cursor_x();
+                    Ok(true)
+                })?,
+            )?;
+            // 返回行号和行中的字符下标，字符下标从0开始
+            api.set(
+                "get_cursor",
+                scope.create_function(|_, ()| {
+                    let editor = editor.borrow();
+                    let cy = editor.buffer.cy as usize;
+                    let at = editor.buffer.rows.get(cy).map_or(0, |r| r.get_raw_index(editor.buffer.cx as usize));
+                    Ok((cy + 1, at))
+                })?,
+            )?;
+            api.set(
+                "set_cursor",
+                scope.create_function(|_, (line, at): (usize, usize)| {
+                    editor.borrow_mut().set_position((line_index(line)?, at));
+                    Ok(())
+                })?,
+            )?;
+            api.set(
+                "file_name",
+                scope.create_function(|_, ()| {
+                    let editor = editor.borrow();
+                    Ok(editor.buffer.current_file.as_ref().map(|p| p.display().to_string()))
+                })?,
+            )?;
+            api.set(
+                "message",
+                scope.create_function(|_, text: String| {
+                    editor.borrow_mut().show_message(Message::new(text));
+                    Ok(())
+                })?,
+            )?;
+            // 执行F2中可以输入的命令
+            api.set(
+                "command",
+                scope.create_function(|_, command: String| {
+                    editor.borrow_mut().run_command(&command).map_err(mlua::Error::runtime)
+                })?,
+            )?;
+            f(&lua)
+        })
+    }
+}
@@ -0,0 +1,111 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use tokio::io::AsyncReadExt;
+use tracing::warn;
+
+use super::buffer::Buffer;
+use super::{Editor, Terminal};
+use crate::config::Config;
+
+/// 最多记住的文件数量，超出时丢弃最久没有打开的
+const MAX_ENTRIES: usize = 1000;
+
+/// 保存位置的文件名称，放在配置目录下
+const FILE_NAME: &str = "positions";
+
+/// 每个文件上次关闭时光标的位置，(行, raw索引)
+/// 文件中每行一条：行、列和文件的绝对路径，用tab分隔，最近的在最后
+pub(super) struct Positions {
+    entries: Vec<(PathBuf, (usize, usize))>,
+    path: Option<PathBuf>,
+}
+
+/// 用绝对路径区分文件，从不同的目录打开同一个文件时也能找到
+fn absolute(file: &Path) -> PathBuf {
+    std::path::absolute(file).unwrap_or_else(|_| file.to_path_buf())
+}
+
+fn parse_entry(line: &str) -> Option<(PathBuf, (usize, usize))> {
+    let mut parts = line.splitn(3, '\t');
+    let row = parts.next()?.parse().ok()?;
+    let col = parts.next()?.parse().ok()?;
+    Some((PathBuf::from(parts.next()?), (row, col)))
+}
+
+impl Positions {
+    pub(super) fn load() -> Self {
+        let path = Config::dir().map(|dir| dir.join(FILE_NAME));
+        let entries = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|content| content.lines().filter_map(parse_entry).collect())
+            .unwrap_or_default();
+        Self { entries, path }
+    }
+
+    pub(super) fn get(&self, file: &Path) -> Option<(usize, usize)> {
+        let file = absolute(file);
+        self.entries.iter().find(|(f, _)| *f == file).map(|(_, position)| *position)
+    }
+
+    /// 记住buffer中光标的位置，没有对应文件的buffer不需要记住
+    fn remember(&mut self, buffer: &Buffer) {
+        let Some(file) = &buffer.current_file else {
+            return;
+        };
+        let file = absolute(file);
+        let row = buffer.cy as usize;
+        let col = buffer.rows.get(row).map_or(0, |r| r.get_raw_index(buffer.cx as usize));
+        self.entries.retain(|(f, _)| *f != file);
+        self.entries.push((file, (row, col)));
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.remove(0);
+        }
+    }
+
+    fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let content: String = self
+            .entries
+            .iter()
+            .map(|(file, (row, col))| format!("{}\t{}\t{}\n", row, col, file.display()))
+            .collect();
+        let result = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(path, content));
+        if let Err(e) = result {
+            warn!("Failed to save cursor positions to {}: {}", path.display(), e);
+        }
+    }
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 记住关闭的buffer中光标的位置
+    pub(super) fn remember_position(&mut self, buffer: &Buffer) {
+        if self.config.remember_position {
+            self.positions.remember(buffer);
+            self.positions.save();
+        }
+    }
+
+    /// 退出时记住所有buffer中光标的位置
+    pub(super) fn remember_all_positions(&mut self) {
+        if !self.config.remember_position {
+            return;
+        }
+        let buffers = std::iter::once(&self.buffer).chain(self.buffers.iter());
+        for buffer in buffers {
+            self.positions.remember(buffer);
+        }
+        self.positions.save();
+    }
+
+    /// 打开文件时恢复的位置，关闭了该功能时返回None
+    pub(super) fn remembered_position(&self, file: &Path) -> Option<(usize, usize)> {
+        self.config.remember_position.then(|| self.positions.get(file))?
+    }
+}
@@ -0,0 +1,266 @@
+use std::mem;
+
+use regex::Regex;
+use tokio::io::AsyncReadExt;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use super::buffer::Buffer;
+use super::status::file_type;
+use super::view::{Rect, SplitDirection};
+use super::{Editor, Message, Terminal};
+use crate::theme::Theme;
+use crate::utils::{self, color};
+
+/// 块级元素的模式
+struct Patterns {
+    heading: Regex,
+    rule: Regex,
+    quote: Regex,
+    bullet: Regex,
+    ordered: Regex,
+}
+
+impl Patterns {
+    fn new() -> Self {
+        let regex = |p: &str| Regex::new(p).expect("valid regex");
+        Self {
+            heading: regex(r"^(#{1,6})\s+(.*?)\s*#*\s*$"),
+            rule: regex(r"^\s*(?:(?:-\s*){3,}|(?:\*\s*){3,}|(?:_\s*){3,})$"),
+            quote: regex(r"^\s*>\s?(.*)$"),
+            bullet: regex(r"^(\s*)[-*+]\s+(?:\[([ xX])\]\s+)?(.*)$"),
+            ordered: regex(r"^(\s*)(\d+[.)])\s+(.*)$"),
+        }
+    }
+}
+
+/// 一段使用相同样式的文本，样式是转义序列
+struct Span {
+    style: String,
+    text: String,
+}
+
+/// 解析行内的强调、代码和链接，base是整行的样式
+fn inline_spans(text: &str, base: &str, theme: &Theme) -> Vec<Span> {
+    let mut spans = Vec::new();
+    // 粗体、斜体和代码
+    let mut state = [false; 3];
+    let mut current = String::new();
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let prev = i.checked_sub(1).map(|p| chars[p]);
+        let toggled = match c {
+            '`' => Some(2),
+            _ if state[2] => None,
+            '\\' if next.is_some_and(|n| n.is_ascii_punctuation()) => {
+                current.push(chars[i + 1]);
+                i += 2;
+                continue;
+            }
+            '*' | '_' if next == Some(c) => {
+                i += 1;
+                Some(0)
+            }
+            // 单词中间的下划线不是强调，比如snake_case
+            '_' if prev.is_some_and(char::is_alphanumeric) && next.is_some_and(char::is_alphanumeric) => None,
+            '*' | '_' => Some(1),
+            '[' => {
+                // [文本](链接)只显示带下划线的文本
+                let rest: String = chars[i + 1..].iter().collect();
+                if let Some(end) = rest.find("](")
+                    && let Some(close) = rest[end..].find(')')
+                {
+                    let style = span_style(base, state, theme);
+                    spans.push(Span { style, text: mem::take(&mut current) });
+                    let style = format!("{}{}", base, color::UNDERLINE);
+                    spans.push(Span { style, text: rest[..end].to_string() });
+                    i += 1 + rest[..end + close + 1].chars().count();
+                    continue;
+                }
+                None
+            }
+            _ => None,
+        };
+        match toggled {
+            Some(flag) => {
+                let style = span_style(base, state, theme);
+                spans.push(Span { style, text: mem::take(&mut current) });
+                state[flag] = !state[flag];
+            }
+            None => current.push(c),
+        }
+        i += 1;
+    }
+    spans.push(Span { style: span_style(base, state, theme), text: current });
+    spans.retain(|s| !s.text.is_empty());
+    spans
+}
+
+fn span_style(base: &str, [bold, italic, code]: [bool; 3], theme: &Theme) -> String {
+    let mut style = base.to_string();
+    if bold {
+        style.push_str(color::BOLD);
+    }
+    if italic {
+        style.push_str(color::ITALIC);
+    }
+    if code {
+        style.push_str(&theme.string);
+    }
+    style
+}
+
+/// 把piece用style包起来加到line后面
+fn push_piece(line: &mut String, style: &str, piece: &mut String) {
+    if piece.is_empty() {
+        return;
+    }
+    if style.is_empty() {
+        line.push_str(piece);
+    } else {
+        line.push_str(&format!("{}{}{}", style, piece, color::RESET));
+    }
+    piece.clear();
+}
+
+/// 按单词把spans折成不超过width列的行，第一行以prefix开头，后面的行缩进到prefix之后
+fn wrap_spans(prefix: &str, prefix_width: usize, spans: &[Span], width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = prefix.to_string();
+    let mut used = prefix_width;
+    let indent = prefix_width.min(width / 2);
+    for span in spans {
+        let mut piece = String::new();
+        for word in span.text.split_inclusive(' ') {
+            let fits = |used: usize, w: usize| used + w <= width || used <= indent;
+            if !fits(used, word.width()) {
+                push_piece(&mut line, &span.style, &mut piece);
+                lines.push(mem::replace(&mut line, " ".repeat(indent)));
+                used = indent;
+            }
+            // 比一行还长的单词直接截断成多行
+            for c in word.chars() {
+                let w = c.width().unwrap_or(0);
+                if !fits(used, w) {
+                    push_piece(&mut line, &span.style, &mut piece);
+                    lines.push(mem::replace(&mut line, " ".repeat(indent)));
+                    used = indent;
+                }
+                piece.push(c);
+                used += w;
+            }
+        }
+        push_piece(&mut line, &span.style, &mut piece);
+    }
+    lines.push(line);
+    lines
+}
+
+/// 把markdown渲染成带颜色的行，每一行附带对应的源文件行号
+fn render_markdown(rows: impl Iterator<Item = String>, width: usize, theme: &Theme) -> Vec<(usize, String)> {
+    let patterns = Patterns::new();
+    let mut lines = Vec::new();
+    // 正在代码块中时是代码块开始的标记
+    let mut fence: Option<&str> = None;
+    for (i, text) in rows.enumerate() {
+        let trimmed = text.trim_start();
+        let marker = ["```", "~~~"].into_iter().find(|m| trimmed.starts_with(m));
+        if let Some(marker) = marker
+            && fence.is_none_or(|f| f == marker)
+        {
+            fence = if fence.is_some() { None } else { Some(marker) };
+            continue;
+        }
+        if fence.is_some() {
+            let code = utils::fit_width(&text.replace('\t', "    "), width.saturating_sub(2));
+            lines.push((i, format!("  {}{}{}", theme.string, code.trim_end(), color::RESET)));
+            continue;
+        }
+        let rendered = if let Some(caps) = patterns.heading.captures(&text) {
+            let mut style = format!("{}{}", color::BOLD, theme.keyword);
+            if caps[1].len() == 1 {
+                style.push_str(color::UNDERLINE);
+            }
+            wrap_spans("", 0, &inline_spans(&caps[2], &style, theme), width)
+        } else if patterns.rule.is_match(&text) {
+            vec![format!("{}{}{}", theme.separator, "─".repeat(width), color::RESET)]
+        } else if let Some(caps) = patterns.quote.captures(&text) {
+            let prefix = format!("{}│{} ", theme.comment, color::RESET);
+            let base = format!("{}{}", theme.comment, color::ITALIC);
+            wrap_spans(&prefix, 2, &inline_spans(&caps[1], &base, theme), width)
+        } else if let Some(caps) = patterns.bullet.captures(&text) {
+            let bullet = match caps.get(2).map(|m| m.as_str()) {
+                Some(" ") => "☐",
+                Some(_) => "☑",
+                None => "•",
+            };
+            let prefix = format!("{}{} ", &caps[1], bullet);
+            wrap_spans(&prefix, prefix.width(), &inline_spans(&caps[3], "", theme), width)
+        } else if let Some(caps) = patterns.ordered.captures(&text) {
+            let prefix = format!("{}{} ", &caps[1], &caps[2]);
+            wrap_spans(&prefix, prefix.width(), &inline_spans(&caps[3], "", theme), width)
+        } else {
+            wrap_spans("", 0, &inline_spans(text.trim(), "", theme), width)
+        };
+        lines.extend(rendered.into_iter().map(|line| (i, line)));
+    }
+    lines
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 在右边打开或者关闭当前markdown buffer的预览
+    pub(super) fn toggle_preview(&mut self) {
+        let id = self.buffer.id;
+        if let Some(index) = self.views.iter().position(|v| v.preview && v.buffer_id == id) {
+            self.remove_view(index);
+            self.show_message(Message::new("Preview closed".to_string()));
+            return;
+        }
+        if self.buffer.current_file.as_deref().and_then(file_type) != Some("Markdown") {
+            self.show_message(Message::new("Preview is only available for Markdown".to_string()));
+            return;
+        }
+        if !self.split_preview(SplitDirection::Vertical) {
+            self.show_message(Message::new("Not enough room to split".to_string()));
+        }
+    }
+
+    /// 预览窗口，和源buffer的第一行显示的位置同步
+    pub(super) fn render_preview(&self, buffer: &Buffer, rect: Rect) -> Vec<String> {
+        let width = rect.width as usize;
+        let height = rect.height.saturating_sub(1) as usize;
+        let row_offset = if buffer.id == self.buffer.id {
+            self.buffer.row_offset
+        } else {
+            buffer.row_offset
+        };
+        let rendered = render_markdown(buffer.rows.iter().map(|r| r.text().to_string()), width, &self.theme);
+        let start = rendered
+            .iter()
+            .position(|(source, _)| *source >= row_offset)
+            .unwrap_or(rendered.len());
+        // 文档末尾时尽量填满窗口
+        let start = start.min(rendered.len().saturating_sub(height));
+        let mut lines: Vec<String> = rendered[start..]
+            .iter()
+            .take(height)
+            .map(|(_, line)| {
+                let padding = width.saturating_sub(utils::visible_width(line));
+                format!("{}{}", line, " ".repeat(padding))
+            })
+            .collect();
+        while lines.len() < height {
+            lines.push(format!("~{}", " ".repeat(width.saturating_sub(1))));
+        }
+        let title = format!("Preview: {}", buffer.name());
+        lines.push(format!(
+            "{}{}{}",
+            self.theme.inactive_status_bar,
+            utils::fit_width(&title, width),
+            color::RESET
+        ));
+        lines
+    }
+}
@@ -0,0 +1,289 @@
+use std::fs;
+use std::path::Path;
+
+use tokio::io::AsyncReadExt;
+use unicode_width::UnicodeWidthStr;
+
+use super::history::HistoryKind;
+use super::{ControlKey, Direction, Editor, Key, Message, Modifiers, Row, Terminal};
+
+/// 按Tab时根据当前输入返回候选，每个候选替换整个输入
+pub(super) type Completer = fn(&str) -> Vec<String>;
+
+/// 补全文件路径，目录的候选以/结尾
+pub(super) fn complete_path(input: &str) -> Vec<String> {
+    let (dir, prefix) = match input.rfind('/') {
+        Some(i) => (&input[..=i], &input[i + 1..]),
+        None => ("", input),
+    };
+    let Ok(entries) = fs::read_dir(if dir.is_empty() { Path::new(".") } else { Path::new(dir) }) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            // 输入.开头时才补全隐藏文件
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let slash = if entry.path().is_dir() { "/" } else { "" };
+            Some(format!("{}{}{}", dir, name, slash))
+        })
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// 消息栏中的一行输入，光标可以在输入中移动，按键和readline相同
+pub(super) struct Prompt {
+    input: Row,
+    /// 光标在input.raw中的位置
+    cursor: usize,
+    history: Option<HistoryKind>,
+    /// 正在浏览的历史记录，等于历史记录的长度时是正在编辑的输入
+    index: usize,
+    draft: Vec<Key>,
+    completer: Option<Completer>,
+    /// 连续按Tab时依次使用的候选和下一个候选的位置
+    completions: Option<(Vec<String>, usize)>,
+}
+
+impl Prompt {
+    pub(super) fn new(tab_width: usize) -> Self {
+        Self {
+            input: Row::new(Vec::new(), tab_width),
+            cursor: 0,
+            history: None,
+            index: 0,
+            draft: Vec::new(),
+            completer: None,
+            completions: None,
+        }
+    }
+
+    pub(super) fn with_history(mut self, kind: HistoryKind) -> Self {
+        self.history = Some(kind);
+        self
+    }
+
+    pub(super) fn with_completer(mut self, completer: Completer) -> Self {
+        self.completer = Some(completer);
+        self
+    }
+
+    fn set_input(&mut self, raw: Vec<Key>) {
+        self.input = Row::new(raw, self.input.tab_width);
+        self.cursor = self.input.len();
+    }
+
+    /// 光标前一个字素簇的开头
+    fn previous(&self) -> usize {
+        let rendered = self.input.render();
+        let cells = rendered.cells.iter().rev();
+        cells.map(|c| c.raw).find(|&raw| raw < self.cursor).unwrap_or(0)
+    }
+
+    /// 光标后一个字素簇的开头
+    fn next(&self) -> usize {
+        let rendered = self.input.render();
+        let cells = rendered.cells.iter();
+        cells.map(|c| c.raw).find(|&raw| raw > self.cursor).unwrap_or(self.input.len())
+    }
+
+    fn delete(&mut self, start: usize, end: usize) {
+        self.input.replace(start..end, Vec::new());
+        self.cursor = start;
+    }
+
+    /// 处理移动光标和编辑的按键，忽略其他按键
+    /// Ctrl+A/E或Home/End移到开头和结尾，Ctrl+B/F或左右键移动一个字符，Ctrl+左右键或Alt+B/F移动一个单词
+    /// Ctrl+U删除光标之前的内容，Ctrl+K删除光标之后的内容，Ctrl+W删除前一个单词
+    fn edit(&mut self, key: &Key) {
+        let ctrl = Modifiers {
+            ctrl: true,
+            ..Default::default()
+        };
+        let len = self.input.len();
+        match key {
+            Key::ControlKey(ControlKey::Home | ControlKey::Ctrl('a')) => self.cursor = 0,
+            Key::ControlKey(ControlKey::End | ControlKey::Ctrl('e')) => self.cursor = len,
+            Key::ArrowKey(Direction::Left) | Key::ControlKey(ControlKey::Ctrl('b')) => self.cursor = self.previous(),
+            Key::ArrowKey(Direction::Right) | Key::ControlKey(ControlKey::Ctrl('f')) => self.cursor = self.next(),
+            Key::ControlKey(ControlKey::Alt('b')) => self.cursor = self.input.word_start_before(self.cursor),
+            Key::ControlKey(ControlKey::Alt('f')) => self.cursor = self.input.word_end_after(self.cursor),
+            Key::Modified(modifiers, key) if *modifiers == ctrl => match **key {
+                Key::ArrowKey(Direction::Left) => self.cursor = self.input.word_start_before(self.cursor),
+                Key::ArrowKey(Direction::Right) => self.cursor = self.input.word_end_after(self.cursor),
+                _ => {}
+            },
+            Key::ControlKey(ControlKey::Backspace) => self.delete(self.previous(), self.cursor),
+            Key::ControlKey(ControlKey::Delete) => self.delete(self.cursor, self.next()),
+            Key::ControlKey(ControlKey::Ctrl('u')) => self.delete(0, self.cursor),
+            Key::ControlKey(ControlKey::Ctrl('k')) => self.delete(self.cursor, len),
+            Key::ControlKey(ControlKey::Ctrl('w')) => self.delete(self.input.word_start_before(self.cursor), self.cursor),
+            Key::Text(text) => {
+                let keys: Vec<Key> = text.chars().map(Key::Char).collect();
+                let count = keys.len();
+                self.input.replace(self.cursor..self.cursor, keys);
+                self.cursor += count;
+            }
+            key if key.get_display_width(self.input.tab_width) > 0 => {
+                self.input.replace(self.cursor..self.cursor, vec![key.clone()]);
+                self.cursor += 1;
+            }
+            _ => {}
+        }
+    }
+
+    /// 在宽度为width的消息栏中显示prefix和输入，输入太长时向左滚动让光标可见
+    /// 返回显示的内容和光标所在的列
+    fn render(&self, prefix: &str, width: usize) -> (String, u16) {
+        let prefix_width = prefix.width();
+        let rendered = self.input.render();
+        let column = rendered.column_of(self.cursor);
+        let room = width.saturating_sub(prefix_width + 1);
+        let offset = column.saturating_sub(room);
+        let (start, skipped) = rendered
+            .cells
+            .iter()
+            .find(|c| c.col >= offset)
+            .map_or((rendered.text.len(), column), |c| (c.text.start, c.col));
+        let text = format!("{}{}", prefix, &rendered.text[start..]);
+        let x = (prefix_width + column - skipped).min(width.saturating_sub(1));
+        (text, x as u16)
+    }
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 在消息栏中读取用户输入
+    /// 每次按键后都会调用callback，传入当前输入和按下的键，可用于增量搜索
+    /// 回车确认输入，ESC取消输入并返回None
+    pub(super) async fn prompt(
+        &mut self,
+        prompt: &str,
+        callback: Option<fn(&mut Self, &[Key], &Key)>,
+    ) -> Option<Vec<Key>> {
+        let input = Prompt::new(self.config.tab_width);
+        self.read_prompt(prompt, input, callback).await
+    }
+
+    /// 和prompt相同，上下键浏览历史记录，确认的输入加入历史记录
+    pub(super) async fn prompt_with_history(
+        &mut self,
+        prompt: &str,
+        callback: Option<fn(&mut Self, &[Key], &Key)>,
+        history: HistoryKind,
+    ) -> Option<Vec<Key>> {
+        let input = Prompt::new(self.config.tab_width).with_history(history);
+        self.read_prompt(prompt, input, callback).await
+    }
+
+    /// 和prompt相同，按Tab依次使用completer返回的候选
+    pub(super) async fn prompt_with_completion(&mut self, prompt: &str, completer: Completer) -> Option<Vec<Key>> {
+        let input = Prompt::new(self.config.tab_width).with_completer(completer);
+        self.read_prompt(prompt, input, None).await
+    }
+
+    pub(super) async fn read_prompt(
+        &mut self,
+        prompt: &str,
+        mut input: Prompt,
+        callback: Option<fn(&mut Self, &[Key], &Key)>,
+    ) -> Option<Vec<Key>> {
+        input.index = input.history.map_or(0, |kind| self.history_mut(kind).len());
+        let result = loop {
+            let flags = match input.history {
+                Some(HistoryKind::Search | HistoryKind::Grep) => self.search_flags(),
+                _ => "",
+            };
+            let (text, x) = input.render(&format!("{}{}", flags, prompt), self.screen_cols as usize);
+            self.message = Some(Message::new(text));
+            self.prompt_cursor = Some(x);
+            if let Err(e) = self.refresh_screen() {
+                self.show_message(Message::error(format!("Error refreshing screen: {}", e), &e));
+                break None;
+            }
+            let key = match self.get_key().await {
+                Ok(key) => key,
+                Err(e) => {
+                    self.show_message(Message::error(format!("Error reading Key: {}", e), &e));
+                    break None;
+                }
+            };
+            match key {
+                Key::ControlKey(ControlKey::Escape) => {
+                    self.message = None;
+                    if let Some(callback) = callback {
+                        callback(self, &input.input.keys(), &key);
+                    }
+                    break None;
+                }
+                Key::ControlKey(ControlKey::CR) => {
+                    self.message = None;
+                    if let Some(callback) = callback {
+                        callback(self, &input.input.keys(), &key);
+                    }
+                    if let Some(kind) = input.history {
+                        self.history_mut(kind).push(input.input.text().to_string());
+                    }
+                    break Some(input.input.keys());
+                }
+                Key::ControlKey(ControlKey::Tab) if input.completer.is_some() => self.complete_prompt(&mut input),
+                Key::ArrowKey(ref direction @ (Direction::Up | Direction::Down)) => {
+                    self.browse_history(&mut input, *direction == Direction::Up);
+                }
+                ref key => input.edit(key),
+            }
+            if key != Key::ControlKey(ControlKey::Tab) {
+                input.completions = None;
+            }
+            if let Some(callback) = callback {
+                callback(self, &input.input.keys(), &key);
+            }
+        };
+        self.prompt_cursor = None;
+        result
+    }
+
+    /// 切换到上一条或者下一条历史记录
+    fn browse_history(&mut self, input: &mut Prompt, up: bool) {
+        let Some(history) = input.history.map(|kind| self.history_mut(kind)) else {
+            return;
+        };
+        let len = history.len();
+        let next = if up {
+            input.index.checked_sub(1)
+        } else {
+            (input.index < len).then_some(input.index + 1)
+        };
+        let Some(next) = next else {
+            return;
+        };
+        // 离开正在编辑的输入时先保存下来，回来时恢复
+        if input.index == len {
+            input.draft = input.input.keys();
+        }
+        let raw = match history.get(next) {
+            Some(entry) => Row::raw_keys(entry),
+            None => input.draft.clone(),
+        };
+        input.set_input(raw);
+        input.index = next;
+    }
+
+    /// 第一次按Tab时计算候选，只有一个候选时直接使用，之后每次按Tab换成下一个候选
+    fn complete_prompt(&mut self, input: &mut Prompt) {
+        let Some(completer) = input.completer else {
+            return;
+        };
+        let (candidates, next) = input.completions.get_or_insert_with(|| (completer(input.input.text()), 0));
+        if candidates.is_empty() {
+            return;
+        }
+        let candidate = candidates[*next % candidates.len()].clone();
+        *next += 1;
+        let raw = Row::raw_keys(&candidate);
+        input.set_input(raw);
+    }
+}
@@ -0,0 +1,111 @@
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use regex::Regex;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::grep::{GrepEvent, GrepMatch, ResultSource, ResultsPane};
+use super::{Editor, Message, Terminal};
+
+/// 编译输出中的位置，比如 src/main.rs:10:5: error: ...
+/// 或者rustc的 --> src/main.rs:10:5，这时消息在前面的error或warning行中
+struct OutputParser {
+    location: Regex,
+    arrow: Regex,
+    /// 最近一行以error或warning开头的消息
+    last_message: Option<String>,
+}
+
+impl OutputParser {
+    fn new() -> Self {
+        Self {
+            location: Regex::new(r"^([^:\s][^:]*):(\d+):(?:(\d+):)?\s*(.*)$").expect("valid regex"),
+            arrow: Regex::new(r"^\s*-->\s*([^:]+):(\d+):(\d+)").expect("valid regex"),
+            last_message: None,
+        }
+    }
+
+    /// 解析一行输出，文件不存在的位置不算，避免把URL之类的内容当作位置
+    fn parse(&mut self, line: &str) -> Option<GrepMatch> {
+        if line.starts_with("error") || line.starts_with("warning") {
+            self.last_message = Some(line.to_string());
+        }
+        let (path, line_number, column, text) = if let Some(caps) = self.arrow.captures(line) {
+            let text = self.last_message.clone().unwrap_or_default();
+            (caps[1].to_string(), caps.get(2)?.as_str(), caps.get(3), text)
+        } else {
+            let caps = self.location.captures(line)?;
+            (caps[1].to_string(), caps.get(2)?.as_str(), caps.get(3), caps[4].to_string())
+        };
+        if !Path::new(&path).is_file() {
+            return None;
+        }
+        let line_number: usize = line_number.parse().ok().filter(|n| *n > 0)?;
+        let column = column.and_then(|c| c.as_str().parse::<usize>().ok()).unwrap_or(1);
+        Some(GrepMatch::new(
+            PathBuf::from(path),
+            line_number,
+            column.saturating_sub(1),
+            text,
+        ))
+    }
+}
+
+/// 在shell中执行编译命令，逐行解析输出中的位置
+async fn run_make(id: usize, command: String, sender: UnboundedSender<GrepEvent>, cancel: Arc<AtomicBool>) {
+    let shell = std::env::var("SHELL").unwrap_or_else(|_| "sh".to_string());
+    let child = tokio::process::Command::new(shell)
+        .arg("-c")
+        .arg(format!("exec 2>&1\n{}", command))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            let _ = sender.send(GrepEvent::Failed(id, e.to_string()));
+            return;
+        }
+    };
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let mut lines = BufReader::new(stdout).lines();
+    let mut parser = OutputParser::new();
+    // 关闭列表后停止读取，child被drop时命令也会被杀掉
+    while let Ok(Some(line)) = lines.next_line().await {
+        if cancel.load(Ordering::Relaxed) {
+            return;
+        }
+        if let Some(item) = parser.parse(&line)
+            && sender.send(GrepEvent::Match(id, item)).is_err()
+        {
+            return;
+        }
+    }
+    let _ = child.wait().await;
+    let _ = sender.send(GrepEvent::Done(id, false));
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 执行配置的make_command，参数加在命令后面，输出中的位置显示在结果列表中
+    pub(super) fn make(&mut self, args: &[&str]) {
+        let mut command = self.config.make_command.clone();
+        for arg in args {
+            command.push(' ');
+            command.push_str(arg);
+        }
+        if command.trim().is_empty() {
+            self.show_message(Message::warning("make_command is empty".to_string()));
+            return;
+        }
+        self.grep_id += 1;
+        let cancel = Arc::new(AtomicBool::new(false));
+        let pane = ResultsPane::new(self.grep_id, ResultSource::Make, command.clone(), cancel.clone());
+        self.results = Some(pane);
+        let (id, sender) = (self.grep_id, self.grep_sender.clone());
+        tokio::spawn(run_make(id, command, sender, cancel));
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::BTreeMap;
+
+use tokio::io::AsyncReadExt;
+use tokio::process::Command;
+
+use super::selection::{Clipboard, SelectionKind};
+use super::{Editor, Key, Message, Row, Terminal};
+
+/// 系统剪贴板对应的寄存器
+pub(super) const CLIPBOARD_REGISTER: char = '+';
+
+/// 寄存器列表buffer的名称
+const REGISTERS_TITLE: &str = "[Registers]";
+
+/// 读取系统剪贴板的命令，按顺序尝试
+const PASTE_COMMANDS: [&[&str]; 4] = [
+    &["pbpaste"],
+    &["wl-paste", "--no-newline"],
+    &["xclip", "-selection", "clipboard", "-o"],
+    &["xsel", "--clipboard", "--output"],
+];
+
+/// 复制和删除的内容
+/// 默认寄存器总是保存最近一次的内容，选择了寄存器时同时保存到该寄存器
+#[derive(Default)]
+pub(super) struct Registers {
+    unnamed: Option<Clipboard>,
+    /// a-z和系统剪贴板
+    named: BTreeMap<char, Clipboard>,
+    /// 用"选择的寄存器，只对下一次复制、删除或粘贴有效
+    selected: Option<char>,
+}
+
+impl Registers {
+    pub(super) fn is_register(c: char) -> bool {
+        c.is_ascii_lowercase() || c == CLIPBOARD_REGISTER
+    }
+
+    /// 保存复制的内容，返回保存到的寄存器
+    pub(super) fn store(&mut self, clipboard: Clipboard) -> Option<char> {
+        let register = self.selected.take();
+        if let Some(register) = register {
+            self.named.insert(register, clipboard.clone());
+        }
+        self.unnamed = Some(clipboard);
+        register
+    }
+
+    /// 选择的寄存器中的内容，没有选择时使用默认寄存器
+    pub(super) fn get(&self) -> Option<&Clipboard> {
+        match self.selected {
+            Some(register) => self.named.get(&register),
+            None => self.unnamed.as_ref(),
+        }
+    }
+
+    /// 粘贴之后清除选择的寄存器
+    pub(super) fn take(&mut self) -> Option<Clipboard> {
+        match self.selected.take() {
+            Some(register) => self.named.get(&register).cloned(),
+            None => self.unnamed.clone(),
+        }
+    }
+}
+
+impl Clipboard {
+    fn kind_label(&self) -> &'static str {
+        match self.kind {
+            SelectionKind::Char => "char",
+            SelectionKind::Line => "line",
+            SelectionKind::Block => "block",
+        }
+    }
+
+    /// 换行符连接的文本，按行复制的内容以换行符结尾
+    fn text(&self) -> String {
+        let lines: Vec<String> = self.lines.iter().map(|l| Row::raw_str(l)).collect();
+        let mut text = lines.join("\n");
+        if self.kind == SelectionKind::Line {
+            text.push('\n');
+        }
+        text
+    }
+
+    /// 从系统剪贴板中的文本生成，以换行符结尾时按行粘贴
+    fn from_text(text: &str) -> Self {
+        let (kind, text) = match text.strip_suffix('\n') {
+            Some(text) => (SelectionKind::Line, text),
+            None => (SelectionKind::Char, text),
+        };
+        let text = text.replace("\r\n", "\n");
+        let lines = text.split('\n').map(Row::raw_keys).collect();
+        Self { kind, lines }
+    }
+}
+
+/// 依次尝试读取系统剪贴板的命令，都失败时返回None
+async fn read_system_clipboard() -> Option<String> {
+    for command in PASTE_COMMANDS {
+        let Ok(output) = Command::new(command[0]).args(&command[1..]).output().await else {
+            continue;
+        };
+        if output.status.success() {
+            return Some(String::from_utf8_lossy(&output.stdout).into_owned());
+        }
+    }
+    None
+}
+
+/// 用OSC 52转义序列让终端设置系统剪贴板，通过ssh连接时也有效
+fn osc52(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", base64(text.as_bytes()))
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, b)| n | ((*b as u32) << (16 - 8 * i)));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(TABLE[((n >> (18 - 6 * i)) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 选择下一次复制、删除或粘贴使用的寄存器
+    pub(super) async fn select_register(&mut self) -> bool {
+        self.message = Some(Message::new("Register: a-z, + clipboard".to_string()));
+        if self.refresh_screen().is_err() {
+            return false;
+        }
+        let key = self.get_key().await;
+        self.message = None;
+        match key {
+            Ok(Key::Char(c)) if Registers::is_register(c) => {
+                self.registers.selected = Some(c);
+                self.show_message(Message::new(format!("\"{}", c)));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// 保存复制的内容，保存到系统剪贴板寄存器时同时设置系统剪贴板
+    pub(super) fn store_register(&mut self, clipboard: Clipboard) {
+        let text = clipboard.text();
+        if self.registers.store(clipboard) == Some(CLIPBOARD_REGISTER) {
+            let _ = self.screen.lock().write_styled(&osc52(&text));
+        }
+    }
+
+    /// 选择了系统剪贴板寄存器时先读取系统剪贴板
+    /// 读取失败时使用上一次复制到该寄存器的内容
+    pub(super) async fn load_clipboard_register(&mut self) {
+        if self.registers.selected != Some(CLIPBOARD_REGISTER) {
+            return;
+        }
+        if let Some(text) = read_system_clipboard().await {
+            let clipboard = Clipboard::from_text(&text);
+            self.registers.named.insert(CLIPBOARD_REGISTER, clipboard);
+        }
+    }
+
+    /// 询问寄存器并粘贴其中的内容，?列出所有寄存器
+    pub(super) async fn register_command(&mut self) {
+        self.message = Some(Message::new(
+            "Paste from register: a-z, + clipboard, \" default, ? list".to_string(),
+        ));
+        if self.refresh_screen().is_err() {
+            return;
+        }
+        let Ok(key) = self.get_key().await else {
+            return;
+        };
+        self.message = None;
+        match key {
+            Key::Char('?') => self.show_registers(),
+            Key::Char('"') => self.paste().await,
+            Key::Char(c) if Registers::is_register(c) => {
+                self.registers.selected = Some(c);
+                self.paste().await;
+            }
+            _ => {}
+        }
+    }
+
+    /// 在只读的buffer中列出所有寄存器和宏的内容
+    pub(super) fn show_registers(&mut self) {
+        let registers = &self.registers;
+        let unnamed = registers.unnamed.as_ref().map(|c| ('"', c));
+        let mut lines: Vec<String> = unnamed
+            .into_iter()
+            .chain(registers.named.iter().map(|(r, c)| (*r, c)))
+            .map(|(register, clipboard)| {
+                let text = clipboard.text().replace('\n', "^J");
+                format!("\"{}  {:<5}  {}", register, clipboard.kind_label(), text)
+            })
+            .collect();
+        lines.extend(self.macros.iter().map(|(register, keys)| {
+            let keys: String = keys.iter().map(Key::notation).collect();
+            format!("@{}  macro  {}", register, keys)
+        }));
+        let rows = lines
+            .iter()
+            .map(|l| Row::from_text(l, self.config.tab_width))
+            .collect();
+        self.show_list(REGISTERS_TITLE, rows);
+    }
+}
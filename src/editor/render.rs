@@ -0,0 +1,358 @@
+use tokio::io::AsyncReadExt;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use super::buffer::{self, Buffer};
+use super::screen::Frame;
+use super::view::Rect;
+use super::{Editor, LineNumbers, Terminal, brackets};
+use crate::error::Result;
+use crate::utils::{self, color};
+
+/// 屏幕上的一个单元格，宽字符后面被占用的单元格内容为空
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cell {
+    symbol: String,
+    /// 显示这个单元格之前需要输出的SGR转义序列，为空时是默认样式
+    style: String,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            symbol: " ".to_string(),
+            style: String::new(),
+        }
+    }
+}
+
+/// 一帧中屏幕所有单元格的内容，按行排列
+/// 渲染时把每一段带样式的文本写入网格，输出时和上一帧逐个单元格比较
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Grid {
+    cols: u16,
+    rows: u16,
+    cells: Vec<Cell>,
+}
+
+impl Grid {
+    pub(super) fn new(cols: u16, rows: u16) -> Self {
+        Self {
+            cols,
+            rows,
+            cells: vec![Cell::default(); cols as usize * rows as usize],
+        }
+    }
+
+    pub(super) fn size(&self) -> (u16, u16) {
+        (self.cols, self.rows)
+    }
+
+    /// 从(x, y)开始写入文本，后写入的内容覆盖之前的内容，超出屏幕的部分被截断
+    /// 文本中可以包含设置样式的SGR转义序列，重置样式的序列之前的样式都被清除
+    pub(super) fn put_styled(&mut self, x: u16, y: u16, text: &str) {
+        if y >= self.rows {
+            return;
+        }
+        let mut x = x as usize;
+        let mut style = String::new();
+        let mut rest = text;
+        while !rest.is_empty() {
+            let (plain, sequence) = match rest.find('\x1b') {
+                Some(i) => {
+                    // 转义序列以字母结束
+                    let end = rest[i + 1..]
+                        .find(|c: char| c.is_ascii_alphabetic())
+                        .map_or(rest.len(), |j| i + j + 2);
+                    (&rest[..i], &rest[i..end])
+                }
+                None => (rest, ""),
+            };
+            rest = &rest[plain.len() + sequence.len()..];
+            for grapheme in plain.graphemes(true) {
+                x = self.put_grapheme(x, y as usize, grapheme, &style);
+            }
+            match sequence {
+                "" => {}
+                color::RESET | "\x1b[m" => style.clear(),
+                _ if sequence.ends_with('m') => style.push_str(sequence),
+                // 其他转义序列不影响单元格的内容
+                _ => {}
+            }
+        }
+    }
+
+    /// 在第x列写入一个字素簇，返回下一个字素簇所在的列
+    fn put_grapheme(&mut self, x: usize, y: usize, grapheme: &str, style: &str) -> usize {
+        let cols = self.cols as usize;
+        let width = grapheme.width();
+        // 组合字符等零宽度的内容附加到前一个单元格中
+        if width == 0 {
+            if x > 0 && x <= cols {
+                self.cells[y * cols + x - 1].symbol.push_str(grapheme);
+            }
+            return x;
+        }
+        if x + width > cols {
+            // 放不下的宽字符用空格代替，避免终端自动换行
+            for column in x..cols {
+                self.cells[y * cols + column] = Cell {
+                    symbol: " ".to_string(),
+                    style: style.to_string(),
+                };
+            }
+            return cols;
+        }
+        self.cells[y * cols + x] = Cell {
+            symbol: grapheme.to_string(),
+            style: style.to_string(),
+        };
+        for column in x + 1..x + width {
+            self.cells[y * cols + column] = Cell {
+                symbol: String::new(),
+                style: style.to_string(),
+            };
+        }
+        x + width
+    }
+
+    /// 和上一帧比较，返回每一段变化的单元格和它在屏幕上的起始位置
+    /// 每一段都以默认样式结束，所以段与段之间不会互相影响
+    pub(super) fn diff(&self, previous: &Grid) -> Vec<((u16, u16), String)> {
+        let cols = self.cols as usize;
+        let mut changes = Vec::new();
+        for y in 0..self.rows as usize {
+            let row = &self.cells[y * cols..(y + 1) * cols];
+            let old = &previous.cells[y * cols..(y + 1) * cols];
+            let mut x = 0;
+            while x < cols {
+                if row[x] == old[x] {
+                    x += 1;
+                    continue;
+                }
+                // 从宽字符的开头开始输出
+                let mut start = x;
+                while start > 0 && row[start].symbol.is_empty() {
+                    start -= 1;
+                }
+                let mut end = x + 1;
+                while end < cols && (row[end] != old[end] || row[end].symbol.is_empty()) {
+                    end += 1;
+                }
+                changes.push(((start as u16, y as u16), render_cells(&row[start..end])));
+                x = end;
+            }
+        }
+        changes
+    }
+}
+
+/// 把连续的单元格转换成带样式的文本
+fn render_cells(cells: &[Cell]) -> String {
+    let mut text = String::new();
+    let mut style = "";
+    for cell in cells {
+        if cell.style != style {
+            if !style.is_empty() {
+                text.push_str(color::RESET);
+            }
+            text.push_str(&cell.style);
+            style = &cell.style;
+        }
+        text.push_str(&cell.symbol);
+    }
+    if !style.is_empty() {
+        text.push_str(color::RESET);
+    }
+    text
+}
+
+/// 空白buffer中在版本号下面显示的提示
+const WELCOME_HINT: &str = "F1 for help, fim --tutor to learn the basics";
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 生成一帧交给后台的绘制任务，不等待输出完成
+    pub fn refresh_screen(&mut self) -> Result<()> {
+        (self.screen_cols, self.screen_rows) = self.screen.size();
+
+        self.fix_views();
+        let rects = self.view_rects();
+        let (_, rect) = self.focused_rect();
+
+        // 窗口大小和行号栏的宽度都会变化，每次刷新时重新计算当前窗口文本区域的大小
+        // 并修正偏移量保证光标在屏幕内
+        let gutter = self.gutter_width(&self.buffer);
+        self.max_col = rect.width.saturating_sub(gutter).max(1);
+        self.max_row = rect.height.saturating_sub(1).max(1);
+        self.scroll();
+
+        let mut grid = self.render_frame(&rects);
+        // 浮动窗口画在文本上面
+        let bounds = self.views_area();
+        for overlay in self.completion_overlay().iter().chain(&self.overlay) {
+            for ((x, y), line) in overlay.render(&self.theme, bounds) {
+                grid.put_styled(x, y, &line);
+            }
+        }
+
+        // 正在输入提示时光标在消息栏中，结果列表获得焦点时光标在选中的结果上
+        let cursor = self
+            .prompt_cursor
+            .map(|x| (x, self.screen_rows.saturating_sub(1)))
+            .or_else(|| self.results_cursor())
+            .unwrap_or_else(|| self.cursor_screen_position());
+        self.screen.draw(Frame { grid, cursor })?;
+
+        Ok(())
+    }
+
+    /// 光标在屏幕上的位置
+    pub(super) fn cursor_screen_position(&self) -> (u16, u16) {
+        let (_, rect) = self.focused_rect();
+        let gutter = self.gutter_width(&self.buffer);
+        // cx和cy是rows中的坐标，所以需要减去偏移量
+        let (x, y) = if self.wrap {
+            let (x, y) = self.wrapped_cursor();
+            (x as u16, y as u16)
+        } else {
+            (
+                self.buffer.cx - self.buffer.col_offset as u16,
+                self.buffer.cy - self.buffer.row_offset as u16,
+            )
+        };
+        (rect.x + gutter + x, rect.y + y)
+    }
+
+    /// 把整个屏幕渲染到新的单元格网格中
+    fn render_frame(&self, rects: &[(usize, Rect)]) -> Grid {
+        let mut grid = Grid::new(self.screen_cols, self.screen_rows);
+        // 终端太小时窗口可能被挤到没有宽度或者高度，这时不画
+        for &(index, rect) in rects.iter().filter(|(_, r)| r.width > 0 && r.height > 0) {
+            for (i, line) in self.render_view(index, rect).into_iter().enumerate() {
+                grid.put_styled(rect.x, rect.y + i as u16, &line);
+            }
+        }
+
+        // 左右分屏之间的分隔线
+        let mut separators = Vec::new();
+        self.layout.separators(self.views_area(), &mut separators);
+        for rect in separators {
+            for y in rect.y..rect.y + rect.height {
+                grid.put_styled(rect.x, y, &format!("{}│{}", self.theme.separator, color::RESET));
+            }
+        }
+
+        let top = self.views_area().height;
+        for (i, line) in self.render_results().into_iter().enumerate() {
+            grid.put_styled(0, top + i as u16, &line);
+        }
+
+        grid.put_styled(0, self.screen_rows.saturating_sub(1), &self.render_message_bar());
+        grid
+    }
+
+    /// 将窗口渲染为若干行，最后一行是状态栏
+    fn render_view(&self, index: usize, rect: Rect) -> Vec<String> {
+        let focused = index == self.focus;
+        let (buffer, cursor) = if focused {
+            (&self.buffer, self.buffer.cursor())
+        } else {
+            let view = &self.views[index];
+            let buffer = self.find_buffer(view.buffer_id).unwrap_or(&self.buffer);
+            if view.preview {
+                return self.render_preview(buffer, rect);
+            }
+            (buffer, view.cursor)
+        };
+
+        let gutter = self.gutter_width(buffer);
+        let text_width = rect.width.saturating_sub(gutter).max(1) as usize;
+        let text_height = rect.height.saturating_sub(1) as usize;
+        let (cx, cy, row_offset, col_offset) = if self.wrap {
+            Self::fit_cursor_wrapped(buffer, cursor, text_width, text_height)
+        } else {
+            Self::fit_cursor(cursor, text_width, text_height)
+        };
+
+        // 只在焦点窗口中标出光标下的括号和配对的括号
+        let brackets = if focused {
+            brackets::matching_columns(&buffer.rows, (cx as usize, cy as usize))
+        } else {
+            Vec::new()
+        };
+        let mut lines = if self.wrap {
+            let cursor = (cx as usize, cy as usize, row_offset);
+            let size = (text_width, text_height);
+            self.render_wrapped(buffer, cursor, gutter, size, focused, &brackets)
+        } else {
+            Vec::with_capacity(rect.height as usize)
+        };
+        // 折行时所有行都已经渲染，剩下的屏幕行都在文件末尾之后
+        for i in row_offset + lines.len()..row_offset + text_height {
+            let mut line = String::new();
+            if i < buffer.rows.len() {
+                line.push_str(&self.render_gutter(buffer, i, cy as usize));
+                let style = self.row_style(buffer, i, (cx as usize, cy as usize), focused, &brackets);
+                line.push_str(&self.render_row(&buffer.rows[i], col_offset, text_width, &style));
+                if focused && i == cy as usize {
+                    let free = (rect.width as usize).saturating_sub(utils::visible_width(&line));
+                    line.push_str(&self.render_inline_blame(buffer, i, free));
+                }
+            } else if (i == text_height / 3 || i == text_height / 3 + 1) && buffer.rows.is_empty() {
+                let welcome = if i == text_height / 3 {
+                    format!("fim -- version: {}", utils::get_version_from_env())
+                } else {
+                    WELCOME_HINT.to_string()
+                };
+                // 如果欢迎字符串的宽度超过窗口宽度，则截断
+                // welcome中只有ASCII，可以直接按字节截断
+                let welcome = &welcome[..welcome.len().min((rect.width as usize).saturating_sub(1))];
+                // 计算边距
+                let margin = (rect.width as usize).saturating_sub(welcome.len()) / 2;
+                line.push('~');
+                line.push_str(&" ".repeat(margin.saturating_sub(1)));
+                line.push_str(welcome);
+            } else {
+                line.push('~');
+            }
+            let width = utils::visible_width(&line);
+            line.push_str(&" ".repeat((rect.width as usize).saturating_sub(width)));
+            lines.push(line);
+        }
+
+        // 终端窗口没有焦点时所有状态栏都显示为不活动
+        lines.push(self.render_status_bar(buffer, (cx, cy), rect.width, focused && self.terminal_focused));
+        lines
+    }
+
+    /// 非焦点窗口的大小可能已经变化，重新计算偏移量保证光标在窗口内
+    fn fit_cursor(cursor: buffer::CursorState, width: usize, height: usize) -> buffer::CursorState {
+        let (cx, cy, mut row_offset, mut col_offset) = cursor;
+        let height = height.max(1);
+        if (cy as usize) < row_offset {
+            row_offset = cy as usize;
+        } else if cy as usize >= row_offset + height {
+            row_offset = cy as usize + 1 - height;
+        }
+        if (cx as usize) < col_offset {
+            col_offset = cx as usize;
+        } else if cx as usize >= col_offset + width {
+            col_offset = cx as usize + 1 - width;
+        }
+        (cx, cy, row_offset, col_offset)
+    }
+
+    pub(super) fn render_gutter(&self, buffer: &Buffer, i: usize, cy: usize) -> String {
+        let sign = self.render_git_sign(buffer, i);
+        if self.line_numbers == LineNumbers::Off {
+            return sign;
+        }
+        let width = self.gutter_width(buffer) as usize - usize::from(buffer.git.is_some()) - 1;
+        let (number, color) = match self.line_numbers {
+            LineNumbers::Relative if i != cy => (i.abs_diff(cy), &self.theme.line_number),
+            _ if i == cy => (i + 1, &self.theme.current_line_number),
+            _ => (i + 1, &self.theme.line_number),
+        };
+        format!("{}{}{:>width$}{} ", sign, color, number, color::RESET)
+    }
+}
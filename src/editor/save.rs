@@ -0,0 +1,375 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::task::JoinHandle;
+use tokio::time::{self, Instant, Interval, MissedTickBehavior};
+use tracing::warn;
+
+use super::event::Event;
+use super::prompt::complete_path;
+use super::{Editor, Message, Row, Terminal, buffer, watch};
+use crate::error::Result;
+use crate::reader::{Bom, decoder};
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    pub async fn save(&mut self) -> Result<()> {
+        if !self.check_writable() {
+            return Ok(());
+        }
+        // 没有文件名时先询问
+        if self.buffer.current_file.is_none() {
+            let Some(path) = self.prompt_path("Save as: ").await else {
+                self.show_message(Message::new("Save aborted".to_string()));
+                return Ok(());
+            };
+            self.buffer.current_file = Some(path);
+        }
+        self.write_buffer().await
+    }
+
+    /// 保存到新的文件，之后的保存也写入新文件
+    pub async fn save_as(&mut self) -> Result<()> {
+        if !self.check_writable() {
+            return Ok(());
+        }
+        let Some(path) = self.prompt_path("Save as: ").await else {
+            self.show_message(Message::new("Save aborted".to_string()));
+            return Ok(());
+        };
+        let exists = fs::try_exists(&path).await.unwrap_or(false);
+        if exists && self.buffer.current_file.as_ref() != Some(&path) {
+            let question = format!("{} already exists, overwrite? (y/n)", path.display());
+            if !self.confirm(&question).await {
+                self.show_message(Message::new("Save aborted".to_string()));
+                return Ok(());
+            }
+        }
+        let old = self.buffer.current_file.replace(path);
+        let result = self.write_buffer().await;
+        // 写入失败时仍然关联原来的文件
+        if result.is_err() {
+            self.buffer.current_file = old;
+        }
+        result
+    }
+
+    /// 在消息栏中询问文件路径，取消或者输入为空时返回None
+    async fn prompt_path(&mut self, prompt: &str) -> Option<PathBuf> {
+        let input = self.prompt_with_completion(prompt, complete_path).await?;
+        let filename = Row::raw_str(&input);
+        (!filename.is_empty()).then(|| PathBuf::from(filename))
+    }
+
+    /// 将buffer的内容写入current_file
+    async fn write_buffer(&mut self) -> Result<()> {
+        let Some(path) = self.buffer.current_file.clone() else {
+            return Ok(());
+        };
+        let path = path.as_path();
+        // 格式化失败时仍然保存原来的内容
+        let format_error = if self.config.format_on_save && self.formatter().is_some() {
+            self.format_buffer().await.err()
+        } else {
+            None
+        };
+        self.emit(Event::BeforeSave(self.buffer.id));
+        // create会完全截断文件，写入过程中崩溃会留下不完整的文件
+        // 所以写入新的临时文件，然后将该文件重命名为用户想要覆盖的实际文件
+        // 先拼接所有行再一次写入，大文件逐行写入会产生大量系统调用
+        let content = self.buffer.text(self.buffer.line_ending.as_str());
+        // 按打开时的编码写回，无法表示的字符在截断文件前就报错
+        let bytes = match decoder::encode(&self.buffer.encoding, &content) {
+            Ok(bytes) => match Bom::for_encoding(&self.buffer.encoding) {
+                Some(bom) if self.buffer.bom => [bom.bytes(), &bytes].concat(),
+                _ => bytes,
+            },
+            Err(e) => {
+                self.show_message(Message::error(format!("Can't save: {e}"), &e.into()));
+                return Ok(());
+            }
+        };
+        // 文件在读取之后被其他程序修改过，避免悄悄覆盖
+        if self.changed_on_disk().await {
+            let question = format!(
+                "{} changed on disk since it was read, overwrite? (y/n)",
+                self.buffer.name()
+            );
+            if !self.confirm(&question).await {
+                self.show_message(Message::new("Save aborted".to_string()));
+                return Ok(());
+            }
+        }
+        if self.config.backup {
+            backup(path, self.config.backup_dir.as_deref(), self.config.backup_count).await?;
+        }
+        write_atomic(path, &bytes).await?;
+        self.buffer.disk_state = watch::disk_state(path).await;
+        let message = match format_error {
+            Some(e) => Message::warning(format!("File saved without formatting: {}", e)),
+            None => Message::new("File saved".to_string()),
+        };
+        self.show_message(message);
+        self.buffer.mark_saved();
+        self.emit(Event::AfterSave(self.buffer.id));
+        Ok(())
+    }
+
+    /// 已经保存，不再需要交换文件，在后台删除
+    pub(super) fn remove_swap_after_save(&mut self, event: &Event) {
+        if let Event::AfterSave(_) = event {
+            self.buffer.swap_changes = self.buffer.changes;
+            if let Some(swap) = self.buffer.swap.take() {
+                tokio::spawn(swap.remove());
+            }
+        }
+    }
+
+    /// 把至少有min_changes次新修改的buffer写入交换文件，写入在后台进行
+    pub(super) async fn write_swap_files(&mut self, min_changes: u64) {
+        let min_changes = min_changes.max(1);
+        let buffers = std::iter::once(&mut self.buffer).chain(self.buffers.iter_mut());
+        for buffer in buffers.filter(|b| b.unswapped_changes() >= min_changes) {
+            // 没有文件名的buffer不知道交换文件放在哪里
+            let Some(path) = &buffer.current_file else {
+                continue;
+            };
+            let path = swap_path(path);
+            // 等待上一次写入完成，避免同时写同一个文件
+            if let Some(Swap { writer: Some(writer), .. }) = buffer.swap.take() {
+                let _ = writer.await;
+            }
+            let content = buffer.text("\n");
+            let target = path.clone();
+            let writer = tokio::spawn(async move {
+                if let Err(e) = write_atomic(&target, content.as_bytes()).await {
+                    warn!("Failed to write swap file {}: {}", target.display(), e);
+                }
+            });
+            buffer.swap = Some(Swap {
+                path,
+                writer: Some(writer),
+            });
+            buffer.swap_changes = buffer.changes;
+        }
+    }
+
+    /// panic时不能再等待后台任务，直接把有修改的buffer同步写入交换文件，下次打开时可以恢复
+    /// 返回写入的交换文件，没有文件名的buffer无法恢复
+    pub(super) fn write_recovery_files(&self) -> Vec<PathBuf> {
+        let buffers = std::iter::once(&self.buffer).chain(self.buffers.iter());
+        buffers
+            .filter(|b| b.is_dirty())
+            .filter_map(|buffer| {
+                let path = swap_path(buffer.current_file.as_ref()?);
+                std::fs::write(&path, buffer.text("\n")).ok()?;
+                Some(path)
+            })
+            .collect()
+    }
+
+    /// 正常退出时删除所有交换文件
+    pub(super) async fn remove_swap_files(&mut self) {
+        let buffers = std::iter::once(&mut self.buffer).chain(self.buffers.iter_mut());
+        for buffer in buffers {
+            if let Some(swap) = buffer.swap.take() {
+                swap.remove().await;
+            }
+        }
+    }
+
+    /// 打开文件时发现交换文件，询问是否恢复
+    /// 恢复时返回交换文件中的内容，不恢复时删除交换文件
+    pub(super) async fn recover_swap(&mut self, path: &Path) -> Result<Option<(Vec<Row>, Swap)>> {
+        let swap = swap_path(path);
+        if !fs::try_exists(&swap).await.unwrap_or(false) {
+            return Ok(None);
+        }
+        let question = format!(
+            "Found swap file for {}, recover unsaved changes? (y/n)",
+            path.display()
+        );
+        let recover = self.confirm(&question).await;
+        self.message = None;
+        if !recover {
+            let _ = fs::remove_file(&swap).await;
+            return Ok(None);
+        }
+        let rows = buffer::read_rows(&swap, "UTF-8", self.config.tab_width).await?;
+        let swap = Swap {
+            path: swap,
+            writer: None,
+        };
+        Ok(Some((rows, swap)))
+    }
+}
+
+/// 写入过的交换文件
+pub(super) struct Swap {
+    path: PathBuf,
+    /// 后台写入的任务
+    writer: Option<JoinHandle<()>>,
+}
+
+impl Swap {
+    /// 等待写入完成后删除交换文件
+    pub(super) async fn remove(self) {
+        if let Some(writer) = self.writer {
+            let _ = writer.await;
+        }
+        let _ = fs::remove_file(&self.path).await;
+    }
+}
+
+/// 交换文件的路径，和文件在同一目录下
+fn swap_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.fim-swap", name))
+}
+
+/// 自动保存的定时器，第一次在一个周期后触发
+/// seconds为0时定时器不会被使用，这里仍然返回一个有效的定时器
+pub(super) fn autosave_timer(seconds: u64) -> Interval {
+    let period = Duration::from_secs(seconds.max(1));
+    let mut timer = time::interval_at(Instant::now() + period, period);
+    timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    timer
+}
+
+/// 把文件原来的内容复制到备份文件，文件不存在或者count为0时不需要备份
+/// 最新的备份是 文件名~，更早的依次是 文件名~2 到 文件名~count
+async fn backup(path: &Path, dir: Option<&Path>, count: usize) -> io::Result<()> {
+    if count == 0 || !fs::try_exists(path).await? {
+        return Ok(());
+    }
+    let backup = backup_path(path, dir);
+    let numbered = |n: usize| match n {
+        1 => backup.clone(),
+        n => {
+            let mut name = backup.clone().into_os_string();
+            name.push(n.to_string());
+            PathBuf::from(name)
+        }
+    };
+    // 从最旧的开始往后挪，超出数量的被覆盖
+    for n in (1..count).rev() {
+        match fs::rename(numbered(n), numbered(n + 1)).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    if let Some(dir) = dir {
+        fs::create_dir_all(dir).await?;
+    }
+    fs::copy(path, &backup).await?;
+    Ok(())
+}
+
+/// 最新的备份文件的路径
+/// 放在备份目录中时用完整路径作为文件名，避免不同目录的同名文件冲突
+fn backup_path(path: &Path, dir: Option<&Path>) -> PathBuf {
+    match dir {
+        Some(dir) => {
+            let full = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+            let name = full.to_string_lossy().replace(std::path::MAIN_SEPARATOR, "%");
+            dir.join(format!("{}~", name))
+        }
+        None => {
+            let mut name = path.as_os_str().to_owned();
+            name.push("~");
+            PathBuf::from(name)
+        }
+    }
+}
+
+/// 先写入同一目录下的临时文件，再重命名为目标文件
+/// 重命名是原子的，保存过程中崩溃也不会留下被截断的文件
+pub(super) async fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    // 路径是符号链接时替换链接指向的文件，而不是把链接换成普通文件
+    let target = match fs::canonicalize(path).await {
+        Ok(target) => target,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => path.to_path_buf(),
+        Err(e) => return Err(e),
+    };
+    let metadata = match fs::metadata(&target).await {
+        Ok(metadata) => Some(metadata),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+
+    let temp = temp_path(&target);
+    let result = async {
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp)
+            .await?;
+        file.write_all(bytes).await?;
+        // 确保数据落盘后再重命名
+        file.sync_all().await?;
+        if let Some(metadata) = &metadata {
+            fs::set_permissions(&temp, metadata.permissions()).await?;
+            copy_owner(&temp, metadata);
+        }
+        fs::rename(&temp, &target).await
+    }
+    .await;
+    if result.is_err() {
+        let _ = fs::remove_file(&temp).await;
+    }
+    result
+}
+
+/// 临时文件的路径，以点开头并带上进程号，避免和其他文件冲突
+fn temp_path(target: &Path) -> PathBuf {
+    let name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    target.with_file_name(format!(".{}.{}.fim-tmp", name, std::process::id()))
+}
+
+/// 保留原文件的所有者，没有权限修改时忽略
+#[cfg(unix)]
+fn copy_owner(temp: &Path, metadata: &std::fs::Metadata) {
+    use std::os::unix::fs::MetadataExt;
+    let _ = std::os::unix::fs::chown(temp, Some(metadata.uid()), Some(metadata.gid()));
+}
+
+#[cfg(not(unix))]
+fn copy_owner(_temp: &Path, _metadata: &std::fs::Metadata) {}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn backups_rotate_up_to_count() {
+        let dir = env::temp_dir().join(format!("fim-backup-{}", process::id()));
+        fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("file.txt");
+        let read = |name: &str| std::fs::read_to_string(dir.join(name)).ok();
+
+        fs::write(&path, "1").await.unwrap();
+        // 数量为0时不备份
+        backup(&path, None, 0).await.unwrap();
+        assert_eq!(read("file.txt~"), None);
+
+        for content in ["2", "3", "4"] {
+            backup(&path, None, 2).await.unwrap();
+            fs::write(&path, content).await.unwrap();
+        }
+        // 只保留最近的两个备份
+        assert_eq!(read("file.txt~").as_deref(), Some("3"));
+        assert_eq!(read("file.txt~2").as_deref(), Some("2"));
+        assert_eq!(read("file.txt~3"), None);
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
@@ -0,0 +1,136 @@
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use tokio::sync::watch;
+use tracing::error;
+
+use super::render::Grid;
+use crate::terminal::Terminal;
+
+/// 编辑器生成的一帧，交给绘制任务输出到终端
+pub(super) struct Frame {
+    /// 网格的大小是生成这一帧时使用的终端大小
+    pub(super) grid: Grid,
+    pub(super) cursor: (u16, u16),
+}
+
+/// 在后台任务中把帧输出到终端，按键的处理不需要等待终端的输出
+/// 输出一帧的过程中生成的多个帧只输出最新的一个
+pub(super) struct Screen<W: Terminal> {
+    terminal: Arc<Mutex<W>>,
+    /// 恢复终端之后不再输出，需要在持有终端的锁时修改
+    closed: Arc<AtomicBool>,
+    frames: watch::Sender<Option<Arc<Frame>>>,
+    /// 绘制任务最近一次看到的终端大小
+    size: watch::Receiver<(u16, u16)>,
+}
+
+impl<W: Terminal> Screen<W> {
+    pub(super) fn new(terminal: W) -> Self {
+        let (frames, receiver) = watch::channel(None);
+        let (size_sender, size) = watch::channel(terminal.size().unwrap_or_default());
+        let terminal = Arc::new(Mutex::new(terminal));
+        let closed = Arc::new(AtomicBool::new(false));
+        tokio::spawn(draw_frames(terminal.clone(), closed.clone(), receiver, size_sender));
+        Self {
+            terminal,
+            closed,
+            frames,
+            size,
+        }
+    }
+
+    /// 直接操作终端，会等待正在输出的帧
+    pub(super) fn lock(&self) -> MutexGuard<'_, W> {
+        self.terminal.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(super) fn size(&self) -> (u16, u16) {
+        *self.size.borrow()
+    }
+
+    /// 终端大小变化时返回，编辑器需要按新的大小重新生成一帧
+    pub(super) async fn resized(&mut self) {
+        if self.size.changed().await.is_err() {
+            std::future::pending().await
+        }
+    }
+
+    /// 把帧交给绘制任务，不等待输出完成
+    pub(super) fn draw(&self, frame: Frame) -> io::Result<()> {
+        self.frames
+            .send(Some(Arc::new(frame)))
+            .map_err(|_| io::Error::other("Render task has stopped"))
+    }
+
+    /// 停止输出帧，返回的终端用来恢复终端的状态
+    pub(super) fn close(&self) -> MutexGuard<'_, W> {
+        let terminal = self.lock();
+        self.closed.store(true, Ordering::SeqCst);
+        terminal
+    }
+}
+
+/// 绘制任务，编辑器销毁之后结束
+async fn draw_frames<W: Terminal>(
+    terminal: Arc<Mutex<W>>,
+    closed: Arc<AtomicBool>,
+    mut frames: watch::Receiver<Option<Arc<Frame>>>,
+    size: watch::Sender<(u16, u16)>,
+) {
+    // 上一次输出到屏幕上的内容，用于只重绘变化的部分，为None时完整重绘
+    let mut last_grid: Option<Grid> = None;
+    while frames.changed().await.is_ok() {
+        let Some(frame) = frames.borrow_and_update().clone() else {
+            continue;
+        };
+        let mut terminal = terminal.lock().unwrap_or_else(PoisonError::into_inner);
+        if closed.load(Ordering::SeqCst) {
+            break;
+        }
+        // 终端大小变化时丢弃这一帧，完整重绘编辑器按新的大小生成的帧
+        let current = terminal.size().unwrap_or(frame.grid.size());
+        if current != frame.grid.size() {
+            last_grid = None;
+            size.send_replace(current);
+            continue;
+        }
+        match draw_frame(&mut *terminal, &frame, last_grid.as_ref()) {
+            Ok(()) => last_grid = Some(frame.grid.clone()),
+            Err(e) => {
+                error!("Error refreshing screen: {}", e);
+                last_grid = None;
+            }
+        }
+    }
+}
+
+fn draw_frame<W: Terminal>(terminal: &mut W, frame: &Frame, last_grid: Option<&Grid>) -> io::Result<()> {
+    // 刷新屏幕之前隐藏光标，刷新完成之后显示，这样可以防止光标闪烁
+    terminal.hide_cursor()?;
+    terminal.flush()?;
+
+    // 没有上一帧时清除屏幕，和空白的网格比较
+    let blank;
+    let last_grid = match last_grid {
+        Some(grid) => grid,
+        None => {
+            terminal.clear()?;
+            let (cols, rows) = frame.grid.size();
+            blank = Grid::new(cols, rows);
+            &blank
+        }
+    };
+    // 只重绘和上一帧不同的单元格
+    for ((x, y), text) in frame.grid.diff(last_grid) {
+        // Terminal的光标起始位置以0开始
+        terminal.move_to(x, y)?;
+        terminal.write_styled(&text)?;
+    }
+
+    // 将光标移动回来
+    terminal.move_to(frame.cursor.0, frame.cursor.1)?;
+    terminal.show_cursor()?;
+    terminal.flush()
+}
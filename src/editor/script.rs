@@ -0,0 +1,136 @@
+use std::path::PathBuf;
+
+use tokio::io::AsyncReadExt;
+
+use super::motion::parse_target;
+use super::pattern::{Pattern, regex_error};
+use super::{Editor, Key, Message, OpenOptions, Row, Terminal};
+
+/// 用分隔符包起来的查找和替换内容，比如 /foo/bar/，最后的分隔符可以省略
+fn parse_substitution(input: &str) -> Option<(&str, &str)> {
+    let mut chars = input.chars();
+    let delimiter = chars.next()?;
+    let rest = chars.as_str();
+    let (pattern, replacement) = rest.split_once(delimiter)?;
+    let replacement = replacement.strip_suffix(delimiter).unwrap_or(replacement);
+    Some((pattern, replacement))
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 不进入界面，依次执行脚本中的每一行，遇到警告或者错误时停止并返回出错的行
+    /// 执行过程中显示的消息输出到标准错误
+    /// open 文件：打开文件
+    /// goto 行[:列]：跳转到指定的位置
+    /// insert 文本：在光标处插入文本
+    /// keys 按键：按顺序处理按键，和在界面中按下一样，比如 keys dd<C-s>
+    /// replace /查找/替换/：用正则替换整个buffer中的所有匹配项
+    /// write [文件]：保存当前buffer，可以保存到新的文件
+    /// quit：停止执行
+    /// 其他行作为命令执行，空行和#开头的行被忽略
+    pub async fn run_script(&mut self, script: &str) -> Result<(), String> {
+        self.wait_for_loading().await;
+        self.report_messages("start", None)?;
+        for (i, line) in script.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let result = self.run_script_line(line).await;
+            self.report_messages(&format!("line {}", i + 1), result.err())?;
+            if self.quit {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// 输出显示过的消息，有警告或者错误时返回第一个，location是消息产生的位置
+    fn report_messages(&mut self, location: &str, error: Option<String>) -> Result<(), String> {
+        let mut error = error;
+        for message in self.message_log.drain(..) {
+            if message.is_problem() {
+                error.get_or_insert(message.text);
+            } else {
+                eprintln!("{}: {}", location, message.text);
+            }
+        }
+        match error {
+            Some(e) => Err(format!("{}: {}", location, e)),
+            None => Ok(()),
+        }
+    }
+
+    async fn run_script_line(&mut self, line: &str) -> Result<(), String> {
+        let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+        let args = args.trim();
+        match (command, args) {
+            ("open", "") | ("goto", "") | ("keys", "") | ("replace", "") => {
+                return Err(format!("Usage: {} <argument>", command));
+            }
+            ("open", path) => {
+                self.open_file(path, &OpenOptions::default()).await.map_err(|e| e.to_string())?;
+                self.wait_for_loading().await;
+            }
+            ("goto", target) => {
+                let (line, column) = parse_target(target).ok_or("Invalid line number")?;
+                self.goto_position(line, column.unwrap_or(1));
+            }
+            ("insert", _) => {
+                // 保留文本前面的空格
+                if self.check_writable() {
+                    let text = line.strip_prefix("insert ").unwrap_or_default();
+                    for c in text.chars() {
+                        self.insert_key(Key::Char(c));
+                    }
+                }
+            }
+            ("keys", notation) => {
+                let keys = Key::parse_notation(notation)?;
+                self.pending_keys.extend(keys);
+                while let Some(key) = self.pending_keys.pop_front() {
+                    let state = self.cursor_state();
+                    self.handle_command(&key).await;
+                    self.emit_cursor_events(state);
+                    if self.quit {
+                        self.pending_keys.clear();
+                    }
+                }
+            }
+            ("replace", substitution) => {
+                let (query, replacement) = parse_substitution(substitution).ok_or("Usage: replace /pattern/replacement/")?;
+                if self.check_writable() {
+                    let query = Row::raw_keys(query);
+                    let pattern = Pattern::new(&query, true).map_err(|e| regex_error(&e))?;
+                    let replacement = Row::raw_keys(replacement);
+                    let count = self.replace_all(&pattern, &replacement);
+                    self.show_message(Message::new(format!("Replaced {} occurrence(s)", count)));
+                }
+            }
+            ("write", path) => {
+                let path = PathBuf::from(path);
+                if !path.as_os_str().is_empty() && self.buffer.current_file.as_ref() != Some(&path) {
+                    // 新的文件不需要检查是否在读取之后被修改过
+                    self.buffer.current_file = Some(path);
+                    self.buffer.disk_state = None;
+                }
+                if self.buffer.current_file.is_none() {
+                    return Err("No file name".to_string());
+                }
+                self.save().await.map_err(|e| e.to_string())?;
+            }
+            ("quit", "") => self.quit = true,
+            _ => self.execute_command(line).await?,
+        }
+        Ok(())
+    }
+
+    /// 等待所有buffer加载完成，持续读取追加内容的buffer不需要等待
+    async fn wait_for_loading(&mut self) {
+        while self.buffer.is_loading() || self.buffers.iter().any(|b| b.is_loading()) {
+            let Some(event) = self.load_receiver.recv().await else {
+                break;
+            };
+            self.handle_load_event(event);
+        }
+    }
+}
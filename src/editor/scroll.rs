@@ -0,0 +1,108 @@
+use std::time::Duration;
+
+use tokio::io::AsyncReadExt;
+use tokio::time;
+
+use super::{Editor, Key, Message, Terminal};
+
+/// 平滑滚动时分成多少帧
+const SMOOTH_SCROLL_FRAMES: usize = 8;
+/// 每一帧之间的间隔
+const SMOOTH_SCROLL_FRAME_TIME: Duration = Duration::from_millis(8);
+
+/// 把光标所在行滚动到窗口中的位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Align {
+    Center,
+    Top,
+    Bottom,
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    pub(super) async fn scroll_command(&mut self) {
+        self.message = Some(Message::new("Scroll: z(center) t(op) b(ottom)".to_string()));
+        if self.refresh_screen().is_err() {
+            return;
+        }
+        let Ok(key) = self.get_key().await else {
+            return;
+        };
+        self.message = None;
+        match key {
+            Key::Char('z') => self.align_cursor(Align::Center),
+            Key::Char('t') => self.align_cursor(Align::Top),
+            Key::Char('b') => self.align_cursor(Align::Bottom),
+            _ => {}
+        }
+    }
+
+    /// 滚动窗口使光标所在行位于中间、顶部或底部，光标本身不动
+    /// 顶部和底部仍然保留scroll_margin行
+    pub(super) fn align_cursor(&mut self, align: Align) {
+        let max_row = self.max_row as usize;
+        if max_row == 0 {
+            return;
+        }
+        let line = match align {
+            Align::Center => (max_row - 1) / 2,
+            Align::Top => 0,
+            Align::Bottom => max_row - 1,
+        };
+        self.buffer.row_offset = if self.wrap {
+            self.wrapped_offset_for(line)
+        } else {
+            (self.buffer.cy as usize).saturating_sub(line)
+        };
+        self.scroll();
+    }
+
+    /// 半屏的行数
+    pub(super) fn half_page(&self) -> usize {
+        (self.max_row as usize / 2).max(1)
+    }
+
+    /// 整屏的行数，保留两行上一屏的内容
+    pub(super) fn full_page(&self) -> usize {
+        (self.max_row as usize).saturating_sub(2).max(1)
+    }
+
+    /// 窗口和光标一起滚动lines行，负数向上，光标在屏幕上的行不变
+    /// 开启平滑滚动时分几帧完成
+    pub(super) async fn scroll_page(&mut self, lines: isize) {
+        let frames = if self.config.smooth_scroll && self.pending_keys.is_empty() {
+            SMOOTH_SCROLL_FRAMES.min(lines.unsigned_abs())
+        } else {
+            1
+        };
+        if frames <= 1 {
+            return self.scroll_view(lines);
+        }
+        let step = lines / frames as isize;
+        for i in 0..frames {
+            // 除不尽的部分放到最后一帧
+            let delta = if i + 1 == frames { lines - step * i as isize } else { step };
+            self.scroll_view(delta);
+            if self.refresh_screen().is_err() {
+                return;
+            }
+            time::sleep(SMOOTH_SCROLL_FRAME_TIME).await;
+        }
+    }
+
+    /// 直接移动窗口，不需要逐行移动光标
+    /// 窗口到达文件首尾时停住，光标继续移动
+    pub(super) fn scroll_view(&mut self, lines: isize) {
+        let len = self.buffer.rows.len();
+        let limit = (len + 1).saturating_sub(self.max_row as usize);
+        let offset = self.buffer.row_offset.saturating_add_signed(lines);
+        self.buffer.row_offset = if lines > 0 {
+            offset.min(limit.max(self.buffer.row_offset))
+        } else {
+            offset
+        };
+        let cy = (self.buffer.cy as usize).saturating_add_signed(lines);
+        self.buffer.cy = cy.min(len.saturating_sub(1)) as u16;
+        self.clamp_cursor_x();
+        self.scroll();
+    }
+}
@@ -0,0 +1,419 @@
+use std::ops::Range;
+
+use tokio::io::AsyncReadExt;
+
+use super::history::HistoryKind;
+use super::pattern::{Pattern, regex_error};
+use super::{Buffer, ControlKey, Editor, Key, Message, Terminal};
+
+/// 统计匹配项的上限，超过时状态栏显示999+，避免匹配项很多时每次跳转都要找遍整个文件
+const MAX_COUNTED_MATCHES: usize = 999;
+
+/// 搜索的状态，用于在匹配项之间跳转
+#[derive(Default)]
+pub(super) struct SearchState {
+    // 使用正则表达式搜索，在搜索提示中按Ctrl+R切换
+    pub(super) regex: bool,
+    // 增量搜索开始时光标的位置，(行, raw索引)，从这里向后查找
+    origin: (usize, usize),
+    // 最近一次确认的搜索，Alt+n和Alt+N用它跳转
+    pattern: Option<Pattern>,
+    // 最近一次跳转到的匹配项，光标离开后状态栏不再显示
+    current: Option<MatchStatus>,
+}
+
+/// 跳转到的匹配项是第几个，以及一共有多少个
+/// 增量搜索输入时不统计，确认搜索或者在匹配项之间跳转时才统计
+#[derive(Debug, Clone, Copy)]
+struct MatchStatus {
+    buffer_id: usize,
+    cursor: (u16, u16),
+    count: Option<MatchCount>,
+}
+
+/// 超过统计上限的数量为None
+#[derive(Debug, Clone, Copy)]
+struct MatchCount {
+    index: Option<usize>,
+    total: Option<usize>,
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 从光标处开始向后搜索，到文件末尾后从头继续
+    /// 搜索提示中Ctrl+N和Ctrl+P跳到下一个和上一个匹配项
+    pub(super) async fn find(&mut self) {
+        let saved = self.buffer.cursor();
+        self.begin_search();
+
+        // 搜索过程中光标会跟随匹配项移动
+        // 取消搜索或者找不到时回到原来的位置
+        let query = self
+            .prompt_with_history("Search: ", Some(Self::find_callback), HistoryKind::Search)
+            .await;
+        let Some(query) = query.filter(|query| !query.is_empty()) else {
+            self.buffer.set_cursor(saved);
+            return;
+        };
+        match Pattern::new(&query, self.search_state.regex) {
+            // 增量搜索已经跳到了匹配项
+            Ok(pattern) if self.search_state.current.is_some() => {
+                self.count_current(&pattern);
+                self.search_state.pattern = Some(pattern);
+            }
+            Ok(_) => {
+                self.buffer.set_cursor(saved);
+                self.show_message(Message::warning(format!(
+                    "Not Found: {}",
+                    super::Row::raw_str(&query)
+                )));
+            }
+            Err(e) => {
+                self.buffer.set_cursor(saved);
+                self.show_message(Message::warning(regex_error(&e)));
+            }
+        }
+    }
+
+    /// 记录增量搜索开始的位置
+    fn begin_search(&mut self) {
+        self.search_state.origin = (self.buffer.cy as usize, self.raw_cursor());
+        self.search_state.current = None;
+    }
+
+    /// 搜索提示前显示的搜索模式
+    pub(super) fn search_flags(&self) -> &'static str {
+        if self.search_state.regex { "[regex] " } else { "" }
+    }
+
+    /// 增量搜索，每输入一个键就从开始的位置跳转到下一个匹配项
+    /// 输入时只查找下一个匹配项，不统计整个文件中的匹配项
+    /// Ctrl+R切换普通搜索和正则搜索，输入的正则不完整时不跳转
+    fn find_callback(&mut self, query: &[Key], key: &Key) {
+        let forward = match key {
+            Key::ControlKey(ControlKey::CR | ControlKey::Escape) => return,
+            Key::ControlKey(ControlKey::Ctrl('n')) => Some(true),
+            Key::ControlKey(ControlKey::Ctrl('p')) => Some(false),
+            Key::ControlKey(ControlKey::Ctrl('r')) => {
+                self.search_state.regex = !self.search_state.regex;
+                None
+            }
+            _ => None,
+        };
+        let pattern = match Pattern::new(query, self.search_state.regex) {
+            Ok(pattern) if !query.is_empty() => pattern,
+            _ => {
+                self.search_state.current = None;
+                return;
+            }
+        };
+        match forward {
+            Some(forward) => {
+                self.jump_to_next(&pattern, forward);
+            }
+            None => {
+                let (row, at) = self.search_state.origin;
+                let found = self
+                    .find_match(&pattern, row, at)
+                    .or_else(|| self.find_match(&pattern, 0, 0));
+                match found {
+                    Some((row, range)) => self.move_to_match(row, range.start),
+                    None => self.search_state.current = None,
+                }
+            }
+        }
+    }
+
+    /// 跳到上一次搜索的下一个或上一个匹配项
+    pub(super) fn search_next(&mut self, forward: bool) {
+        let Some(pattern) = self.search_state.pattern.take() else {
+            self.show_message(Message::new("No previous search".to_string()));
+            return;
+        };
+        match self.jump_to_next(&pattern, forward) {
+            None => self.show_message(Message::warning("Pattern not found".to_string())),
+            Some(true) if forward => {
+                self.show_message(Message::new("Search wrapped to top".to_string()));
+            }
+            Some(true) => {
+                self.show_message(Message::new("Search wrapped to bottom".to_string()));
+            }
+            Some(false) => {}
+        }
+        self.search_state.pattern = Some(pattern);
+    }
+
+    /// 从光标处跳到下一个或上一个匹配项，到文件首尾后从另一头继续
+    ///
+    /// # Returns
+    /// 没有匹配项时返回None，否则返回是否绕回
+    fn jump_to_next(&mut self, pattern: &Pattern, forward: bool) -> Option<bool> {
+        let (cy, at) = (self.buffer.cy as usize, self.raw_cursor());
+        let (found, wrapped) = if forward {
+            match self.find_match(pattern, cy, at + 1) {
+                Some(found) => (found, false),
+                None => (self.find_match(pattern, 0, 0)?, true),
+            }
+        } else {
+            match self.find_match_before(pattern, cy, at) {
+                Some(found) => (found, false),
+                None => (self.find_match_before(pattern, usize::MAX, 0)?, true),
+            }
+        };
+        self.move_to_match(found.0, found.1.start);
+        self.count_current(pattern);
+        Some(wrapped)
+    }
+
+    /// 从(row, at)开始查找下一个匹配项
+    /// at是raw中的索引而不是渲染后的列
+    ///
+    /// # Returns
+    /// 匹配项所在的行和在raw中的范围，可以用于高亮
+    fn find_match(&self, pattern: &Pattern, row: usize, at: usize) -> Option<(usize, Range<usize>)> {
+        for (i, r) in self.buffer.rows.iter_from(row).enumerate().map(|(i, r)| (i + row, r)) {
+            let start = if i == row { at } else { 0 };
+            // 超出行尾说明这一行已经找完了
+            if start > r.len() {
+                continue;
+            }
+            if let Some(range) = pattern.find(r, start) {
+                return Some((i, range));
+            }
+        }
+        None
+    }
+
+    /// 查找(row, at)之前的最后一个匹配项，row超出范围时从最后一行开始
+    fn find_match_before(
+        &self,
+        pattern: &Pattern,
+        row: usize,
+        at: usize,
+    ) -> Option<(usize, Range<usize>)> {
+        let rows = &self.buffer.rows;
+        let last = row.min(rows.len().checked_sub(1)?);
+        (0..=last).rev().find_map(|i| {
+            let matches = pattern.find_all(&rows[i]);
+            let before = matches
+                .into_iter()
+                .rfind(|range| i != row || range.start < at)?;
+            Some((i, before))
+        })
+    }
+
+    /// (row, at)处的匹配项是第几个，以及所有匹配项的数量
+    /// 数到MAX_COUNTED_MATCHES个之后停止，不再读剩下的行
+    fn count_matches(&self, pattern: &Pattern, row: usize, at: usize) -> MatchCount {
+        let (mut index, mut total) = (0, 0);
+        for (i, r) in self.buffer.rows.iter().enumerate() {
+            let matches = pattern.find_all(r);
+            if i < row {
+                index += matches.len();
+            } else if i == row {
+                index += matches.iter().filter(|range| range.start <= at).count();
+            }
+            total += matches.len();
+            if total > MAX_COUNTED_MATCHES {
+                // 已经数过了匹配项所在的行时序号是准确的
+                let index = (i >= row && index <= MAX_COUNTED_MATCHES).then_some(index);
+                return MatchCount { index, total: None };
+            }
+        }
+        MatchCount {
+            index: Some(index),
+            total: Some(total),
+        }
+    }
+
+    /// 统计光标所在的匹配项的序号
+    fn count_current(&mut self, pattern: &Pattern) {
+        let Some(current) = self.search_state.current else {
+            return;
+        };
+        let (row, at) = (self.buffer.cy as usize, self.raw_cursor());
+        let count = self.count_matches(pattern, row, at);
+        self.search_state.current = Some(MatchStatus {
+            count: Some(count),
+            ..current
+        });
+    }
+
+    /// 状态栏中显示的匹配项序号，还没有统计时不显示
+    pub(super) fn match_status(&self, buffer: &Buffer) -> Option<String> {
+        let current = self.search_state.current?;
+        let count = current.count?;
+        let text = |n: Option<usize>| n.map_or(format!("{}+", MAX_COUNTED_MATCHES), |n| n.to_string());
+        (current.buffer_id == buffer.id && current.cursor == (buffer.cx, buffer.cy))
+            .then(|| format!("match {} of {}", text(count.index), text(count.total)))
+    }
+
+    /// 将光标移动到匹配项，cx需要从raw索引转换为渲染后的列
+    fn move_to_raw(&mut self, row: usize, at: usize) {
+        let column = self.buffer.rows[row].column_of(at);
+        self.buffer.cy = row as u16;
+        self.buffer.cx = column as u16;
+        self.scroll();
+    }
+
+    /// 移动到匹配项，记下光标的位置，之后用count_current统计序号
+    fn move_to_match(&mut self, row: usize, at: usize) {
+        self.move_to_raw(row, at);
+        self.search_state.current = Some(MatchStatus {
+            buffer_id: self.buffer.id,
+            cursor: (self.buffer.cx, self.buffer.cy),
+            count: None,
+        });
+    }
+
+    /// 查找并替换
+    /// 先输入要查找的内容，再输入替换的内容，然后逐个确认每个匹配项：
+    /// y替换当前项，n跳过当前项，a替换当前及剩余所有项，l替换当前项后退出，q或ESC退出
+    /// 正则搜索时替换内容中可以用$1引用捕获组
+    pub(super) async fn replace(&mut self) {
+        if !self.check_writable() {
+            return;
+        }
+        let saved = self.buffer.cursor();
+        self.begin_search();
+
+        let query = self
+            .prompt_with_history("Replace: ", Some(Self::find_callback), HistoryKind::Search)
+            .await;
+        let query = match query {
+            Some(query) if !query.is_empty() => query,
+            _ => {
+                self.buffer.set_cursor(saved);
+                return;
+            }
+        };
+
+        let pattern = match Pattern::new(&query, self.search_state.regex) {
+            Ok(pattern) => pattern,
+            Err(e) => {
+                self.buffer.set_cursor(saved);
+                self.show_message(Message::warning(regex_error(&e)));
+                return;
+            }
+        };
+
+        // 替换内容可以为空，相当于删除匹配项
+        let Some(replacement) = self.prompt("Replace with: ", None).await else {
+            self.buffer.set_cursor(saved);
+            return;
+        };
+
+        let mut replace_all = false;
+        let mut count = 0;
+        let (mut row, mut at) = (0, 0);
+
+        while let Some((match_row, range)) = self.find_match(&pattern, row, at) {
+            self.move_to_raw(match_row, range.start);
+
+            if !replace_all {
+                self.message = Some(Message::new(
+                    "Replace this match? (y/n/a/l/q)".to_string(),
+                ));
+                if self.refresh_screen().is_err() {
+                    break;
+                }
+                let Ok(key) = self.get_key().await else {
+                    break;
+                };
+                match key {
+                    Key::Char('y') => {}
+                    Key::Char('n') => {
+                        (row, at) = (match_row, range.start + 1);
+                        continue;
+                    }
+                    Key::Char('a') => replace_all = true,
+                    Key::Char('l') => {
+                        self.replace_match(&pattern, match_row, range, &replacement);
+                        count += 1;
+                        break;
+                    }
+                    Key::Char('q') | Key::ControlKey(ControlKey::Escape) => break,
+                    // 其他按键重新询问
+                    _ => {
+                        (row, at) = (match_row, range.start);
+                        continue;
+                    }
+                }
+            }
+
+            let start = range.start;
+            let empty = range.is_empty();
+            let len = self.replace_match(&pattern, match_row, range, &replacement);
+            count += 1;
+            // 跳过替换后的内容，避免替换内容包含查找内容时陷入死循环
+            // 正则可能匹配空字符串，这时还要再前进一个字符
+            (row, at) = (match_row, start + len + empty as usize);
+        }
+
+        self.show_message(Message::new(format!("Replaced {} occurrence(s)", count)));
+    }
+
+    /// 不逐个确认，替换整个buffer中的所有匹配项，返回替换的个数
+    pub(super) fn replace_all(&mut self, pattern: &Pattern, replacement: &[Key]) -> usize {
+        let (mut row, mut at, mut count) = (0, 0, 0);
+        while let Some((match_row, range)) = self.find_match(pattern, row, at) {
+            let start = range.start;
+            let empty = range.is_empty();
+            let len = self.replace_match(pattern, match_row, range, replacement);
+            count += 1;
+            (row, at) = (match_row, start + len + empty as usize);
+        }
+        count
+    }
+
+    /// 替换一个匹配项，返回替换后内容的长度
+    fn replace_match(
+        &mut self,
+        pattern: &Pattern,
+        row: usize,
+        range: Range<usize>,
+        replacement: &[Key],
+    ) -> usize {
+        let row = &mut self.buffer.rows[row];
+        let keys = pattern.expand(row, range.clone(), replacement);
+        let len = keys.len();
+        row.replace(range, keys);
+        self.buffer.mark_dirty();
+        // 替换后行的宽度可能变化，重新修正光标
+        self.clamp_cursor_x();
+        len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::Row;
+
+    #[tokio::test]
+    async fn counting_stops_at_the_limit() {
+        let mut editor = super::super::tests::editor(40, 5).await;
+        let pattern = Pattern::new(&Row::raw_keys("a"), false).unwrap();
+        editor.buffer.rows = vec![Row::from_text("a-a", 4); 3].into();
+        let count = editor.count_matches(&pattern, 1, 2);
+        assert_eq!((count.index, count.total), (Some(4), Some(6)));
+
+        // 每行两个匹配项，数到第500行时超过上限
+        editor.buffer.rows = vec![Row::from_text("a-a", 4); 1000].into();
+        let count = editor.count_matches(&pattern, 1, 2);
+        assert_eq!((count.index, count.total), (Some(4), None));
+        let count = editor.count_matches(&pattern, 900, 0);
+        assert_eq!((count.index, count.total), (None, None));
+
+        editor.buffer.cy = 1;
+        editor.buffer.cx = 2;
+        editor.search_state.current = Some(MatchStatus {
+            buffer_id: editor.buffer.id,
+            cursor: (2, 1),
+            count: None,
+        });
+        // 增量搜索还没有统计时不显示
+        assert_eq!(editor.match_status(&editor.buffer), None);
+        editor.count_current(&pattern);
+        assert_eq!(editor.match_status(&editor.buffer).as_deref(), Some("match 4 of 999+"));
+    }
+}
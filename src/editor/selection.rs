@@ -0,0 +1,655 @@
+use std::ops::Range;
+
+use tokio::io::AsyncReadExt;
+
+use super::buffer::Buffer;
+use super::indent::Indent;
+use super::{ControlKey, Editor, Key, Message, Row, Terminal};
+use crate::utils::color;
+
+/// 选择的方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum SelectionKind {
+    /// 按字符选择，从锚点到光标，不包括光标所在的字符
+    Char,
+    /// 按行选择，包括锚点和光标所在的整行
+    Line,
+    /// 矩形选择，锚点和光标所在的列之间的部分，不包括右边界
+    Block,
+}
+
+impl SelectionKind {
+    pub(super) fn label(self) -> &'static str {
+        match self {
+            SelectionKind::Char => "VISUAL",
+            SelectionKind::Line => "VISUAL LINE",
+            SelectionKind::Block => "VISUAL BLOCK",
+        }
+    }
+}
+
+/// 选择区域，一端固定在锚点，另一端跟随光标
+/// 位置使用(行, 渲染后的列)表示，和光标一致
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Selection {
+    pub(super) kind: SelectionKind,
+    pub(super) anchor: (usize, usize),
+    /// 矩形选择时输入的内容插入到每一行
+    pub(super) inserting: bool,
+}
+
+impl Selection {
+    /// 按先后顺序返回选择区域的两端
+    pub(super) fn bounds(&self, cursor: (usize, usize)) -> ((usize, usize), (usize, usize)) {
+        if self.anchor <= cursor {
+            (self.anchor, cursor)
+        } else {
+            (cursor, self.anchor)
+        }
+    }
+
+    /// 矩形选择的列范围
+    pub(super) fn columns(&self, cursor: (usize, usize)) -> Range<usize> {
+        self.anchor.1.min(cursor.1)..self.anchor.1.max(cursor.1)
+    }
+
+    /// 第i行中被选中的列，len是该行渲染后的宽度
+    /// 选中了行尾的换行符时范围会超出len
+    pub(super) fn highlight(
+        &self,
+        cursor: (usize, usize),
+        i: usize,
+        len: usize,
+    ) -> Option<Range<usize>> {
+        let (start, end) = self.bounds(cursor);
+        if i < start.0 || i > end.0 {
+            return None;
+        }
+        match self.kind {
+            SelectionKind::Line => Some(0..len + 1),
+            SelectionKind::Block => {
+                let columns = self.columns(cursor);
+                (columns.start < columns.end.min(len)).then_some(columns)
+            }
+            SelectionKind::Char => {
+                let from = if i == start.0 { start.1 } else { 0 };
+                let to = if i == end.0 { end.1 } else { len + 1 };
+                (from < to).then_some(from..to)
+            }
+        }
+    }
+}
+
+/// 渲染一行时需要用颜色标出的部分，都是渲染后的列
+pub(super) struct RowStyle {
+    /// 选中的列
+    highlight: Option<Range<usize>>,
+    /// 配对括号所在的列
+    brackets: Vec<usize>,
+    /// 行尾空白开始的列
+    trailing: Option<usize>,
+    /// 拼写错误的单词所在的列
+    misspelled: Vec<Range<usize>>,
+    /// 主光标之外的其他光标所在的列
+    cursors: Vec<usize>,
+    /// 竖线所在的列
+    rulers: Vec<usize>,
+    /// 缩进线所在的列
+    guides: Vec<usize>,
+}
+
+/// 文本中的位置，(行, raw索引)
+type Position = (usize, usize);
+
+/// 复制的内容
+#[derive(Debug, Clone)]
+pub(super) struct Clipboard {
+    pub(super) kind: SelectionKind,
+    /// 按字符复制时，相邻两行之间原来是换行符
+    /// 矩形复制时，每一项是一行中选中的部分
+    pub(super) lines: Vec<Vec<Key>>,
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    fn cursor_position(&self) -> (usize, usize) {
+        (self.buffer.cy as usize, self.buffer.cx as usize)
+    }
+
+    /// 开始选择，再次使用同一种方式时取消选择，使用另一种方式时切换
+    pub(super) fn toggle_selection(&mut self, kind: SelectionKind) {
+        self.buffer.selection = match self.buffer.selection {
+            Some(selection) if selection.kind == kind => None,
+            Some(selection) => Some(Selection {
+                kind,
+                inserting: false,
+                ..selection
+            }),
+            None => Some(Selection {
+                kind,
+                anchor: self.cursor_position(),
+                inserting: false,
+            }),
+        };
+    }
+
+    /// 在矩形选择的左边开始插入，change为true时先删除选中的内容
+    /// 不是矩形选择时返回false
+    pub(super) fn block_insert(&mut self, change: bool) -> bool {
+        if !self.is_block_selection() {
+            return false;
+        }
+        if self.check_writable() {
+            // 删除后光标会移到左上角，先记下选择的范围
+            let (rows, columns) = self.block_range();
+            if change {
+                self.copy_selection();
+                self.delete_block();
+            }
+            self.start_block_insert(rows, columns.start);
+        }
+        true
+    }
+
+    pub(super) fn yank_selection(&mut self) {
+        self.copy_selection();
+        self.buffer.selection = None;
+    }
+
+    /// 复制后删除选中的内容
+    pub(super) fn cut_selection(&mut self) {
+        if self.check_writable() {
+            self.copy_selection();
+            self.delete_selection();
+        }
+    }
+
+    /// 用寄存器中的内容替换选中的内容
+    pub(super) async fn paste_over_selection(&mut self) {
+        self.load_clipboard_register().await;
+        if self.check_writable() && self.registers.get().is_some() {
+            self.delete_selection();
+            self.paste().await;
+        }
+    }
+
+    /// 选择区域在raw中的位置，结束位置不包括在内
+    /// 按字符选择时结束行可能等于rows.len()，表示选中了最后一行的换行符
+    fn selection_range(&self) -> Option<(SelectionKind, Position, Position)> {
+        let selection = self.buffer.selection?;
+        let rows = &self.buffer.rows;
+        if rows.is_empty() {
+            return None;
+        }
+        let (start, end) = selection.bounds(self.cursor_position());
+        if start.0 >= rows.len() {
+            return None;
+        }
+        let raw_index = |(row, col): (usize, usize)| match rows.get(row) {
+            Some(r) => (row, r.get_raw_index(col)),
+            None => (rows.len(), 0),
+        };
+        match selection.kind {
+            SelectionKind::Char => Some((selection.kind, raw_index(start), raw_index(end))),
+            SelectionKind::Line | SelectionKind::Block => {
+                let last = rows.len() - 1;
+                Some((selection.kind, (start.0.min(last), 0), (end.0.min(last), 0)))
+            }
+        }
+    }
+
+    fn copy_selection(&mut self) {
+        let Some((kind, lines)) = self.selected_text() else {
+            return;
+        };
+        let count = lines.len();
+        self.store_register(Clipboard { kind, lines });
+        self.show_message(Message::new(format!("Copied {} line(s)", count)));
+    }
+
+    /// 选中的内容，按字符选择时第一行和最后一行只包括选中的部分
+    pub(super) fn selected_text(&self) -> Option<(SelectionKind, Vec<Vec<Key>>)> {
+        let (kind, start, end) = self.selection_range()?;
+        let rows = &self.buffer.rows;
+        let lines: Vec<Vec<Key>> = match kind {
+            SelectionKind::Block => {
+                let (rows_range, columns) = self.block_range();
+                rows.range(rows_range)
+                    .map(|r| r.keys_in(Self::block_raw_range(r, &columns)))
+                    .collect()
+            }
+            SelectionKind::Line => rows.range(start.0..end.0 + 1).map(|r| r.keys()).collect(),
+            SelectionKind::Char if start.0 == end.0 => {
+                vec![rows[start.0].keys_in(start.1..end.1)]
+            }
+            SelectionKind::Char => {
+                let mut lines = vec![rows[start.0].keys_in(start.1..rows[start.0].len())];
+                lines.extend(rows.range(start.0 + 1..end.0).map(|r| r.keys()));
+                lines.push(rows.get(end.0).map_or(Vec::new(), |r| r.keys_in(0..end.1)));
+                lines
+            }
+        };
+        Some((kind, lines))
+    }
+
+    /// 选择区域在每一行中的raw范围
+    pub(super) fn selected_ranges(&self) -> Option<Vec<(usize, Range<usize>)>> {
+        let (kind, start, end) = self.selection_range()?;
+        let rows = &self.buffer.rows;
+        let ranges = match kind {
+            SelectionKind::Block => {
+                let (rows_range, columns) = self.block_range();
+                rows_range
+                    .map(|i| (i, Self::block_raw_range(&rows[i], &columns)))
+                    .collect()
+            }
+            SelectionKind::Line => (start.0..end.0 + 1).map(|i| (i, 0..rows[i].len())).collect(),
+            SelectionKind::Char => (start.0..(end.0 + 1).min(rows.len()))
+                .map(|i| {
+                    let from = if i == start.0 { start.1 } else { 0 };
+                    let to = if i == end.0 { end.1 } else { rows[i].len() };
+                    (i, from..to)
+                })
+                .collect(),
+        };
+        Some(ranges)
+    }
+
+    fn delete_selection(&mut self) {
+        let Some((kind, start, end)) = self.selection_range() else {
+            self.buffer.selection = None;
+            return;
+        };
+        if kind == SelectionKind::Block {
+            self.delete_block();
+            self.buffer.selection = None;
+            return;
+        }
+        let tab_width = self.config.tab_width;
+        let rows = &mut self.buffer.rows;
+        match kind {
+            SelectionKind::Block => unreachable!(),
+            SelectionKind::Line => {
+                rows.remove_range(start.0..end.0 + 1);
+            }
+            SelectionKind::Char if start.0 == end.0 => {
+                rows[start.0].replace(start.1..end.1, Vec::new());
+            }
+            SelectionKind::Char => {
+                let tail = rows.get(end.0).map_or(Vec::new(), |r| r.keys_in(end.1..r.len()));
+                let len = rows[start.0].len();
+                rows[start.0].replace(start.1..len, tail);
+                rows.remove_range(start.0 + 1..(end.0 + 1).min(rows.len()));
+            }
+        }
+        if rows.is_empty() {
+            rows.push(Row::new(Vec::new(), tab_width));
+        }
+        self.buffer.selection = None;
+        self.buffer.mark_dirty();
+        self.set_position(start);
+    }
+
+    /// 将光标移到(行, raw索引)
+    pub(super) fn set_position(&mut self, (row, at): Position) {
+        let row = row.min(self.buffer.rows.len().saturating_sub(1));
+        let cx = match self.buffer.rows.get(row) {
+            Some(r) if at < r.len() => r.get_render_index(at).0,
+            Some(r) => r.display_len(),
+            None => 0,
+        };
+        self.buffer.cy = row as u16;
+        self.buffer.cx = cx as u16;
+        self.scroll();
+    }
+
+    /// 粘贴选择的寄存器中的内容
+    /// 按字符复制的内容插入到光标处，按行复制的内容插入到当前行的下面
+    pub(super) async fn paste(&mut self) {
+        if !self.check_writable() {
+            return;
+        }
+        self.load_clipboard_register().await;
+        let Some(clipboard) = self.registers.take() else {
+            self.show_message(Message::new("Nothing to paste".to_string()));
+            return;
+        };
+        let tab_width = self.config.tab_width;
+        let cy = self.buffer.cy as usize;
+        let rows = &mut self.buffer.rows;
+        let new_rows = clipboard.lines.iter().map(|l| Row::new(l.clone(), tab_width));
+        let position = match clipboard.kind {
+            SelectionKind::Block => {
+                // 每一行插入到同一列，行不够长时用空格补齐
+                let cx = self.buffer.cx as usize;
+                for (i, line) in clipboard.lines.iter().enumerate() {
+                    if cy + i == rows.len() {
+                        rows.push(Row::new(Vec::new(), tab_width));
+                    }
+                    Self::insert_at_column(&mut rows[cy + i], cx, line.clone());
+                }
+                let row = &rows[cy];
+                (cy, row.get_raw_index(cx))
+            }
+            SelectionKind::Line => {
+                let at = (cy + 1).min(rows.len());
+                rows.insert_rows(at, new_rows);
+                (at, 0)
+            }
+            SelectionKind::Char => {
+                if cy == rows.len() {
+                    rows.push(Row::new(Vec::new(), tab_width));
+                }
+                let at = rows[cy].get_raw_index(self.buffer.cx as usize);
+                let tail = rows[cy].keys_in(at..rows[cy].len());
+                let len = rows[cy].len();
+                rows[cy].replace(at..len, Vec::new());
+                let mut lines = clipboard.lines.clone();
+                let last = lines.len() - 1;
+                let end = (cy + last, lines[last].len() + if last == 0 { at } else { 0 });
+                lines[last].extend(tail);
+                let first = lines.remove(0);
+                let len = rows[cy].len();
+                rows[cy].replace(len..len, first);
+                let new_rows = lines.into_iter().map(|l| Row::new(l, tab_width));
+                rows.insert_rows(cy + 1, new_rows);
+                end
+            }
+        };
+        self.buffer.mark_dirty();
+        self.set_position(position);
+    }
+
+    /// 增加或减少选中行的缩进
+    pub(super) fn indent_selection(&mut self, indent: bool) {
+        if !self.check_writable() {
+            return;
+        }
+        let Some((_, start, end)) = self.selection_range() else {
+            return;
+        };
+        let unit = self.indent_unit();
+        let width = match self.indent() {
+            Indent::Spaces(width) => width,
+            Indent::Tabs => self.config.tab_width,
+        };
+        let last = end.0.min(self.buffer.rows.len() - 1);
+        for row in self.buffer.rows.range_mut(start.0..last + 1) {
+            if indent {
+                if !row.is_empty() {
+                    row.replace(0..0, unit.clone());
+                }
+            } else {
+                let width = match row.text().chars().next() {
+                    Some('\t') => 1,
+                    _ => row.text().chars().take(width).take_while(|&c| c == ' ').count(),
+                };
+                row.replace(0..width, Vec::new());
+            }
+        }
+        self.buffer.mark_dirty();
+        self.clamp_cursor_x();
+    }
+
+    /// 第i行渲染时需要标出的部分
+    /// cursor是窗口中光标的位置(cx, cy)，brackets是整个窗口中配对括号的位置
+    pub(super) fn row_style(
+        &self,
+        buffer: &Buffer,
+        i: usize,
+        (cx, cy): (usize, usize),
+        focused: bool,
+        brackets: &[(usize, usize)],
+    ) -> RowStyle {
+        let row = &buffer.rows[i];
+        let rendered = row.render();
+        // 只有焦点窗口显示选择区域
+        let highlight = buffer
+            .selection
+            .filter(|_| focused)
+            .and_then(|s| s.highlight((cy, cx), i, rendered.display_len()));
+        let brackets = brackets.iter().filter(|(r, _)| *r == i).map(|(_, c)| *c).collect();
+        // 正在行尾输入时不标出刚输入的空白
+        let trailing = rendered.column_of(row.trailing_whitespace());
+        let typing = focused && i == cy && cx >= trailing;
+        let trailing = (trailing < rendered.display_len() && !typing).then_some(trailing);
+        // 不标出光标前正在输入的单词
+        let misspelled = self
+            .misspelled_words(buffer.current_file.as_deref(), &row.keys())
+            .into_iter()
+            .map(|word| rendered.column_of(word.start)..rendered.column_of(word.end))
+            .filter(|word| !(focused && i == cy && word.end == cx))
+            .collect();
+        let cursors = buffer
+            .cursors
+            .iter()
+            .filter(|(r, _)| focused && *r == i)
+            .map(|(_, at)| rendered.column_of(*at))
+            .collect();
+        RowStyle {
+            highlight,
+            brackets,
+            trailing,
+            misspelled,
+            cursors,
+            rulers: self.rulers.clone(),
+            guides: self.indent_guides(buffer, i),
+        }
+    }
+
+    /// 渲染一行中从col_offset开始的width列，选中的部分使用高亮颜色
+    pub(super) fn render_row(
+        &self,
+        row: &Row,
+        col_offset: usize,
+        width: usize,
+        style: &RowStyle,
+    ) -> String {
+        let selection_color = self.theme.selection.as_str();
+        let highlight = &style.highlight;
+        let end = col_offset + width;
+        let mut line = String::new();
+        let mut selected = false;
+        let mut select = |line: &mut String, in_range: bool| {
+            if in_range != selected {
+                line.push_str(if in_range { selection_color } else { color::RESET });
+                selected = in_range;
+            }
+        };
+        let rendered = row.render();
+        for (cell, text) in rendered.cells() {
+            if cell.col + cell.width <= col_offset {
+                continue;
+            }
+            if cell.col >= end {
+                break;
+            }
+            let in_range = highlight.as_ref().is_some_and(|r| r.contains(&cell.col));
+            select(&mut line, in_range);
+            let mark = if in_range {
+                None
+            } else if style.cursors.contains(&cell.col) {
+                Some(self.theme.secondary_cursor.as_str())
+            } else if style.brackets.contains(&cell.col) {
+                Some(self.theme.matching_bracket.as_str())
+            } else if style.trailing.is_some_and(|t| cell.col >= t) {
+                Some(self.theme.trailing_whitespace.as_str())
+            } else if style.misspelled.iter().any(|w| w.contains(&cell.col)) {
+                Some(self.theme.misspelled.as_str())
+            } else if style.rulers.contains(&cell.col) {
+                Some(self.theme.ruler.as_str())
+            } else {
+                None
+            };
+            if cell.col < col_offset || cell.col + cell.width > end {
+                // 宽字符只有一部分在窗口内，露出的部分用空格代替
+                let visible = (cell.col + cell.width).min(end) - cell.col.max(col_offset);
+                line.push_str(&" ".repeat(visible));
+            } else if !in_range && style.guides.contains(&cell.col) {
+                // 缩进中的空格或者tab，tab剩下的宽度用空格补齐
+                line.push_str(&self.theme.indent_guide);
+                line.push('│');
+                line.push_str(color::RESET);
+                line.push_str(&" ".repeat(cell.width - 1));
+            } else if let Some(mark) = mark {
+                line.push_str(mark);
+                line.push_str(text);
+                line.push_str(color::RESET);
+            } else {
+                line.push_str(text);
+            }
+        }
+        // 选中了换行符时在行尾显示一个高亮的空格
+        let len = row.display_len();
+        let mut col = len.clamp(col_offset, end);
+        if highlight.as_ref().is_some_and(|r| r.contains(&len)) && (col_offset..end).contains(&len) {
+            select(&mut line, true);
+            line.push(' ');
+            col += 1;
+        }
+        select(&mut line, false);
+        // 在行尾的光标也显示成一个空格
+        if col == len && style.cursors.contains(&len) && (col_offset..end).contains(&len) {
+            line.push_str(&self.theme.secondary_cursor);
+            line.push(' ');
+            line.push_str(color::RESET);
+            col += 1;
+        }
+        // 行尾后面的缩进线和竖线，缩进线只在空行中出现
+        let mut marks: Vec<(usize, &str)> = style
+            .guides
+            .iter()
+            .map(|c| (*c, self.theme.indent_guide.as_str()))
+            .chain(style.rulers.iter().map(|c| (*c, self.theme.ruler.as_str())))
+            .filter(|(c, _)| *c >= col && *c < end)
+            .collect();
+        marks.sort_by_key(|(c, _)| *c);
+        marks.dedup_by_key(|(c, _)| *c);
+        for (c, color) in marks {
+            line.push_str(&" ".repeat(c - col));
+            line.push_str(color);
+            line.push('│');
+            line.push_str(color::RESET);
+            col = c + 1;
+        }
+        line
+    }
+
+    fn is_block_selection(&self) -> bool {
+        self.buffer
+            .selection
+            .is_some_and(|s| s.kind == SelectionKind::Block)
+    }
+
+    /// 矩形选择的行范围和列范围
+    fn block_range(&self) -> (Range<usize>, Range<usize>) {
+        let Some(selection) = self.buffer.selection else {
+            return (0..0, 0..0);
+        };
+        let cursor = self.cursor_position();
+        let (start, end) = selection.bounds(cursor);
+        let end = (end.0 + 1).min(self.buffer.rows.len());
+        (start.0.min(end)..end, selection.columns(cursor))
+    }
+
+    /// 一行中位于渲染后的列范围内的按键在raw中的范围
+    fn block_raw_range(row: &Row, columns: &Range<usize>) -> Range<usize> {
+        row.get_raw_index(columns.start)..row.get_raw_index(columns.end)
+    }
+
+    /// 在渲染后的列处插入按键，行不够长时先用空格补齐
+    fn insert_at_column(row: &mut Row, column: usize, keys: Vec<Key>) {
+        let len = row.display_len();
+        if len < column {
+            let padding = vec![Key::Char(' '); column - len];
+            row.replace(row.len()..row.len(), padding);
+        }
+        let at = row.get_raw_index(column);
+        row.replace(at..at, keys);
+    }
+
+    /// 删除矩形选择的内容，光标移到左上角
+    fn delete_block(&mut self) {
+        if !self.check_writable() {
+            return;
+        }
+        let (rows, columns) = self.block_range();
+        for row in self.buffer.rows.range_mut(rows.clone()) {
+            let range = Self::block_raw_range(row, &columns);
+            row.replace(range, Vec::new());
+        }
+        self.buffer.mark_dirty();
+        self.buffer.cy = rows.start as u16;
+        self.buffer.cx = columns.start as u16;
+        self.clamp_cursor_x();
+        self.scroll();
+    }
+
+    /// 开始在矩形选择的左边界插入，选择区域收缩为宽度为0的一列
+    fn start_block_insert(&mut self, rows: Range<usize>, column: usize) {
+        if rows.is_empty() {
+            self.buffer.selection = None;
+            return;
+        }
+        self.buffer.selection = Some(Selection {
+            kind: SelectionKind::Block,
+            anchor: (rows.start, column),
+            inserting: true,
+        });
+        self.buffer.cy = (rows.end - 1) as u16;
+        self.buffer.cx = column as u16;
+        self.scroll();
+    }
+
+    /// 矩形插入时的按键，输入的内容插入到每一行，ESC结束插入
+    /// 返回false表示按键结束了插入，需要按普通按键处理
+    pub(super) fn block_insert_command(&mut self, key: &Key) -> bool {
+        let (rows, columns) = self.block_range();
+        let column = columns.start;
+        match key {
+            Key::Char(_) | Key::Text(_) | Key::ControlKey(ControlKey::Tab) => {
+                let tab_width = self.config.tab_width;
+                let width = match key {
+                    Key::ControlKey(ControlKey::Tab) => tab_width - column % tab_width,
+                    _ => key.get_display_width(tab_width),
+                };
+                let keys = match key {
+                    Key::Text(text) => text.chars().map(Key::Char).collect(),
+                    key => vec![key.clone()],
+                };
+                for row in self.buffer.rows.range_mut(rows.clone()) {
+                    Self::insert_at_column(row, column, keys.clone());
+                }
+                self.move_block_column(column + width);
+            }
+            Key::ControlKey(ControlKey::Backspace) if column > 0 => {
+                let mut width = 0;
+                for row in self.buffer.rows.range_mut(rows.clone()) {
+                    if row.display_len() < column {
+                        continue;
+                    }
+                    let at = row.get_raw_index(column - 1);
+                    let (start, end) = row.get_render_index(at);
+                    width = width.max(end - start);
+                    row.replace(at..at + 1, Vec::new());
+                }
+                self.move_block_column(column.saturating_sub(width.max(1)));
+            }
+            Key::ControlKey(ControlKey::Backspace) => {}
+            _ => {
+                self.buffer.selection = None;
+                // ESC只结束插入，其他按键结束插入后按普通按键处理
+                return *key == Key::ControlKey(ControlKey::Escape);
+            }
+        }
+        self.buffer.mark_dirty();
+        true
+    }
+
+    fn move_block_column(&mut self, column: usize) {
+        if let Some(selection) = &mut self.buffer.selection {
+            selection.anchor.1 = column;
+        }
+        self.buffer.cx = column as u16;
+        self.scroll();
+    }
+}
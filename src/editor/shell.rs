@@ -0,0 +1,157 @@
+use std::env;
+use std::ops::Range;
+use std::process::Stdio;
+
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::process::Command;
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::buffer::LoadEvent;
+use super::format::LineEnding;
+use super::{Editor, Message, Row, Terminal};
+
+/// 用于执行命令的shell
+fn shell_command(command: &str) -> Command {
+    let shell = env::var("SHELL").unwrap_or_else(|_| "sh".to_string());
+    let mut cmd = Command::new(shell);
+    cmd.arg("-c").arg(command).kill_on_drop(true);
+    cmd
+}
+
+/// 逐行读取命令的输出并发送给buffer，stderr合并到stdout中
+async fn stream_output(command: String, id: usize, tab_width: usize, sender: UnboundedSender<LoadEvent>) {
+    // 在shell中重定向，这样两者的输出按实际的顺序交错
+    let mut child = match shell_command(&format!("exec 2>&1\n{}", command))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            let _ = sender.send(LoadEvent::Failed(id, e.to_string()));
+            return;
+        }
+    };
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let mut lines = BufReader::new(stdout).lines();
+    loop {
+        match lines.next_line().await {
+            Ok(Some(line)) => {
+                let _ = sender.send(LoadEvent::Rows(id, vec![Row::from_text(&line, tab_width)]));
+            }
+            Ok(None) => break,
+            Err(e) => {
+                let _ = sender.send(LoadEvent::Failed(id, e.to_string()));
+                return;
+            }
+        }
+    }
+    match child.wait().await {
+        Ok(status) if !status.success() => {
+            let row = Row::from_text(format!("[{}]", status), tab_width);
+            let _ = sender.send(LoadEvent::Rows(id, vec![row]));
+        }
+        Err(e) => {
+            let _ = sender.send(LoadEvent::Failed(id, e.to_string()));
+            return;
+        }
+        _ => {}
+    }
+    let _ = sender.send(LoadEvent::Done(id, Vec::new(), LineEnding::Lf));
+}
+
+/// 把text写入命令的stdin，返回stdout，命令失败时返回stderr的内容
+pub(super) async fn pipe_through(command: &str, text: String) -> Result<String, String> {
+    let mut child = shell_command(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    // 同时写入和读取，输出很多时命令会等待读取而不再接收输入
+    let writer = tokio::spawn(async move {
+        let _ = stdin.write_all(text.as_bytes()).await;
+    });
+    let output = child.wait_with_output().await.map_err(|e| e.to_string())?;
+    let _ = writer.await;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let first = stderr.lines().next().unwrap_or_default();
+        return Err(format!("{}: {}", output.status, first));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 在后台执行命令，输出显示在标题为[!命令]的buffer中
+    pub(super) fn run_shell(&mut self, command: &str) {
+        self.show_list(&format!("[!{}]", command), Vec::new());
+        // 再次执行同一个命令时停止上一次的输出
+        if let Some(loader) = self.buffer.loader.take() {
+            loader.abort();
+        }
+        let task = stream_output(
+            command.to_string(),
+            self.buffer.id,
+            self.config.tab_width,
+            self.load_sender.clone(),
+        );
+        self.buffer.loader = Some(tokio::spawn(task));
+    }
+
+    /// 把lines中的行通过命令过滤，用命令的输出替换
+    /// 命令失败时不修改buffer
+    pub(super) async fn filter_lines(&mut self, lines: Range<usize>, command: &str) {
+        if !self.check_writable() {
+            return;
+        }
+        if self.buffer.is_loading() {
+            self.show_message(Message::warning("File is still loading".to_string()));
+            return;
+        }
+        let lines = lines.start..lines.end.min(self.buffer.rows.len());
+        let mut text = String::new();
+        for row in self.buffer.rows.range(lines.clone()) {
+            text.push_str(row.text());
+            text.push('\n');
+        }
+        self.show_message(Message::new(format!("Running {}...", command)));
+        let _ = self.refresh_screen();
+        let output = match pipe_through(command, text).await {
+            Ok(output) => output,
+            Err(e) => {
+                self.show_message(Message::warning(format!("Filter failed: {}", e)));
+                return;
+            }
+        };
+        let tab_width = self.config.tab_width;
+        let rows: Vec<Row> = output
+            .lines()
+            .map(|line| Row::from_text(line, tab_width))
+            .collect();
+        let count = rows.len();
+        self.buffer.rows.remove_range(lines.clone());
+        self.buffer.rows.insert_rows(lines.start, rows);
+        self.buffer.selection = None;
+        self.buffer.mark_dirty();
+        self.set_position((lines.start, 0));
+        self.show_message(Message::new(format!(
+            "{} lines filtered through {}",
+            count, command
+        )));
+    }
+
+    /// 在消息栏中输入命令，过滤选中的行
+    pub(super) async fn filter_selection(&mut self) {
+        let lines = self.selected_lines();
+        let Some(input) = self.prompt("!", None).await else {
+            return;
+        };
+        let command = Row::raw_str(&input);
+        if command.trim().is_empty() {
+            return;
+        }
+        self.filter_lines(lines, &command).await;
+    }
+}
@@ -0,0 +1,102 @@
+use std::future;
+use std::io;
+
+use tokio::io::AsyncReadExt;
+#[cfg(unix)]
+use tokio::signal::unix::{Signal, SignalKind, signal};
+use tracing::warn;
+
+use super::{Editor, Terminal};
+use crate::error::EditorError;
+
+/// 要求编辑器退出的信号：SIGTERM和关闭终端时的SIGHUP
+/// 注册失败的信号保持默认的行为
+#[derive(Default)]
+pub(super) struct Signals {
+    #[cfg(unix)]
+    terminate: Option<Signal>,
+    #[cfg(unix)]
+    hangup: Option<Signal>,
+    /// 等待按键时收到的信号，取消正在等待的提示，当前命令结束之后再退出
+    pub(super) received: Option<&'static str>,
+}
+
+#[cfg(unix)]
+fn listen(kind: SignalKind, name: &str) -> Option<Signal> {
+    signal(kind)
+        .inspect_err(|e| warn!("Failed to listen for {}: {}", name, e))
+        .ok()
+}
+
+/// 等待信号，没有注册时一直等待
+#[cfg(unix)]
+async fn recv(signal: &mut Option<Signal>) {
+    // 信号的流结束之后不会再收到信号
+    if let Some(signal) = signal
+        && signal.recv().await.is_some()
+    {
+        return;
+    }
+    future::pending().await
+}
+
+impl Signals {
+    #[cfg(unix)]
+    pub(super) fn new() -> Self {
+        Self {
+            terminate: listen(SignalKind::terminate(), "SIGTERM"),
+            hangup: listen(SignalKind::hangup(), "SIGHUP"),
+            received: None,
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub(super) fn new() -> Self {
+        Self::default()
+    }
+
+    /// 等待任意一个信号，返回信号的名称
+    #[cfg(unix)]
+    pub(super) async fn recv(&mut self) -> &'static str {
+        tokio::select! {
+            _ = recv(&mut self.terminate) => "SIGTERM",
+            _ = recv(&mut self.hangup) => "SIGHUP",
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub(super) async fn recv(&mut self) -> &'static str {
+        future::pending().await
+    }
+}
+
+/// 收到信号后等待按键返回的错误，提示和对话框按取消处理
+pub(super) fn interrupted(signal: &str) -> EditorError {
+    io::Error::new(io::ErrorKind::Interrupted, format!("Received {}", signal)).into()
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 收到退出信号时把有修改的buffer写入交换文件，析构时恢复终端并输出写入的文件
+    pub(super) fn terminate(&mut self, signal: &str) {
+        warn!("Received {}, exiting", signal);
+        self.recovered = self.write_recovery_files();
+        self.quit = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[tokio::test]
+    async fn signal_cancels_waiting_prompts() {
+        let mut editor = super::super::tests::editor(40, 5).await;
+        editor.signals.received = Some("SIGTERM");
+        assert!(!editor.confirm("Quit?").await);
+        assert!(editor.overlay.is_none());
+        assert_eq!(editor.prompt("Search: ", None).await, None);
+        assert!(editor.prompt_cursor.is_none());
+        // 信号留到run中处理，命令结束之后退出
+        assert_eq!(editor.signals.received, Some("SIGTERM"));
+        editor.run().await;
+        assert!(editor.quit);
+    }
+}
@@ -0,0 +1,350 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+
+use tokio::io::AsyncReadExt;
+
+use super::motion::is_word;
+use super::{ControlKey, Editor, Key, Message, Modifiers, Row, Terminal, status};
+use crate::config::Config;
+
+/// 代码片段，按文件类型分组，all中的片段在所有文件中都可以使用
+/// 放在配置目录的snippets.toml中，比如
+/// [rust]
+/// fn = "fn ${1:name}($2) {\n\t$0\n}"
+#[derive(Debug, Default)]
+pub(super) struct Snippets {
+    groups: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Snippets {
+    fn path() -> Option<PathBuf> {
+        Some(Config::dir()?.join("snippets.toml"))
+    }
+
+    /// 加载snippets.toml，文件不存在时为空
+    pub(super) fn load() -> Result<Self, String> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+        let groups = toml::from_str(&content)
+            .map_err(|e| format!("{}: {}", path.display(), e.message()))?;
+        Ok(Self { groups })
+    }
+
+    /// 先查找文件类型的片段，再查找all中的片段
+    fn get(&self, file_type: Option<&str>, trigger: &str) -> Option<&str> {
+        let group = |name: &str| self.groups.get(name)?.get(trigger);
+        file_type
+            .and_then(|t| group(&t.to_ascii_lowercase()))
+            .or_else(|| group("all"))
+            .map(String::as_str)
+    }
+}
+
+/// 占位符在buffer中的位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Field {
+    row: usize,
+    start: usize,
+    len: usize,
+}
+
+/// 展开后还没有结束的片段，Tab和Shift+Tab在占位符之间跳转
+pub(super) struct SnippetSession {
+    buffer_id: usize,
+    /// 按跳转顺序排列的占位符，同一个编号出现多次时一起修改，第一个是输入的位置
+    stops: Vec<Vec<Field>>,
+    current: usize,
+    /// 当前占位符所在行上一次的长度，用来计算输入改变了多少
+    row_len: usize,
+    /// 刚跳到占位符，输入时先删除默认文本
+    fresh: bool,
+}
+
+impl SnippetSession {
+    /// 把row行中from之后的占位符移动delta，skip是正在修改的占位符
+    fn shift(&mut self, row: usize, from: usize, delta: isize, skip: (usize, usize)) {
+        for (i, stop) in self.stops.iter_mut().enumerate() {
+            for (j, field) in stop.iter_mut().enumerate() {
+                if (i, j) != skip && field.row == row && field.start >= from {
+                    field.start = field.start.saturating_add_signed(delta);
+                }
+            }
+        }
+    }
+}
+
+/// 解析后的片段
+struct Body {
+    lines: Vec<Vec<Key>>,
+    /// (编号, 行, 起始位置, 长度)
+    fields: Vec<(usize, usize, usize, usize)>,
+}
+
+/// 解析片段的内容，后面的行加上indent，行首的tab换成缩进单位unit
+/// $1、${1}和${1:默认文本}是占位符，$0是最后停留的位置，\$是$本身
+/// 同一个编号再次出现且没有默认文本时使用第一次的默认文本
+fn parse_body(body: &str, indent: &[Key], unit: &[Key]) -> Body {
+    let mut lines = vec![Vec::new()];
+    let mut fields = Vec::new();
+    let mut defaults: HashMap<usize, Vec<Key>> = HashMap::new();
+    let mut line_start = false;
+    let mut chars = body.chars().peekable();
+    while let Some(c) = chars.next() {
+        let index = lines.len() - 1;
+        let line = &mut lines[index];
+        if line_start && c != '\t' {
+            line_start = false;
+        }
+        match c {
+            '\\' if chars.peek() == Some(&'$') => {
+                chars.next();
+                line.push(Key::Char('$'));
+            }
+            '$' if chars.peek().is_some_and(|c| c.is_ascii_digit() || *c == '{') => {
+                let braced = chars.next_if_eq(&'{').is_some();
+                let mut number = String::new();
+                while let Some(d) = chars.next_if(char::is_ascii_digit) {
+                    number.push(d);
+                }
+                let mut default = Vec::new();
+                if braced {
+                    if chars.next_if_eq(&':').is_some() {
+                        let text = chars.by_ref().take_while(|c| *c != '}');
+                        default.extend(text.map(Key::Char));
+                    } else {
+                        chars.next_if_eq(&'}');
+                    }
+                }
+                let number = number.parse().unwrap_or(0);
+                if default.is_empty() {
+                    default = defaults.get(&number).cloned().unwrap_or_default();
+                } else {
+                    defaults.entry(number).or_insert_with(|| default.clone());
+                }
+                fields.push((number, index, line.len(), default.len()));
+                line.extend(default);
+            }
+            '\n' => {
+                lines.push(indent.to_vec());
+                line_start = true;
+            }
+            '\t' if line_start => line.extend(unit.iter().cloned()),
+            '\t' => line.push(Key::ControlKey(ControlKey::Tab)),
+            c => line.push(Key::Char(c)),
+        }
+    }
+    Body { lines, fields }
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 重新加载snippets.toml
+    pub(super) fn load_snippets(&mut self) {
+        match Snippets::load() {
+            Ok(snippets) => self.snippets = snippets,
+            Err(e) => self.show_message(Message::warning(format!("Error loading snippets: {}", e))),
+        }
+    }
+
+    /// 光标前的单词是片段的触发词时展开，返回false表示没有展开
+    pub(super) fn expand_snippet(&mut self) -> bool {
+        let cy = self.buffer.cy as usize;
+        let Some(row) = self.buffer.rows.get(cy) else {
+            return false;
+        };
+        let end = row.get_raw_index(self.buffer.cx as usize);
+        let keys = row.keys();
+        let len = keys[..end].iter().rev().take_while(|k| is_word(k)).count();
+        if len == 0 {
+            return false;
+        }
+        let trigger = Row::raw_str(&keys[end - len..end]);
+        let file_type = self.buffer.current_file.as_deref().and_then(status::file_type);
+        let Some(body) = self.snippets.get(file_type, &trigger).map(str::to_string) else {
+            return false;
+        };
+        if !self.check_writable() {
+            return true;
+        }
+        let start = end - len;
+        let indent: Vec<Key> = keys
+            .iter()
+            .take_while(|k| matches!(k, Key::Char(' ') | Key::ControlKey(ControlKey::Tab)))
+            .cloned()
+            .collect();
+        let Body { lines, fields } = parse_body(&body, &indent, &self.indent_unit());
+
+        // 第一行接在触发词前面的内容后面，最后一行后面接光标后面的内容
+        let last = lines.len() - 1;
+        let end_position = (cy + last, lines[last].len() + if last == 0 { start } else { 0 });
+        let before = &keys[..start];
+        let after = &keys[end..];
+        let tab_width = self.config.tab_width;
+        let rows: Vec<Row> = lines
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut raw)| {
+                if i == 0 {
+                    raw.splice(0..0, before.iter().cloned());
+                }
+                if i == last {
+                    raw.extend(after.iter().cloned());
+                }
+                Row::new(raw, tab_width)
+            })
+            .collect();
+        self.buffer.rows.remove(cy);
+        self.buffer.rows.insert_rows(cy, rows);
+        self.buffer.mark_dirty();
+
+        // 按编号排列占位符，$0在最后，没有$0时停在片段的末尾
+        let mut numbers: Vec<usize> = fields.iter().map(|f| f.0).collect();
+        numbers.sort_by_key(|n| (*n == 0, *n));
+        numbers.dedup();
+        let mut stops: Vec<Vec<Field>> = numbers
+            .iter()
+            .map(|n| {
+                let fields = fields.iter().filter(|f| f.0 == *n);
+                fields
+                    .map(|&(_, line, col, len)| Field {
+                        row: cy + line,
+                        start: col + if line == 0 { start } else { 0 },
+                        len,
+                    })
+                    .collect()
+            })
+            .collect();
+        if numbers.last() != Some(&0) {
+            let (row, start) = end_position;
+            stops.push(vec![Field { row, start, len: 0 }]);
+        }
+        self.snippet = Some(SnippetSession {
+            buffer_id: self.buffer.id,
+            stops,
+            current: 0,
+            row_len: 0,
+            fresh: false,
+        });
+        self.jump_to_stop(0);
+        true
+    }
+
+    /// 光标移到第index个占位符的末尾，到最后一个时片段结束
+    fn jump_to_stop(&mut self, index: usize) {
+        let Some(session) = &mut self.snippet else {
+            return;
+        };
+        let Some(field) = session.stops.get(index).and_then(|s| s.first()).copied() else {
+            return;
+        };
+        session.current = index;
+        session.fresh = field.len > 0;
+        session.row_len = self.buffer.rows.get(field.row).map_or(0, |r| r.len());
+        if index + 1 == session.stops.len() {
+            self.snippet = None;
+        }
+        self.set_position((field.row, field.start + field.len));
+    }
+
+    /// 有展开的片段时先处理按键，返回true表示按键已经处理
+    /// Tab跳到下一个占位符，Shift+Tab跳到上一个，Esc结束片段
+    pub(super) fn snippet_command(&mut self, key: &Key) -> bool {
+        let Some(session) = &mut self.snippet else {
+            return false;
+        };
+        if session.buffer_id != self.buffer.id {
+            self.snippet = None;
+            return false;
+        }
+        let (current, fresh) = (session.current, session.fresh);
+        session.fresh = false;
+        match key {
+            Key::ControlKey(ControlKey::Tab) => self.jump_to_stop(current + 1),
+            Key::Modified(Modifiers { shift: true, .. }, key)
+                if **key == Key::ControlKey(ControlKey::Tab) =>
+            {
+                self.jump_to_stop(current.saturating_sub(1))
+            }
+            Key::ControlKey(ControlKey::Escape) => self.snippet = None,
+            // 刚跳到的占位符中的默认文本被输入的内容代替
+            Key::Char(_) if fresh => {
+                self.clear_field();
+                return false;
+            }
+            Key::ControlKey(ControlKey::Backspace | ControlKey::Delete) if fresh => {
+                self.clear_field();
+                self.sync_snippet();
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// 删除当前占位符中的文本
+    fn clear_field(&mut self) {
+        let Some(session) = &mut self.snippet else {
+            return;
+        };
+        let field = session.stops[session.current][0];
+        if let Some(row) = self.buffer.rows.get_mut(field.row) {
+            row.replace(field.start..field.start + field.len, Vec::new());
+            self.buffer.mark_dirty();
+        }
+        self.set_position((field.row, field.start));
+    }
+
+    /// 处理完按键后更新当前占位符的长度，并把它的内容复制到同一个编号的其他位置
+    /// 光标离开当前占位符时片段结束
+    pub(super) fn sync_snippet(&mut self) {
+        let Some(session) = &mut self.snippet else {
+            return;
+        };
+        let current = session.current;
+        let field = session.stops[current][0];
+        let cy = self.buffer.cy as usize;
+        let Some(row) = self.buffer.rows.get(cy) else {
+            self.snippet = None;
+            return;
+        };
+        let delta = row.len() as isize - session.row_len as isize;
+        let len = field.len as isize + delta;
+        let mut at = row.get_raw_index(self.buffer.cx as usize);
+        if cy != field.row || len < 0 || at < field.start || at > field.start + len as usize {
+            self.snippet = None;
+            return;
+        }
+        let len = len as usize;
+        session.shift(field.row, field.start + field.len, delta, (current, 0));
+        session.stops[current][0].len = len;
+        let text = row.keys_in(field.start..field.start + len);
+
+        for i in 1..session.stops[current].len() {
+            let mirror = session.stops[current][i];
+            let Some(row) = self.buffer.rows.get_mut(mirror.row) else {
+                continue;
+            };
+            if mirror.start + mirror.len > row.len()
+                || row.keys_in(mirror.start..mirror.start + mirror.len) == text
+            {
+                continue;
+            }
+            row.replace(mirror.start..mirror.start + mirror.len, text.clone());
+            let delta = text.len() as isize - mirror.len as isize;
+            session.shift(mirror.row, mirror.start + mirror.len, delta, (current, i));
+            session.stops[current][i].len = text.len();
+            // 同一行中前面的副本变长或变短时光标跟着移动
+            if mirror.row == cy && mirror.start < at {
+                at = at.saturating_add_signed(delta);
+            }
+        }
+        let field = session.stops[current][0];
+        session.row_len = self.buffer.rows[field.row].len();
+        self.set_position((cy, at));
+    }
+}
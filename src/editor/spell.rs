@@ -0,0 +1,394 @@
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use tokio::io::AsyncReadExt;
+
+use super::completion::Completion;
+use super::key::Direction;
+use super::{ControlKey, Editor, Key, Message, Row, Terminal, status};
+use crate::config::Config;
+
+/// 没有配置dictionary时依次尝试的系统词典
+const SYSTEM_DICTIONARIES: &[&str] = &[
+    "/usr/share/dict/words",
+    "/usr/share/dict/american-english",
+    "/usr/share/dict/british-english",
+    "/usr/dict/words",
+];
+/// 最多显示的建议数
+const MAX_SUGGESTIONS: usize = 9;
+
+/// 拼写检查使用的单词表
+pub(super) struct Dictionary {
+    words: HashSet<String>,
+}
+
+/// 文件中需要检查拼写的部分
+enum Syntax {
+    /// 纯文本，检查整行
+    Text,
+    /// 代码，只检查注释和双引号中的字符串
+    Code {
+        line_comment: Option<&'static str>,
+        block_comment: bool,
+    },
+}
+
+impl Syntax {
+    fn of(file_type: Option<&str>) -> Self {
+        let (line_comment, block_comment) = match file_type {
+            None | Some("Text" | "Markdown") => return Self::Text,
+            Some("Rust" | "C" | "C++" | "Go" | "JavaScript" | "TypeScript" | "Java") => {
+                (Some("//"), true)
+            }
+            Some("CSS") => (None, true),
+            Some("Python" | "Shell" | "TOML" | "YAML" | "Makefile") => (Some("#"), false),
+            Some(_) => (None, false),
+        };
+        Self::Code {
+            line_comment,
+            block_comment,
+        }
+    }
+}
+
+fn starts_with(raw: &[Key], at: usize, text: &str) -> bool {
+    let mut keys = raw[at..].iter();
+    text.chars().all(|c| keys.next() == Some(&Key::Char(c)))
+}
+
+/// 一行中需要检查拼写的范围
+/// 每一行单独判断，跨行的块注释只能识别以*开头的中间行
+fn spell_regions(raw: &[Key], syntax: &Syntax) -> Vec<Range<usize>> {
+    let Syntax::Code {
+        line_comment,
+        block_comment,
+    } = *syntax
+    else {
+        let line = 0..raw.len();
+        return vec![line];
+    };
+    let indent = raw.iter().take_while(|k| !matches!(k, Key::Char(c) if !c.is_whitespace())).count();
+    if block_comment && starts_with(raw, indent, "*") {
+        let comment = indent..raw.len();
+        return vec![comment];
+    }
+    let mut regions = Vec::new();
+    let mut i = 0;
+    while i < raw.len() {
+        if line_comment.is_some_and(|prefix| starts_with(raw, i, prefix)) {
+            regions.push(i..raw.len());
+            break;
+        }
+        let end = if block_comment && starts_with(raw, i, "/*") {
+            (i + 2..raw.len()).find(|&j| starts_with(raw, j, "*/")).map_or(raw.len(), |j| j + 2)
+        } else if raw[i] == Key::Char('"') {
+            let mut j = i + 1;
+            while j < raw.len() && raw[j] != Key::Char('"') {
+                j += if raw[j] == Key::Char('\\') { 2 } else { 1 };
+            }
+            (j + 1).min(raw.len())
+        } else {
+            i += 1;
+            continue;
+        };
+        regions.push(i..end);
+        i = end;
+    }
+    regions
+}
+
+/// 范围内需要检查的单词，跳过带数字或下划线的标识符、驼峰写法和全大写的缩写
+fn words(raw: &[Key], range: Range<usize>) -> Vec<Range<usize>> {
+    let char_at = |i: usize| match raw[i] {
+        Key::Char(c) => Some(c),
+        _ => None,
+    };
+    let is_part = |i: usize| char_at(i).is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '\'');
+    let mut words = Vec::new();
+    let mut i = range.start;
+    while i < range.end {
+        if !is_part(i) {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < range.end && is_part(i) {
+            i += 1;
+        }
+        // 去掉两边的引号
+        let mut word = start..i;
+        while word.start < word.end && char_at(word.start) == Some('\'') {
+            word.start += 1;
+        }
+        while word.end > word.start && char_at(word.end - 1) == Some('\'') {
+            word.end -= 1;
+        }
+        let chars: Vec<char> = word.clone().filter_map(char_at).collect();
+        let plain = chars.iter().all(|c| c.is_alphabetic() || *c == '\'');
+        let inner_upper = chars.iter().skip(1).any(|c| c.is_uppercase());
+        if chars.len() > 1 && plain && !inner_upper {
+            words.push(word);
+        }
+    }
+    words
+}
+
+/// 编辑距离，相邻字符交换算一次
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut rows = vec![(0..=b.len()).collect::<Vec<_>>()];
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (rows[i - 1][j] + 1).min(row[j - 1] + 1).min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        rows.push(row);
+    }
+    rows[a.len()][b.len()]
+}
+
+impl Dictionary {
+    /// 自己添加的单词，每行一个
+    fn personal_path() -> Option<PathBuf> {
+        Some(Config::dir()?.join("words.txt"))
+    }
+
+    /// 加载词典和配置目录的words.txt中添加的单词
+    pub(super) fn load(path: Option<&Path>) -> Result<Self, String> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => SYSTEM_DICTIONARIES
+                .iter()
+                .map(PathBuf::from)
+                .find(|p| p.is_file())
+                .ok_or("No dictionary found, set dictionary in the config file")?,
+        };
+        let content = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut words: HashSet<String> = content.lines().map(str::trim).map(str::to_string).collect();
+        if let Some(personal) = Self::personal_path()
+            && let Ok(content) = fs::read_to_string(personal)
+        {
+            words.extend(content.lines().map(str::trim).map(str::to_string));
+        }
+        words.remove("");
+        Ok(Self { words })
+    }
+
+    /// 句首大写的单词也按小写查找
+    fn contains(&self, word: &str) -> bool {
+        self.words.contains(word) || self.words.contains(&word.to_lowercase())
+    }
+
+    /// 把单词加入words.txt
+    fn add(&mut self, word: &str) -> io::Result<()> {
+        let path = Self::personal_path().ok_or(io::ErrorKind::NotFound)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", word)?;
+        self.words.insert(word.to_string());
+        Ok(())
+    }
+
+    /// 一行中拼写错误的单词在raw中的范围
+    fn misspelled(&self, raw: &[Key], syntax: &Syntax) -> Vec<Range<usize>> {
+        spell_regions(raw, syntax)
+            .into_iter()
+            .flat_map(|region| words(raw, region))
+            .filter(|word| {
+                let text = Row::raw_str(&raw[word.clone()]);
+                !self.contains(&text)
+            })
+            .collect()
+    }
+
+    /// 编辑距离不超过2的单词，距离近的排在前面，保持首字母的大小写
+    fn suggestions(&self, word: &str) -> Vec<String> {
+        let target: Vec<char> = word.to_lowercase().chars().collect();
+        let capitalized = word.chars().next().is_some_and(char::is_uppercase);
+        let mut matches: Vec<(usize, &String)> = self
+            .words
+            .iter()
+            .filter(|w| w.chars().count().abs_diff(target.len()) <= 2)
+            .filter_map(|w| {
+                let chars: Vec<char> = w.to_lowercase().chars().collect();
+                let distance = edit_distance(&chars, &target);
+                (distance <= 2).then_some((distance, w))
+            })
+            .collect();
+        matches.sort();
+        let mut seen = HashSet::new();
+        matches
+            .into_iter()
+            .map(|(_, w)| {
+                let mut chars = w.chars();
+                match chars.next() {
+                    Some(first) if capitalized => first.to_uppercase().chain(chars).collect(),
+                    _ => w.clone(),
+                }
+            })
+            .filter(|w| seen.insert(w.clone()))
+            .take(MAX_SUGGESTIONS)
+            .collect()
+    }
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 按配置开启或者关闭拼写检查
+    pub(super) fn load_dictionary(&mut self) {
+        self.spell = None;
+        if !self.config.spell_check {
+            return;
+        }
+        match Dictionary::load(self.config.dictionary.as_deref()) {
+            Ok(dictionary) => self.spell = Some(dictionary),
+            Err(e) => self.show_message(Message::warning(format!("Spell check disabled: {}", e))),
+        }
+    }
+
+    pub(super) fn toggle_spell_check(&mut self) {
+        if self.spell.take().is_some() {
+            self.show_message(Message::new("Spell check off".to_string()));
+            return;
+        }
+        match Dictionary::load(self.config.dictionary.as_deref()) {
+            Ok(dictionary) => {
+                self.spell = Some(dictionary);
+                self.show_message(Message::new("Spell check on".to_string()));
+            }
+            Err(e) => self.show_message(Message::warning(e)),
+        }
+    }
+
+    /// 一行中拼写错误的单词在raw中的范围，没有开启拼写检查时为空
+    pub(super) fn misspelled_words(&self, path: Option<&Path>, raw: &[Key]) -> Vec<Range<usize>> {
+        let Some(dictionary) = &self.spell else {
+            return Vec::new();
+        };
+        let syntax = Syntax::of(path.and_then(status::file_type));
+        dictionary.misspelled(raw, &syntax)
+    }
+
+    /// 跳到下一个或者上一个拼写错误的单词，到文件末尾后从头开始
+    pub(super) fn goto_misspelling(&mut self, forward: bool) {
+        if self.spell.is_none() {
+            self.show_message(Message::warning("Spell check is off".to_string()));
+            return;
+        }
+        let len = self.buffer.rows.len();
+        if len == 0 {
+            return;
+        }
+        let path = self.buffer.current_file.clone();
+        let cy = (self.buffer.cy as usize).min(len - 1);
+        let at = self.buffer.rows[cy].get_raw_index(self.buffer.cx as usize);
+        // 多检查一次当前行，光标前面或者后面的单词在最后才轮到
+        for step in 0..=len {
+            let i = if forward { (cy + step) % len } else { (cy + len - step % len) % len };
+            let starts = self
+                .misspelled_words(path.as_deref(), &self.buffer.rows[i].keys())
+                .into_iter()
+                .map(|word| word.start);
+            let found = match (forward, step) {
+                (true, 0) => starts.filter(|s| *s > at).min(),
+                (false, 0) => starts.filter(|s| *s < at).max(),
+                (true, _) => starts.min(),
+                (false, _) => starts.max(),
+            };
+            if let Some(start) = found {
+                self.set_position((i, start));
+                return;
+            }
+        }
+        self.show_message(Message::new("No misspelled words".to_string()));
+    }
+
+    /// 显示光标所在的拼写错误的单词的建议，回车替换，a加入词典，数字直接选择
+    pub(super) async fn spell_suggestions(&mut self) {
+        if self.spell.is_none() {
+            self.show_message(Message::warning("Spell check is off".to_string()));
+            return;
+        }
+        let cy = self.buffer.cy as usize;
+        let Some(row) = self.buffer.rows.get(cy) else {
+            return;
+        };
+        let at = row.get_raw_index(self.buffer.cx as usize);
+        let path = self.buffer.current_file.as_deref();
+        let Some(word) = self
+            .misspelled_words(path, &row.keys())
+            .into_iter()
+            .find(|w| w.start <= at && at <= w.end)
+        else {
+            self.show_message(Message::new("No misspelled word under cursor".to_string()));
+            return;
+        };
+        let text = Row::raw_str(&row.keys_in(word.clone()));
+        let items = self.spell.as_ref().map(|d| d.suggestions(&text)).unwrap_or_default();
+        let hint = if items.is_empty() {
+            format!("No suggestions for '{}': a(dd to dictionary)", text)
+        } else {
+            "Spelling: Enter/1-9 replace, a(dd to dictionary), Esc cancel".to_string()
+        };
+        let mut selected = 0;
+        let choice = loop {
+            self.message = Some(Message::new(hint.clone()));
+            self.completion = Some(Completion::new(word.start, items.clone(), selected));
+            if self.refresh_screen().is_err() {
+                break None;
+            }
+            let Ok(key) = self.get_key().await else {
+                break None;
+            };
+            let count = items.len().max(1);
+            match key {
+                Key::ControlKey(ControlKey::Tab | ControlKey::Ctrl('n'))
+                | Key::ArrowKey(Direction::Down) => selected = (selected + 1) % count,
+                Key::ControlKey(ControlKey::Ctrl('p')) | Key::ArrowKey(Direction::Up) => {
+                    selected = (selected + count - 1) % count
+                }
+                Key::ControlKey(ControlKey::CR) => break items.get(selected).cloned(),
+                Key::Char(c @ '1'..='9') => {
+                    let index = c as usize - '1' as usize;
+                    if index < items.len() {
+                        break items.get(index).cloned();
+                    }
+                }
+                Key::Char('a') => {
+                    let result = self.spell.as_mut().map(|d| d.add(&text));
+                    self.message = None;
+                    if let Some(Err(e)) = result {
+                        self.show_message(Message::warning(format!("Error adding word: {}", e)));
+                    } else {
+                        self.show_message(Message::new(format!("Added '{}' to dictionary", text)));
+                    }
+                    break None;
+                }
+                _ => break None,
+            }
+        };
+        self.completion = None;
+        if self.message.as_ref().is_some_and(|m| m.text == hint) {
+            self.message = None;
+        }
+        let Some(replacement) = choice else {
+            return;
+        };
+        if !self.check_writable() {
+            return;
+        }
+        let keys: Vec<Key> = replacement.chars().map(Key::Char).collect();
+        let end = word.start + keys.len();
+        self.buffer.rows[cy].replace(word, keys);
+        self.buffer.mark_dirty();
+        self.set_position((cy, end));
+    }
+}
@@ -0,0 +1,121 @@
+use tokio::io::AsyncReadExt;
+
+use super::selection::SelectionKind;
+use super::{Editor, Message, Row, Terminal};
+use crate::reader::{Bom, decoder};
+
+/// 按编码保存时的字节数，无法表示的字符按UTF-8计算
+fn encoded_len(encoding: &str, text: &str) -> usize {
+    if encoding.eq_ignore_ascii_case("utf-8") {
+        return text.len();
+    }
+    decoder::encode(encoding, text).map_or(text.len(), |bytes| bytes.len())
+}
+
+/// 行数、单词数、字符数和字节数，单词是用空白分隔的部分，换行符也算在字符和字节中
+#[derive(Default)]
+struct Counts {
+    lines: usize,
+    words: usize,
+    chars: usize,
+    bytes: usize,
+}
+
+impl Counts {
+    fn add(&mut self, text: &str, encoding: &str) {
+        self.words += text.split_whitespace().count();
+        self.chars += text.chars().count();
+        self.bytes += encoded_len(encoding, text);
+    }
+
+    fn label(&self) -> String {
+        format!(
+            "{} lines, {} words, {} chars, {} bytes",
+            self.lines, self.words, self.chars, self.bytes
+        )
+    }
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 在消息栏中显示统计，有选择区域时统计选中的内容，并且在选择变化时更新
+    pub(super) fn show_statistics(&mut self) {
+        let whole = self.buffer_statistics();
+        let text = self.statistics(&whole);
+        self.show_message(Message::new(text));
+        self.live_statistics = self.buffer.selection.is_some().then_some(whole);
+    }
+
+    /// 选择区域变化后更新消息栏中的统计，选择结束后不再更新
+    /// 整个buffer的统计使用执行命令时的结果，避免大文件每次按键都要遍历
+    pub(super) fn update_statistics(&mut self) {
+        let Some(whole) = &self.live_statistics else {
+            return;
+        };
+        if self.buffer.selection.is_none() {
+            self.live_statistics = None;
+            return;
+        }
+        self.message = Some(Message::new(self.statistics(whole)));
+    }
+
+    /// 选中的内容的统计加上整个buffer的统计，以及光标所在的字节和字符偏移量
+    fn statistics(&self, whole: &str) -> String {
+        let encoding = self.buffer.encoding.as_str();
+        let ending = self.buffer.line_ending.as_str();
+        let mut parts = Vec::new();
+        if let Some((kind, lines)) = self.selected_text() {
+            let mut counts = Counts {
+                lines: lines.len(),
+                ..Default::default()
+            };
+            for (i, line) in lines.iter().enumerate() {
+                counts.add(&Row::raw_str(line), encoding);
+                // 按行选择时包括每一行的换行符，其他选择只包括行之间的换行符
+                if kind == SelectionKind::Line || i + 1 < lines.len() {
+                    counts.add(ending, encoding);
+                }
+            }
+            parts.push(format!("Selected: {}", counts.label()));
+        }
+        parts.push(whole.to_string());
+        let (byte, char) = self.cursor_offset();
+        parts.push(format!("Cursor: byte {}, char {}", byte, char));
+        parts.join(" | ")
+    }
+
+    fn buffer_statistics(&self) -> String {
+        let encoding = self.buffer.encoding.as_str();
+        let ending = self.buffer.line_ending.as_str();
+        let mut counts = Counts {
+            lines: self.buffer.rows.len(),
+            ..Default::default()
+        };
+        for row in self.buffer.rows.iter() {
+            counts.add(row.text(), encoding);
+            counts.add(ending, encoding);
+        }
+        if let Some(bom) = Bom::for_encoding(encoding).filter(|_| self.buffer.bom) {
+            counts.bytes += bom.bytes().len();
+        }
+        format!("Buffer: {}", counts.label())
+    }
+
+    /// 光标之前的内容按编码的字节数和字符数，从0开始，字节数包括BOM
+    fn cursor_offset(&self) -> (usize, usize) {
+        let encoding = self.buffer.encoding.as_str();
+        let ending = self.buffer.line_ending.as_str();
+        let cy = (self.buffer.cy as usize).min(self.buffer.rows.len());
+        let mut counts = Counts::default();
+        for row in self.buffer.rows.range(0..cy) {
+            counts.add(row.text(), encoding);
+            counts.add(ending, encoding);
+        }
+        if let Some(row) = self.buffer.rows.get(cy) {
+            counts.add(&row.text()[..row.byte_index(self.raw_cursor())], encoding);
+        }
+        if let Some(bom) = Bom::for_encoding(encoding).filter(|_| self.buffer.bom) {
+            counts.bytes += bom.bytes().len();
+        }
+        (counts.bytes, counts.chars)
+    }
+}
@@ -0,0 +1,168 @@
+use std::path::Path;
+
+use serde::Deserialize;
+use tokio::io::AsyncReadExt;
+use unicode_width::UnicodeWidthStr;
+
+use super::{Buffer, Editor, Terminal};
+use crate::utils::{self, color};
+
+/// 状态栏中的一段内容，在配置文件的status_left和status_right中按顺序列出
+/// 内容为空的段不显示
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatusSegment {
+    /// 选择模式，只在焦点窗口中显示
+    Mode,
+    /// 正在录制宏的寄存器
+    Recording,
+    /// 打开了多个buffer时显示buffer编号
+    Buffer,
+    FileName,
+    /// 有未保存的修改
+    Modified,
+    ReadOnly,
+    /// 还在后台加载
+    Loading,
+    /// 缩进方式，检测到的或者配置的
+    Indent,
+    /// 根据扩展名判断的文件类型
+    FileType,
+    /// 编码和BOM
+    Encoding,
+    LineEnding,
+    /// 有损解码替换过的字节数，保存时会写入替换字符
+    Damage,
+    /// 当前是第几个搜索结果
+    Matches,
+    /// 光标所在的行和列
+    Position,
+    /// 文件所在仓库的分支，有未提交的修改时加上*
+    Branch,
+}
+
+impl StatusSegment {
+    pub fn default_left() -> Vec<Self> {
+        use StatusSegment::*;
+        vec![Mode, Recording, Buffer, FileName, Modified, ReadOnly, Loading]
+    }
+
+    pub fn default_right() -> Vec<Self> {
+        use StatusSegment::*;
+        vec![Matches, Damage, Branch, Indent, FileType, Encoding, LineEnding, Position]
+    }
+}
+
+/// 根据扩展名判断文件类型，无法判断时返回None
+pub(super) fn file_type(path: &Path) -> Option<&'static str> {
+    if path.file_name()? == "Makefile" {
+        return Some("Makefile");
+    }
+    let file_type = match path.extension()?.to_str()? {
+        "rs" => "Rust",
+        "c" | "h" => "C",
+        "cc" | "cpp" | "cxx" | "hpp" => "C++",
+        "go" => "Go",
+        "py" => "Python",
+        "js" | "mjs" => "JavaScript",
+        "ts" => "TypeScript",
+        "java" => "Java",
+        "sh" | "bash" => "Shell",
+        "md" | "markdown" => "Markdown",
+        "toml" => "TOML",
+        "json" => "JSON",
+        "yaml" | "yml" => "YAML",
+        "html" | "htm" => "HTML",
+        "css" => "CSS",
+        "txt" => "Text",
+        _ => return None,
+    };
+    Some(file_type)
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    fn segment_text(
+        &self,
+        segment: StatusSegment,
+        buffer: &Buffer,
+        (cx, cy): (u16, u16),
+        focused: bool,
+    ) -> Option<String> {
+        let text = match segment {
+            StatusSegment::Mode => match buffer.selection {
+                Some(selection) if focused => format!("-- {} --", selection.kind.label()),
+                _ => return None,
+            },
+            StatusSegment::Recording => format!("recording @{}", self.macros.recording()?),
+            StatusSegment::Buffer if self.buffers.is_empty() => return None,
+            StatusSegment::Buffer => format!("[{}]", buffer.id),
+            StatusSegment::FileName => buffer.name().to_string(),
+            StatusSegment::Modified if buffer.is_dirty() => match buffer.unsaved_changes() {
+                1 => "(1 unsaved change)".to_string(),
+                n => format!("({} unsaved changes)", n),
+            },
+            StatusSegment::ReadOnly if buffer.readonly => "[RO]".to_string(),
+            StatusSegment::Loading if buffer.is_loading() => "loading...".to_string(),
+            StatusSegment::Loading if buffer.is_following() => "following".to_string(),
+            StatusSegment::Modified | StatusSegment::ReadOnly | StatusSegment::Loading => {
+                return None;
+            }
+            StatusSegment::Indent => self.indent_of(buffer).label(),
+            StatusSegment::FileType => file_type(buffer.current_file.as_deref()?)?.to_string(),
+            StatusSegment::Encoding if buffer.bom => format!("{} BOM", buffer.encoding),
+            StatusSegment::Encoding => buffer.encoding.clone(),
+            StatusSegment::LineEnding => buffer.line_ending.label().to_string(),
+            StatusSegment::Damage => match buffer.damage.len() {
+                0 => return None,
+                n => format!("[{} replaced]", n),
+            },
+            StatusSegment::Matches if focused => self.match_status(buffer)?,
+            StatusSegment::Matches => return None,
+            StatusSegment::Branch => buffer.repo.as_ref()?.label(),
+            StatusSegment::Position => {
+                format!("Ln {}/{}, Col {}", cy + 1, buffer.rows.len(), cx + 1)
+            }
+        };
+        Some(text)
+    }
+
+    fn render_segments(
+        &self,
+        segments: &[StatusSegment],
+        buffer: &Buffer,
+        cursor: (u16, u16),
+        focused: bool,
+    ) -> String {
+        segments
+            .iter()
+            .filter_map(|&segment| self.segment_text(segment, buffer, cursor, focused))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// 左边的段靠左对齐，右边的段靠右对齐，宽度不够时优先截断左边
+    pub(super) fn render_status_bar(
+        &self,
+        buffer: &Buffer,
+        cursor: (u16, u16),
+        width: u16,
+        focused: bool,
+    ) -> String {
+        let width = width as usize;
+        let left = self.render_segments(&self.config.status_left, buffer, cursor, focused);
+        let right = self.render_segments(&self.config.status_right, buffer, cursor, focused);
+        let right_width = right.width();
+        let content = if right_width + 1 > width {
+            utils::fit_width(&right, width)
+        } else {
+            let left = utils::fit_width(&left, width - right_width - 1);
+            format!("{} {}", left, right)
+        };
+        let color = if focused {
+            &self.theme.status_bar
+        } else {
+            &self.theme.inactive_status_bar
+        };
+        format!("{}{}{}", color, content, color::RESET)
+    }
+}
@@ -0,0 +1,99 @@
+use regex::Regex;
+use tokio::io::AsyncReadExt;
+
+use super::status::file_type;
+use super::{Editor, Message, Terminal};
+
+/// 每种文件类型中定义符号的行，第一个分组是符号的名称
+fn symbol_patterns(file_type: &str) -> &'static [&'static str] {
+    match file_type {
+        "Rust" => &[
+            r#"^\s*(?:pub(?:\([^)]*\))?\s+)?(?:(?:async|const|unsafe|extern(?:\s+"[^"]*")?)\s+)*(?:fn|struct|enum|union|trait|type|mod|static|const)\s+(?:mut\s+)?([A-Za-z_]\w*)"#,
+            r"^\s*macro_rules!\s*([A-Za-z_]\w*)",
+            r"^\s*(?:unsafe\s+)?(impl\b[^{]*?)\s*(?:where\b.*)?\{?\s*$",
+        ],
+        "C" | "C++" => &[
+            r"^\s*(?:typedef\s+)?(?:struct|class|union|enum(?:\s+class)?|namespace)\s+([A-Za-z_]\w*)\s*(?:[:{].*)?$",
+            r"^#\s*define\s+([A-Za-z_]\w*)",
+            r"^[A-Za-z_][\w\s\*&:<>,]*?[\s\*&]([A-Za-z_~][\w:~]*)\s*\([^;]*$",
+        ],
+        "Go" => &[
+            r"^func\s+(?:\([^)]*\)\s*)?([A-Za-z_]\w*)",
+            r"^\s*type\s+([A-Za-z_]\w*)",
+        ],
+        "Python" => &[r"^\s*(?:async\s+)?(?:def|class)\s+([A-Za-z_]\w*)"],
+        "JavaScript" | "TypeScript" => &[
+            r"^\s*(?:export\s+)?(?:default\s+)?(?:declare\s+)?(?:abstract\s+)?(?:async\s+)?(?:function\s*\*?|class|interface|type|enum|namespace)\s*([A-Za-z_$][\w$]*)",
+            r"^\s*(?:export\s+)?(?:const|let|var)\s+([A-Za-z_$][\w$]*)\s*=\s*(?:async\s+)?(?:function\b|\([^)]*\)\s*=>|[A-Za-z_$][\w$]*\s*=>)",
+        ],
+        "Java" => &[
+            r"^\s*(?:(?:public|private|protected|static|final|abstract|sealed)\s+)*(?:class|interface|enum|record)\s+([A-Za-z_]\w*)",
+            r"^\s*(?:(?:public|private|protected|static|final|abstract|synchronized|native)\s+)+[\w<>\[\],\s]+?\s+([A-Za-z_]\w*)\s*\(",
+        ],
+        "Shell" => &[
+            r"^\s*(?:function\s+)?([A-Za-z_][\w-]*)\s*\(\)",
+            r"^\s*function\s+([A-Za-z_][\w-]*)",
+        ],
+        "Markdown" => &[r"^(#{1,6}\s+.+?)\s*#*\s*$"],
+        "Makefile" => &[r"^([\w./%-]+)\s*:(?:[^=]|$)"],
+        "TOML" => &[r"^\s*\[\[?\s*([^\]]+?)\s*\]\]?"],
+        "YAML" => &[r"^([A-Za-z_][\w-]*)\s*:"],
+        "CSS" => &[r"^\s*([^\s{}/][^{}]*?)\s*\{"],
+        _ => &[],
+    }
+}
+
+/// buffer中的一个符号，位置是(行, raw索引)
+struct Symbol {
+    name: String,
+    position: (usize, usize),
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 按文件类型的模式找出当前buffer中定义的符号
+    fn buffer_symbols(&self) -> Option<Vec<Symbol>> {
+        let file_type = file_type(self.buffer.current_file.as_deref()?)?;
+        let patterns: Vec<Regex> = symbol_patterns(file_type)
+            .iter()
+            .map(|p| Regex::new(p).expect("valid regex"))
+            .collect();
+        if patterns.is_empty() {
+            return None;
+        }
+        let mut symbols = Vec::new();
+        for (i, row) in self.buffer.rows.iter().enumerate() {
+            let text = row.text().to_string();
+            let Some(name) = patterns.iter().find_map(|p| p.captures(&text)?.get(1)) else {
+                continue;
+            };
+            let column = text[..name.start()].chars().count();
+            symbols.push(Symbol {
+                name: name.as_str().to_string(),
+                position: (i, column),
+            });
+        }
+        Some(symbols)
+    }
+
+    /// 在菜单中列出当前buffer中的符号，输入的字符过滤符号，选中后跳过去
+    pub(super) async fn symbol_picker(&mut self) {
+        let Some(symbols) = self.buffer_symbols() else {
+            self.show_message(Message::new("No symbol pattern for this file type".to_string()));
+            return;
+        };
+        if symbols.is_empty() {
+            self.show_message(Message::new("No symbols".to_string()));
+            return;
+        }
+        let items: Vec<String> = symbols
+            .iter()
+            .map(|s| format!("{} :{}", s.name, s.position.0 + 1))
+            .collect();
+        let Some(index) = self.pick("Symbol: ", &items).await else {
+            return;
+        };
+        self.push_jump();
+        self.set_position(symbols[index].position);
+        self.center_cursor();
+    }
+}
@@ -0,0 +1,232 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use tokio::io::AsyncReadExt;
+
+use super::{Editor, Message, OpenOptions, Row, Terminal};
+
+/// 标签文件的名称，从当前文件所在的目录开始向上查找
+const TAGS_FILE: &str = "tags";
+
+/// 标签所在的行，ctags生成的通常是查找行的模式
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Address {
+    Line(usize),
+    /// 去掉了^和$的模式，start和end表示是否需要从行首或者到行尾都相同
+    Pattern { text: String, start: bool, end: bool },
+}
+
+impl Address {
+    /// 解析 123、/^fn main() {$/ 或者 ?^fn main() {$?
+    fn parse(address: &str) -> Option<Self> {
+        if let Ok(line) = address.parse() {
+            return Some(Self::Line(line));
+        }
+        let delimiter = address.chars().next().filter(|c| matches!(c, '/' | '?'))?;
+        let inner = address[1..].strip_suffix(delimiter)?;
+        let mut text = String::new();
+        let mut chars = inner.chars();
+        while let Some(c) = chars.next() {
+            match (c, chars.clone().next()) {
+                ('\\', Some(next)) if next == delimiter || next == '\\' => {
+                    text.push(next);
+                    chars.next();
+                }
+                _ => text.push(c),
+            }
+        }
+        let start = text.starts_with('^');
+        if start {
+            text.remove(0);
+        }
+        let end = text.ends_with('$') && !text.ends_with("\\$");
+        if end {
+            text.pop();
+        }
+        Some(Self::Pattern { text, start, end })
+    }
+
+    fn matches(&self, line: &str) -> bool {
+        match self {
+            Self::Line(_) => false,
+            Self::Pattern { text, start: true, end: true } => line == text,
+            Self::Pattern { text, start: true, .. } => line.starts_with(text.as_str()),
+            Self::Pattern { text, end: true, .. } => line.ends_with(text.as_str()),
+            Self::Pattern { text, .. } => line.contains(text.as_str()),
+        }
+    }
+}
+
+/// tags文件中的一行：名称、文件、位置，后面是;"开头的扩展字段
+#[derive(Debug, Clone)]
+struct Tag {
+    name: String,
+    path: PathBuf,
+    address: Address,
+    /// 扩展字段中的类型，比如f表示函数
+    kind: Option<String>,
+}
+
+impl Tag {
+    /// 文件的路径相对于tags文件所在的目录
+    fn parse(line: &str, dir: &Path) -> Option<Self> {
+        if line.starts_with("!_TAG_") {
+            return None;
+        }
+        let mut parts = line.splitn(3, '\t');
+        let (name, file, rest) = (parts.next()?, parts.next()?, parts.next()?);
+        let (address, fields) = match rest.rfind(";\"") {
+            Some(i) => (&rest[..i], &rest[i + 2..]),
+            None => (rest, ""),
+        };
+        let kind = fields
+            .split('\t')
+            .filter(|f| !f.is_empty())
+            .find_map(|f| match f.strip_prefix("kind:") {
+                Some(kind) => Some(kind),
+                None => (!f.contains(':')).then_some(f),
+            })
+            .map(str::to_string);
+        Some(Self {
+            name: name.to_string(),
+            path: relative(&dir.join(file)),
+            address: Address::parse(address.trim_end())?,
+            kind,
+        })
+    }
+
+    /// 有多个同名的标签时在菜单中显示的内容
+    fn label(&self) -> String {
+        let location = match &self.address {
+            Address::Line(line) => line.to_string(),
+            Address::Pattern { text, .. } => text.trim().to_string(),
+        };
+        match &self.kind {
+            Some(kind) => format!("{} {}: {}", kind, self.path.display(), location),
+            None => format!("{}: {}", self.path.display(), location),
+        }
+    }
+}
+
+/// 在当前目录下的文件用相对路径，和打开文件时使用的路径一致
+fn relative(path: &Path) -> PathBuf {
+    let cwd = std::env::current_dir().ok();
+    match cwd.as_deref().and_then(|cwd| path.strip_prefix(cwd).ok()) {
+        Some(path) => path.to_path_buf(),
+        None => path.to_path_buf(),
+    }
+}
+
+/// 从dir开始向上查找tags文件
+fn find_tags_file(dir: &Path) -> Option<PathBuf> {
+    let dir = std::path::absolute(dir).ok()?;
+    dir.ancestors().map(|d| d.join(TAGS_FILE)).find(|p| p.is_file())
+}
+
+/// tags文件中名称为name的标签
+fn lookup(tags: &Path, name: &str) -> std::io::Result<Vec<Tag>> {
+    let content = fs::read(tags)?;
+    let content = String::from_utf8_lossy(&content);
+    let dir = tags.parent().unwrap_or(Path::new(""));
+    let prefix = format!("{}\t", name);
+    Ok(content
+        .lines()
+        .filter(|line| line.starts_with(&prefix))
+        .filter_map(|line| Tag::parse(line, dir))
+        .collect())
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 跳到光标所在标识符的定义
+    pub(super) async fn goto_definition(&mut self) {
+        let row = self.buffer.rows.get(self.buffer.cy as usize);
+        match self.word_under_cursor().zip(row) {
+            Some((word, row)) => self.goto_tag(&Row::raw_str(&row.keys_in(word))).await,
+            None => self.show_message(Message::new("No identifier under cursor".to_string())),
+        }
+    }
+
+    /// 在tags文件中查找name并跳过去，跳转前的位置加入跳转列表
+    /// 有多个同名的标签时在菜单中选择
+    pub(super) async fn goto_tag(&mut self, name: &str) {
+        let dir = match self.buffer.current_file.as_deref().and_then(Path::parent) {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let Some(tags) = find_tags_file(&dir).or_else(|| find_tags_file(Path::new("."))) else {
+            self.show_message(Message::warning("No tags file found".to_string()));
+            return;
+        };
+        let mut found = match lookup(&tags, name) {
+            Ok(found) => found,
+            Err(e) => {
+                self.show_message(Message::warning(format!("Error reading {}: {}", tags.display(), e)));
+                return;
+            }
+        };
+        let tag = match found.len() {
+            0 => {
+                self.show_message(Message::new(format!("Tag not found: {}", name)));
+                return;
+            }
+            1 => found.remove(0),
+            n => {
+                let items: Vec<String> = found.iter().map(Tag::label).collect();
+                let prompt = format!("{} ({} tags): ", name, n);
+                match self.pick(&prompt, &items).await {
+                    Some(index) => found.swap_remove(index),
+                    None => return,
+                }
+            }
+        };
+        self.jump_to_tag(&tag).await;
+    }
+
+    async fn jump_to_tag(&mut self, tag: &Tag) {
+        let line = match &tag.address {
+            Address::Line(line) => Some(*line),
+            Address::Pattern { .. } => self.find_tag_line(tag).await,
+        };
+        self.push_jump();
+        let options = OpenOptions {
+            line,
+            ..Default::default()
+        };
+        if let Err(e) = self.open_file(&tag.path, &options).await {
+            self.show_message(Message::error(format!("Error opening {}: {}", tag.path.display(), e), &e));
+            return;
+        }
+        let Some(line) = line else {
+            self.show_message(Message::warning(format!("Tag pattern not found: {}", tag.name)));
+            return;
+        };
+        let row = line.saturating_sub(1);
+        // 光标放在行中的名称上
+        let text = self.buffer.rows.get(row).map(|r| r.text().to_string()).unwrap_or_default();
+        let column = text.find(&tag.name).map_or(0, |i| text[..i].chars().count());
+        self.set_position((row, column));
+        self.center_cursor();
+    }
+
+    /// 按模式查找标签所在的行，从1开始
+    /// 文件已经打开时在buffer中查找，这样修改过的buffer也能找到正确的行
+    async fn find_tag_line(&self, tag: &Tag) -> Option<usize> {
+        let opened = std::iter::once(&self.buffer)
+            .chain(&self.buffers)
+            .find(|b| b.current_file.as_deref() == Some(tag.path.as_path()));
+        let index = match opened {
+            Some(buffer) => buffer
+                .rows
+                .iter()
+                .position(|row| tag.address.matches(row.text()))?,
+            None => {
+                let content = tokio::fs::read(&tag.path).await.ok()?;
+                let content = String::from_utf8_lossy(&content);
+                content
+                    .lines()
+                    .position(|line| tag.address.matches(line.strip_suffix('\r').unwrap_or(line)))?
+            }
+        };
+        Some(index + 1)
+    }
+}
@@ -0,0 +1,4 @@
+use super::Row;
+
+/// 编辑器中的行按块存储，见fim_core::text::TextBuffer
+pub(super) type TextBuffer = fim_core::text::TextBuffer<Row>;
@@ -0,0 +1,534 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tokio::io::AsyncReadExt;
+use tracing::warn;
+
+use super::buffer::Buffer;
+use super::event::Event;
+use super::git::format_age;
+use super::key::{ControlKey, Direction};
+use super::overlay::{Anchor, Overlay};
+use super::text::TextBuffer;
+use super::{Editor, Key, Message, Row, Terminal};
+use crate::config::Config;
+
+/// 保存撤销历史的目录，放在配置目录下
+const DIR_NAME: &str = "undo";
+
+/// 撤销历史文件的第一行，格式改变时修改版本号
+const FILE_HEADER: &str = "fim-undo 1";
+
+/// 文本中的位置，(行, raw索引)
+type Position = (usize, usize);
+
+/// 一个状态和父状态之间的差别：从start行开始，removed被替换成了inserted
+/// 只在保存撤销历史时计算
+struct Diff {
+    start: usize,
+    removed: Vec<String>,
+    inserted: Vec<String>,
+}
+
+/// 撤销历史中的一个状态，保存修改之后的全部内容
+/// 快照和buffer共享没有修改的块，所以每个状态只占用修改过的块
+struct UndoState {
+    parent: Option<usize>,
+    children: Vec<usize>,
+    /// 重做时进入的子状态，是最近一次创建或者撤销离开的分支
+    redo: Option<usize>,
+    text: TextBuffer,
+    /// 修改之前和之后光标的位置，撤销和重做后光标回到修改的地方
+    before: Position,
+    after: Position,
+    /// 创建的时间，unix秒数
+    time: u64,
+    /// 连续输入的字符合并成一个状态
+    typing: bool,
+    diff: Option<Diff>,
+}
+
+/// 撤销树，撤销之后再修改会创建新的分支，原来的分支仍然可以在撤销树中找到
+/// 第一个状态是开始记录时buffer的内容
+#[derive(Default)]
+pub(super) struct UndoTree {
+    states: Vec<UndoState>,
+    current: usize,
+    /// 和磁盘上的文件内容相同的状态
+    saved: Option<usize>,
+    /// 最近一次记录时buffer的修改次数，不同时有新的修改需要记录
+    changes: u64,
+}
+
+/// 处理一个按键之前记下的状态
+pub(super) struct UndoStep {
+    before: Position,
+    typing: bool,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// 主光标的位置
+fn position(buffer: &Buffer) -> Position {
+    let row = buffer.cy as usize;
+    let at = buffer.rows.get(row).map_or(0, |r| r.get_raw_index(buffer.cx as usize));
+    (row, at)
+}
+
+/// buffer有新的修改时记录到撤销历史，before是修改之前光标的位置
+fn record_changes(buffer: &mut Buffer, before: Option<Position>, typing: bool) {
+    if buffer.undo.is_empty() || buffer.changes == buffer.undo.changes {
+        return;
+    }
+    let after = position(buffer);
+    let text = buffer.rows.clone();
+    buffer.undo.record(text, buffer.changes, (before.unwrap_or(after), after), typing);
+}
+
+/// 内容的FNV-1a哈希，用于确认撤销历史和文件对应
+fn fingerprint(rows: &TextBuffer) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for row in rows.iter() {
+        for byte in row.text().bytes().chain([b'\n']) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
+
+/// 比较开头和结尾相同的行，得到from变成to的修改
+fn diff(from: &TextBuffer, to: &TextBuffer) -> Diff {
+    let prefix = from.iter().zip(to.iter()).take_while(|(a, b)| a.text() == b.text()).count();
+    let rest = from.len().min(to.len()) - prefix;
+    let suffix = (0..rest)
+        .take_while(|i| from[from.len() - 1 - i].text() == to[to.len() - 1 - i].text())
+        .count();
+    let lines = |rows: &TextBuffer| -> Vec<String> {
+        rows.range(prefix..rows.len() - suffix).map(|r| r.text().to_string()).collect()
+    };
+    Diff {
+        start: prefix,
+        removed: lines(from),
+        inserted: lines(to),
+    }
+}
+
+/// 在text的start行把remove替换成insert
+fn apply(text: &TextBuffer, start: usize, remove: usize, insert: &[String], tab_width: usize) -> Option<TextBuffer> {
+    if start + remove > text.len() {
+        return None;
+    }
+    let mut text = text.clone();
+    text.remove_range(start..start + remove);
+    text.insert_rows(start, insert.iter().map(|line| Row::from_text(line, tab_width)));
+    Some(text)
+}
+
+/// 撤销历史文件的路径，用文件的绝对路径命名
+fn undo_path(file: &Path) -> Option<PathBuf> {
+    let file = std::path::absolute(file).ok()?;
+    Some(Config::dir()?.join(DIR_NAME).join(history_name(&file)))
+}
+
+/// 按URL的方式转义%和路径分隔符，不同的路径不会对应同一个文件
+fn history_name(file: &Path) -> String {
+    file.to_string_lossy()
+        .replace('%', "%25")
+        .replace('/', "%2F")
+        .replace('\\', "%5C")
+}
+
+impl UndoTree {
+    pub(super) fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    /// 丢弃历史，下一次处理按键时从buffer当前的内容重新开始
+    pub(super) fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// 从buffer当前的内容开始记录
+    fn new(buffer: &Buffer) -> Self {
+        let cursor = position(buffer);
+        Self {
+            states: vec![UndoState {
+                parent: None,
+                children: Vec::new(),
+                redo: None,
+                text: buffer.rows.clone(),
+                before: cursor,
+                after: cursor,
+                time: now(),
+                typing: false,
+                diff: None,
+            }],
+            current: 0,
+            saved: (!buffer.is_dirty()).then_some(0),
+            changes: buffer.changes,
+        }
+    }
+
+    /// 创建一个子状态，连续输入时合并到当前状态
+    fn record(&mut self, text: TextBuffer, changes: u64, (before, after): (Position, Position), typing: bool) {
+        self.changes = changes;
+        let id = self.states.len();
+        let current = &mut self.states[self.current];
+        if typing && current.typing && current.children.is_empty() && self.saved != Some(self.current) {
+            current.text = text;
+            current.diff = None;
+            current.after = after;
+            current.time = now();
+            return;
+        }
+        current.children.push(id);
+        current.redo = Some(id);
+        self.states.push(UndoState {
+            parent: Some(self.current),
+            children: Vec::new(),
+            redo: None,
+            text,
+            before,
+            after,
+            time: now(),
+            typing,
+            diff: None,
+        });
+        self.current = id;
+    }
+
+    /// 按深度优先的顺序列出所有状态，最新的分支和父状态对齐，较早的分支向右缩进
+    fn outline(&self) -> Vec<(usize, String)> {
+        let mut lines = Vec::new();
+        let mut stack = vec![(0, 0)];
+        while let Some((id, depth)) = stack.pop() {
+            let state = &self.states[id];
+            let mark = if id == self.current { '*' } else { 'o' };
+            let saved = if self.saved == Some(id) { " [saved]" } else { "" };
+            let line = format!("{}{} #{} {}{}", "| ".repeat(depth), mark, id, format_age(state.time), saved);
+            lines.push((id, line));
+            // 后压入的先弹出，最新的分支紧跟在父状态后面
+            let newest = state.children.len().saturating_sub(1);
+            for (i, child) in state.children.iter().enumerate() {
+                stack.push((*child, depth + usize::from(i < newest)));
+            }
+        }
+        lines
+    }
+
+    /// 写入撤销历史，状态的内容保存为和父状态之间的差别
+    fn write(&mut self, path: &Path) -> std::io::Result<()> {
+        let Some(saved) = self.saved else {
+            return Ok(());
+        };
+        let mut content = format!("{}\n{:016x} {}\n", FILE_HEADER, fingerprint(&self.states[saved].text), saved);
+        for id in 0..self.states.len() {
+            if let Some(parent) = self.states[id].parent
+                && self.states[id].diff.is_none()
+            {
+                self.states[id].diff = Some(diff(&self.states[parent].text, &self.states[id].text));
+            }
+            let state = &self.states[id];
+            let parent = state.parent.map_or("-".to_string(), |p| p.to_string());
+            let (start, removed, inserted) = state
+                .diff
+                .as_ref()
+                .map_or((0, &[][..], &[][..]), |d| (d.start, &d.removed[..], &d.inserted[..]));
+            content.push_str(&format!(
+                "{} {} {} {} {} {} {} {} {}\n",
+                parent,
+                state.time,
+                state.before.0,
+                state.before.1,
+                state.after.0,
+                state.after.1,
+                start,
+                removed.len(),
+                inserted.len()
+            ));
+            for line in removed.iter().chain(inserted) {
+                content.push_str(line);
+                content.push('\n');
+            }
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, content)
+    }
+
+    /// 读取撤销历史，只有保存时的内容和buffer相同时才使用
+    fn read(path: &Path, buffer: &Buffer, tab_width: usize) -> Option<Self> {
+        let content = fs::read_to_string(path).ok()?;
+        let mut lines = content.split('\n');
+        if lines.next()? != FILE_HEADER {
+            return None;
+        }
+        let mut header = lines.next()?.split(' ');
+        let hash = u64::from_str_radix(header.next()?, 16).ok()?;
+        let saved: usize = header.next()?.parse().ok()?;
+        if hash != fingerprint(&buffer.rows) {
+            return None;
+        }
+        let mut states: Vec<UndoState> = Vec::new();
+        while let Some(line) = lines.next().filter(|l| !l.is_empty()) {
+            let fields: Vec<&str> = line.split(' ').collect();
+            let [parent, time, fields @ ..] = fields.as_slice() else {
+                return None;
+            };
+            let parent = match *parent {
+                "-" => None,
+                p => Some(p.parse::<usize>().ok().filter(|p| *p < states.len())?),
+            };
+            let numbers: Vec<usize> = fields.iter().map(|f| f.parse().ok()).collect::<Option<_>>()?;
+            let [brow, bcol, arow, acol, start, removed, inserted] = numbers[..] else {
+                return None;
+            };
+            let mut take = |n| lines.by_ref().take(n).map(str::to_string).collect::<Vec<_>>();
+            let diff = Diff {
+                start,
+                removed: take(removed),
+                inserted: take(inserted),
+            };
+            if diff.removed.len() != removed || diff.inserted.len() != inserted {
+                return None;
+            }
+            let id = states.len();
+            if let Some(parent) = parent {
+                states[parent].children.push(id);
+                states[parent].redo = Some(id);
+            }
+            states.push(UndoState {
+                parent,
+                children: Vec::new(),
+                redo: None,
+                text: TextBuffer::new(),
+                before: (brow, bcol),
+                after: (arow, acol),
+                time: time.parse().ok()?,
+                typing: false,
+                diff: parent.map(|_| diff),
+            });
+        }
+        if saved >= states.len() {
+            return None;
+        }
+        // 从保存的状态出发，沿着父子关系应用或者反向应用差别，恢复每个状态的内容
+        states[saved].text = buffer.rows.clone();
+        let mut visited = vec![false; states.len()];
+        visited[saved] = true;
+        let mut queue = VecDeque::from([saved]);
+        while let Some(id) = queue.pop_front() {
+            let mut next = states[id].children.clone();
+            next.extend(states[id].parent);
+            for other in next {
+                if visited[other] {
+                    continue;
+                }
+                let text = if states[other].parent == Some(id) {
+                    let d = states[other].diff.as_ref()?;
+                    apply(&states[id].text, d.start, d.removed.len(), &d.inserted, tab_width)?
+                } else {
+                    let d = states[id].diff.as_ref()?;
+                    apply(&states[id].text, d.start, d.inserted.len(), &d.removed, tab_width)?
+                };
+                states[other].text = text;
+                visited[other] = true;
+                queue.push_back(other);
+            }
+        }
+        Some(Self {
+            states,
+            current: saved,
+            saved: Some(saved),
+            changes: buffer.changes,
+        })
+    }
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 处理按键之前调用，buffer还没有撤销历史时从当前内容开始记录
+    pub(super) fn begin_undo_step(&mut self, key: &Key) -> UndoStep {
+        let undo_file = self.config.undo_file;
+        let tab_width = self.config.tab_width;
+        let buffers = std::iter::once(&mut self.buffer).chain(self.buffers.iter_mut());
+        // 还在加载或者跟随文件的buffer内容还会变化
+        for buffer in buffers.filter(|b| b.undo.is_empty() && !b.is_loading() && !b.is_following()) {
+            let persisted = buffer
+                .current_file
+                .as_deref()
+                .filter(|_| undo_file && !buffer.is_dirty())
+                .and_then(undo_path)
+                .and_then(|path| UndoTree::read(&path, buffer, tab_width));
+            buffer.undo = persisted.unwrap_or_else(|| UndoTree::new(buffer));
+        }
+        let typing = match key {
+            Key::Char(c) => !c.is_whitespace() && self.buffer.selection.is_none(),
+            _ => self.pasting,
+        };
+        UndoStep {
+            before: position(&self.buffer),
+            typing,
+        }
+    }
+
+    /// 处理按键之后调用，把buffer中的修改记录到撤销历史
+    pub(super) fn commit_undo_step(&mut self, step: UndoStep) {
+        let typing = step.typing || self.pasting;
+        record_changes(&mut self.buffer, Some(step.before), typing);
+        // 按键也可能修改了其他buffer
+        for buffer in self.buffers.iter_mut() {
+            record_changes(buffer, None, false);
+        }
+    }
+
+    /// 保存之后把当前状态标记为和文件相同，需要时写入撤销历史
+    pub(super) fn record_undo_after_save(&mut self, event: &Event) {
+        let Event::AfterSave(_) = event else {
+            return;
+        };
+        let buffer = &mut self.buffer;
+        if buffer.undo.is_empty() {
+            buffer.undo = UndoTree::new(buffer);
+        }
+        record_changes(buffer, None, false);
+        buffer.undo.saved = Some(buffer.undo.current);
+        if !self.config.undo_file {
+            return;
+        }
+        let Some(path) = buffer.current_file.as_deref().and_then(undo_path) else {
+            return;
+        };
+        if let Err(e) = buffer.undo.write(&path) {
+            warn!("Failed to write undo history {}: {}", path.display(), e);
+        }
+    }
+
+    fn check_undo(&mut self) -> bool {
+        if !self.check_writable() {
+            return false;
+        }
+        if self.buffer.undo.is_empty() {
+            self.show_message(Message::new("No undo history".to_string()));
+            return false;
+        }
+        true
+    }
+
+    /// 把buffer恢复成撤销历史中的一个状态，回到保存的状态时buffer不再有未保存的修改
+    fn goto_undo_state(&mut self, id: usize, cursor: Position) {
+        let tab_width = self.config.tab_width;
+        let buffer = &mut self.buffer;
+        buffer.rows = buffer.undo.states[id].text.clone();
+        // 快照中的行可能是修改tab宽度之前渲染的
+        if buffer.rows.iter().any(|row| row.tab_width != tab_width) {
+            buffer.rows.iter_mut().for_each(|row| row.set_tab_width(tab_width));
+        }
+        buffer.undo.current = id;
+        buffer.mark_dirty();
+        if buffer.undo.saved == Some(id) {
+            buffer.mark_saved();
+        }
+        buffer.undo.changes = buffer.changes;
+        buffer.selection = None;
+        buffer.cursors.clear();
+        self.set_position(cursor);
+    }
+
+    pub(super) fn undo(&mut self) {
+        if !self.check_undo() {
+            return;
+        }
+        let tree = &mut self.buffer.undo;
+        let current = tree.current;
+        let Some(parent) = tree.states[current].parent else {
+            self.show_message(Message::new("Already at oldest change".to_string()));
+            return;
+        };
+        tree.states[parent].redo = Some(current);
+        let cursor = tree.states[current].before;
+        let age = format_age(tree.states[current].time);
+        self.goto_undo_state(parent, cursor);
+        self.show_message(Message::new(format!("Undid #{}, {}", current, age)));
+    }
+
+    pub(super) fn redo(&mut self) {
+        if !self.check_undo() {
+            return;
+        }
+        let tree = &self.buffer.undo;
+        let state = &tree.states[tree.current];
+        let Some(child) = state.redo.or(state.children.last().copied()) else {
+            self.show_message(Message::new("Already at newest change".to_string()));
+            return;
+        };
+        let cursor = tree.states[child].after;
+        let age = format_age(tree.states[child].time);
+        self.goto_undo_state(child, cursor);
+        self.show_message(Message::new(format!("Redid #{}, {}", child, age)));
+    }
+
+    /// 在浮动窗口中浏览撤销树，移动时预览选中的状态，回车确定，Esc回到原来的状态
+    pub(super) async fn undo_tree(&mut self) {
+        if !self.check_undo() {
+            return;
+        }
+        let outline = self.buffer.undo.outline();
+        let original = self.buffer.undo.current;
+        let cursor = position(&self.buffer);
+        let mut selected = outline.iter().position(|(id, _)| *id == original).unwrap_or(0);
+        let lines: Vec<String> = outline.iter().map(|(_, line)| line.clone()).collect();
+        loop {
+            let title = "Undo tree".to_string();
+            self.overlay = Some(Overlay::list(Anchor::Center, lines.clone(), selected).with_title(title));
+            if self.refresh_screen().is_err() {
+                break;
+            }
+            let Ok(key) = self.get_key().await else {
+                break;
+            };
+            match key {
+                Key::ArrowKey(Direction::Down) | Key::ControlKey(ControlKey::Ctrl('n')) => {
+                    selected = (selected + 1).min(lines.len() - 1)
+                }
+                Key::ArrowKey(Direction::Up) | Key::ControlKey(ControlKey::Ctrl('p')) => {
+                    selected = selected.saturating_sub(1)
+                }
+                Key::ControlKey(ControlKey::CR) => break,
+                Key::ControlKey(ControlKey::Escape) => {
+                    if self.buffer.undo.current != original {
+                        self.goto_undo_state(original, cursor);
+                    }
+                    break;
+                }
+                _ => continue,
+            }
+            let id = outline[selected].0;
+            let cursor = self.buffer.undo.states[id].after;
+            self.goto_undo_state(id, cursor);
+        }
+        self.overlay = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_names_are_distinct() {
+        let paths = ["/a%b", "/a/b", "/a%2Fb", "/a\\b", "/a%/b", "/a/%b"];
+        let names: Vec<String> = paths.iter().map(|path| history_name(Path::new(path))).collect();
+        for (i, name) in names.iter().enumerate() {
+            assert!(!name.contains('/'), "{name}");
+            assert!(!names[i + 1..].contains(name), "{} collides", paths[i]);
+        }
+    }
+}
@@ -0,0 +1,415 @@
+use std::mem;
+
+use tokio::io::AsyncReadExt;
+
+use super::buffer::CursorState;
+use super::{ControlKey, Editor, Key, Message, Terminal};
+
+/// 屏幕上的一块矩形区域
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Rect {
+    pub(super) x: u16,
+    pub(super) y: u16,
+    pub(super) width: u16,
+    pub(super) height: u16,
+}
+
+/// 分屏方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitDirection {
+    /// 上下排列
+    Horizontal,
+    /// 左右排列，中间有一列分隔线
+    Vertical,
+}
+
+/// 一个窗口，显示某个buffer的一部分
+/// 获得焦点的窗口直接使用buffer中的光标，其余窗口保存各自的光标
+pub(super) struct View {
+    pub(super) buffer_id: usize,
+    pub(super) cursor: CursorState,
+    /// 显示buffer的markdown预览，不能获得焦点
+    pub(super) preview: bool,
+}
+
+/// 窗口的布局树，叶子节点是views中的索引
+pub(super) enum Layout {
+    View(usize),
+    Split {
+        direction: SplitDirection,
+        /// 第一部分所占的百分比
+        ratio: u16,
+        first: Box<Layout>,
+        second: Box<Layout>,
+    },
+}
+
+/// 每个窗口至少保留一行文本和一行状态栏
+const MIN_VIEW_HEIGHT: u16 = 2;
+const MIN_VIEW_WIDTH: u16 = 1;
+
+impl Layout {
+    /// 计算每个窗口所占的区域，按照从上到下、从左到右的顺序
+    pub(super) fn rects(&self, area: Rect, out: &mut Vec<(usize, Rect)>) {
+        match self {
+            Layout::View(index) => out.push((*index, area)),
+            Layout::Split {
+                direction,
+                ratio,
+                first,
+                second,
+            } => {
+                let (a, b) = Self::split_rect(area, *direction, *ratio);
+                first.rects(a, out);
+                second.rects(b, out);
+            }
+        }
+    }
+
+    /// 左右分屏之间的分隔线位置
+    pub(super) fn separators(&self, area: Rect, out: &mut Vec<Rect>) {
+        if let Layout::Split {
+            direction,
+            ratio,
+            first,
+            second,
+        } = self
+        {
+            let (a, b) = Self::split_rect(area, *direction, *ratio);
+            if *direction == SplitDirection::Vertical {
+                out.push(Rect {
+                    x: a.x + a.width,
+                    y: area.y,
+                    width: 1,
+                    height: area.height,
+                });
+            }
+            first.separators(a, out);
+            second.separators(b, out);
+        }
+    }
+
+    fn split_rect(area: Rect, direction: SplitDirection, ratio: u16) -> (Rect, Rect) {
+        match direction {
+            SplitDirection::Horizontal => {
+                let first = (area.height as u32 * ratio as u32 / 100) as u16;
+                let first = first
+                    .max(MIN_VIEW_HEIGHT)
+                    .min(area.height.saturating_sub(MIN_VIEW_HEIGHT));
+                (
+                    Rect {
+                        height: first,
+                        ..area
+                    },
+                    Rect {
+                        y: area.y + first,
+                        height: area.height - first,
+                        ..area
+                    },
+                )
+            }
+            SplitDirection::Vertical => {
+                // 减去分隔线的宽度
+                let usable = area.width.saturating_sub(1);
+                let first = (usable as u32 * ratio as u32 / 100) as u16;
+                let first = first
+                    .max(MIN_VIEW_WIDTH)
+                    .min(usable.saturating_sub(MIN_VIEW_WIDTH));
+                (
+                    Rect {
+                        width: first,
+                        ..area
+                    },
+                    Rect {
+                        x: area.x + first + 1,
+                        width: usable - first,
+                        ..area
+                    },
+                )
+            }
+        }
+    }
+
+    /// 将窗口view分成两半，after为false时new_view放在第一部分，否则放在第二部分
+    fn split(&mut self, view: usize, direction: SplitDirection, new_view: usize, after: bool) -> bool {
+        match self {
+            Layout::View(index) if *index == view => {
+                let (first, second) = if after { (view, new_view) } else { (new_view, view) };
+                *self = Layout::Split {
+                    direction,
+                    ratio: 50,
+                    first: Box::new(Layout::View(first)),
+                    second: Box::new(Layout::View(second)),
+                };
+                true
+            }
+            Layout::View(_) => false,
+            Layout::Split { first, second, .. } => {
+                first.split(view, direction, new_view, after)
+                    || second.split(view, direction, new_view, after)
+            }
+        }
+    }
+
+    /// 移除窗口，兄弟节点占据父节点的位置
+    /// 索引大于view的窗口索引减一，与views中的位置保持一致
+    fn remove(&mut self, view: usize) -> bool {
+        let removed = self.remove_leaf(view);
+        if removed {
+            self.renumber(view);
+        }
+        removed
+    }
+
+    fn remove_leaf(&mut self, view: usize) -> bool {
+        let Layout::Split { first, second, .. } = self else {
+            return false;
+        };
+        let remaining = if matches!(**first, Layout::View(index) if index == view) {
+            mem::replace(&mut **second, Layout::View(0))
+        } else if matches!(**second, Layout::View(index) if index == view) {
+            mem::replace(&mut **first, Layout::View(0))
+        } else {
+            return first.remove_leaf(view) || second.remove_leaf(view);
+        };
+        *self = remaining;
+        true
+    }
+
+    fn renumber(&mut self, removed: usize) {
+        match self {
+            Layout::View(index) => {
+                if *index > removed {
+                    *index -= 1;
+                }
+            }
+            Layout::Split { first, second, .. } => {
+                first.renumber(removed);
+                second.renumber(removed);
+            }
+        }
+    }
+
+    fn contains(&self, view: usize) -> bool {
+        match self {
+            Layout::View(index) => *index == view,
+            Layout::Split { first, second, .. } => first.contains(view) || second.contains(view),
+        }
+    }
+
+    /// 调整离窗口最近的同方向分屏的比例
+    /// delta为正时窗口变大
+    fn resize(&mut self, view: usize, direction: SplitDirection, delta: i16) -> bool {
+        let Layout::Split {
+            direction: split_direction,
+            ratio,
+            first,
+            second,
+        } = self
+        else {
+            return false;
+        };
+        let in_first = first.contains(view);
+        if !in_first && !second.contains(view) {
+            return false;
+        }
+        let child = if in_first { first } else { second };
+        // 优先调整更内层的分屏
+        if child.resize(view, direction, delta) {
+            return true;
+        }
+        if *split_direction != direction {
+            return false;
+        }
+        let delta = if in_first { delta } else { -delta };
+        *ratio = (*ratio as i16 + delta).clamp(10, 90) as u16;
+        true
+    }
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 窗口所占的区域，最后一行留给消息栏
+    pub(super) fn view_rects(&self) -> Vec<(usize, Rect)> {
+        let mut rects = Vec::with_capacity(self.views.len());
+        self.layout.rects(self.views_area(), &mut rects);
+        rects
+    }
+
+    pub(super) fn views_area(&self) -> Rect {
+        Rect {
+            x: 0,
+            y: 0,
+            width: self.screen_cols,
+            height: self.screen_rows.saturating_sub(1 + self.results_height()),
+        }
+    }
+
+    /// 分割当前窗口，新窗口显示同一个buffer并获得焦点
+    pub fn split_view(&mut self, direction: SplitDirection) {
+        if !self.can_split(direction) {
+            self.show_message(Message::new("Not enough room to split".to_string()));
+            return;
+        }
+        let new_view = self.views.len();
+        self.views.push(View {
+            buffer_id: self.buffer.id,
+            cursor: self.buffer.cursor(),
+            preview: false,
+        });
+        self.layout.split(self.focus, direction, new_view, false);
+        self.focus_view(new_view);
+    }
+
+    fn can_split(&self, direction: SplitDirection) -> bool {
+        let (_, rect) = self.focused_rect();
+        match direction {
+            SplitDirection::Horizontal => rect.height >= MIN_VIEW_HEIGHT * 2,
+            SplitDirection::Vertical => rect.width > MIN_VIEW_WIDTH * 2,
+        }
+    }
+
+    /// 在当前窗口后面分出预览窗口，焦点留在当前窗口，空间不够时返回false
+    pub(super) fn split_preview(&mut self, direction: SplitDirection) -> bool {
+        if !self.can_split(direction) {
+            return false;
+        }
+        let new_view = self.views.len();
+        self.views.push(View {
+            buffer_id: self.buffer.id,
+            cursor: self.buffer.cursor(),
+            preview: true,
+        });
+        self.layout.split(self.focus, direction, new_view, true);
+        true
+    }
+
+    /// 关闭当前窗口，焦点移到下一个窗口
+    pub fn close_view(&mut self) {
+        let order = self.focus_order();
+        if order.len() == 1 {
+            self.show_message(Message::new("Cannot close the last window".to_string()));
+            return;
+        }
+        let position = order.iter().position(|&i| i == self.focus).unwrap_or(0);
+        let next = order[(position + 1) % order.len()];
+
+        let closed = self.focus;
+        self.focus_view(next);
+        self.remove_view(closed);
+    }
+
+    /// 移除不是焦点的窗口
+    pub(super) fn remove_view(&mut self, index: usize) {
+        self.layout.remove(index);
+        self.views.remove(index);
+        if self.focus > index {
+            self.focus -= 1;
+        }
+    }
+
+    /// 可以获得焦点的窗口，按照从上到下、从左到右的顺序
+    fn focus_order(&self) -> Vec<usize> {
+        self.view_rects()
+            .into_iter()
+            .map(|(i, _)| i)
+            .filter(|&i| !self.views[i].preview)
+            .collect()
+    }
+
+    /// 只保留当前窗口，预览窗口也会关闭
+    pub fn only_view(&mut self) {
+        let view = self.views.swap_remove(self.focus);
+        self.views = vec![view];
+        self.focus = 0;
+        self.layout = Layout::View(0);
+    }
+
+    /// 焦点移到下一个（或上一个）窗口
+    pub fn cycle_view(&mut self, forward: bool) {
+        let order = self.focus_order();
+        let position = order.iter().position(|&i| i == self.focus).unwrap_or(0);
+        let next = if forward {
+            (position + 1) % order.len()
+        } else {
+            (position + order.len() - 1) % order.len()
+        };
+        self.focus_view(order[next]);
+    }
+
+    pub fn resize_view(&mut self, direction: SplitDirection, delta: i16) {
+        if !self.layout.resize(self.focus, direction, delta) {
+            self.show_message(Message::new("No split to resize".to_string()));
+        }
+    }
+
+    /// 切换焦点，保存当前窗口的光标，恢复目标窗口的buffer和光标
+    pub(super) fn focus_view(&mut self, index: usize) {
+        if index == self.focus {
+            return;
+        }
+        let current = &mut self.views[self.focus];
+        current.buffer_id = self.buffer.id;
+        current.cursor = self.buffer.cursor();
+
+        self.focus = index;
+        let View { buffer_id, cursor, .. } = self.views[index];
+        if self.rotate_to_buffer(buffer_id) {
+            self.buffer.set_cursor(cursor);
+        }
+    }
+
+    /// 窗口显示的buffer被关闭后，改为显示当前buffer，预览窗口直接关闭
+    pub(super) fn fix_views(&mut self) {
+        let focus = self.focus;
+        self.views[focus].buffer_id = self.buffer.id;
+        let exists = |id: usize| id == self.buffer.id || self.buffers.iter().any(|b| b.id == id);
+        let closed: Vec<usize> = (0..self.views.len())
+            .filter(|&i| self.views[i].preview && !exists(self.views[i].buffer_id))
+            .collect();
+        for index in closed.into_iter().rev() {
+            self.remove_view(index);
+        }
+        for view in &mut self.views {
+            let exists = view.buffer_id == self.buffer.id
+                || self.buffers.iter().any(|b| b.id == view.buffer_id);
+            if !exists {
+                view.buffer_id = self.buffer.id;
+                view.cursor = (0, 0, 0, 0);
+            }
+        }
+    }
+
+    pub(super) fn focused_rect(&self) -> (usize, Rect) {
+        self.view_rects()
+            .into_iter()
+            .find(|(i, _)| *i == self.focus)
+            .unwrap_or((self.focus, self.views_area()))
+    }
+
+    /// Ctrl+W之后的按键决定对窗口的操作
+    pub(super) async fn window_command(&mut self) {
+        self.message = Some(Message::new(
+            "Window: s(plit) v(split) w(next) W(prev) c(lose) o(nly) +/- </>".to_string(),
+        ));
+        if self.refresh_screen().is_err() {
+            return;
+        }
+        let Ok(key) = self.get_key().await else {
+            return;
+        };
+        self.message = None;
+        match key {
+            Key::Char('s') => self.split_view(SplitDirection::Horizontal),
+            Key::Char('v') => self.split_view(SplitDirection::Vertical),
+            Key::Char('w') | Key::ControlKey(ControlKey::Ctrl('w')) => self.cycle_view(true),
+            Key::Char('W') => self.cycle_view(false),
+            Key::Char('c') | Key::Char('q') => self.close_view(),
+            Key::Char('o') => self.only_view(),
+            Key::Char('+') => self.resize_view(SplitDirection::Horizontal, 5),
+            Key::Char('-') => self.resize_view(SplitDirection::Horizontal, -5),
+            Key::Char('>') => self.resize_view(SplitDirection::Vertical, 5),
+            Key::Char('<') => self.resize_view(SplitDirection::Vertical, -5),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use tokio::fs;
+use tokio::io::AsyncReadExt;
+use tokio::time::{self, Instant, Interval, MissedTickBehavior};
+
+use super::buffer::Buffer;
+use super::{Editor, Key, Message, Row, Terminal};
+
+/// 检查文件是否被外部修改的间隔
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// 文件在磁盘上的修改时间和大小，任何一个变化都认为文件被修改了
+pub(super) type DiskState = (SystemTime, u64);
+
+pub(super) async fn disk_state(path: &Path) -> Option<DiskState> {
+    let metadata = fs::metadata(path).await.ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+pub(super) fn watch_timer() -> Interval {
+    let mut timer = time::interval_at(Instant::now() + WATCH_INTERVAL, WATCH_INTERVAL);
+    timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    timer
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 当前buffer的文件在读取之后是否被其他程序修改了
+    pub(super) async fn changed_on_disk(&self) -> bool {
+        let Some(path) = &self.buffer.current_file else {
+            return false;
+        };
+        // 还在加载的文件和新文件不检查，跟随的文件变长是正常的
+        if self.buffer.is_loading() || self.buffer.is_following() || self.buffer.disk_state.is_none() {
+            return false;
+        }
+        disk_state(path).await != self.buffer.disk_state
+    }
+
+    /// 定时检查当前buffer的文件，被修改时询问如何处理
+    pub(super) async fn check_disk_changes(&mut self) {
+        if !self.changed_on_disk().await {
+            return;
+        }
+        let name = self.buffer.name().to_string();
+        let question = if self.buffer.is_dirty() {
+            format!("{} changed on disk: r(eload, discard changes) k(eep) d(iff)", name)
+        } else {
+            format!("{} changed on disk: r(eload) k(eep) d(iff)", name)
+        };
+        self.message = Some(Message::new(question));
+        if self.refresh_screen().is_err() {
+            return;
+        }
+        let Ok(key) = self.get_key().await else {
+            return;
+        };
+        self.message = None;
+        match key {
+            Key::Char('r') => {
+                if let Err(e) = self.reload_buffer().await {
+                    self.show_message(Message::error(format!("Error reloading {}: {}", name, e), &e));
+                }
+            }
+            Key::Char('d') => {
+                if let Err(e) = self.diff_with_disk().await {
+                    self.show_message(Message::error(format!("Error reading {}: {}", name, e), &e));
+                }
+            }
+            // 保留内存中的内容，之后保存时不再提示
+            _ => {
+                if let Some(path) = &self.buffer.current_file {
+                    self.buffer.disk_state = disk_state(path).await;
+                }
+            }
+        }
+    }
+
+    /// 在新的buffer中显示磁盘上的文件和内存中内容的差异
+    /// 只比较开头和结尾相同的部分，中间不同的行全部列出
+    async fn diff_with_disk(&mut self) -> crate::error::Result<()> {
+        let Some(path) = self.buffer.current_file.clone() else {
+            return Ok(());
+        };
+        let disk = super::buffer::read_rows(&path, &self.buffer.encoding, self.config.tab_width)
+            .await?;
+        let disk: Vec<String> = disk.iter().map(|r| r.text().to_string()).collect();
+        let memory: Vec<String> = self.buffer.rows.iter().map(|r| r.text().to_string()).collect();
+
+        let prefix = disk.iter().zip(&memory).take_while(|(a, b)| a == b).count();
+        let suffix = disk[prefix..]
+            .iter()
+            .rev()
+            .zip(memory[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        let mut lines = vec![
+            format!("--- {} (disk)", path.display()),
+            format!("+++ {} (buffer)", path.display()),
+        ];
+        if prefix + suffix < disk.len().max(memory.len()) {
+            lines.push(format!(
+                "@@ -{},{} +{},{} @@",
+                prefix + 1,
+                disk.len() - prefix - suffix,
+                prefix + 1,
+                memory.len() - prefix - suffix
+            ));
+            lines.extend(disk[prefix..disk.len() - suffix].iter().map(|l| format!("-{}", l)));
+            lines.extend(memory[prefix..memory.len() - suffix].iter().map(|l| format!("+{}", l)));
+        }
+
+        let tab_width = self.config.tab_width;
+        let mut buffer = Buffer::new(0);
+        buffer.rows = lines
+            .iter()
+            .map(|l| Row::from_text(l, tab_width))
+            .collect::<Vec<_>>()
+            .into();
+        buffer.readonly = true;
+        self.push_buffer(buffer);
+        Ok(())
+    }
+}
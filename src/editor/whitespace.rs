@@ -0,0 +1,52 @@
+use tokio::io::AsyncReadExt;
+
+use super::event::Event;
+use super::{Editor, Message, Row, Terminal};
+
+impl Row {
+    /// 行尾空白在raw中的起始位置，没有行尾空白时等于raw.len()
+    pub(super) fn trailing_whitespace(&self) -> usize {
+        self.len() - self.text().chars().rev().take_while(|&c| c == ' ' || c == '\t').count()
+    }
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    /// 删除所有行的行尾空白，返回修改的行数
+    /// 保存时每一行后面都会写入换行符，所以文件总是以换行符结尾
+    pub(super) fn strip_trailing_whitespace(&mut self) -> usize {
+        let mut changed = 0;
+        for row in self.buffer.rows.iter_mut() {
+            let start = row.trailing_whitespace();
+            if start < row.len() {
+                row.replace(start..row.len(), Vec::new());
+                changed += 1;
+            }
+        }
+        if changed > 0 {
+            self.buffer.mark_dirty();
+            self.clamp_cursor_x();
+        }
+        changed
+    }
+
+    /// 配置了strip_trailing_whitespace时保存前删除行尾空白
+    pub(super) fn strip_whitespace_before_save(&mut self, event: &Event) {
+        if let Event::BeforeSave(_) = event
+            && self.config.strip_trailing_whitespace
+        {
+            self.strip_trailing_whitespace();
+        }
+    }
+
+    /// 手动删除行尾空白
+    pub(super) fn strip_command(&mut self) {
+        if !self.check_writable() {
+            return;
+        }
+        let changed = self.strip_trailing_whitespace();
+        self.show_message(Message::new(format!(
+            "Stripped trailing whitespace from {} lines",
+            changed
+        )));
+    }
+}
@@ -0,0 +1,224 @@
+use std::ops::Range;
+
+use tokio::io::AsyncReadExt;
+
+use super::buffer::{Buffer, CursorState};
+use super::{Editor, Message, Row, Terminal};
+use crate::utils::{self, color};
+
+/// 折行的屏幕行末尾显示的标记
+const WRAP_MARKER: char = '\\';
+
+impl Row {
+    /// 折行显示时每一个屏幕行对应的列范围，至少有一段
+    /// 除了最后一段，每段的最后一列留给折行标记，放不下的宽字符整个移到下一段
+    pub(super) fn segments(&self, width: usize) -> Vec<Range<usize>> {
+        let width = width.saturating_sub(1).max(1);
+        let mut segments = Vec::new();
+        let mut start = 0;
+        for (cell, _) in self.render().cells() {
+            if cell.col + cell.width - start > width && cell.col > start {
+                segments.push(start..cell.col);
+                start = cell.col;
+            }
+        }
+        segments.push(start..self.display_len());
+        segments
+    }
+}
+
+/// col所在的段，行尾的位置属于最后一段
+fn segment_of(segments: &[Range<usize>], col: usize) -> usize {
+    segments.iter().rposition(|s| s.start <= col).unwrap_or(0)
+}
+
+/// 光标在窗口文本区域中的位置，row_offset所在行的第一段是窗口的第一行
+fn visual_position(
+    buffer: &Buffer,
+    (cx, cy): (usize, usize),
+    row_offset: usize,
+    width: usize,
+) -> (usize, usize) {
+    let above: usize = (row_offset..cy.min(buffer.rows.len()))
+        .map(|i| buffer.rows[i].segments(width).len())
+        .sum();
+    match buffer.rows.get(cy) {
+        Some(row) => {
+            let segments = row.segments(width);
+            let i = segment_of(&segments, cx);
+            (cx - segments[i].start, above + i)
+        }
+        // 光标在最后一行的后面
+        None => (0, above),
+    }
+}
+
+impl<R: AsyncReadExt + Unpin, W: Terminal> Editor<R, W> {
+    pub fn toggle_wrap(&mut self) {
+        self.wrap = !self.wrap;
+        self.buffer.col_offset = 0;
+        let text = if self.wrap { "Soft wrap: on" } else { "Soft wrap: off" };
+        self.show_message(Message::new(text.to_string()));
+    }
+
+    /// 折行时不水平滚动，光标所在的屏幕行超出窗口时继续向下滚动
+    pub(super) fn scroll_wrapped(&mut self) {
+        let cursor = Self::fit_cursor_wrapped(
+            &self.buffer,
+            self.buffer.cursor(),
+            self.max_col as usize,
+            self.max_row as usize,
+        );
+        self.buffer.set_cursor(cursor);
+    }
+
+    /// 折行时保证光标所在的屏幕行在窗口内
+    /// 一行折出的屏幕行比窗口还多时，从这一行的开头显示
+    pub(super) fn fit_cursor_wrapped(
+        buffer: &Buffer,
+        cursor: CursorState,
+        width: usize,
+        height: usize,
+    ) -> CursorState {
+        let (cx, cy, mut row_offset, _) = cursor;
+        let height = height.max(1);
+        row_offset = row_offset.min(cy as usize);
+        while row_offset < cy as usize
+            && visual_position(buffer, (cx as usize, cy as usize), row_offset, width).1 >= height
+        {
+            row_offset += 1;
+        }
+        (cx, cy, row_offset, 0)
+    }
+
+    /// 焦点窗口中光标在文本区域中的位置
+    pub(super) fn wrapped_cursor(&self) -> (usize, usize) {
+        let (x, y) = visual_position(
+            &self.buffer,
+            (self.buffer.cx as usize, self.buffer.cy as usize),
+            self.buffer.row_offset,
+            self.max_col as usize,
+        );
+        (x, y.min(self.max_row as usize - 1))
+    }
+
+    /// 让光标所在的屏幕行显示在窗口第line行的row_offset，上面的行不够时为0
+    pub(super) fn wrapped_offset_for(&self, line: usize) -> usize {
+        let width = self.max_col as usize;
+        let (cx, cy) = (self.buffer.cx as usize, self.buffer.cy as usize);
+        let mut above = visual_position(&self.buffer, (cx, cy), cy, width).1;
+        let mut row_offset = cy.min(self.buffer.rows.len());
+        while row_offset > 0 {
+            let height = self.buffer.rows[row_offset - 1].segments(width).len();
+            if above + height > line {
+                break;
+            }
+            above += height;
+            row_offset -= 1;
+        }
+        row_offset
+    }
+
+    /// 屏幕上文本区域中(x, y)对应的行和列
+    pub(super) fn wrapped_position_at(&self, x: usize, y: usize) -> (usize, usize) {
+        let width = self.max_col as usize;
+        let mut line = 0;
+        for i in self.buffer.row_offset..self.buffer.rows.len() {
+            let row = &self.buffer.rows[i];
+            let segments = row.segments(width);
+            if y < line + segments.len() {
+                let segment = &segments[y - line];
+                return (i, Self::column_in_segment(row, segment, x));
+            }
+            line += segments.len();
+        }
+        (self.buffer.rows.len(), 0)
+    }
+
+    /// 段内第x列所在字符的开始位置，超出段尾时停在段尾
+    fn column_in_segment(row: &Row, segment: &Range<usize>, x: usize) -> usize {
+        // 不是最后一段时段尾属于下一段，停在段内最后一个字符上
+        let last = segment.end == row.display_len();
+        let end = if last { segment.end } else { segment.end - 1 };
+        let col = (segment.start + x).min(end);
+        row.column_of(row.get_raw_index(col))
+    }
+
+    /// 折行时按屏幕行上下移动，尽量保持光标在屏幕上的列
+    pub(super) fn move_visual_line(&mut self, up: bool) {
+        let width = self.max_col as usize;
+        let (cx, cy) = (self.buffer.cx as usize, self.buffer.cy as usize);
+        let (x, index) = match self.buffer.rows.get(cy) {
+            Some(row) => {
+                let segments = row.segments(width);
+                let i = segment_of(&segments, cx);
+                (cx - segments[i].start, Some((i, segments.len())))
+            }
+            None => (0, None),
+        };
+        let (cy, segment) = match (up, index) {
+            (true, Some((i, _))) if i > 0 => (cy, i - 1),
+            (true, _) if cy > 0 => (cy - 1, usize::MAX),
+            (false, Some((i, count))) if i + 1 < count => (cy, i + 1),
+            // 光标可以在最后一行的后面
+            (false, Some(_)) => (cy + 1, 0),
+            _ => return,
+        };
+        self.buffer.cy = cy as u16;
+        self.buffer.cx = match self.buffer.rows.get(cy) {
+            Some(row) => {
+                let segments = row.segments(width);
+                let segment = &segments[segment.min(segments.len() - 1)];
+                Self::column_in_segment(row, segment, x) as u16
+            }
+            None => 0,
+        };
+        self.scroll();
+    }
+
+    /// 折行显示窗口中的行，最多height个屏幕行
+    pub(super) fn render_wrapped(
+        &self,
+        buffer: &Buffer,
+        (cx, cy, row_offset): (usize, usize, usize),
+        gutter: u16,
+        (width, height): (usize, usize),
+        focused: bool,
+        brackets: &[(usize, usize)],
+    ) -> Vec<String> {
+        let mut lines = Vec::with_capacity(height);
+        for i in row_offset..buffer.rows.len() {
+            let row = &buffer.rows[i];
+            let style = self.row_style(buffer, i, (cx, cy), focused, brackets);
+            let segments = row.segments(width);
+            for (n, segment) in segments.iter().enumerate() {
+                if lines.len() == height {
+                    return lines;
+                }
+                let last = n + 1 == segments.len();
+                let mut line = if n == 0 {
+                    self.render_gutter(buffer, i, cy)
+                } else {
+                    " ".repeat(gutter as usize)
+                };
+                // 最后一段多留一列给选中的换行符
+                let len = segment.len() + usize::from(last);
+                line.push_str(&self.render_row(row, segment.start, len, &style));
+                let full = gutter as usize + width;
+                if last && focused && i == cy {
+                    let free = full.saturating_sub(utils::visible_width(&line));
+                    line.push_str(&self.render_inline_blame(buffer, i, free));
+                }
+                if !last {
+                    let used = utils::visible_width(&line);
+                    line.push_str(&" ".repeat((full - 1).saturating_sub(used)));
+                    line.push_str(&format!("{}{}{}", self.theme.separator, WRAP_MARKER, color::RESET));
+                }
+                let used = utils::visible_width(&line);
+                line.push_str(&" ".repeat(full.saturating_sub(used)));
+                lines.push(line);
+            }
+        }
+        lines
+    }
+}
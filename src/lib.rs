@@ -1,4 +1,10 @@
-pub mod reader;
+pub use fim_core::reader;
 pub mod error;
 pub mod editor;
-pub mod utils;
\ No newline at end of file
+pub mod utils;
+pub mod config;
+pub mod theme;
+pub mod cli;
+pub mod term;
+pub mod terminal;
+pub mod tutor;
@@ -1,11 +1,17 @@
+use std::fs::File;
 use std::io::{self, BufWriter};
+use std::path::Path;
 
-use fim::reader::{ByteStream, Decoder, KeyStream};
+use fim::reader::{ByteStream, Decoder, InputBackend, KeyStream, record};
 use tokio::io::stdin;
 use tracing::Level;
 
+use fim::cli::{Args, USAGE};
+use fim::config::Config;
 use fim::editor::Editor;
-use fim::error::Result;
+use fim::error::{EditorError, Result};
+use fim::terminal::{self, CrosstermTerminal, MemoryTerminal};
+use fim::{tutor, utils};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -13,6 +19,32 @@ async fn main() -> Result<()> {
         .with_max_level(Level::ERROR)
         .init();
 
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("fim: {}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+    if args.help {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+    if args.version {
+        println!("fim {}", utils::get_version_from_env());
+        return Ok(());
+    }
+
+    // 配置文件有误时使用默认配置，不影响启动
+    let config = Config::load().unwrap_or_else(|e| {
+        tracing::error!("{}", e);
+        Config::default()
+    });
+
+    if let Some(script) = &args.script {
+        return run_script(&args, script, config).await;
+    }
+
     // std::io::stout() 会返回返回当前进程的标准输出流 stdout 的句柄
     // 将内容刷新到终端是很昂贵的操作
     // 封装一个writer并缓冲其输出，避免频繁系统调用
@@ -24,28 +56,75 @@ async fn main() -> Result<()> {
 
     // let mut editor = Editor::start(stdout, None).await;
 
-    let reader = stdin();
+    let mut key_stream = match config.input {
+        InputBackend::Builtin => {
+            let reader = stdin();
 
-    let byte_stream = ByteStream::new(reader);
+            let byte_stream = ByteStream::new(reader);
 
-    let decoder = Decoder::builder()
-        .encoding("utf-8".to_owned())
-        .byte_stream(byte_stream)
-        .build()?;
+            let decoder = Decoder::builder()
+                .encoding("utf-8".to_owned())
+                .byte_stream(byte_stream)
+                .build()?;
 
-    let key_stream = KeyStream::new(decoder);
+            KeyStream::new(decoder)
+        }
+        // crossterm自己读取终端，不能再读取标准输入
+        InputBackend::Crossterm => KeyStream::with_events(),
+    };
+    if let Some(path) = &args.replay {
+        let text = std::fs::read_to_string(path)?;
+        let keys = record::parse_recording(&text)
+            .map_err(|e| EditorError::invalid_argument(path.display().to_string(), e))?;
+        key_stream.replay(keys);
+    }
+    if let Some(path) = &args.record {
+        key_stream.record(BufWriter::new(File::create(path)?));
+    }
 
-    let mut editor = Editor::new(key_stream, stdout).await;
+    // 教程的副本在退出时删除
+    let tutor = if args.tutor { Some(tutor::create_copy()?) } else { None };
+    let files: Vec<_> = tutor.iter().chain(&args.files).cloned().collect();
 
-    editor.start(Some("test.txt")).await;
+    // 编辑器发生panic时恢复终端之后再输出panic的信息
+    terminal::install_panic_hook();
+    let mut editor = Editor::new(key_stream, CrosstermTerminal::new(stdout), config).await;
+
+    editor.start(&files, &args.open_options()).await;
 
     editor.run().await;
+    // 收到信号退出时可能还在等待终端的输入，读取标准输入的阻塞任务无法取消，
+    // 返回后运行时会一直等待它结束，所以恢复终端之后直接退出
+    drop(editor);
+    if let Some(path) = tutor {
+        let _ = std::fs::remove_file(path);
+    }
+    std::process::exit(0);
 
     // 不需要join来使主线程阻塞等待handler关联的线程结束
     // 线程不结束sender不会被销毁，receiver的循环也不会结束
     // if let Err(e) = handler.join() {
     //     eprintln!("线程发生恐慌：{:?}", e);
     // };
+}
 
+/// 不进入界面执行脚本，出错时以状态1退出
+async fn run_script(args: &Args, script: &Path, config: Config) -> Result<()> {
+    let text = if script == Path::new("-") {
+        io::read_to_string(io::stdin())?
+    } else {
+        std::fs::read_to_string(script)?
+    };
+    // 没有终端输入，需要按键回答的提示都会被取消
+    let decoder = Decoder::builder()
+        .encoding("utf-8".to_owned())
+        .byte_stream(ByteStream::new(tokio::io::empty()))
+        .build()?;
+    let mut editor = Editor::new(KeyStream::new(decoder), MemoryTerminal::new(80, 24), config).await;
+    editor.start(&args.files, &args.open_options()).await;
+    if let Err(e) = editor.run_script(&text).await {
+        eprintln!("fim: {}", e);
+        std::process::exit(1);
+    }
     Ok(())
 }
@@ -1,7 +0,0 @@
-pub mod byte_stream;
-pub mod key_stream;
-pub mod decoder;
-
-pub use byte_stream::ByteStream;
-pub use decoder::{Decoder, DecoderBuilder};
-pub use key_stream::KeyStream;
\ No newline at end of file
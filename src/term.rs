@@ -0,0 +1,245 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// 终端可以显示的颜色
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorDepth {
+    /// 不支持颜色，只能使用粗体、反色等样式
+    None,
+    /// 基础的8种颜色
+    Basic,
+    /// 256色
+    Indexed,
+    /// 24位真彩色
+    TrueColor,
+}
+
+/// 终端支持的功能，决定编辑器输出哪些转义序列
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    pub colors: ColorDepth,
+    /// 支持备用屏幕，退出后恢复原来的内容
+    pub alternate_screen: bool,
+    /// 支持鼠标、括号粘贴和焦点事件报告
+    pub mouse: bool,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self {
+            colors: ColorDepth::TrueColor,
+            alternate_screen: true,
+            mouse: true,
+        }
+    }
+}
+
+/// 终端描述文件中需要的部分
+struct TermInfo {
+    colors: Option<i32>,
+    alternate_screen: bool,
+}
+
+impl Capabilities {
+    /// 根据$TERM、$COLORTERM和terminfo检测当前终端的功能
+    pub fn detect() -> Self {
+        let term = env::var("TERM").ok();
+        let colorterm = env::var("COLORTERM").ok();
+        let info = term.as_deref().and_then(read_terminfo);
+        Self::from_env(term.as_deref(), colorterm.as_deref(), info.as_ref())
+    }
+
+    fn from_env(term: Option<&str>, colorterm: Option<&str>, info: Option<&TermInfo>) -> Self {
+        let term = term.unwrap_or("").to_ascii_lowercase();
+        // 没有设置TERM或者是dumb时什么也不支持
+        if term.is_empty() || term == "dumb" {
+            return Self {
+                colors: ColorDepth::None,
+                alternate_screen: false,
+                mouse: false,
+            };
+        }
+        let colorterm = colorterm.unwrap_or("").to_ascii_lowercase();
+        let colors = if colorterm == "truecolor" || colorterm == "24bit" || term.contains("direct") {
+            ColorDepth::TrueColor
+        } else if term.contains("256color") {
+            ColorDepth::Indexed
+        } else {
+            // 没有terminfo时假设支持基础颜色，terminfo中没有max_colors时不支持颜色
+            match info.map(|info| info.colors.unwrap_or(0)) {
+                Some(colors) if colors >= 1 << 24 => ColorDepth::TrueColor,
+                Some(colors) if colors >= 256 => ColorDepth::Indexed,
+                Some(colors) if colors >= 8 => ColorDepth::Basic,
+                Some(_) => ColorDepth::None,
+                None => ColorDepth::Basic,
+            }
+        };
+        // Linux控制台和硬件终端没有备用屏幕，也不支持xterm的鼠标协议
+        let legacy = term == "linux" || term.starts_with("vt");
+        Self {
+            colors,
+            alternate_screen: info.map_or(!legacy, |info| info.alternate_screen),
+            mouse: !legacy,
+        }
+    }
+}
+
+/// 查找并解析term的terminfo文件，找不到或者格式不对时返回None
+fn read_terminfo(term: &str) -> Option<TermInfo> {
+    let first = term.chars().next()?;
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    if let Some(dir) = env::var_os("TERMINFO") {
+        dirs.push(dir.into());
+    }
+    if let Some(home) = env::var_os("HOME") {
+        dirs.push(PathBuf::from(home).join(".terminfo"));
+    }
+    if let Ok(list) = env::var("TERMINFO_DIRS") {
+        dirs.extend(list.split(':').filter(|dir| !dir.is_empty()).map(PathBuf::from));
+    }
+    dirs.extend(["/etc/terminfo", "/lib/terminfo", "/usr/share/terminfo"].map(PathBuf::from));
+    // 一般按首字母分目录，macOS上用首字母的十六进制
+    let subdirs = [first.to_string(), format!("{:x}", first as u32)];
+    dirs.iter()
+        .flat_map(|dir| subdirs.iter().map(move |sub| dir.join(sub).join(term)))
+        .find_map(|path| fs::read(path).ok())
+        .and_then(|data| parse_terminfo(&data))
+}
+
+/// max_colors在数字能力中的索引，enter_ca_mode在字符串能力中的索引
+const MAX_COLORS: usize = 13;
+const ENTER_CA_MODE: usize = 28;
+
+/// 解析编译后的terminfo，文件头是6个小端序的16位整数
+/// 之后依次是名称、布尔能力、数字能力、字符串偏移和字符串表
+fn parse_terminfo(data: &[u8]) -> Option<TermInfo> {
+    let short = |i: usize| -> Option<i16> { Some(i16::from_le_bytes([*data.get(i)?, *data.get(i + 1)?])) };
+    let header: Vec<usize> = (0..6).map(|i| short(i * 2).map(|n| n as u16 as usize)).collect::<Option<_>>()?;
+    // 旧格式的数字是16位，扩展的格式是32位
+    let number_size = match header[0] {
+        0o432 => 2,
+        0o1036 => 4,
+        _ => return None,
+    };
+    let (names, bools, numbers, strings) = (header[1], header[2], header[3], header[4]);
+    let mut offset = 12 + names + bools;
+    // 数字能力从偶数偏移开始
+    offset += offset % 2;
+    let colors = (MAX_COLORS < numbers)
+        .then(|| {
+            let at = offset + MAX_COLORS * number_size;
+            let bytes = data.get(at..at + number_size)?;
+            Some(match number_size {
+                2 => i16::from_le_bytes([bytes[0], bytes[1]]) as i32,
+                _ => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            })
+        })
+        .flatten()
+        // 负数表示没有这个能力
+        .filter(|&colors| colors >= 0);
+    offset += numbers * number_size;
+    let alternate_screen = ENTER_CA_MODE < strings && short(offset + ENTER_CA_MODE * 2)? >= 0;
+    Some(TermInfo { colors, alternate_screen })
+}
+
+/// 把由SGR序列组成的样式转换为终端支持的颜色
+/// 不支持颜色时去掉颜色，去掉背景色时改用反色，保证状态栏和选择区域仍然可以分辨
+pub fn adapt_style(style: &str, colors: ColorDepth) -> String {
+    if colors == ColorDepth::TrueColor {
+        return style.to_string();
+    }
+    let mut adapted = String::new();
+    for sequence in style.split_inclusive('m') {
+        let Some(params) = sequence.strip_prefix("\x1b[").and_then(|s| s.strip_suffix('m')) else {
+            adapted.push_str(sequence);
+            continue;
+        };
+        let Ok(params) = params.split(';').map(str::parse::<u8>).collect::<Result<Vec<_>, _>>() else {
+            adapted.push_str(sequence);
+            continue;
+        };
+        let params = adapt_params(&params, colors);
+        if !params.is_empty() {
+            let params: Vec<String> = params.iter().map(u8::to_string).collect();
+            adapted.push_str(&format!("\x1b[{}m", params.join(";")));
+        }
+    }
+    adapted
+}
+
+fn adapt_params(params: &[u8], colors: ColorDepth) -> Vec<u8> {
+    let mut adapted = Vec::new();
+    let mut dropped_background = false;
+    let mut i = 0;
+    while i < params.len() {
+        let (rgb, base, len) = match params[i..] {
+            [code @ (38 | 48), 2, r, g, b, ..] => (Some((r, g, b)), code - 8, 5),
+            [code @ (38 | 48), 5, _, ..] => (None, code - 8, 3),
+            [code @ (30..=37 | 40..=47), ..] => (None, code - code % 10, 1),
+            [code @ (90..=97 | 100..=107), ..] => (None, code - code % 10 - 60, 1),
+            [code, ..] => {
+                adapted.push(code);
+                i += 1;
+                continue;
+            }
+            [] => break,
+        };
+        let segment = &params[i..(i + len).min(params.len())];
+        i += len;
+        match colors {
+            ColorDepth::None => dropped_background |= base == 40,
+            ColorDepth::Indexed => match rgb {
+                Some((r, g, b)) => adapted.extend([base + 8, 5, rgb_to_indexed(r, g, b)]),
+                None => adapted.extend_from_slice(segment),
+            },
+            ColorDepth::Basic => {
+                let index = match (rgb, segment) {
+                    (Some((r, g, b)), _) => rgb_to_basic(r, g, b),
+                    (None, [_, 5, index]) => indexed_to_basic(*index),
+                    (None, [code]) => code % 10,
+                    _ => continue,
+                };
+                adapted.push(base + index);
+            }
+            ColorDepth::TrueColor => adapted.extend_from_slice(segment),
+        }
+    }
+    if dropped_background && !adapted.contains(&7) {
+        adapted.push(7);
+    }
+    adapted
+}
+
+/// 最接近的256色，r、g、b相同时使用灰阶
+fn rgb_to_indexed(r: u8, g: u8, b: u8) -> u8 {
+    if r == g && g == b {
+        return match r {
+            0..8 => 16,
+            248.. => 231,
+            _ => 232 + (r - 8) / 10,
+        };
+    }
+    let level = |c: u8| if c < 48 { 0 } else if c < 115 { 1 } else { (c - 35) / 40 };
+    16 + 36 * level(r) + 6 * level(g) + level(b)
+}
+
+/// 最接近的基础颜色，红绿蓝分别对应第0、1、2位
+fn rgb_to_basic(r: u8, g: u8, b: u8) -> u8 {
+    u8::from(r > 127) | u8::from(g > 127) << 1 | u8::from(b > 127) << 2
+}
+
+fn indexed_to_basic(index: u8) -> u8 {
+    match index {
+        0..16 => index % 8,
+        16..232 => {
+            let level = |n: u8| if n == 0 { 0 } else { 55 + n * 40 };
+            let n = index - 16;
+            rgb_to_basic(level(n / 36), level(n / 6 % 6), level(n % 6))
+        }
+        _ => {
+            let gray = 8 + (index - 232) * 10;
+            rgb_to_basic(gray, gray, gray)
+        }
+    }
+}
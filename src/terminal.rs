@@ -0,0 +1,315 @@
+use std::backtrace::Backtrace;
+use std::io::{self, Write};
+use std::mem;
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+
+use crossterm::{QueueableCommand, cursor, terminal};
+use unicode_width::UnicodeWidthChar;
+
+use crate::term::Capabilities;
+
+/// 真实的终端处于原始模式和备用屏幕中
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+/// 编辑器运行时主线程发生的panic，等终端恢复之后再输出
+static PANIC_REPORT: Mutex<Option<String>> = Mutex::new(None);
+
+/// 主线程panic时先保存panic的信息和backtrace，编辑器析构恢复终端之后再输出
+/// 直接输出会写到备用屏幕上，离开备用屏幕之后就看不到了
+/// 后台任务的panic会被tokio捕获，编辑器还在运行，仍然使用原来的hook
+pub fn install_panic_hook() {
+    let default = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if thread::current().name() != Some("main") || !ACTIVE.load(Ordering::SeqCst) {
+            default(info);
+            return;
+        }
+        let report = format!("{}\n\nBacktrace:\n{}", info, Backtrace::force_capture());
+        *PANIC_REPORT.lock().unwrap_or_else(PoisonError::into_inner) = Some(report);
+    }));
+}
+
+/// 输出保存下来的panic信息，需要在恢复终端之后调用
+pub fn print_panic_report() {
+    if let Some(report) = PANIC_REPORT.lock().unwrap_or_else(PoisonError::into_inner).take() {
+        eprintln!("fim panicked: {}", report);
+    }
+}
+
+/// 编辑器输出到终端的所有操作，编辑器不直接使用crossterm
+/// 坐标从0开始，写入的文本可以包含设置颜色和样式的转义序列
+/// 编辑器在后台任务中输出到终端，所以需要能够在线程间转移
+pub trait Terminal: Send + 'static {
+    /// 终端的列数和行数
+    fn size(&self) -> io::Result<(u16, u16)>;
+
+    /// 终端支持的颜色和功能，默认全部支持
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+
+    /// 进入原始模式和备用屏幕
+    fn enter_alternate_screen(&mut self) -> io::Result<()>;
+
+    /// 离开备用屏幕并恢复到规范模式
+    fn leave_alternate_screen(&mut self) -> io::Result<()>;
+
+    fn set_title(&mut self, title: &str) -> io::Result<()>;
+
+    /// 清除整个屏幕
+    fn clear(&mut self) -> io::Result<()>;
+
+    fn move_to(&mut self, x: u16, y: u16) -> io::Result<()>;
+
+    /// 从光标处写入文本，光标移到文本之后
+    fn write_styled(&mut self, text: &str) -> io::Result<()>;
+
+    fn hide_cursor(&mut self) -> io::Result<()>;
+
+    fn show_cursor(&mut self) -> io::Result<()>;
+
+    /// 输出之前的所有操作
+    fn flush(&mut self) -> io::Result<()>;
+}
+
+/// 使用crossterm输出到真实的终端，所有操作先写入writer，flush时才输出
+pub struct CrosstermTerminal<W: Write> {
+    writer: W,
+    capabilities: Capabilities,
+}
+
+impl<W: Write> CrosstermTerminal<W> {
+    /// 创建时根据环境变量检测终端的功能
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            capabilities: Capabilities::detect(),
+        }
+    }
+}
+
+impl<W: Write + Send + 'static> Terminal for CrosstermTerminal<W> {
+    fn size(&self) -> io::Result<(u16, u16)> {
+        terminal::size()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    fn enter_alternate_screen(&mut self) -> io::Result<()> {
+        terminal::enable_raw_mode()?;
+        if self.capabilities.alternate_screen {
+            self.writer.queue(terminal::EnterAlternateScreen)?;
+        }
+        ACTIVE.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn leave_alternate_screen(&mut self) -> io::Result<()> {
+        ACTIVE.store(false, Ordering::SeqCst);
+        terminal::disable_raw_mode()?;
+        if self.capabilities.alternate_screen {
+            self.writer.queue(terminal::LeaveAlternateScreen)?;
+        } else {
+            // 没有备用屏幕时清除编辑器的内容，shell的提示符从左上角开始
+            self.writer.queue(terminal::Clear(terminal::ClearType::All))?;
+            self.writer.queue(cursor::MoveTo(0, 0))?;
+        }
+        self.writer.flush()
+    }
+
+    fn set_title(&mut self, title: &str) -> io::Result<()> {
+        self.writer.queue(terminal::SetTitle(title))?;
+        Ok(())
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        self.writer.queue(terminal::Clear(terminal::ClearType::All))?;
+        Ok(())
+    }
+
+    fn move_to(&mut self, x: u16, y: u16) -> io::Result<()> {
+        self.writer.queue(cursor::MoveTo(x, y))?;
+        Ok(())
+    }
+
+    fn write_styled(&mut self, text: &str) -> io::Result<()> {
+        self.writer.write_all(text.as_bytes())
+    }
+
+    fn hide_cursor(&mut self) -> io::Result<()> {
+        self.writer.queue(cursor::Hide)?;
+        Ok(())
+    }
+
+    fn show_cursor(&mut self) -> io::Result<()> {
+        self.writer.queue(cursor::Show)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// 在内存中模拟的终端，用于在没有TTY的环境中检查渲染的结果和光标的位置
+/// 只记录字符，写入的转义序列会被丢弃
+pub struct MemoryTerminal {
+    cols: u16,
+    rows: u16,
+    /// 每个单元格中的字符，宽字符后面的单元格是'\0'
+    cells: Vec<Vec<char>>,
+    cursor: (u16, u16),
+    cursor_visible: bool,
+    alternate_screen: bool,
+    title: String,
+}
+
+impl MemoryTerminal {
+    pub fn new(cols: u16, rows: u16) -> Self {
+        Self {
+            cols,
+            rows,
+            cells: vec![vec![' '; cols as usize]; rows as usize],
+            cursor: (0, 0),
+            cursor_visible: true,
+            alternate_screen: false,
+            title: String::new(),
+        }
+    }
+
+    /// 改变终端的大小，编辑器下次刷新时会完整重绘
+    pub fn resize(&mut self, cols: u16, rows: u16) {
+        *self = Self {
+            cursor_visible: self.cursor_visible,
+            alternate_screen: self.alternate_screen,
+            title: mem::take(&mut self.title),
+            ..Self::new(cols, rows)
+        };
+    }
+
+    /// 第y行的内容，不包括行尾的空格
+    pub fn line(&self, y: u16) -> String {
+        let line: String = self.cells[y as usize].iter().filter(|&&c| c != '\0').collect();
+        line.trim_end().to_string()
+    }
+
+    /// 整个屏幕的内容，每行之间用换行分隔
+    pub fn contents(&self) -> String {
+        let lines: Vec<String> = (0..self.rows).map(|y| self.line(y)).collect();
+        lines.join("\n")
+    }
+
+    pub fn cursor(&self) -> (u16, u16) {
+        self.cursor
+    }
+
+    pub fn cursor_visible(&self) -> bool {
+        self.cursor_visible
+    }
+
+    pub fn is_alternate_screen(&self) -> bool {
+        self.alternate_screen
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn put(&mut self, c: char) {
+        let width = c.width().unwrap_or(0);
+        if width == 0 {
+            return;
+        }
+        let (x, y) = (self.cursor.0 as usize, self.cursor.1 as usize);
+        // 超出屏幕的部分被截断
+        if y >= self.cells.len() || x + width > self.cols as usize {
+            return;
+        }
+        self.cells[y][x] = c;
+        for cell in &mut self.cells[y][x + 1..x + width] {
+            *cell = '\0';
+        }
+        self.cursor.0 += width as u16;
+    }
+}
+
+impl Terminal for MemoryTerminal {
+    fn size(&self) -> io::Result<(u16, u16)> {
+        Ok((self.cols, self.rows))
+    }
+
+    fn enter_alternate_screen(&mut self) -> io::Result<()> {
+        self.alternate_screen = true;
+        Ok(())
+    }
+
+    fn leave_alternate_screen(&mut self) -> io::Result<()> {
+        self.alternate_screen = false;
+        Ok(())
+    }
+
+    fn set_title(&mut self, title: &str) -> io::Result<()> {
+        self.title = title.to_string();
+        Ok(())
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        for line in &mut self.cells {
+            line.fill(' ');
+        }
+        Ok(())
+    }
+
+    fn move_to(&mut self, x: u16, y: u16) -> io::Result<()> {
+        self.cursor = (x, y);
+        Ok(())
+    }
+
+    fn write_styled(&mut self, text: &str) -> io::Result<()> {
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '\x1b' {
+                self.put(c);
+                continue;
+            }
+            match chars.next() {
+                // CSI序列以0x40到0x7e之间的字符结束
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if ('\x40'..='\x7e').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // OSC序列以BEL或者ESC \结束
+                Some(']') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn hide_cursor(&mut self) -> io::Result<()> {
+        self.cursor_visible = false;
+        Ok(())
+    }
+
+    fn show_cursor(&mut self) -> io::Result<()> {
+        self.cursor_visible = true;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
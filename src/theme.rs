@@ -0,0 +1,302 @@
+use std::collections::BTreeMap;
+
+use crate::term::{self, ColorDepth};
+use crate::utils::color;
+
+/// 内置的主题名称
+pub const THEMES: [&str; 4] = ["default", "mono", "ocean", "solarized"];
+
+/// 配置文件中可以设置的颜色
+const FIELDS: [&str; 26] = [
+    "status_bar",
+    "inactive_status_bar",
+    "message_bar",
+    "warning_message",
+    "error_message",
+    "line_number",
+    "current_line_number",
+    "separator",
+    "selection",
+    "secondary_cursor",
+    "matching_bracket",
+    "trailing_whitespace",
+    "ruler",
+    "indent_guide",
+    "misspelled",
+    "git_added",
+    "git_modified",
+    "git_removed",
+    "popup",
+    "popup_selected",
+    "keyword",
+    "string",
+    "comment",
+    "number",
+    "type_name",
+    "function",
+];
+
+/// 界面各部分的颜色，每个字段都是终端的转义序列，可以组合前景、背景和样式
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+    pub status_bar: String,
+    /// 没有焦点的窗口的状态栏
+    pub inactive_status_bar: String,
+    pub message_bar: String,
+    pub warning_message: String,
+    /// 错误和严重错误的消息
+    pub error_message: String,
+    pub line_number: String,
+    /// 光标所在行的行号
+    pub current_line_number: String,
+    /// 左右分屏之间的分隔线和折行标记
+    pub separator: String,
+    pub selection: String,
+    /// 多个光标时主光标之外的光标
+    pub secondary_cursor: String,
+    /// 配对括号
+    pub matching_bracket: String,
+    pub trailing_whitespace: String,
+    /// 竖线和缩进线
+    pub ruler: String,
+    pub indent_guide: String,
+    /// 拼写错误的单词
+    pub misspelled: String,
+    /// 行号栏中和HEAD相比增加、修改和删除的行的标记
+    pub git_added: String,
+    pub git_modified: String,
+    pub git_removed: String,
+    /// 补全菜单和其中选中的一项
+    pub popup: String,
+    pub popup_selected: String,
+    /// 语法高亮使用的颜色
+    pub keyword: String,
+    pub string: String,
+    pub comment: String,
+    pub number: String,
+    pub type_name: String,
+    pub function: String,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            status_bar: color::BG_RED.to_string(),
+            inactive_status_bar: color::REVERSE.to_string(),
+            message_bar: color::BG_BLUE.to_string(),
+            warning_message: color::BG_YELLOW.to_string() + color::BLACK,
+            error_message: color::BG_RED.to_string() + color::BRIGHT_WHITE,
+            line_number: color::GRAY.to_string(),
+            current_line_number: color::YELLOW.to_string(),
+            separator: color::GRAY.to_string(),
+            selection: color::REVERSE.to_string(),
+            secondary_cursor: color::REVERSE.to_string(),
+            matching_bracket: color::BG_CYAN.to_string(),
+            trailing_whitespace: color::BG_RED.to_string(),
+            ruler: color::GRAY.to_string(),
+            indent_guide: color::GRAY.to_string() + color::DIM,
+            misspelled: color::UNDERLINE.to_string() + color::RED,
+            git_added: color::GREEN.to_string(),
+            git_modified: color::YELLOW.to_string(),
+            git_removed: color::RED.to_string(),
+            popup: color::BG_WHITE.to_string() + color::BLACK,
+            popup_selected: color::BG_BLUE.to_string() + color::BRIGHT_WHITE,
+            keyword: color::MAGENTA.to_string(),
+            string: color::GREEN.to_string(),
+            comment: color::GRAY.to_string(),
+            number: color::CYAN.to_string(),
+            type_name: color::YELLOW.to_string(),
+            function: color::BLUE.to_string(),
+        }
+    }
+}
+
+impl Theme {
+    /// 内置的主题，名称未知时返回None
+    pub fn builtin(name: &str) -> Option<Self> {
+        let theme = match name {
+            "default" => Self::default(),
+            // 只使用样式，适合不支持颜色的终端
+            "mono" => Self {
+                status_bar: color::REVERSE.to_string(),
+                inactive_status_bar: color::UNDERLINE.to_string(),
+                message_bar: color::REVERSE.to_string(),
+                warning_message: color::REVERSE.to_string() + color::BOLD,
+                error_message: color::REVERSE.to_string() + color::BOLD + color::UNDERLINE,
+                line_number: color::DIM.to_string(),
+                current_line_number: color::BOLD.to_string(),
+                separator: color::DIM.to_string(),
+                selection: color::REVERSE.to_string(),
+                secondary_cursor: color::REVERSE.to_string(),
+                matching_bracket: color::UNDERLINE.to_string(),
+                trailing_whitespace: color::REVERSE.to_string(),
+                ruler: color::DIM.to_string(),
+                indent_guide: color::DIM.to_string(),
+                misspelled: color::UNDERLINE.to_string(),
+                git_added: color::BOLD.to_string(),
+                git_modified: color::BOLD.to_string(),
+                git_removed: color::BOLD.to_string(),
+                popup: color::REVERSE.to_string(),
+                popup_selected: color::BOLD.to_string() + color::UNDERLINE,
+                keyword: color::BOLD.to_string(),
+                string: String::new(),
+                comment: color::DIM.to_string(),
+                number: String::new(),
+                type_name: color::BOLD.to_string(),
+                function: String::new(),
+            },
+            "ocean" => Self {
+                status_bar: color::BG_BLUE.to_string(),
+                message_bar: color::BG_CYAN.to_string(),
+                current_line_number: color::BRIGHT_CYAN.to_string(),
+                selection: color::BG_CYAN.to_string(),
+                matching_bracket: color::BG_MAGENTA.to_string(),
+                keyword: color::BRIGHT_BLUE.to_string(),
+                function: color::CYAN.to_string(),
+                ..Self::default()
+            },
+            // 真彩色主题，需要终端支持24位颜色
+            "solarized" => Self {
+                status_bar: color::rgb_bg(7, 54, 66) + &color::rgb_fg(147, 161, 161),
+                inactive_status_bar: color::rgb_bg(0, 43, 54) + &color::rgb_fg(88, 110, 117),
+                message_bar: color::rgb_bg(38, 139, 210) + &color::rgb_fg(253, 246, 227),
+                warning_message: color::rgb_bg(181, 137, 0) + &color::rgb_fg(253, 246, 227),
+                error_message: color::rgb_bg(220, 50, 47) + &color::rgb_fg(253, 246, 227),
+                line_number: color::rgb_fg(88, 110, 117),
+                current_line_number: color::rgb_fg(181, 137, 0),
+                separator: color::rgb_fg(88, 110, 117),
+                selection: color::rgb_bg(7, 54, 66),
+                secondary_cursor: color::rgb_bg(147, 161, 161) + &color::rgb_fg(0, 43, 54),
+                matching_bracket: color::rgb_bg(42, 161, 152),
+                trailing_whitespace: color::rgb_bg(220, 50, 47),
+                ruler: color::rgb_fg(88, 110, 117),
+                indent_guide: color::rgb_fg(7, 54, 66),
+                misspelled: color::UNDERLINE.to_string() + &color::rgb_fg(220, 50, 47),
+                git_added: color::rgb_fg(133, 153, 0),
+                git_modified: color::rgb_fg(181, 137, 0),
+                git_removed: color::rgb_fg(220, 50, 47),
+                popup: color::rgb_bg(7, 54, 66) + &color::rgb_fg(147, 161, 161),
+                popup_selected: color::rgb_bg(38, 139, 210) + &color::rgb_fg(253, 246, 227),
+                keyword: color::rgb_fg(133, 153, 0),
+                string: color::rgb_fg(42, 161, 152),
+                comment: color::rgb_fg(88, 110, 117),
+                number: color::rgb_fg(211, 54, 130),
+                type_name: color::rgb_fg(181, 137, 0),
+                function: color::rgb_fg(38, 139, 210),
+            },
+            _ => return None,
+        };
+        Some(theme)
+    }
+
+    /// 内置主题加上配置文件中[colors]表覆盖的颜色
+    pub fn load(name: &str, colors: &BTreeMap<String, String>) -> Result<Self, String> {
+        let mut theme = Self::builtin(name).ok_or_else(|| {
+            format!("unknown theme '{}', available themes: {}", name, THEMES.join(", "))
+        })?;
+        for (key, spec) in colors {
+            let value = parse_color(spec).map_err(|e| format!("colors.{}: {}", key, e))?;
+            *theme.field_mut(key)? = value;
+        }
+        Ok(theme)
+    }
+
+    /// 把所有颜色转换为终端支持的颜色，比如在256色的终端中使用真彩色主题
+    pub fn adapt(mut self, colors: ColorDepth) -> Self {
+        for name in FIELDS {
+            if let Ok(field) = self.field_mut(name) {
+                *field = term::adapt_style(field, colors);
+            }
+        }
+        self
+    }
+
+    fn field_mut(&mut self, key: &str) -> Result<&mut String, String> {
+        let field = match key {
+            "status_bar" => &mut self.status_bar,
+            "inactive_status_bar" => &mut self.inactive_status_bar,
+            "message_bar" => &mut self.message_bar,
+            "warning_message" => &mut self.warning_message,
+            "error_message" => &mut self.error_message,
+            "line_number" => &mut self.line_number,
+            "current_line_number" => &mut self.current_line_number,
+            "separator" => &mut self.separator,
+            "selection" => &mut self.selection,
+            "secondary_cursor" => &mut self.secondary_cursor,
+            "matching_bracket" => &mut self.matching_bracket,
+            "trailing_whitespace" => &mut self.trailing_whitespace,
+            "ruler" => &mut self.ruler,
+            "indent_guide" => &mut self.indent_guide,
+            "misspelled" => &mut self.misspelled,
+            "git_added" => &mut self.git_added,
+            "git_modified" => &mut self.git_modified,
+            "git_removed" => &mut self.git_removed,
+            "popup" => &mut self.popup,
+            "popup_selected" => &mut self.popup_selected,
+            "keyword" => &mut self.keyword,
+            "string" => &mut self.string,
+            "comment" => &mut self.comment,
+            "number" => &mut self.number,
+            "type_name" => &mut self.type_name,
+            "function" => &mut self.function,
+            _ => return Err(format!("unknown color '{}'", key)),
+        };
+        Ok(field)
+    }
+}
+
+/// 解析颜色，用空格分隔多个部分，比如 "bold #ffffff bg:#005f87" 或者 "yellow bg:blue"
+/// 颜色可以是名称或者#rrggbb格式的真彩色，bg:开头的是背景色
+fn parse_color(spec: &str) -> Result<String, String> {
+    let mut value = String::new();
+    for part in spec.split_whitespace() {
+        let (background, name) = match part.strip_prefix("bg:") {
+            Some(name) => (true, name),
+            None => (false, part),
+        };
+        if let Some(hex) = name.strip_prefix('#') {
+            let (r, g, b) = parse_hex(hex).ok_or_else(|| format!("invalid color '{}'", part))?;
+            value.push_str(&if background {
+                color::rgb_bg(r, g, b)
+            } else {
+                color::rgb_fg(r, g, b)
+            });
+            continue;
+        }
+        let code = match (background, name) {
+            (false, "black") => color::BLACK,
+            (false, "red") => color::RED,
+            (false, "green") => color::GREEN,
+            (false, "yellow") => color::YELLOW,
+            (false, "blue") => color::BLUE,
+            (false, "magenta") => color::MAGENTA,
+            (false, "cyan") => color::CYAN,
+            (false, "white") => color::WHITE,
+            (false, "gray") => color::GRAY,
+            (false, "bold") => color::BOLD,
+            (false, "dim") => color::DIM,
+            (false, "italic") => color::ITALIC,
+            (false, "underline") => color::UNDERLINE,
+            (false, "reverse") => color::REVERSE,
+            (true, "black") => color::BG_BLACK,
+            (true, "red") => color::BG_RED,
+            (true, "green") => color::BG_GREEN,
+            (true, "yellow") => color::BG_YELLOW,
+            (true, "blue") => color::BG_BLUE,
+            (true, "magenta") => color::BG_MAGENTA,
+            (true, "cyan") => color::BG_CYAN,
+            (true, "white") => color::BG_WHITE,
+            _ => return Err(format!("invalid color '{}'", part)),
+        };
+        value.push_str(code);
+    }
+    Ok(value)
+}
+
+fn parse_hex(hex: &str) -> Option<(u8, u8, u8)> {
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
@@ -0,0 +1,13 @@
+use std::path::PathBuf;
+use std::{env, fs, io, process};
+
+/// 随程序发布的教程
+const TUTOR: &str = include_str!("tutor.txt");
+
+/// 把教程复制到临时目录，返回副本的路径
+/// 每次启动都使用新的副本，练习时可以随意修改和保存
+pub fn create_copy() -> io::Result<PathBuf> {
+    let path = env::temp_dir().join(format!("fim-tutor-{}.txt", process::id()));
+    fs::write(&path, TUTOR)?;
+    Ok(path)
+}
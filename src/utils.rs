@@ -56,6 +56,9 @@ pub fn rgb_bg(r: u8, g: u8, b: u8) -> String {
 }
 }
 
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
 pub fn get_version_from_env() -> String {
     // Cargo 会在编译时自动设置一些环境变量
     // option_env! 是 Rust 中的一个编译时宏，用于在编译时获取环境变量的值，
@@ -65,6 +68,42 @@ pub fn get_version_from_env() -> String {
         .to_string()
 }
 
+/// 字符串在终端上显示的宽度，不计算其中的颜色转义序列
+pub fn visible_width(s: &str) -> usize {
+    let mut width = 0;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // 跳过到转义序列的结束字母
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            width += c.width().unwrap_or(0);
+        }
+    }
+    width
+}
+
+/// 按显示宽度截断或者用空格补齐到width列，不能包含转义序列
+/// 宽字符放不下时用空格补齐
+pub fn fit_width(s: &str, width: usize) -> String {
+    let mut fitted = String::new();
+    let mut used = 0;
+    for grapheme in s.graphemes(true) {
+        let w = grapheme.width();
+        if used + w > width {
+            break;
+        }
+        fitted.push_str(grapheme);
+        used += w;
+    }
+    fitted.push_str(&" ".repeat(width - used));
+    fitted
+}
+
 pub fn find_subsequence<T: PartialEq>(haystack: &[T], needle: &[T]) -> Option<usize> {
     let needle_len = needle.len();
     if needle_len == 0 {
@@ -0,0 +1,84 @@
+use std::time::Duration;
+
+use fim::config::Config;
+use fim::editor::{ControlKey, Editor, Key, OpenOptions, SplitDirection};
+use fim::reader::{ByteStream, Decoder, KeyStream};
+use fim::terminal::MemoryTerminal;
+use tokio::io::Empty;
+use tokio::time;
+
+async fn editor(cols: u16, rows: u16) -> Editor<Empty, MemoryTerminal> {
+    let decoder = Decoder::builder()
+        .encoding("utf-8".to_owned())
+        .byte_stream(ByteStream::new(tokio::io::empty()))
+        .build()
+        .unwrap();
+    let mut editor = Editor::new(KeyStream::new(decoder), MemoryTerminal::new(cols, rows), Config::default()).await;
+    editor.start(&[], &OpenOptions::default()).await;
+    editor
+}
+
+async fn type_text(editor: &mut Editor<Empty, MemoryTerminal>, text: &str) {
+    for c in text.chars() {
+        let key = match c {
+            '\n' => Key::ControlKey(ControlKey::CR),
+            c => Key::Char(c),
+        };
+        editor.handle_command(&key).await;
+    }
+}
+
+/// 帧在后台输出，等到第一行是expected时返回整个屏幕和光标的位置
+async fn wait_for_frame(editor: &Editor<Empty, MemoryTerminal>, first_line: &str) -> (String, (u16, u16)) {
+    for _ in 0..200 {
+        {
+            let terminal = editor.terminal();
+            if terminal.line(0) == first_line {
+                return (terminal.contents(), terminal.cursor());
+            }
+        }
+        time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("screen never showed {first_line:?}:\n{}", editor.terminal().contents());
+}
+
+#[tokio::test]
+async fn renders_text_and_cursor() {
+    let mut editor = editor(40, 8).await;
+    type_text(&mut editor, "first line\nsecond").await;
+    editor.refresh_screen().unwrap();
+
+    let (contents, cursor) = wait_for_frame(&editor, "first line").await;
+    let lines: Vec<&str> = contents.lines().collect();
+    assert_eq!(lines[1], "second");
+    // 文本下面的空行显示为~，最后两行是状态栏和消息栏
+    assert!(lines[2..6].iter().all(|line| *line == "~"), "{contents}");
+    assert!(lines[6].contains("Ln 2/2, Col 7"), "{contents}");
+    assert_eq!(cursor, (6, 1));
+    assert!(editor.terminal().cursor_visible());
+}
+
+#[tokio::test]
+async fn wide_characters_take_two_cells() {
+    let mut editor = editor(20, 5).await;
+    type_text(&mut editor, "ab世界c").await;
+    editor.refresh_screen().unwrap();
+
+    let (_, cursor) = wait_for_frame(&editor, "ab世界c").await;
+    assert_eq!(cursor, (7, 0));
+}
+
+#[tokio::test]
+async fn views_squeezed_to_nothing_are_skipped() {
+    let mut editor = editor(20, 8).await;
+    editor.split_view(SplitDirection::Vertical);
+    editor.refresh_screen().unwrap();
+    // 终端缩小之后左边的窗口没有宽度
+    // 绘制任务输出下一帧时才发现终端的大小变了
+    editor.terminal().resize(2, 8);
+    for _ in 0..5 {
+        editor.refresh_screen().unwrap();
+        time::sleep(Duration::from_millis(20)).await;
+    }
+    assert!(editor.terminal().line(0).starts_with('│'));
+}
@@ -0,0 +1,46 @@
+use std::fs;
+use std::path::PathBuf;
+
+use fim::config::Config;
+use fim::editor::{Editor, OpenOptions};
+use fim::reader::{ByteStream, Decoder, KeyStream, record};
+use fim::terminal::MemoryTerminal;
+
+/// 每个测试使用单独的目录，配置目录也放在里面，不读写用户的配置
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[tokio::test]
+async fn replays_a_recording_through_the_editor_loop() {
+    let dir = scratch_dir("replay");
+    // 这个文件只有一个测试，修改环境变量不会影响其他线程
+    unsafe { std::env::set_var("XDG_CONFIG_HOME", dir.join("config")) };
+    let file = dir.join("notes.txt");
+    fs::write(&file, "one\ntwo\n").unwrap();
+
+    // 在第二行开头插入文本，保存之后退出
+    let recording = "<Down>\nn\ne\nw\n \n<C-s>\n<C-q>\n";
+    let decoder = Decoder::builder()
+        .encoding("utf-8".to_owned())
+        .byte_stream(ByteStream::new(tokio::io::empty()))
+        .build()
+        .unwrap();
+    let mut key_stream = KeyStream::new(decoder);
+    key_stream.replay(record::parse_recording(recording).unwrap());
+    let recorded = dir.join("keys.txt");
+    key_stream.record(fs::File::create(&recorded).unwrap());
+
+    let mut editor = Editor::new(key_stream, MemoryTerminal::new(40, 10), Config::default()).await;
+    editor.start(std::slice::from_ref(&file), &OpenOptions::default()).await;
+    editor.run().await;
+    drop(editor);
+
+    assert_eq!(fs::read_to_string(&file).unwrap(), "one\nnew two\n");
+    // 重放时读到的按键也被记录下来，和原来的记录相同
+    let recorded = fs::read_to_string(&recorded).unwrap();
+    assert_eq!(record::parse_recording(&recorded), record::parse_recording(recording));
+}
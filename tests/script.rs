@@ -0,0 +1,48 @@
+use std::fs;
+use std::path::PathBuf;
+
+use fim::config::Config;
+use fim::editor::{Editor, OpenOptions};
+use fim::reader::{ByteStream, Decoder, KeyStream};
+use fim::terminal::MemoryTerminal;
+
+/// 脚本模式下不读终端输入，按键流直接是空的
+async fn editor() -> Editor<tokio::io::Empty, MemoryTerminal> {
+    let decoder = Decoder::builder()
+        .encoding("utf-8".to_owned())
+        .byte_stream(ByteStream::new(tokio::io::empty()))
+        .build()
+        .unwrap();
+    let mut editor = Editor::new(KeyStream::new(decoder), MemoryTerminal::new(80, 24), Config::default()).await;
+    editor.start(&[], &OpenOptions::default()).await;
+    editor
+}
+
+#[tokio::test]
+async fn script_edits_and_saves_a_file() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("script");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    // 这个文件只有一个测试，修改环境变量不会影响其他线程
+    unsafe { std::env::set_var("XDG_CONFIG_HOME", dir.join("config")) };
+    let input = dir.join("input.txt");
+    let output = dir.join("output.txt");
+    fs::write(&input, "alpha\nbeta\ngamma\n").unwrap();
+
+    // 脚本每一行前后的空白会被去掉
+    let script = format!(
+        "# 注释行会被跳过\nopen {}\ngoto 3\ninsert >>\nreplace /beta/BETA/\nkeys <C-Home>x\nwrite {}\nquit\n",
+        input.display(),
+        output.display()
+    );
+    let mut script_editor = editor().await;
+    script_editor.run_script(&script).await.unwrap();
+    drop(script_editor);
+
+    assert_eq!(fs::read_to_string(&output).unwrap(), "xalpha\nBETA\n>>gamma\n");
+    // 另存为新文件，原来的文件不变
+    assert_eq!(fs::read_to_string(&input).unwrap(), "alpha\nbeta\ngamma\n");
+
+    let error = editor().await.run_script("goto nowhere\n").await.unwrap_err();
+    assert!(error.starts_with("line 1:"), "{error}");
+}